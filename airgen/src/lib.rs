@@ -12,11 +12,13 @@ use ast::{
 };
 
 const MAIN_MACHINE: &str = "Main";
-const MAIN_FUNCTION: &str = "main";
 
 use number::FieldElement;
+use parser_util::SourceError;
 
-pub fn compile<T: FieldElement>(input: AnalysisASMFile<T>) -> PILGraph<T> {
+pub fn compile<T: FieldElement>(
+    input: AnalysisASMFile<T>,
+) -> Result<PILGraph<T>, Vec<SourceError>> {
     let main_location = Location::main();
 
     // we start from the main machine
@@ -25,7 +27,12 @@ pub fn compile<T: FieldElement>(input: AnalysisASMFile<T>) -> PILGraph<T> {
         1 => input.machines.keys().next().unwrap().clone(),
         // otherwise, use the machine called `MAIN`
         _ => {
-            assert!(input.machines.contains_key(MAIN_MACHINE));
+            if !input.machines.contains_key(MAIN_MACHINE) {
+                return Err(vec![format!(
+                    "There are multiple machines, but none of them is called {MAIN_MACHINE}"
+                )
+                .into()]);
+            }
             MAIN_MACHINE.into()
         }
     };
@@ -51,17 +58,23 @@ pub fn compile<T: FieldElement>(input: AnalysisASMFile<T>) -> PILGraph<T> {
     }
 
     // visit the tree compiling the machines
-    let objects = instances
-        .into_iter()
-        .map(|(location, ty)| {
-            let object = ASMPILConverter::convert_machine(&location, &ty, &input);
-            (location, object)
-        })
-        .collect();
+    let mut errors = vec![];
+    let mut objects = BTreeMap::new();
+    for (location, ty) in instances {
+        match ASMPILConverter::convert_machine(&location, &ty, &input) {
+            Ok(object) => {
+                objects.insert(location, object);
+            }
+            Err(e) => errors.extend(e),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
 
     let main_ty = input.machines.get(&main_ty).unwrap();
 
-    PILGraph {
+    Ok(PILGraph {
         main: ast::object::Machine {
             location: main_location,
             function_id: main_ty.function_id.clone().unwrap(),
@@ -71,13 +84,13 @@ pub fn compile<T: FieldElement>(input: AnalysisASMFile<T>) -> PILGraph<T> {
             .functions
             .iter()
             .map(|f| Function {
-                name: MAIN_FUNCTION.into(),
+                name: f.name.clone(),
                 id: f.id.unwrap(),
                 params: f.params.clone(),
             })
             .collect(),
         objects,
-    }
+    })
 }
 
 struct ASMPILConverter<'a, T> {
@@ -107,11 +120,11 @@ impl<'a, T: FieldElement> ASMPILConverter<'a, T> {
         location: &'a Location,
         ty: &'a str,
         input: &'a AnalysisASMFile<T>,
-    ) -> Object<T> {
+    ) -> Result<Object<T>, Vec<SourceError>> {
         Self::new(location, input).convert_machine_inner(ty)
     }
 
-    fn convert_machine_inner(mut self, ty: &str) -> Object<T> {
+    fn convert_machine_inner(mut self, ty: &str) -> Result<Object<T>, Vec<SourceError>> {
         let input = self.machines.get(ty).unwrap().clone();
 
         let degree = input.degree.map(|s| T::from(s.degree).to_degree());
@@ -131,27 +144,34 @@ impl<'a, T: FieldElement> ASMPILConverter<'a, T> {
             self.handle_inline_pil(block);
         }
 
-        let links = input
-            .instructions
-            .into_iter()
-            .filter_map(|instr| self.handle_instruction_def(instr))
-            .collect();
+        let mut errors = vec![];
+        let mut links = vec![];
+        for instr in input.instructions {
+            match self.handle_instruction_def(instr) {
+                Ok(Some(link)) => links.push(link),
+                Ok(None) => {}
+                Err(e) => errors.push(e),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
 
-        Object {
+        Ok(Object {
             degree,
             pil: self.pil,
             links,
-        }
+        })
     }
 
     fn handle_instruction_def(
         &mut self,
         InstructionDefinitionStatement {
-            start: _,
+            start,
             name,
             instruction: Instruction { params, body },
         }: InstructionDefinitionStatement<T>,
-    ) -> Option<Link<T>> {
+    ) -> Result<Option<Link<T>>, SourceError> {
         // TODO: this relies on `asm_to_pil` calling the instructions flags a certain way. It will go away once external instructions are turned into links earlier
         let instruction_flag = format!("instr_{name}");
         let instr = Instr {
@@ -168,7 +188,9 @@ impl<'a, T: FieldElement> ASMPILConverter<'a, T> {
                     .submachines
                     .iter()
                     .find(|s| s.name == instance)
-                    .unwrap()
+                    .ok_or_else(|| {
+                        SourceError::at(start, format!("Submachine {instance} not found"))
+                    })?
                     .ty
                     .clone();
                 // get the machine type from the machine map
@@ -194,10 +216,17 @@ impl<'a, T: FieldElement> ASMPILConverter<'a, T> {
                                 params: d.params.clone(),
                             },
                         })
-                        .unwrap(),
+                        .ok_or_else(|| {
+                            SourceError::at(
+                                start,
+                                format!(
+                                    "Function {function} not found in machine {instance_ty_name}"
+                                ),
+                            )
+                        })?,
                 })
             }
         };
-        link
+        Ok(link)
     }
 }
@@ -7,10 +7,37 @@ pub use macro_expansion::MacroExpander;
 
 use ast::{asm_analysis::AnalysisASMFile, parsed::asm::ASMFile, DiffMonitor};
 use number::FieldElement;
+use parser_util::SourceError;
 
-pub fn analyze<T: FieldElement>(file: ASMFile<T>) -> Result<AnalysisASMFile<T>, Vec<String>> {
+/// Analyzes a parsed asm file, which includes type checking and reducing
+/// all VM machines to constrained machines.
+pub fn analyze<T: FieldElement>(file: ASMFile<T>) -> Result<AnalysisASMFile<T>, Vec<SourceError>> {
     let mut monitor = DiffMonitor::default();
+    let file = analyze_functions_with_monitor(file, &mut monitor)?;
+    reduce_vms_with_monitor(file, &mut monitor)
+}
+
+/// Analyzes a parsed asm file up to the batching of function statements. Contrary to
+/// [analyze], the functions of VM machines are kept instead of being reduced to constraints,
+/// which is what executing a program directly requires.
+pub fn analyze_functions<T: FieldElement>(
+    file: ASMFile<T>,
+) -> Result<AnalysisASMFile<T>, Vec<SourceError>> {
+    analyze_functions_with_monitor(file, &mut DiffMonitor::default())
+}
 
+/// Completes the analysis of a file returned by [analyze_functions] by reducing
+/// all VM machines to constrained machines.
+pub fn reduce_vms<T: FieldElement>(
+    file: AnalysisASMFile<T>,
+) -> Result<AnalysisASMFile<T>, Vec<SourceError>> {
+    reduce_vms_with_monitor(file, &mut DiffMonitor::default())
+}
+
+fn analyze_functions_with_monitor<T: FieldElement>(
+    file: ASMFile<T>,
+    monitor: &mut DiffMonitor,
+) -> Result<AnalysisASMFile<T>, Vec<SourceError>> {
     // expand macros
     log::debug!("Run expand analysis step");
     let file = macro_expansion::expand(file);
@@ -19,10 +46,19 @@ pub fn analyze<T: FieldElement>(file: ASMFile<T>) -> Result<AnalysisASMFile<T>,
     let file = type_check::check(file)?;
     monitor.push(&file);
 
-    // run analysis on vm machines, reducing them to block machines
+    // infer assignment registers and batch statements in each function
     log::debug!("Start asm analysis");
-    let file = vm::analyze(file, &mut monitor)?;
+    let file = vm::batch_functions(file, monitor)?;
     log::debug!("End asm analysis");
+    Ok(file)
+}
+
+fn reduce_vms_with_monitor<T: FieldElement>(
+    file: AnalysisASMFile<T>,
+    monitor: &mut DiffMonitor,
+) -> Result<AnalysisASMFile<T>, Vec<SourceError>> {
+    // run analysis on vm machines, reducing them to block machines
+    let file = vm::reduce(file, monitor)?;
 
     // enforce blocks using `function_id` and `latch`
     log::debug!("Run enforce_block analysis step");
@@ -49,6 +85,8 @@ mod test_util {
     use number::FieldElement;
     use parser::parse_asm;
 
+    use parser_util::SourceError;
+
     use crate::macro_expansion;
 
     /// A test utility to process a source file until after macro expansion
@@ -58,7 +96,9 @@ mod test_util {
     }
 
     /// A test utility to process a source file until after type checking
-    pub fn typecheck_str<T: FieldElement>(source: &str) -> Result<AnalysisASMFile<T>, Vec<String>> {
+    pub fn typecheck_str<T: FieldElement>(
+        source: &str,
+    ) -> Result<AnalysisASMFile<T>, Vec<SourceError>> {
         type_check::check(expand_str(source))
     }
 }
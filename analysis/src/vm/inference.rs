@@ -4,8 +4,11 @@ use ast::asm_analysis::{
     AnalysisASMFile, AssignmentStatement, Expression, FunctionStatement, Machine,
 };
 use number::FieldElement;
+use parser_util::SourceError;
 
-pub fn infer<T: FieldElement>(file: AnalysisASMFile<T>) -> Result<AnalysisASMFile<T>, Vec<String>> {
+pub fn infer<T: FieldElement>(
+    file: AnalysisASMFile<T>,
+) -> Result<AnalysisASMFile<T>, Vec<SourceError>> {
     let mut errors = vec![];
     let mut res = AnalysisASMFile::default();
 
@@ -27,7 +30,7 @@ pub fn infer<T: FieldElement>(file: AnalysisASMFile<T>) -> Result<AnalysisASMFil
     }
 }
 
-fn infer_machine<T: FieldElement>(mut machine: Machine<T>) -> Result<Machine<T>, Vec<String>> {
+fn infer_machine<T: FieldElement>(mut machine: Machine<T>) -> Result<Machine<T>, Vec<SourceError>> {
     let mut errors = vec![];
 
     for f in machine.functions.iter_mut() {
@@ -35,11 +38,13 @@ fn infer_machine<T: FieldElement>(mut machine: Machine<T>) -> Result<Machine<T>,
             if let FunctionStatement::Assignment(a) = s {
                 let expr_reg = match &*a.rhs {
                     Expression::FunctionCall(c) => {
-                        let def = machine
-                            .instructions
-                            .iter()
-                            .find(|i| i.name == c.id)
-                            .unwrap();
+                        let Some(def) = machine.instructions.iter().find(|i| i.name == c.id) else {
+                            errors.push(SourceError::at(
+                                a.start,
+                                format!("Instruction not found: {}", c.id),
+                            ));
+                            continue;
+                        };
                         let output = {
                             let outputs = def.instruction.params.outputs.as_ref().unwrap();
                             assert!(outputs.params.len() == 1);
@@ -53,7 +58,7 @@ fn infer_machine<T: FieldElement>(mut machine: Machine<T>) -> Result<Machine<T>,
 
                 match (&mut a.using_reg, expr_reg) {
                     (Some(using_reg), Some(expr_reg)) if *using_reg != expr_reg => {
-                        errors.push(format!("Assignment register `{}` is incompatible with `{}`. Try replacing `<={}=` by `<==`.", using_reg, a.rhs, using_reg));
+                        errors.push(SourceError::at(a.start, format!("Assignment register `{}` is incompatible with `{}`. Try replacing `<={}=` by `<==`.", using_reg, a.rhs, using_reg)));
                     }
                     (Some(_), _) => {}
                     (None, Some(expr_reg)) => {
@@ -73,7 +78,7 @@ fn infer_machine<T: FieldElement>(mut machine: Machine<T>) -> Result<Machine<T>,
                             ),
                             ..a.clone()
                         };
-                        errors.push(format!("Impossible to infer the assignment register for `{a}`. Try using an assignment register like `{hint}`."));
+                        errors.push(SourceError::at(a.start, format!("Impossible to infer the assignment register for `{a}`. Try using an assignment register like `{hint}`.")));
                     }
                 }
             }
@@ -96,6 +101,10 @@ mod tests {
 
     use super::*;
 
+    fn error_messages(errors: Vec<SourceError>) -> Vec<String> {
+        errors.into_iter().map(|e| e.message).collect()
+    }
+
     #[test]
     fn inferred() {
         let file = r#"
@@ -179,7 +188,7 @@ mod tests {
             }
         "#;
 
-        assert_eq!(infer_str::<Bn254Field>(file).unwrap_err(), vec!["Assignment register `Y` is incompatible with `foo()`. Try replacing `<=Y=` by `<==`."]);
+        assert_eq!(error_messages(infer_str::<Bn254Field>(file).unwrap_err()), vec!["Assignment register `Y` is incompatible with `foo()`. Try replacing `<=Y=` by `<==`."]);
     }
 
     #[test]
@@ -197,6 +206,6 @@ mod tests {
             }
         "#;
 
-        assert_eq!(infer_str::<Bn254Field>(file).unwrap_err(), vec!["Impossible to infer the assignment register for `A <== 1;`. Try using an assignment register like `A <=X= 1;`.".to_string()]);
+        assert_eq!(error_messages(infer_str::<Bn254Field>(file).unwrap_err()), vec!["Impossible to infer the assignment register for `A <== 1;`. Try using an assignment register like `A <=X= 1;`.".to_string()]);
     }
 }
@@ -3,14 +3,16 @@
 
 use ast::{asm_analysis::AnalysisASMFile, DiffMonitor};
 use number::FieldElement;
+use parser_util::SourceError;
 
 pub mod batcher;
 pub mod inference;
 
-pub fn analyze<T: FieldElement>(
+/// Infers the assignment registers and batches the statements of all functions.
+pub fn batch_functions<T: FieldElement>(
     file: AnalysisASMFile<T>,
     monitor: &mut DiffMonitor,
-) -> Result<AnalysisASMFile<T>, Vec<String>> {
+) -> Result<AnalysisASMFile<T>, Vec<SourceError>> {
     // infer assignment registers
     log::debug!("Run inference analysis step");
     let file = inference::infer(file)?;
@@ -19,9 +21,17 @@ pub fn analyze<T: FieldElement>(
     log::debug!("Run batch analysis step");
     let file = batcher::batch(file);
     monitor.push(&file);
+    Ok(file)
+}
+
+/// Reduces VM machines with batched functions to constrained machines.
+pub fn reduce<T: FieldElement>(
+    file: AnalysisASMFile<T>,
+    monitor: &mut DiffMonitor,
+) -> Result<AnalysisASMFile<T>, Vec<SourceError>> {
     // remove all asm (except external instructions)
     log::debug!("Run asm_to_pil analysis step");
-    let file = asm_to_pil::compile(file);
+    let file = asm_to_pil::compile(file)?;
     monitor.push(&file);
 
     Ok(file)
@@ -34,7 +44,9 @@ mod test_utils {
     use super::*;
 
     /// A test utility to process a source file until after inference
-    pub fn infer_str<T: FieldElement>(source: &str) -> Result<AnalysisASMFile<T>, Vec<String>> {
+    pub fn infer_str<T: FieldElement>(
+        source: &str,
+    ) -> Result<AnalysisASMFile<T>, Vec<SourceError>> {
         inference::infer(typecheck_str(source).unwrap())
     }
 
@@ -46,6 +58,6 @@ mod test_utils {
     /// A test utility to process a source file until after asm to pil reduction
     #[allow(dead_code)]
     pub fn asm_to_pil_str<T: FieldElement>(source: &str) -> AnalysisASMFile<T> {
-        asm_to_pil::compile(batch_str(source))
+        asm_to_pil::compile(batch_str(source)).unwrap()
     }
 }
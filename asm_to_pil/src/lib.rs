@@ -1,23 +1,33 @@
+use std::collections::BTreeMap;
+
 use ast::asm_analysis::AnalysisASMFile;
 use number::FieldElement;
+use parser_util::SourceError;
 use romgen::generate_machine_rom;
 mod common;
+pub mod rom_layout;
 mod romgen;
 mod vm_to_constrained;
 
 /// Remove all ASM from the machine tree. Takes a tree of virtual or constrained machines and returns a tree of constrained machines
-pub fn compile<T: FieldElement>(file: AnalysisASMFile<T>) -> AnalysisASMFile<T> {
-    AnalysisASMFile {
-        machines: file
-            .machines
-            .into_iter()
-            .map(|(name, m)| {
-                (name, {
-                    let (m, rom) = generate_machine_rom(m);
-                    vm_to_constrained::convert_machine(m, rom)
-                })
-            })
-            .collect(),
+pub fn compile<T: FieldElement>(
+    file: AnalysisASMFile<T>,
+) -> Result<AnalysisASMFile<T>, Vec<SourceError>> {
+    let mut errors = vec![];
+    let mut machines = BTreeMap::new();
+    for (name, m) in file.machines {
+        let (m, rom) = generate_machine_rom(m);
+        match vm_to_constrained::convert_machine(m, rom) {
+            Ok(m) => {
+                machines.insert(name, m);
+            }
+            Err(e) => errors.extend(e),
+        }
+    }
+    if errors.is_empty() {
+        Ok(AnalysisASMFile { machines })
+    } else {
+        Err(errors)
     }
 }
 
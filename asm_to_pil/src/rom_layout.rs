@@ -0,0 +1,88 @@
+//! The layout of the ROM generated for a VM, used to relate its rows to the
+//! statements and source locations of the program without generating it.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+
+use ast::{
+    asm_analysis::{FunctionStatement, Machine},
+    parsed::asm::DebugDirective,
+};
+
+/// The number of rows of the dispatcher, which precede the rows of the functions:
+/// `_start` resets the registers and `_jump_to_operation` jumps to the function.
+pub const DISPATCHER_ROWS: u64 = 2;
+
+/// A location in the source code a program was compiled from,
+/// as given by the debug directives of the program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceLocation {
+    /// The file, if it was declared.
+    pub file: Option<String>,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for SourceLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.file.as_deref().unwrap_or("<unknown>"),
+            self.line,
+            self.column
+        )
+    }
+}
+
+/// A row of the ROM that belongs to a function.
+pub struct FunctionRow<'a, T> {
+    pub pc: u64,
+    /// The name of the function.
+    pub function: &'a str,
+    /// The batch of statements of the row, including labels and debug directives.
+    pub statements: &'a [FunctionStatement<T>],
+    /// The source location given by the last `debug loc` directive before
+    /// the row in the same function.
+    pub location: Option<SourceLocation>,
+}
+
+/// Returns the rows of the functions of a VM with batched functions, in the order in which
+/// the ROM generation places them after the dispatcher.
+pub fn function_rows<T>(machine: &Machine<T>) -> Vec<FunctionRow<'_, T>> {
+    let mut rows = vec![];
+    let mut debug_files = BTreeMap::new();
+    for function in &machine.functions {
+        let mut location = None;
+        for batch in function.body.statements.iter_batches() {
+            for statement in batch.statements() {
+                if let FunctionStatement::DebugDirective(directive) = statement {
+                    match &directive.directive {
+                        DebugDirective::File(id, dir, file) => {
+                            let path = if dir.is_empty() {
+                                file.clone()
+                            } else {
+                                format!("{dir}/{file}")
+                            };
+                            debug_files.insert(*id, path);
+                        }
+                        DebugDirective::Loc(file, line, column) => {
+                            location = Some(SourceLocation {
+                                file: debug_files.get(file).cloned(),
+                                line: *line,
+                                column: *column,
+                            });
+                        }
+                    }
+                }
+            }
+            rows.push(FunctionRow {
+                pc: DISPATCHER_ROWS + rows.len() as u64,
+                function: &function.name,
+                statements: batch.statements(),
+                location: location.clone(),
+            });
+        }
+    }
+    rows
+}
@@ -15,7 +15,8 @@ use ast::{
 use number::FieldElement;
 
 use crate::{
-    common::{input_at, instruction_flag, output_at, RESET_NAME, RETURN_NAME},
+    common::{input_at, output_at, RESET_NAME},
+    rom_layout::DISPATCHER_ROWS,
     utils::{
         parse_function_statement, parse_instruction_definition, parse_pil_statement,
         parse_register_declaration,
@@ -82,7 +83,6 @@ pub fn generate_machine_rom<T: FieldElement>(
                     .collect::<Vec<_>>()
                     .join(", ")
             )),
-            parse_instruction_definition(&format!("instr _loop {{ {pc}' = {pc} }}")),
         ];
 
         machine.instructions.extend(embedded_instructions);
@@ -102,6 +102,7 @@ pub fn generate_machine_rom<T: FieldElement>(
             Batch::from(vec![parse_function_statement("_jump_to_operation;")])
                 .reason(IncompatibleSet::from(Incompatible::Label)),
         ]);
+        assert_eq!(rom.len() as u64, DISPATCHER_ROWS);
 
         // the number of inputs is the max of the number of inputs needed in each function
         let input_count = machine
@@ -199,37 +200,12 @@ pub fn generate_machine_rom<T: FieldElement>(
             rom.extend(batches);
         }
 
-        // add the sink which can be used to fill the rest of the table
-        let sink_id = T::from(rom.len() as u64);
-
-        rom.extend(vec![Batch::from(vec![
-            parse_function_statement("_sink::"),
-            parse_function_statement("_loop;"),
-        ])]);
-
-        // TODO: the following is necessary because of witgen, it can be removed once witgen can call the infinite loop itself
-        // we get the location of the sink so that witgen jumps to it after the first call is done
-        // this constrains the VM to being able to execute only one call, which will be fixed in the future
-        let latch = instruction_flag(RETURN_NAME);
-        let sigma = "_sigma";
-        let first_step = "_romgen_first_step";
-
+        // inject the function_id. Once a function returns, the machine goes back to the dispatcher.
+        // Calling function `0` jumps to the dispatcher again, which keeps the machine idle.
         machine.constraints.push(PilBlock {
             start: 0,
-            statements: vec![
-                // inject the function_id
-                parse_pil_statement(&format!("col witness {function_id}")),
-                // declare `_sigma` as the sum of the latch, will be 0 and then 1 after the end of the first call
-                parse_pil_statement(&format!("col witness {sigma}")),
-                parse_pil_statement(&format!("col fixed {first_step} = [1] + [0]*")),
-                parse_pil_statement(&format!(
-                    "{sigma}' = (1 - {first_step}') * ({sigma} + {latch})"
-                )),
-                // once `_sigma` is 1, constrain `_function_id` to the label of the sink
-                parse_pil_statement(&format!("{sigma} * ({function_id} - {sink_id}) = 0")),
-            ],
+            statements: vec![parse_pil_statement(&format!("col witness {function_id}"))],
         });
-        ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////
 
         machine.function_id = Some(function_id.into());
 
@@ -289,9 +265,6 @@ _reset;
 // END BATCH Unimplemented
 _jump_to_operation;
 // END BATCH Label
-_sink::
-_loop;
-// END BATCH
 "#
             .replace('\t', "    ")
             .trim()
@@ -330,9 +303,6 @@ _jump_to_operation;
 _identity::
 return _input_0;
 // END BATCH Label
-_sink::
-_loop;
-// END BATCH
 "#
             .replace('\t', "    ")
             .trim()
@@ -396,9 +366,6 @@ assert_zero _input_0;
 // END BATCH
 return 0;
 // END BATCH Label
-_sink::
-_loop;
-// END BATCH
 "#
             .replace('\t', "    ")
             .trim()
@@ -20,10 +20,14 @@ use ast::{
 };
 
 use number::FieldElement;
+use parser_util::SourceError;
 
 use crate::common::{instruction_flag, return_instruction, RETURN_NAME};
 
-pub fn convert_machine<T: FieldElement>(machine: Machine<T>, rom: Option<Rom<T>>) -> Machine<T> {
+pub fn convert_machine<T: FieldElement>(
+    machine: Machine<T>,
+    rom: Option<Rom<T>>,
+) -> Result<Machine<T>, Vec<SourceError>> {
     let output_count = machine
         .functions
         .iter()
@@ -73,23 +77,33 @@ impl<T: FieldElement> ASMPILConverter<T> {
         }
     }
 
-    fn convert_machine(mut self, mut input: Machine<T>, rom: Option<Rom<T>>) -> Machine<T> {
+    fn convert_machine(
+        mut self,
+        mut input: Machine<T>,
+        rom: Option<Rom<T>>,
+    ) -> Result<Machine<T>, Vec<SourceError>> {
         if !input.has_pc() {
             assert!(rom.is_none());
-            return input;
+            return Ok(input);
         }
 
+        let mut errors = vec![];
+
         // turn registers into constraints
         for reg in input.registers.drain(..) {
             self.handle_register_declaration(reg);
         }
 
         // turn internal instructions into constraints and only keep external instructions
-        input.instructions = input
-            .instructions
-            .drain(..)
-            .filter_map(|instr| self.handle_instruction_def(instr))
-            .collect();
+        let mut external_instructions = vec![];
+        for instr in input.instructions.drain(..) {
+            match self.handle_instruction_def(instr) {
+                Ok(Some(instr)) => external_instructions.push(instr),
+                Ok(None) => {}
+                Err(e) => errors.push(e),
+            }
+        }
+        input.instructions = external_instructions;
 
         // introduce `return` instruction
         assert!(
@@ -98,6 +112,7 @@ impl<T: FieldElement> ASMPILConverter<T> {
                 name: RETURN_NAME.into(),
                 instruction: self.return_instruction()
             })
+            .unwrap()
             .is_none(),
             "return instruction cannot link to an external function"
         );
@@ -152,12 +167,17 @@ impl<T: FieldElement> ASMPILConverter<T> {
         );
 
         for batch in rom.unwrap().statements.into_iter_batches() {
-            self.handle_batch(batch);
+            if let Err(e) = self.handle_batch(batch) {
+                errors.push(e);
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
         }
 
         input.latch = Some(instruction_flag(RETURN_NAME));
 
-        self.translate_code_lines();
+        self.translate_code_lines()?;
 
         self.pil.push(PilStatement::PlookupIdentity(
             0,
@@ -186,14 +206,16 @@ impl<T: FieldElement> ASMPILConverter<T> {
             });
         }
 
-        input
+        Ok(input)
     }
 
-    fn handle_batch(&mut self, batch: Batch<T>) {
+    fn handle_batch(&mut self, batch: Batch<T>) -> Result<(), SourceError> {
         let code_line = batch
             .statements
             .into_iter()
             .map(|s| self.handle_statement(s))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
             .reduce(|mut acc, e| {
                 // we write to the union of the target registers.
                 assert!(acc.write_regs.is_empty());
@@ -213,36 +235,58 @@ impl<T: FieldElement> ASMPILConverter<T> {
             .expect("unexpected empty batch");
 
         self.code_lines.push(code_line);
+        Ok(())
     }
 
-    fn handle_statement(&mut self, statement: FunctionStatement<T>) -> CodeLine<T> {
-        match statement {
+    fn handle_statement(
+        &mut self,
+        statement: FunctionStatement<T>,
+    ) -> Result<CodeLine<T>, SourceError> {
+        let (start, code_line) = match statement {
             FunctionStatement::Assignment(AssignmentStatement {
                 start,
                 lhs,
                 using_reg,
                 rhs,
-            }) => match *rhs {
-                Expression::FunctionCall(c) => {
-                    self.handle_functional_instruction(lhs, using_reg.unwrap(), c.id, c.arguments)
-                }
-                _ => self.handle_assignment(start, lhs, using_reg, *rhs),
-            },
+            }) => (
+                start,
+                match *rhs {
+                    Expression::FunctionCall(c) => self.handle_functional_instruction(
+                        lhs,
+                        using_reg.unwrap(),
+                        c.id,
+                        c.arguments,
+                    ),
+                    _ => self.handle_assignment(lhs, using_reg, *rhs),
+                },
+            ),
             FunctionStatement::Instruction(InstructionStatement {
+                start,
                 instruction,
                 inputs,
-                ..
-            }) => self.handle_instruction(instruction, inputs),
-            FunctionStatement::Label(LabelStatement { name, .. }) => CodeLine {
-                labels: [name].into(),
-                ..Default::default()
-            },
-            FunctionStatement::DebugDirective(d) => CodeLine {
-                debug_directives: vec![d],
-                ..Default::default()
-            },
-            FunctionStatement::Return(r) => self.handle_instruction(RETURN_NAME.into(), r.values),
-        }
+            }) => (start, self.handle_instruction(instruction, inputs)),
+            FunctionStatement::Label(LabelStatement { start, name }) => (
+                start,
+                Ok(CodeLine {
+                    labels: [name].into(),
+                    ..Default::default()
+                }),
+            ),
+            FunctionStatement::DebugDirective(d) => (
+                d.start,
+                Ok(CodeLine {
+                    debug_directives: vec![d],
+                    ..Default::default()
+                }),
+            ),
+            FunctionStatement::Return(r) => (
+                r.start,
+                self.handle_instruction(RETURN_NAME.into(), r.values),
+            ),
+        };
+        code_line
+            .map(|code_line| CodeLine { start, ..code_line })
+            .map_err(|message| SourceError::at(start, message))
     }
 
     fn handle_register_declaration(
@@ -295,7 +339,7 @@ impl<T: FieldElement> ASMPILConverter<T> {
     fn handle_instruction_def(
         &mut self,
         s: InstructionDefinitionStatement<T>,
-    ) -> Option<InstructionDefinitionStatement<T>> {
+    ) -> Result<Option<InstructionDefinitionStatement<T>>, SourceError> {
         let instruction_name = s.name.clone();
         let instruction_flag = format!("instr_{instruction_name}");
         self.create_witness_fixed_pair(s.start, &instruction_flag);
@@ -329,12 +373,19 @@ impl<T: FieldElement> ASMPILConverter<T> {
                 outputs
                     .params
                     .into_iter()
-                    .map(|param| {
-                        assert!(param.ty.is_none(), "output must be a register");
-                        param.name
+                    .map(|param| match param.ty {
+                        None => Ok(param.name),
+                        Some(_) => Err(SourceError::at(
+                            s.start,
+                            format!(
+                                "Output {} of instruction {instruction_name} must be a register",
+                                param.name
+                            ),
+                        )),
                     })
-                    .collect()
+                    .collect::<Result<_, _>>()
             })
+            .transpose()?
             .unwrap_or_default();
 
         let instruction = Instruction { inputs, outputs };
@@ -419,7 +470,10 @@ impl<T: FieldElement> ASMPILConverter<T> {
                                 self.pil.push(statement)
                             }
                             _ => {
-                                panic!("Invalid statement for instruction body: {statement}");
+                                return Err(SourceError::at(
+                                    s.start,
+                                    format!("Invalid statement for instruction body: {statement}"),
+                                ));
                             }
                         }
                     }
@@ -429,28 +483,24 @@ impl<T: FieldElement> ASMPILConverter<T> {
             InstructionBody::External(..) => Some(s),
         };
         self.instructions.insert(instruction_name, instruction);
-        res
+        Ok(res)
     }
 
     fn handle_assignment(
         &mut self,
-        _start: usize,
         write_regs: Vec<String>,
         assign_reg: Option<String>,
         value: Expression<T>,
-    ) -> CodeLine<T> {
+    ) -> Result<CodeLine<T>, String> {
         assert!(write_regs.len() <= 1);
-        assert!(
-            assign_reg.is_some(),
-            "Implicit assign register not yet supported."
-        );
-        let assign_reg = assign_reg.unwrap();
-        let value = self.process_assignment_value(value);
-        CodeLine {
+        let assign_reg =
+            assign_reg.ok_or_else(|| "Implicit assign register not yet supported.".to_string())?;
+        let value = self.process_assignment_value(value)?;
+        Ok(CodeLine {
             write_regs: [(assign_reg.clone(), write_regs)].into_iter().collect(),
             value: [(assign_reg, value)].into(),
             ..Default::default()
-        }
+        })
     }
 
     fn handle_functional_instruction(
@@ -459,123 +509,137 @@ impl<T: FieldElement> ASMPILConverter<T> {
         assign_reg: String,
         instr_name: String,
         args: Vec<Expression<T>>,
-    ) -> CodeLine<T> {
+    ) -> Result<CodeLine<T>, String> {
         assert!(write_regs.len() == 1);
         let instr = &self
             .instructions
             .get(&instr_name)
-            .unwrap_or_else(|| panic!("Instruction not found: {instr_name}"));
-        assert_eq!(instr.outputs.len(), 1);
+            .ok_or_else(|| format!("Instruction not found: {instr_name}"))?;
+        if instr.outputs.len() != 1 {
+            return Err(format!(
+                "The instruction {instr_name} has to have exactly one output to be used in an assignment."
+            ));
+        }
         let output = instr.outputs[0].clone();
-        assert!(
-            output == assign_reg,
-            "The instruction {instr_name} uses the assignment register {output}, but the caller uses {assign_reg} to further process the value.",
-        );
+        if output != assign_reg {
+            return Err(format!(
+                "The instruction {instr_name} uses the assignment register {output}, but the caller uses {assign_reg} to further process the value."
+            ));
+        }
 
         let mut args = args;
         args.push(direct_reference(write_regs.first().unwrap().clone()));
         self.handle_instruction(instr_name, args)
     }
 
-    fn handle_instruction(&mut self, instr_name: String, args: Vec<Expression<T>>) -> CodeLine<T> {
+    fn handle_instruction(
+        &mut self,
+        instr_name: String,
+        args: Vec<Expression<T>>,
+    ) -> Result<CodeLine<T>, String> {
         let instr = &self
             .instructions
             .get(&instr_name)
-            .unwrap_or_else(|| panic!("Instruction not found: {instr_name}"));
-        assert_eq!(
-            instr.inputs.len() + instr.outputs.len(),
-            args.len(),
-            "Called instruction {} with the wrong number of arguments",
-            instr_name
-        );
+            .ok_or_else(|| format!("Instruction not found: {instr_name}"))?;
+        if instr.inputs.len() + instr.outputs.len() != args.len() {
+            return Err(format!(
+                "Called instruction {instr_name} with the wrong number of arguments: expected {}, found {}",
+                instr.inputs.len() + instr.outputs.len(),
+                args.len()
+            ));
+        }
 
         let mut args = args.into_iter();
 
-        let (value, instruction_literal_args): (BTreeMap<_, _>, Vec<_>) =
-            instr.inputs.iter().zip(&mut args).fold(
-                Default::default(),
-                |(mut value, mut instruction_literal_arg), (input, a)| {
-                    match input {
-                        Input::Register(reg) => {
-                            // We read a value into the assignment register "reg".
-                            assert!(!value.contains_key(reg));
-                            value.insert(reg.clone(), self.process_assignment_value(a));
+        let mut value = BTreeMap::new();
+        let mut instruction_literal_args = vec![];
+        for (input, a) in instr.inputs.iter().zip(&mut args) {
+            match input {
+                Input::Register(reg) => {
+                    // We read a value into the assignment register "reg".
+                    assert!(!value.contains_key(reg));
+                    value.insert(reg.clone(), self.process_assignment_value(a)?);
+                }
+                Input::Literal(name, LiteralKind::Label) => {
+                    if let Expression::PolynomialReference(r) = a {
+                        instruction_literal_args
+                            .push(InstructionLiteralArg::LabelRef(r.name().into()));
+                    } else {
+                        return Err(format!(
+                            "Expected a label for parameter {name} of {instr_name}, received {a}"
+                        ));
+                    }
+                }
+                Input::Literal(_, LiteralKind::UnsignedConstant) => {
+                    // TODO evaluate expression
+                    if let Expression::Number(n) = a {
+                        if !n.is_in_lower_half() {
+                            return Err(format!(
+                                "Number passed to unsigned parameter is negative or too large: {n}"
+                            ));
                         }
-                        Input::Literal(_, LiteralKind::Label) => {
-                            if let Expression::PolynomialReference(r) = a {
-                                instruction_literal_arg
-                                    .push(InstructionLiteralArg::LabelRef(r.name().into()));
-                            } else {
-                                panic!();
-                            }
+                        instruction_literal_args.push(InstructionLiteralArg::Number(n));
+                    } else {
+                        return Err(format!("expected unsigned number, received {a}"));
+                    }
+                }
+                Input::Literal(_, LiteralKind::SignedConstant) => {
+                    // TODO evaluate expression
+                    match a {
+                        Expression::Number(n) => {
+                            instruction_literal_args.push(InstructionLiteralArg::Number(n))
                         }
-                        Input::Literal(_, LiteralKind::UnsignedConstant) => {
-                            // TODO evaluate expression
-                            if let Expression::Number(n) = a {
-                                assert!(n.is_in_lower_half(), "Number passed to unsigned parameter is negative or too large: {n}");
-                                instruction_literal_arg.push(InstructionLiteralArg::Number(n));
-                            } else {
-                                panic!("expected unsigned number, received {}", a);
+                        Expression::UnaryOperation(UnaryOperator::Minus, expr) => match *expr {
+                            Expression::Number(n) => {
+                                instruction_literal_args.push(InstructionLiteralArg::Number(-n))
                             }
-                        }
-                        Input::Literal(_, LiteralKind::SignedConstant) => {
-                            // TODO evaluate expression
-                            if let Expression::Number(n) = a {
-                                instruction_literal_arg.push(InstructionLiteralArg::Number(n));
-                            } else if let Expression::UnaryOperation(UnaryOperator::Minus, expr) = a
-                            {
-                                if let Expression::Number(n) = *expr {
-                                    instruction_literal_arg.push(InstructionLiteralArg::Number(-n));
-                                } else {
-                                    panic!();
-                                }
-                            } else {
-                                panic!();
+                            expr => {
+                                return Err(format!("expected signed number, received -{expr}"))
                             }
-                        }
-                    };
-                    (value, instruction_literal_arg)
-                },
-            );
+                        },
+                        a => return Err(format!("expected signed number, received {a}")),
+                    }
+                }
+            };
+        }
 
-        let write_regs: BTreeMap<_, _> = instr
+        let write_regs = instr
             .outputs
             .iter()
             .zip(&mut args)
             .map(|(reg, a)| {
                 // Output a value trough assignment register "reg"
-                if let Expression::PolynomialReference(r) = a {
-                    assert!(!r.shift());
-                    assert!(r.index().is_none());
-                    (reg.clone(), vec![r.name().into()])
-                } else {
-                    panic!("Expected direct register to assign to in instruction call.");
+                match a {
+                    Expression::PolynomialReference(r) if !r.shift() && r.index().is_none() => {
+                        Ok((reg.clone(), vec![r.name().into()]))
+                    }
+                    _ => Err(format!(
+                        "Expected direct register to assign to in instruction call, received {a}"
+                    )),
                 }
             })
-            .collect();
+            .collect::<Result<BTreeMap<_, _>, _>>()?;
 
         assert_eq!(write_regs.len(), instr.outputs.len());
 
-        CodeLine {
+        Ok(CodeLine {
             write_regs,
             instructions: vec![(instr_name.to_string(), instruction_literal_args)],
             value,
             ..Default::default()
-        }
+        })
     }
 
     fn process_assignment_value(
         &self,
         value: Expression<T>,
-    ) -> Vec<(T, AffineExpressionComponent<T>)> {
-        match value {
-            Expression::Constant(_) => panic!(),
-            Expression::PublicReference(_) => panic!(),
-            Expression::FunctionCall(_) => panic!(),
-            Expression::PolynomialReference(reference) => {
-                assert!(reference.namespace().is_none());
-                assert!(reference.index().is_none());
-                assert!(!reference.shift());
+    ) -> Result<Vec<(T, AffineExpressionComponent<T>)>, String> {
+        Ok(match value {
+            Expression::PolynomialReference(reference)
+                if reference.namespace().is_none()
+                    && reference.index().is_none()
+                    && !reference.shift() =>
+            {
                 // TODO check it actually is a register
                 vec![(
                     1.into(),
@@ -583,24 +647,21 @@ impl<T: FieldElement> ASMPILConverter<T> {
                 )]
             }
             Expression::Number(value) => vec![(value, AffineExpressionComponent::Constant)],
-            Expression::String(_) => panic!(),
-            Expression::Tuple(_) => panic!(),
-            Expression::MatchExpression(_, _) => panic!(),
             Expression::FreeInput(expr) => {
                 vec![(1.into(), AffineExpressionComponent::FreeInput(*expr))]
             }
             Expression::BinaryOperation(left, op, right) => match op {
                 BinaryOperator::Add => self.add_assignment_value(
-                    self.process_assignment_value(*left),
-                    self.process_assignment_value(*right),
+                    self.process_assignment_value(*left)?,
+                    self.process_assignment_value(*right)?,
                 ),
                 BinaryOperator::Sub => self.add_assignment_value(
-                    self.process_assignment_value(*left),
-                    self.negate_assignment_value(self.process_assignment_value(*right)),
+                    self.process_assignment_value(*left)?,
+                    self.negate_assignment_value(self.process_assignment_value(*right)?),
                 ),
                 BinaryOperator::Mul => {
-                    let left = self.process_assignment_value(*left);
-                    let right = self.process_assignment_value(*right);
+                    let left = self.process_assignment_value(*left)?;
+                    let right = self.process_assignment_value(*right)?;
                     if let [(f, AffineExpressionComponent::Constant)] = &left[..] {
                         // TODO overflow?
                         right
@@ -613,12 +674,12 @@ impl<T: FieldElement> ASMPILConverter<T> {
                             .map(|(coeff, comp)| (*f * coeff, comp))
                             .collect()
                     } else {
-                        panic!("Multiplication by non-constant.");
+                        return Err("Multiplication by non-constant.".to_string());
                     }
                 }
                 BinaryOperator::Pow => {
-                    let left = self.process_assignment_value(*left);
-                    let right = self.process_assignment_value(*right);
+                    let left = self.process_assignment_value(*left)?;
+                    let right = self.process_assignment_value(*right)?;
                     if let (
                         [(l, AffineExpressionComponent::Constant)],
                         [(r, AffineExpressionComponent::Constant)],
@@ -626,11 +687,11 @@ impl<T: FieldElement> ASMPILConverter<T> {
                     {
                         // TODO overflow?
                         if r.to_arbitrary_integer() > (u32::MAX).into() {
-                            panic!("Exponent too large");
+                            return Err("Exponent too large".to_string());
                         }
                         vec![(l.pow(r.to_integer()), AffineExpressionComponent::Constant)]
                     } else {
-                        panic!("Exponentiation of non-constants.");
+                        return Err("Exponentiation of non-constants.".to_string());
                     }
                 }
                 BinaryOperator::Div
@@ -640,14 +701,16 @@ impl<T: FieldElement> ASMPILConverter<T> {
                 | BinaryOperator::BinaryOr
                 | BinaryOperator::ShiftLeft
                 | BinaryOperator::ShiftRight => {
-                    panic!("Invalid operation in expression {left} {op} {right}")
+                    return Err(format!(
+                        "Invalid operation in expression {left} {op} {right}"
+                    ))
                 }
             },
-            Expression::UnaryOperation(op, expr) => {
-                assert!(op == UnaryOperator::Minus);
-                self.negate_assignment_value(self.process_assignment_value(*expr))
+            Expression::UnaryOperation(UnaryOperator::Minus, expr) => {
+                self.negate_assignment_value(self.process_assignment_value(*expr)?)
             }
-        }
+            value => return Err(format!("Invalid expression in assignment: {value}")),
+        })
     }
 
     fn add_assignment_value(
@@ -703,7 +766,7 @@ impl<T: FieldElement> ASMPILConverter<T> {
 
     /// Translates the code lines to fixed column but also fills
     /// the query hints for the free inputs.
-    fn translate_code_lines(&mut self) {
+    fn translate_code_lines(&mut self) -> Result<(), Vec<SourceError>> {
         self.pil.push(PilStatement::PolynomialConstantDefinition(
             0,
             "p_line".to_string(),
@@ -728,26 +791,33 @@ impl<T: FieldElement> ASMPILConverter<T> {
             .map(|r| (r.clone(), vec![]))
             .collect::<BTreeMap<_, _>>();
 
-        let label_positions = self.compute_label_positions();
+        let label_positions = self.compute_label_positions()?;
+        let mut errors = vec![];
         for (i, line) in self.code_lines.iter().enumerate() {
             for (assign_reg, writes) in &line.write_regs {
                 for reg in writes {
-                    rom_constants
-                        .get_mut(&format!("p_reg_write_{assign_reg}_{reg}"))
-                        .unwrap_or_else(|| {
-                            panic!("Register combination {reg} <={assign_reg}= not found.")
-                        })[i] = 1.into();
+                    match rom_constants.get_mut(&format!("p_reg_write_{assign_reg}_{reg}")) {
+                        Some(values) => values[i] = 1.into(),
+                        None => errors.push(SourceError::at(
+                            line.start,
+                            format!("Register combination {reg} <={assign_reg}= not found."),
+                        )),
+                    }
                 }
             }
             for (assign_reg, value) in &line.value {
                 for (coeff, item) in value {
                     match item {
                         AffineExpressionComponent::Register(reg) => {
-                            rom_constants
-                                .get_mut(&format!("p_read_{assign_reg}_{reg}"))
-                                .unwrap_or_else(|| {
-                                    panic!("Register combination <={assign_reg}= {reg} not found.")
-                                })[i] += *coeff;
+                            match rom_constants.get_mut(&format!("p_read_{assign_reg}_{reg}")) {
+                                Some(values) => values[i] += *coeff,
+                                None => errors.push(SourceError::at(
+                                    line.start,
+                                    format!(
+                                        "Register combination <={assign_reg}= {reg} not found."
+                                    ),
+                                )),
+                            }
                         }
                         AffineExpressionComponent::Constant => {
                             rom_constants
@@ -786,19 +856,28 @@ impl<T: FieldElement> ASMPILConverter<T> {
                     .iter()
                     .zip(self.instructions[instr].literal_arg_names())
                 {
-                    rom_constants
-                        .get_mut(&format!("p_instr_{instr}_param_{}", param.clone()))
-                        .unwrap()[i] = match arg {
-                        InstructionLiteralArg::LabelRef(name) => (*label_positions
-                            .get(name)
-                            .unwrap_or_else(|| panic!("{name} not found in labels"))
-                            as u64)
-                            .into(),
+                    let value = match arg {
+                        InstructionLiteralArg::LabelRef(name) => match label_positions.get(name) {
+                            Some(position) => (*position as u64).into(),
+                            None => {
+                                errors.push(SourceError::at(
+                                    line.start,
+                                    format!("{name} not found in labels"),
+                                ));
+                                continue;
+                            }
+                        },
                         InstructionLiteralArg::Number(n) => *n,
                     };
+                    rom_constants
+                        .get_mut(&format!("p_instr_{instr}_param_{}", param.clone()))
+                        .unwrap()[i] = value;
                 }
             }
         }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
         let pc_name = self.pc_name.clone();
         let free_value_pil = self
             .assignment_register_names()
@@ -829,17 +908,24 @@ impl<T: FieldElement> ASMPILConverter<T> {
                 ),
             ));
         }
+        Ok(())
     }
 
-    fn compute_label_positions(&self) -> HashMap<String, usize> {
-        self.code_lines
-            .iter()
-            .enumerate()
-            .flat_map(|(i, line)| line.labels.iter().map(|l| (l, i)).collect::<Vec<_>>())
-            .fold(HashMap::new(), |mut r, (l, i)| {
-                assert!(r.insert(l.clone(), i).is_none(), "Duplicate label: {l}");
-                r
-            })
+    fn compute_label_positions(&self) -> Result<HashMap<String, usize>, Vec<SourceError>> {
+        let mut positions = HashMap::new();
+        let mut errors = vec![];
+        for (i, line) in self.code_lines.iter().enumerate() {
+            for l in &line.labels {
+                if positions.insert(l.clone(), i).is_some() {
+                    errors.push(SourceError::at(line.start, format!("Duplicate label: {l}")));
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(positions)
+        } else {
+            Err(errors)
+        }
     }
 
     /// Creates a pair of witness and fixed column and matches them in the lookup.
@@ -940,6 +1026,8 @@ impl Instruction {
 // label, assignment, instruction.
 #[derive(Default)]
 struct CodeLine<T> {
+    /// The start of the first statement in the line.
+    start: usize,
     /// Which regular registers to assign to, from which assignment register
     /// Maps assignment register to a vector of regular registers.
     write_regs: BTreeMap<String, Vec<String>>,
@@ -3,7 +3,9 @@ use std::collections::BTreeMap;
 use crate::ast::{Argument, Expression, FunctionOpKind, Register};
 
 pub trait Compiler {
-    fn compile(assemblies: BTreeMap<String, String>) -> String;
+    /// Compiles the given assembly files to a powdr asm program,
+    /// or returns a description of why they cannot be translated.
+    fn compile(assemblies: BTreeMap<String, String>) -> Result<String, String>;
 }
 
 pub fn next_multiple_of_four(x: usize) -> usize {
@@ -172,6 +172,9 @@ pub fn references_in_statement<R: Register, F: FunctionOpKind>(
     let mut ret = BTreeSet::new();
     match statement {
         Statement::Label(_) | Statement::Directive(_, _) => (),
+        // The symbols in the operands of these instructions are names of
+        // control and status registers or memory ordering sets, not labels.
+        Statement::Instruction(instr, _) if instr == "fence" || instr.starts_with("csr") => (),
         Statement::Instruction(_, args) => {
             for arg in args {
                 arg.post_visit_expressions(&mut |expr| {
@@ -215,12 +218,15 @@ fn iterate_basic_block<'a, R: Register, F: FunctionOpKind>(
 fn ends_control_flow<R: Register, F: FunctionOpKind>(s: &Statement<R, F>) -> bool {
     match s {
         Statement::Instruction(instruction, _) => match instruction.as_str() {
-            "li" | "lui" | "la" | "mv" | "add" | "addi" | "sub" | "neg" | "mul" | "mulhu"
-            | "divu" | "xor" | "xori" | "and" | "andi" | "or" | "ori" | "not" | "slli" | "sll"
-            | "srli" | "srl" | "srai" | "seqz" | "snez" | "slt" | "slti" | "sltu" | "sltiu"
-            | "sgtz" | "beq" | "beqz" | "bgeu" | "bltu" | "blt" | "bge" | "bltz" | "blez"
-            | "bgtz" | "bgez" | "bne" | "bnez" | "jal" | "jalr" | "call" | "ecall" | "ebreak"
-            | "lw" | "lb" | "lbu" | "lhu" | "sw" | "sh" | "sb" | "nop" => false,
+            "li" | "lui" | "la" | "mv" | "auipc" | "add" | "addi" | "sub" | "neg" | "mul"
+            | "mulh" | "mulhsu" | "mulhu" | "div" | "divu" | "rem" | "remu" | "xor" | "xori"
+            | "and" | "andi" | "or" | "ori" | "not" | "slli" | "sll" | "srli" | "srl" | "srai"
+            | "sra" | "seqz" | "snez" | "slt" | "slti" | "sltu" | "sltiu" | "sgtz" | "beq"
+            | "beqz" | "bgeu" | "bltu" | "blt" | "bge" | "bltz" | "blez" | "bgtz" | "bgez"
+            | "bne" | "bnez" | "jal" | "jalr" | "call" | "ecall" | "ebreak" | "lw" | "lb"
+            | "lbu" | "lh" | "lhu" | "sw" | "sh" | "sb" | "nop" | "fence" | "fence.i" | "csrr"
+            | "csrw" | "csrs" | "csrc" | "csrwi" | "csrsi" | "csrci" | "csrrw" | "csrrs"
+            | "csrrc" | "csrrwi" | "csrrsi" | "csrrci" => false,
             "j" | "jr" | "tail" | "ret" | "unimp" => true,
             _ => {
                 panic!("Unknown instruction: {instruction}");
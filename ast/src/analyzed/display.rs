@@ -11,7 +11,7 @@ use super::*;
 
 impl<T: Display> Display for Analyzed<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        for (name, value) in &self.constants {
+        for (name, value) in self.constants.iter().sorted_by_key(|(name, _)| *name) {
             writeln!(f, "constant {name} = {value};")?;
         }
 
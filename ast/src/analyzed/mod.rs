@@ -15,7 +15,7 @@ pub use crate::parsed::UnaryOperator;
 
 use util::expr_any;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum StatementIdentifier {
     Definition(String),
     PublicDeclaration(String),
@@ -23,7 +23,7 @@ pub enum StatementIdentifier {
     Identity(usize),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Analyzed<T> {
     /// Constants are not namespaced!
     pub constants: HashMap<String, T>,
@@ -79,6 +79,55 @@ impl<T> Analyzed<T> {
             .collect()
     }
 
+    pub fn public_declarations_in_source_order(&self) -> Vec<&PublicDeclaration> {
+        self.source_order
+            .iter()
+            .filter_map(move |statement| match statement {
+                StatementIdentifier::PublicDeclaration(name) => {
+                    Some(&self.public_declarations[name])
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the names and values of the public declarations in source order,
+    /// read from the given witness columns.
+    /// Fails if a public refers to a column that is not a witness column or to a row
+    /// the column does not have.
+    pub fn public_values(&self, witness: &[(&str, Vec<T>)]) -> Result<Vec<(String, T)>, String>
+    where
+        T: Copy,
+    {
+        self.public_declarations_in_source_order()
+            .into_iter()
+            .map(|decl| {
+                let column = match decl.polynomial.index {
+                    Some(index) => format!("{}[{index}]", decl.polynomial.name),
+                    None => decl.polynomial.name.clone(),
+                };
+                let (_, values) = witness
+                    .iter()
+                    .find(|(name, _)| *name == column)
+                    .ok_or_else(|| {
+                        format!(
+                            "Public {} refers to {column}, which is not a witness column.",
+                            decl.name
+                        )
+                    })?;
+                let value = values.get(decl.index as usize).ok_or_else(|| {
+                    format!(
+                        "Public {} refers to row {} of {column}, which only has {} rows.",
+                        decl.name,
+                        decl.index,
+                        values.len()
+                    )
+                })?;
+                Ok((decl.name.clone(), *value))
+            })
+            .collect()
+    }
+
     fn declaration_type_count(&self, poly_type: PolynomialType) -> usize {
         self.definitions
             .iter()
@@ -177,6 +226,7 @@ impl<T> Analyzed<T> {
             .max()
             .unwrap_or_default()
             + 1;
+        let index = self.identities.len();
         self.identities.push(Identity {
             id,
             kind: IdentityKind::Polynomial,
@@ -190,11 +240,37 @@ impl<T> Analyzed<T> {
                 expressions: vec![],
             },
         });
-        self.source_order
-            .push(StatementIdentifier::Identity(id as usize));
+        self.source_order.push(StatementIdentifier::Identity(index));
         id
     }
 
+    /// Adds a witness column and returns a reference to it.
+    pub fn append_witness_column(
+        &mut self,
+        absolute_name: String,
+        degree: DegreeType,
+        source: SourceRef,
+    ) -> PolynomialReference {
+        let poly = Polynomial {
+            id: self.commitment_count() as u64,
+            source,
+            absolute_name: absolute_name.clone(),
+            poly_type: PolynomialType::Committed,
+            degree,
+            length: None,
+        };
+        let reference = PolynomialReference {
+            name: absolute_name.clone(),
+            poly_id: Some((&poly).into()),
+            index: None,
+            next: false,
+        };
+        self.definitions.insert(absolute_name.clone(), (poly, None));
+        self.source_order
+            .push(StatementIdentifier::Definition(absolute_name));
+        reference
+    }
+
     /// Remove some identities by their index (not their ID).
     /// Does not re-allocate IDs.
     pub fn remove_identities(&mut self, to_remove: &BTreeSet<usize>) {
@@ -234,7 +310,7 @@ impl Polynomial {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum FunctionValueDefinition<T> {
     Mapping(Expression<T>),
     Array(Vec<RepeatedArray<T>>),
@@ -242,7 +318,7 @@ pub enum FunctionValueDefinition<T> {
 }
 
 /// An array of elements that might be repeated.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct RepeatedArray<T> {
     /// The pattern to be repeated
     pattern: Vec<Expression<T>>,
@@ -282,7 +358,7 @@ impl<T> RepeatedArray<T> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PublicDeclaration {
     pub id: u64,
     pub source: SourceRef,
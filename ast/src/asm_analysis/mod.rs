@@ -79,6 +79,12 @@ pub struct Batch<T> {
     reason: Option<IncompatibleSet>,
 }
 
+impl<'a, T> BatchRef<'a, T> {
+    pub fn statements(&self) -> &'a [FunctionStatement<T>] {
+        self.statements
+    }
+}
+
 impl<T> From<Vec<FunctionStatement<T>>> for Batch<T> {
     fn from(statements: Vec<FunctionStatement<T>>) -> Self {
         Self {
@@ -134,7 +140,7 @@ impl<T> FunctionStatements<T> {
     }
 
     /// iterate over the batches by reference
-    fn iter_batches(&self) -> impl Iterator<Item = BatchRef<T>> {
+    pub fn iter_batches(&self) -> impl Iterator<Item = BatchRef<T>> {
         match &self.batches {
             Some(batches) => Either::Left(batches.iter()),
             None => Either::Right(
@@ -2,12 +2,12 @@ use number::AbstractNumberType;
 
 use super::{Expression, PilStatement, SelectedExpressions};
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct ASMFile<T> {
     pub machines: Vec<Machine<T>>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Machine<T> {
     pub start: usize,
     pub name: String,
@@ -15,7 +15,7 @@ pub struct Machine<T> {
     pub statements: Vec<MachineStatement<T>>,
 }
 
-#[derive(Debug, PartialEq, Eq, Default)]
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
 pub struct MachineArguments {
     pub latch: Option<String>,
     pub function_id: Option<String>,
@@ -41,6 +41,28 @@ pub enum PilStatement<T> {
     FunctionCall(usize, String, Vec<Expression<T>>),
 }
 
+impl<T> PilStatement<T> {
+    /// Returns the byte offset of the start of the statement in the source.
+    pub fn start(&self) -> usize {
+        match self {
+            PilStatement::Include(start, _)
+            | PilStatement::Namespace(start, _, _)
+            | PilStatement::PolynomialDefinition(start, _, _)
+            | PilStatement::PublicDeclaration(start, _, _, _)
+            | PilStatement::PolynomialConstantDeclaration(start, _)
+            | PilStatement::PolynomialConstantDefinition(start, _, _)
+            | PilStatement::PolynomialCommitDeclaration(start, _, _)
+            | PilStatement::PolynomialIdentity(start, _)
+            | PilStatement::PlookupIdentity(start, _, _)
+            | PilStatement::PermutationIdentity(start, _, _)
+            | PilStatement::ConnectIdentity(start, _, _)
+            | PilStatement::ConstantDefinition(start, _, _)
+            | PilStatement::MacroDefinition(start, _, _, _, _)
+            | PilStatement::FunctionCall(start, _, _) => *start,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct SelectedExpressions<T> {
     pub selector: Option<Expression<T>>,
@@ -1,6 +1,8 @@
 use std::io::{self};
 
-use crate::{BackendImpl, BackendImplWithSetup, Proof};
+use crate::{
+    AggregatedProof, AggregationInput, BackendImpl, BackendImplWithSetup, Error, EvmVerifier, Proof,
+};
 use ast::analyzed::Analyzed;
 use halo2::Halo2Prover;
 use number::{DegreeType, FieldElement};
@@ -17,13 +19,66 @@ impl<T: FieldElement> BackendImpl<T> for Halo2Prover {
         fixed: &[(&str, Vec<T>)],
         witness: &[(&str, Vec<T>)],
         prev_proof: Option<Proof>,
-    ) -> (Option<Proof>, Option<String>) {
+    ) -> Result<(Option<Proof>, Option<String>), Error> {
         let proof = match prev_proof {
             Some(proof) => self.prove_aggr(pil, fixed, witness, proof),
             None => self.prove_ast(pil, fixed, witness),
-        };
+        }
+        .map_err(Error::ProvingFailed)?;
+
+        Ok((Some(proof), None))
+    }
+
+    fn verify(
+        &self,
+        pil: &Analyzed<T>,
+        fixed: &[(&str, Vec<T>)],
+        proof: &[u8],
+        publics: &[T],
+    ) -> Result<(), Error> {
+        self.verify(pil, fixed, proof, publics)
+            .map_err(Error::VerificationFailed)
+    }
+
+    /// Only aggregated proofs can be verified in the EVM, the transcript of the other
+    /// proofs is not supported by the verifier contract.
+    fn export_evm_verifier(
+        &self,
+        pil: &Analyzed<T>,
+        fixed: &[(&str, Vec<T>)],
+    ) -> Result<EvmVerifier, Error> {
+        self.aggregation_evm_verifier(pil, fixed)
+            .map(compile_evm_verifier)
+            .map_err(Error::EvmVerifierFailed)
+    }
 
-        (Some(proof), None)
+    fn evm_calldata(&self, proof: &[u8], publics: &[T]) -> Result<Vec<u8>, Error> {
+        Halo2Prover::aggregation_evm_calldata(proof, publics).map_err(Error::EvmVerifierFailed)
+    }
+
+    fn aggregate(
+        &self,
+        proofs: Vec<AggregationInput<T>>,
+        arity: usize,
+    ) -> Result<AggregatedProof<T>, Error> {
+        let proofs = proofs
+            .into_iter()
+            .map(|input| halo2::AggregationInput {
+                pil: input.pil,
+                fixed: input.fixed,
+                proof: input.proof,
+                publics: input.publics,
+            })
+            .collect();
+        let aggregated = self
+            .aggregate(proofs, arity)
+            .map_err(Error::AggregationFailed)?;
+
+        Ok(AggregatedProof {
+            proof: aggregated.proof,
+            instances: aggregated.instances,
+            verifier: compile_evm_verifier(aggregated.verifier),
+        })
     }
 }
 
@@ -36,6 +91,25 @@ impl<T: FieldElement> BackendImplWithSetup<T> for halo2::Halo2Prover {
     fn write_setup(&self, mut output: &mut dyn io::Write) -> Result<(), io::Error> {
         self.write_setup(&mut output)
     }
+
+    fn write_keys(
+        &self,
+        pil: &Analyzed<T>,
+        fixed: &[(&str, Vec<T>)],
+        mut output: &mut dyn io::Write,
+    ) -> Result<(), io::Error> {
+        self.write_keys(pil, fixed, &mut output)
+    }
+
+    fn read_keys(
+        &self,
+        pil: &Analyzed<T>,
+        fixed: &[(&str, Vec<T>)],
+        mut input: &mut dyn io::Read,
+    ) -> Result<(), Error> {
+        self.read_keys(pil, fixed, &mut input)
+            .map_err(Error::InvalidKeys)
+    }
 }
 
 pub struct Halo2Mock;
@@ -50,13 +124,100 @@ impl<T: FieldElement> BackendImpl<T> for Halo2Mock {
         fixed: &[(&str, Vec<T>)],
         witness: &[(&str, Vec<T>)],
         prev_proof: Option<Proof>,
-    ) -> (Option<Proof>, Option<String>) {
+    ) -> Result<(Option<Proof>, Option<String>), Error> {
+        if prev_proof.is_some() {
+            return Err(Error::NoAggregationAvailable);
+        }
+
+        halo2::mock_prove(pil, fixed, witness).map_err(Error::ProvingFailed)?;
+
+        Ok((None, None))
+    }
+}
+
+/// Halo2 backend generating proofs that can be verified in the EVM, but not aggregated.
+pub struct Halo2Evm(Halo2Prover);
+impl<T: FieldElement> BackendImpl<T> for Halo2Evm {
+    fn new(degree: DegreeType) -> Self {
+        Self(<Halo2Prover as BackendImpl<T>>::new(degree))
+    }
+
+    fn prove(
+        &self,
+        pil: &Analyzed<T>,
+        fixed: &[(&str, Vec<T>)],
+        witness: &[(&str, Vec<T>)],
+        prev_proof: Option<Proof>,
+    ) -> Result<(Option<Proof>, Option<String>), Error> {
         if prev_proof.is_some() {
-            unimplemented!("Halo2Mock backend does not support aggregation");
+            return Err(Error::NoAggregationAvailable);
         }
 
-        halo2::mock_prove(pil, fixed, witness);
+        let proof = self
+            .0
+            .prove_ast_evm(pil, fixed, witness)
+            .map_err(Error::ProvingFailed)?;
+
+        Ok((Some(proof), None))
+    }
+
+    fn verify(
+        &self,
+        pil: &Analyzed<T>,
+        fixed: &[(&str, Vec<T>)],
+        proof: &[u8],
+        publics: &[T],
+    ) -> Result<(), Error> {
+        self.0
+            .verify_evm(pil, fixed, proof, publics)
+            .map_err(Error::VerificationFailed)
+    }
+
+    fn export_evm_verifier(
+        &self,
+        pil: &Analyzed<T>,
+        fixed: &[(&str, Vec<T>)],
+    ) -> Result<EvmVerifier, Error> {
+        self.0
+            .evm_verifier(pil, fixed)
+            .map(compile_evm_verifier)
+            .map_err(Error::EvmVerifierFailed)
+    }
+
+    fn evm_calldata(&self, proof: &[u8], publics: &[T]) -> Result<Vec<u8>, Error> {
+        Ok(Halo2Prover::evm_calldata(proof, publics))
+    }
+}
+
+impl<T: FieldElement> BackendImplWithSetup<T> for Halo2Evm {
+    fn new_from_setup(input: &mut dyn io::Read) -> Result<Self, io::Error> {
+        <Halo2Prover as BackendImplWithSetup<T>>::new_from_setup(input).map(Self)
+    }
+
+    fn write_setup(&self, output: &mut dyn io::Write) -> Result<(), io::Error> {
+        <Halo2Prover as BackendImplWithSetup<T>>::write_setup(&self.0, output)
+    }
+
+    fn write_keys(
+        &self,
+        pil: &Analyzed<T>,
+        fixed: &[(&str, Vec<T>)],
+        output: &mut dyn io::Write,
+    ) -> Result<(), io::Error> {
+        BackendImplWithSetup::write_keys(&self.0, pil, fixed, output)
+    }
 
-        (None, None)
+    fn read_keys(
+        &self,
+        pil: &Analyzed<T>,
+        fixed: &[(&str, Vec<T>)],
+        input: &mut dyn io::Read,
+    ) -> Result<(), Error> {
+        BackendImplWithSetup::read_keys(&self.0, pil, fixed, input)
     }
 }
+
+fn compile_evm_verifier(yul: String) -> EvmVerifier {
+    let bytecode = halo2::compile_yul(&yul);
+    EvmVerifier { yul, bytecode }
+}
@@ -1,8 +1,9 @@
 #[cfg(feature = "halo2")]
 mod halo2_impl;
+mod pil_checker;
 mod pilcom_cli;
 
-use ast::analyzed::Analyzed;
+use ast::analyzed::{Analyzed, FunctionValueDefinition, Polynomial};
 use number::{DegreeType, FieldElement};
 use std::{io, marker::PhantomData};
 use strum::{Display, EnumString, EnumVariantNames};
@@ -15,26 +16,56 @@ pub enum BackendType {
     #[cfg(feature = "halo2")]
     #[strum(serialize = "halo2-mock")]
     Halo2Mock,
+    #[cfg(feature = "halo2")]
+    #[strum(serialize = "halo2-evm")]
+    Halo2Evm,
     #[strum(serialize = "pilcom-cli")]
     PilcomCli,
+    #[strum(serialize = "pil-check")]
+    PilCheck,
 }
 
+/// The maximum degree of the identities passed to halo2. The circuit builder multiplies
+/// every identity by a selector, so the constraints of the circuit have one degree more.
+#[cfg(feature = "halo2")]
+const HALO2_MAX_DEGREE: usize = 3;
+
 impl BackendType {
+    /// Returns the maximum degree of identities the backend should be given, if it is limited.
+    /// Identities of a larger degree are split using auxiliary witness columns.
+    pub fn max_degree(&self) -> Option<usize> {
+        match self {
+            #[cfg(feature = "halo2")]
+            BackendType::Halo2 | BackendType::Halo2Mock | BackendType::Halo2Evm => {
+                Some(HALO2_MAX_DEGREE)
+            }
+            BackendType::PilcomCli | BackendType::PilCheck => None,
+        }
+    }
+
     pub fn factory<T: FieldElement>(&self) -> &'static dyn BackendFactory<T> {
         #[cfg(feature = "halo2")]
         const HALO2_FACTORY: WithSetupFactory<halo2::Halo2Prover> = WithSetupFactory(PhantomData);
         #[cfg(feature = "halo2")]
         const HALO2_MOCK_FACTORY: WithoutSetupFactory<halo2_impl::Halo2Mock> =
             WithoutSetupFactory(PhantomData);
+        #[cfg(feature = "halo2")]
+        const HALO2_EVM_FACTORY: WithSetupFactory<halo2_impl::Halo2Evm> =
+            WithSetupFactory(PhantomData);
         const PILCOM_CLI_FACTORY: WithoutSetupFactory<pilcom_cli::PilcomCli> =
             WithoutSetupFactory(PhantomData);
+        const PIL_CHECK_FACTORY: WithoutSetupFactory<pil_checker::PilChecker> =
+            WithoutSetupFactory(PhantomData);
 
         match self {
             #[cfg(feature = "halo2")]
             BackendType::Halo2 => &HALO2_FACTORY,
             #[cfg(feature = "halo2")]
             BackendType::Halo2Mock => &HALO2_MOCK_FACTORY,
+            #[cfg(feature = "halo2")]
+            BackendType::Halo2Evm => &HALO2_EVM_FACTORY,
             BackendType::PilcomCli => &PILCOM_CLI_FACTORY,
+            BackendType::PilCheck => &PIL_CHECK_FACTORY,
         }
     }
 }
@@ -64,13 +95,67 @@ impl<F: FieldElement, B: BackendImpl<F>> Backend<F> for ConcreteBackendWithoutSe
         fixed: &[(&str, Vec<F>)],
         witness: &[(&str, Vec<F>)],
         prev_proof: Option<Proof>,
-    ) -> (Option<Proof>, Option<String>) {
+    ) -> Result<(Option<Proof>, Option<String>), Error> {
+        validate_columns(pil, fixed, witness)?;
         self.0.prove(pil, fixed, witness, prev_proof)
     }
 
+    fn verify(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(&str, Vec<F>)],
+        proof: &[u8],
+        publics: &[F],
+    ) -> Result<(), Error> {
+        validate_columns(pil, fixed, &[])?;
+        self.0.verify(pil, fixed, proof, publics)
+    }
+
     fn write_setup(&self, _output: &mut dyn io::Write) -> Result<(), Error> {
         Err(Error::NoSetupAvailable)
     }
+
+    fn export_evm_verifier(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(&str, Vec<F>)],
+    ) -> Result<EvmVerifier, Error> {
+        validate_columns(pil, fixed, &[])?;
+        self.0.export_evm_verifier(pil, fixed)
+    }
+
+    fn evm_calldata(&self, proof: &[u8], publics: &[F]) -> Result<Vec<u8>, Error> {
+        self.0.evm_calldata(proof, publics)
+    }
+
+    fn aggregate(
+        &self,
+        proofs: Vec<AggregationInput<F>>,
+        arity: usize,
+    ) -> Result<AggregatedProof<F>, Error> {
+        for proof in &proofs {
+            validate_columns(proof.pil, proof.fixed, &[])?;
+        }
+        self.0.aggregate(proofs, arity)
+    }
+
+    fn write_keys(
+        &self,
+        _pil: &Analyzed<F>,
+        _fixed: &[(&str, Vec<F>)],
+        _output: &mut dyn io::Write,
+    ) -> Result<(), Error> {
+        Err(Error::NoKeysAvailable)
+    }
+
+    fn read_keys(
+        &self,
+        _pil: &Analyzed<F>,
+        _fixed: &[(&str, Vec<F>)],
+        _input: &mut dyn io::Read,
+    ) -> Result<(), Error> {
+        Err(Error::NoKeysAvailable)
+    }
 }
 
 /// Factory for backends with setup.
@@ -102,25 +187,170 @@ impl<F: FieldElement, B: BackendImplWithSetup<F>> Backend<F> for ConcreteBackend
         fixed: &[(&str, Vec<F>)],
         witness: &[(&str, Vec<F>)],
         prev_proof: Option<Proof>,
-    ) -> (Option<Proof>, Option<String>) {
+    ) -> Result<(Option<Proof>, Option<String>), Error> {
+        validate_columns(pil, fixed, witness)?;
         self.0.prove(pil, fixed, witness, prev_proof)
     }
 
+    fn verify(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(&str, Vec<F>)],
+        proof: &[u8],
+        publics: &[F],
+    ) -> Result<(), Error> {
+        validate_columns(pil, fixed, &[])?;
+        self.0.verify(pil, fixed, proof, publics)
+    }
+
     fn write_setup(&self, output: &mut dyn io::Write) -> Result<(), Error> {
         Ok(self.0.write_setup(output)?)
     }
+
+    fn export_evm_verifier(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(&str, Vec<F>)],
+    ) -> Result<EvmVerifier, Error> {
+        validate_columns(pil, fixed, &[])?;
+        self.0.export_evm_verifier(pil, fixed)
+    }
+
+    fn evm_calldata(&self, proof: &[u8], publics: &[F]) -> Result<Vec<u8>, Error> {
+        self.0.evm_calldata(proof, publics)
+    }
+
+    fn aggregate(
+        &self,
+        proofs: Vec<AggregationInput<F>>,
+        arity: usize,
+    ) -> Result<AggregatedProof<F>, Error> {
+        for proof in &proofs {
+            validate_columns(proof.pil, proof.fixed, &[])?;
+        }
+        self.0.aggregate(proofs, arity)
+    }
+
+    fn write_keys(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(&str, Vec<F>)],
+        output: &mut dyn io::Write,
+    ) -> Result<(), Error> {
+        validate_columns(pil, fixed, &[])?;
+        Ok(self.0.write_keys(pil, fixed, output)?)
+    }
+
+    fn read_keys(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(&str, Vec<F>)],
+        input: &mut dyn io::Read,
+    ) -> Result<(), Error> {
+        validate_columns(pil, fixed, &[])?;
+        self.0.read_keys(pil, fixed, input)
+    }
+}
+
+/// Checks that the given fixed and witness columns are columns of `pil`, in source order
+/// and with the degree of their namespace, so that columns generated for a different
+/// program are rejected. Columns may be missing, e.g. if the witness was not generated.
+fn validate_columns<F: FieldElement>(
+    pil: &Analyzed<F>,
+    fixed: &[(&str, Vec<F>)],
+    witness: &[(&str, Vec<F>)],
+) -> Result<(), Error> {
+    let validate = |kind: &str,
+                    polys: Vec<&(Polynomial, Option<FunctionValueDefinition<F>>)>,
+                    columns: &[(&str, Vec<F>)]| {
+        // Each element of an array is a column of its own.
+        let mut expected = polys.into_iter().flat_map(|(poly, _)| {
+            let names = match poly.length {
+                Some(length) => (0..length)
+                    .map(|i| format!("{}[{i}]", poly.absolute_name))
+                    .collect(),
+                None => vec![poly.absolute_name.clone()],
+            };
+            names.into_iter().map(|name| (name, poly.degree))
+        });
+        columns.iter().try_for_each(|(name, values)| {
+            let (_, degree) = expected
+                .find(|(expected, _)| expected == name)
+                .ok_or_else(|| {
+                    Error::InvalidColumns(format!(
+                        "{name} is not a {kind} column of the PIL or is out of order"
+                    ))
+                })?;
+            if values.len() as DegreeType != degree {
+                return Err(Error::InvalidColumns(format!(
+                    "the {kind} column {name} has {} rows, but its degree is {degree}",
+                    values.len()
+                )));
+            }
+            Ok(())
+        })
+    };
+    validate("fixed", pil.constant_polys_in_source_order(), fixed)?;
+    validate("witness", pil.committed_polys_in_source_order(), witness)
 }
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("input/output error")]
     IO(#[from] std::io::Error),
+    #[error("proof generation failed: {0}")]
+    ProvingFailed(String),
     #[error("the backend has not setup operations")]
     NoSetupAvailable,
+    #[error("the backend has no proving and verifying keys")]
+    NoKeysAvailable,
+    #[error("the keys cannot be used: {0}")]
+    InvalidKeys(String),
+    #[error("the backend does not support proof verification")]
+    NoVerificationAvailable,
+    #[error("proof verification failed: {0}")]
+    VerificationFailed(String),
+    #[error("the backend has no EVM verifier")]
+    NoEvmVerifierAvailable,
+    #[error("the EVM verifier cannot be generated: {0}")]
+    EvmVerifierFailed(String),
+    #[error("the backend does not support proof aggregation")]
+    NoAggregationAvailable,
+    #[error("proof aggregation failed: {0}")]
+    AggregationFailed(String),
+    #[error("the columns do not belong to the PIL: {0}")]
+    InvalidColumns(String),
 }
 
 pub type Proof = Vec<u8>;
 
+/// A contract verifying proofs in the EVM.
+pub struct EvmVerifier {
+    /// The Yul source of the contract.
+    pub yul: String,
+    /// The deployment code of the contract, None if the Solidity compiler is not installed.
+    pub bytecode: Option<Vec<u8>>,
+}
+
+/// A proof generated by `prove` for a PIL and fixed columns, to be aggregated.
+pub struct AggregationInput<'a, F> {
+    pub pil: &'a Analyzed<F>,
+    pub fixed: &'a [(&'a str, Vec<F>)],
+    pub proof: Proof,
+    pub publics: Vec<F>,
+}
+
+/// The root proof of an aggregation tree.
+pub struct AggregatedProof<F> {
+    /// The proof, preceded by the accumulator as expected by `verifier`.
+    pub proof: Proof,
+    /// The public instances of the proof: the accumulator, followed by the public
+    /// values of the aggregated proofs.
+    pub instances: Vec<F>,
+    /// The contract verifying the proof in the EVM.
+    pub verifier: EvmVerifier,
+}
+
 /*
     Bellow are the public interface traits. They are implemented in this
     module, wrapping the traits implemented by each backend.
@@ -133,17 +363,67 @@ pub trait Backend<F: FieldElement> {
     /// If prev_proof is provided, proof aggregation is performed.
     ///
     /// Returns the generated proof, and the string serialization of the
-    /// constraints.
+    /// constraints, or an error if the witness does not satisfy the constraints
+    /// and the backend checks them.
     fn prove(
         &self,
         pil: &Analyzed<F>,
         fixed: &[(&str, Vec<F>)],
         witness: &[(&str, Vec<F>)],
         prev_proof: Option<Proof>,
-    ) -> (Option<Proof>, Option<String>);
+    ) -> Result<(Option<Proof>, Option<String>), Error>;
+
+    /// Verify a proof previously generated by `prove` for the same PIL
+    /// and fixed columns, given its public values.
+    fn verify(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(&str, Vec<F>)],
+        proof: &[u8],
+        publics: &[F],
+    ) -> Result<(), Error>;
 
     /// Write the prover setup to a file, so that it can be loaded later.
     fn write_setup(&self, output: &mut dyn io::Write) -> Result<(), Error>;
+
+    /// Export a contract verifying the proofs generated by `prove` for the same
+    /// PIL and fixed columns in the EVM.
+    fn export_evm_verifier(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(&str, Vec<F>)],
+    ) -> Result<EvmVerifier, Error>;
+
+    /// Encode the calldata to call the contract of `export_evm_verifier` with,
+    /// given a proof and its public values.
+    fn evm_calldata(&self, proof: &[u8], publics: &[F]) -> Result<Vec<u8>, Error>;
+
+    /// Aggregate proofs generated by `prove`, possibly for different PILs, into a
+    /// single proof, in a tree where every node aggregates at most `arity` proofs.
+    fn aggregate(
+        &self,
+        proofs: Vec<AggregationInput<F>>,
+        arity: usize,
+    ) -> Result<AggregatedProof<F>, Error>;
+
+    /// Write the proving and verifying keys for the PIL and fixed columns to a file,
+    /// generating them if needed, so that they can be loaded later.
+    fn write_keys(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(&str, Vec<F>)],
+        output: &mut dyn io::Write,
+    ) -> Result<(), Error>;
+
+    /// Load keys written by `write_keys`, which are then used by `prove` and `verify`
+    /// instead of generating them. Fails if the keys were generated for a different
+    /// PIL, different fixed columns or a different setup.
+    fn read_keys(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(&str, Vec<F>)],
+        input: &mut dyn io::Read,
+    ) -> Result<(), Error>;
 }
 
 /// Dynamic interface for a backend factory.
@@ -169,7 +449,37 @@ trait BackendImpl<F: FieldElement> {
         fixed: &[(&str, Vec<F>)],
         witness: &[(&str, Vec<F>)],
         prev_proof: Option<Proof>,
-    ) -> (Option<Proof>, Option<String>);
+    ) -> Result<(Option<Proof>, Option<String>), Error>;
+
+    fn verify(
+        &self,
+        _pil: &Analyzed<F>,
+        _fixed: &[(&str, Vec<F>)],
+        _proof: &[u8],
+        _publics: &[F],
+    ) -> Result<(), Error> {
+        Err(Error::NoVerificationAvailable)
+    }
+
+    fn export_evm_verifier(
+        &self,
+        _pil: &Analyzed<F>,
+        _fixed: &[(&str, Vec<F>)],
+    ) -> Result<EvmVerifier, Error> {
+        Err(Error::NoEvmVerifierAvailable)
+    }
+
+    fn evm_calldata(&self, _proof: &[u8], _publics: &[F]) -> Result<Vec<u8>, Error> {
+        Err(Error::NoEvmVerifierAvailable)
+    }
+
+    fn aggregate(
+        &self,
+        _proofs: Vec<AggregationInput<F>>,
+        _arity: usize,
+    ) -> Result<AggregatedProof<F>, Error> {
+        Err(Error::NoAggregationAvailable)
+    }
 }
 
 /// Trait implemented by backends that have a setup phase that must be saved to
@@ -183,4 +493,20 @@ where
 
     /// Write the setup to a file.
     fn write_setup(&self, output: &mut dyn io::Write) -> Result<(), io::Error>;
+
+    /// Write the proving and verifying keys to a file.
+    fn write_keys(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(&str, Vec<F>)],
+        output: &mut dyn io::Write,
+    ) -> Result<(), io::Error>;
+
+    /// Load the proving and verifying keys from a file.
+    fn read_keys(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(&str, Vec<F>)],
+        input: &mut dyn io::Read,
+    ) -> Result<(), Error>;
 }
@@ -0,0 +1,639 @@
+//! A backend that does not generate a proof, but directly checks that the
+//! fixed and witness columns satisfy all identities of the PIL.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+
+use ast::analyzed::{
+    Analyzed, Expression, FunctionValueDefinition, Identity, IdentityKind, PolynomialReference,
+    PolynomialType, SelectedExpressions, SourceRef,
+};
+use ast::parsed::{BinaryOperator, UnaryOperator};
+use number::{DegreeType, FieldElement};
+
+use crate::{BackendImpl, Error, Proof};
+
+pub struct PilChecker;
+
+impl<T: FieldElement> BackendImpl<T> for PilChecker {
+    fn new(_degree: DegreeType) -> Self {
+        Self
+    }
+
+    fn prove(
+        &self,
+        pil: &Analyzed<T>,
+        fixed: &[(&str, Vec<T>)],
+        witness: &[(&str, Vec<T>)],
+        prev_proof: Option<Proof>,
+    ) -> Result<(Option<Proof>, Option<String>), Error> {
+        if prev_proof.is_some() {
+            return Err(Error::NoAggregationAvailable);
+        }
+
+        if let Err(failures) = check(pil, fixed, witness) {
+            for failure in &failures {
+                log::error!("{failure}");
+            }
+            return Err(Error::VerificationFailed(format!(
+                "{} identities are not satisfied:\n{}",
+                failures.len(),
+                failures
+                    .iter()
+                    .map(|f| f.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            )));
+        }
+        log::info!("All {} identities are satisfied.", pil.identities.len());
+
+        Ok((None, None))
+    }
+}
+
+/// A single identity that is not satisfied.
+pub struct Failure<T> {
+    pub source: SourceRef,
+    pub identity: String,
+    /// The first row at which the identity fails.
+    pub row: DegreeType,
+    /// The total number of rows at which the identity fails.
+    pub failing_rows: usize,
+    /// The values of the columns referenced by the identity at the failing row.
+    pub values: Vec<(String, T)>,
+    pub message: Option<String>,
+}
+
+impl<T: fmt::Display> fmt::Display for Failure<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: identity `{}` is not satisfied at row {}",
+            self.source.file, self.source.line, self.identity, self.row
+        )?;
+        if self.failing_rows > 1 {
+            write!(f, " (and {} other rows)", self.failing_rows - 1)?;
+        }
+        if let Some(message) = &self.message {
+            write!(f, ": {message}")?;
+        }
+        if !self.values.is_empty() {
+            write!(
+                f,
+                "\n    {}",
+                self.values
+                    .iter()
+                    .map(|(name, value)| format!("{name} = {value}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks all identities of `pil` against the given fixed and witness columns.
+/// Returns one failure for every identity that is not satisfied.
+///
+/// Next references wrap around, i.e. `x'` in the last row refers to `x` in the first row.
+/// Namespaces can have different degrees: Each expression is evaluated on the rows
+/// of the namespace of the columns it references, so lookups and permutations can
+/// connect namespaces of different degrees.
+///
+/// Connect identities `{ a, b } connect { sa, sb }` use the encoding where the cell
+/// in row `r` of the `i`-th column of the left side is identified by `i * N + r`,
+/// `N` being the degree.
+pub fn check<T: FieldElement>(
+    pil: &Analyzed<T>,
+    fixed: &[(&str, Vec<T>)],
+    witness: &[(&str, Vec<T>)],
+) -> Result<(), Vec<Failure<T>>> {
+    let columns: HashMap<&str, &Vec<T>> = fixed
+        .iter()
+        .chain(witness.iter())
+        .map(|(name, values)| (*name, values))
+        .collect();
+    let degree = fixed
+        .iter()
+        .chain(witness.iter())
+        .map(|(_, values)| values.len() as DegreeType)
+        .max()
+        .unwrap_or_default();
+    let evaluator = Evaluator {
+        pil,
+        columns,
+        degree,
+    };
+
+    let failures = pil
+        .identities
+        .iter()
+        .filter_map(|identity| evaluator.check_identity(identity).err())
+        .collect::<Vec<_>>();
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures)
+    }
+}
+
+/// Shifts `left` by `right` bits, or returns None if the amount or the result
+/// does not fit.
+fn shift<T: FieldElement>(left: T, op: &BinaryOperator, right: T) -> Option<T> {
+    let amount = DegreeType::try_from(right.to_arbitrary_integer()).ok()?;
+    let value = left.to_arbitrary_integer();
+    let value = match op {
+        BinaryOperator::ShiftLeft if left.is_zero() => value,
+        BinaryOperator::ShiftLeft if amount > T::BITS as DegreeType => return None,
+        BinaryOperator::ShiftLeft => value << amount,
+        _ => value >> amount,
+    };
+    T::Integer::try_from(value).ok().map(T::from)
+}
+
+/// The row at which an evaluation error occurred and the error message.
+type CheckError = (DegreeType, String);
+
+struct Evaluator<'a, T> {
+    pil: &'a Analyzed<T>,
+    columns: HashMap<&'a str, &'a Vec<T>>,
+    /// The maximum degree, used for expressions that do not reference any column.
+    degree: DegreeType,
+}
+
+impl<'a, T: FieldElement> Evaluator<'a, T> {
+    fn check_identity(&self, identity: &Identity<T>) -> Result<(), Failure<T>> {
+        let failure = |row, failing_rows, message| Failure {
+            source: identity.source.clone(),
+            identity: identity.to_string(),
+            row,
+            failing_rows,
+            values: self.referenced_values(identity, row),
+            message,
+        };
+        let result = match identity.kind {
+            IdentityKind::Polynomial => self.check_polynomial_identity(identity),
+            IdentityKind::Plookup => self.check_plookup(identity),
+            IdentityKind::Permutation => self.check_permutation(identity),
+            IdentityKind::Connect => self.check_connect(identity),
+        };
+        match result {
+            Ok(failing_rows) if failing_rows.is_empty() => Ok(()),
+            Ok(failing_rows) => Err(failure(failing_rows[0], failing_rows.len(), None)),
+            Err((row, message)) => Err(failure(row, 1, Some(message))),
+        }
+    }
+
+    /// Returns the rows at which the polynomial identity is non-zero.
+    fn check_polynomial_identity(
+        &self,
+        identity: &Identity<T>,
+    ) -> Result<Vec<DegreeType>, CheckError> {
+        let expr = identity.expression_for_poly_id();
+        let mut failing_rows = vec![];
+        for row in 0..self.degree_of([expr]) {
+            if !self.evaluate(expr, row)?.is_zero() {
+                failing_rows.push(row);
+            }
+        }
+        Ok(failing_rows)
+    }
+
+    /// Returns the rows at which the left side's tuple is not contained in the right side.
+    fn check_plookup(&self, identity: &Identity<T>) -> Result<Vec<DegreeType>, CheckError> {
+        let right = self
+            .selected_tuples(&identity.right)?
+            .into_iter()
+            .map(|(_, tuple)| tuple)
+            .collect::<HashSet<_>>();
+        Ok(self
+            .selected_tuples(&identity.left)?
+            .into_iter()
+            .filter(|(_, tuple)| !right.contains(tuple))
+            .map(|(row, _)| row)
+            .collect())
+    }
+
+    /// Returns the rows of the left side whose tuples are not matched by a tuple
+    /// in the right side, or the first selected row on the right if the right side
+    /// has more tuples than the left.
+    fn check_permutation(&self, identity: &Identity<T>) -> Result<Vec<DegreeType>, CheckError> {
+        let mut right: HashMap<Vec<T>, Vec<DegreeType>> = HashMap::new();
+        for (row, tuple) in self.selected_tuples(&identity.right)? {
+            right.entry(tuple).or_default().push(row);
+        }
+        let mut failing_rows = vec![];
+        for (row, tuple) in self.selected_tuples(&identity.left)? {
+            match right.get_mut(&tuple).and_then(|rows| rows.pop()) {
+                Some(_) => {}
+                None => failing_rows.push(row),
+            }
+        }
+        if failing_rows.is_empty() {
+            if let Some(row) = right.values().flatten().min() {
+                return Err((
+                    *row,
+                    "the right side contains more tuples than the left side".to_string(),
+                ));
+            }
+        }
+        Ok(failing_rows)
+    }
+
+    /// Returns the rows at which a cell on the left side differs from the cell it is
+    /// connected to.
+    fn check_connect(&self, identity: &Identity<T>) -> Result<Vec<DegreeType>, CheckError> {
+        let cells = &identity.left.expressions;
+        let permutation = &identity.right.expressions;
+        if cells.len() != permutation.len() {
+            return Err((
+                0,
+                "both sides of a connect identity need to have the same length".to_string(),
+            ));
+        }
+        let degree = self.degree_of(cells);
+        let cell_count = cells.len() as DegreeType * degree;
+        let mut failing_rows = BTreeSet::new();
+        for row in 0..degree {
+            for (cell, target) in cells.iter().zip(permutation) {
+                let target = self.evaluate(target, row)?;
+                let target = DegreeType::try_from(target.to_arbitrary_integer())
+                    .ok()
+                    .filter(|target| *target < cell_count)
+                    .ok_or_else(|| (row, format!("cell index {target} is out of bounds")))?;
+                let (target_column, target_row) = ((target / degree) as usize, target % degree);
+                if self.evaluate(cell, row)? != self.evaluate(&cells[target_column], target_row)? {
+                    failing_rows.insert(row);
+                }
+            }
+        }
+        Ok(failing_rows.into_iter().collect())
+    }
+
+    /// Returns the rows and the values of the expressions at which the selector is non-zero.
+    fn selected_tuples(
+        &self,
+        selected: &SelectedExpressions<T>,
+    ) -> Result<Vec<(DegreeType, Vec<T>)>, CheckError> {
+        let mut tuples = vec![];
+        let degree = self.degree_of(selected.selector.iter().chain(&selected.expressions));
+        for row in 0..degree {
+            let selector = match &selected.selector {
+                Some(selector) => self.evaluate(selector, row)?,
+                None => T::one(),
+            };
+            if selector.is_zero() {
+                continue;
+            }
+            if selector != T::one() {
+                return Err((row, format!("selector has non-binary value {selector}")));
+            }
+            let tuple = selected
+                .expressions
+                .iter()
+                .map(|e| self.evaluate(e, row))
+                .collect::<Result<_, _>>()?;
+            tuples.push((row, tuple));
+        }
+        Ok(tuples)
+    }
+
+    /// Returns the values of all fixed and witness columns referenced in the
+    /// identity at the given row, in order of first occurrence.
+    fn referenced_values(&self, identity: &Identity<T>, row: DegreeType) -> Vec<(String, T)> {
+        let mut references = vec![];
+        let mut seen = HashSet::new();
+        for expr in [&identity.left, &identity.right]
+            .into_iter()
+            .flat_map(|side| side.selector.iter().chain(side.expressions.iter()))
+        {
+            collect_references(expr, &mut |poly| {
+                if poly.poly_id().ptype != PolynomialType::Intermediate
+                    && seen.insert((poly.name.clone(), poly.index, poly.next))
+                {
+                    references.push(poly.clone());
+                }
+            });
+        }
+        references
+            .into_iter()
+            .filter_map(|poly| {
+                let value = self.reference_value(&poly, row).ok()?;
+                let name = column_name(&poly);
+                let name = if poly.next { format!("{name}'") } else { name };
+                Some((name, value))
+            })
+            .collect()
+    }
+
+    /// Returns the degree of the namespace of the first column referenced in the expressions.
+    fn degree_of<'b>(&self, exprs: impl IntoIterator<Item = &'b Expression<T>>) -> DegreeType
+    where
+        T: 'b,
+    {
+        let mut degree = None;
+        for expr in exprs {
+            collect_references(expr, &mut |poly| {
+                degree = degree.or(Some(self.degree_of_column(poly)));
+            });
+        }
+        degree.unwrap_or(self.degree)
+    }
+
+    fn degree_of_column(&self, poly: &PolynomialReference) -> DegreeType {
+        self.pil
+            .definitions
+            .get(&poly.name)
+            .map_or(self.degree, |(definition, _)| definition.degree)
+    }
+
+    fn evaluate(&self, expr: &Expression<T>, row: DegreeType) -> Result<T, CheckError> {
+        match expr {
+            Expression::Number(n) => Ok(*n),
+            Expression::Constant(name) => self
+                .pil
+                .constants
+                .get(name)
+                .cloned()
+                .ok_or_else(|| (row, format!("unknown constant {name}"))),
+            Expression::PolynomialReference(poly) => self.reference_value(poly, row),
+            Expression::PublicReference(name) => {
+                let public = self
+                    .pil
+                    .public_declarations
+                    .get(name)
+                    .ok_or_else(|| (row, format!("unknown public {name}")))?;
+                self.reference_value(&public.polynomial, public.index)
+            }
+            Expression::BinaryOperation(left, op, right) => {
+                let left = self.evaluate(left, row)?;
+                let right = self.evaluate(right, row)?;
+                Ok(match op {
+                    BinaryOperator::Add => left + right,
+                    BinaryOperator::Sub => left - right,
+                    BinaryOperator::Mul => left * right,
+                    BinaryOperator::Div => left.integer_div(right),
+                    BinaryOperator::Pow => left.pow(right.to_integer()),
+                    BinaryOperator::Mod => left.integer_mod(right),
+                    BinaryOperator::BinaryAnd => (left.to_integer() & right.to_integer()).into(),
+                    BinaryOperator::BinaryXor => (left.to_integer() ^ right.to_integer()).into(),
+                    BinaryOperator::BinaryOr => (left.to_integer() | right.to_integer()).into(),
+                    BinaryOperator::ShiftLeft | BinaryOperator::ShiftRight => {
+                        shift(left, op, right).ok_or_else(|| {
+                            (
+                                row,
+                                format!("{left} {op} {right} does not fit into a field element"),
+                            )
+                        })?
+                    }
+                })
+            }
+            Expression::UnaryOperation(op, expr) => {
+                let v = self.evaluate(expr, row)?;
+                Ok(match op {
+                    UnaryOperator::Plus => v,
+                    UnaryOperator::Minus => -v,
+                })
+            }
+            Expression::LocalVariableReference(_)
+            | Expression::String(_)
+            | Expression::Tuple(_)
+            | Expression::FunctionCall(_, _)
+            | Expression::MatchExpression(_, _) => Err((
+                row,
+                format!("cannot evaluate expression {expr} in an identity"),
+            )),
+        }
+    }
+
+    fn reference_value(
+        &self,
+        poly: &PolynomialReference,
+        row: DegreeType,
+    ) -> Result<T, CheckError> {
+        let row = if poly.next {
+            (row + 1) % self.degree_of_column(poly)
+        } else {
+            row
+        };
+        match poly.poly_id().ptype {
+            PolynomialType::Committed | PolynomialType::Constant => {
+                let name = column_name(poly);
+                self.columns
+                    .get(name.as_str())
+                    .map(|values| values[row as usize])
+                    .ok_or_else(|| (row, format!("no values provided for column {name}")))
+            }
+            PolynomialType::Intermediate => match &self.pil.definitions[&poly.name].1 {
+                Some(FunctionValueDefinition::Mapping(definition)) => {
+                    self.evaluate(definition, row)
+                }
+                _ => Err((
+                    row,
+                    format!("intermediate column {} has no definition", poly.name),
+                )),
+            },
+        }
+    }
+}
+
+/// Returns the name of the column a reference refers to. The elements of array
+/// columns are columns named by the array and the index, e.g. `main.x[2]`.
+fn column_name(poly: &PolynomialReference) -> String {
+    match poly.index {
+        Some(index) => format!("{}[{index}]", poly.name),
+        None => poly.name.clone(),
+    }
+}
+
+fn collect_references<T>(expr: &Expression<T>, f: &mut impl FnMut(&PolynomialReference)) {
+    ast::analyzed::util::expr_any(expr, |e| {
+        if let Expression::PolynomialReference(poly) = e {
+            f(poly);
+        }
+        false
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use number::{Bn254Field, GoldilocksField};
+    use pil_analyzer::analyze_string;
+    use test_log::test;
+
+    use super::*;
+
+    fn column(values: &[u64]) -> Vec<GoldilocksField> {
+        values.iter().map(|v| GoldilocksField::from(*v)).collect()
+    }
+
+    const PIL: &str = r#"
+        namespace F(4);
+        pol constant FIRST = [1, 0, 0, 0];
+        pol constant BYTE(i) { i };
+        pol commit x;
+        pol commit y;
+        FIRST * x = 0;
+        (1 - FIRST') * (x' - x - 1) = 0;
+        { y } in { BYTE };
+        { x } is { y };
+    "#;
+
+    #[test]
+    fn satisfied() {
+        let pil = analyze_string::<GoldilocksField>(PIL).unwrap();
+        let fixed = vec![
+            ("F.FIRST", column(&[1, 0, 0, 0])),
+            ("F.BYTE", column(&[0, 1, 2, 3])),
+        ];
+        let witness = vec![
+            ("F.x", column(&[0, 1, 2, 3])),
+            ("F.y", column(&[3, 2, 1, 0])),
+        ];
+        assert!(check(&pil, &fixed, &witness).is_ok());
+    }
+
+    #[test]
+    fn not_satisfied() {
+        let pil = analyze_string::<GoldilocksField>(PIL).unwrap();
+        let fixed = vec![
+            ("F.FIRST", column(&[1, 0, 0, 0])),
+            ("F.BYTE", column(&[0, 1, 2, 3])),
+        ];
+        let witness = vec![
+            ("F.x", column(&[0, 1, 3, 4])),
+            ("F.y", column(&[3, 7, 1, 0])),
+        ];
+        let failures = check(&pil, &fixed, &witness).err().unwrap();
+        let failures = failures.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+        assert_eq!(failures.len(), 3);
+        assert!(failures[0].contains("is not satisfied at row 1"));
+        assert!(failures[0].contains("F.FIRST' = 0, F.x' = 3, F.x = 1"));
+        assert!(failures[1].contains("is not satisfied at row 1"));
+        assert!(failures[1].contains("F.y = 7"));
+        assert!(failures[2].contains("is not satisfied at row 3"));
+    }
+
+    #[test]
+    fn prove_reports_failure() {
+        let pil = analyze_string::<GoldilocksField>(PIL).unwrap();
+        let fixed = vec![
+            ("F.FIRST", column(&[1, 0, 0, 0])),
+            ("F.BYTE", column(&[0, 1, 2, 3])),
+        ];
+        let witness = vec![
+            ("F.x", column(&[0, 1, 2, 2])),
+            ("F.y", column(&[3, 2, 1, 0])),
+        ];
+        let Err(Error::VerificationFailed(message)) =
+            PilChecker.prove(&pil, &fixed, &witness, None)
+        else {
+            panic!("expected a verification error");
+        };
+        assert!(message.contains("((1 - F.FIRST') * ((F.x' - F.x) - 1)) = 0"));
+        assert!(message.contains("is not satisfied at row 2"));
+    }
+
+    #[test]
+    fn no_aggregation() {
+        let pil = analyze_string::<GoldilocksField>(PIL).unwrap();
+        let result = PilChecker.prove(&pil, &[], &[], Some(vec![]));
+        assert!(matches!(result, Err(Error::NoAggregationAvailable)));
+    }
+
+    #[test]
+    fn columns_of_other_program() {
+        let pil = analyze_string::<GoldilocksField>(PIL).unwrap();
+        let backend = crate::BackendType::PilCheck
+            .factory::<GoldilocksField>()
+            .create(4);
+        let fixed = vec![
+            ("F.FIRST", column(&[1, 0, 0, 0])),
+            ("F.BYTE", column(&[0, 1, 2, 3])),
+        ];
+        let prove = |witness: Vec<(&str, Vec<GoldilocksField>)>| match backend
+            .prove(&pil, &fixed, &witness, None)
+        {
+            Err(Error::InvalidColumns(message)) => message,
+            _ => panic!("expected invalid columns"),
+        };
+        let message = prove(vec![
+            ("F.x", column(&[0, 1, 2, 3])),
+            ("F.z", column(&[0; 4])),
+        ]);
+        assert!(message.contains("F.z is not a witness column"));
+        let message = prove(vec![("F.y", column(&[0; 4])), ("F.x", column(&[0; 4]))]);
+        assert!(message.contains("F.x is not a witness column of the PIL or is out of order"));
+        let message = prove(vec![("F.x", column(&[0, 1, 2, 3, 4, 5, 6, 7]))]);
+        assert!(message.contains("F.x has 8 rows, but its degree is 4"));
+    }
+
+    #[test]
+    fn array_columns() {
+        let pil = analyze_string::<GoldilocksField>(
+            r#"
+            namespace F(2);
+            pol constant C(i) { i + 1 };
+            pol commit x[2];
+            x[0] = C;
+            x[1] = C + 2;
+        "#,
+        )
+        .unwrap();
+        let fixed = vec![("F.C", column(&[1, 2]))];
+        let witness = vec![("F.x[0]", column(&[1, 2])), ("F.x[1]", column(&[3, 4]))];
+        assert!(check(&pil, &fixed, &witness).is_ok());
+        let witness = vec![("F.x[0]", column(&[1, 2])), ("F.x[1]", column(&[2, 4]))];
+        let failures = check(&pil, &fixed, &witness).err().unwrap();
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].to_string().contains("F.x[1] = 2"));
+    }
+
+    #[test]
+    fn connect() {
+        let pil = analyze_string::<GoldilocksField>(
+            r#"
+            namespace F(2);
+            pol constant SA = [1, 0];
+            pol constant SB = [3, 2];
+            pol commit a, b;
+            { a, b } connect { SA, SB };
+        "#,
+        )
+        .unwrap();
+        let fixed = vec![("F.SA", column(&[1, 0])), ("F.SB", column(&[3, 2]))];
+        let witness = vec![("F.a", column(&[5, 5])), ("F.b", column(&[1, 1]))];
+        assert!(check(&pil, &fixed, &witness).is_ok());
+        let witness = vec![("F.a", column(&[5, 6])), ("F.b", column(&[1, 1]))];
+        assert!(check(&pil, &fixed, &witness).is_err());
+    }
+
+    #[test]
+    fn out_of_range_values() {
+        let pil = analyze_string::<Bn254Field>(
+            r#"
+            namespace F(2);
+            pol constant SA = [0, 0];
+            pol constant SB = [2, 3];
+            pol commit a, b, s;
+            { a, b } connect { SA, SB };
+            a = 1 << s;
+        "#,
+        )
+        .unwrap();
+        let minus_one = -Bn254Field::from(1);
+        let fixed = vec![
+            ("F.SA", vec![minus_one, 1.into()]),
+            ("F.SB", vec![2.into(), 3.into()]),
+        ];
+        let witness = vec![
+            ("F.a", vec![1.into(), 1.into()]),
+            ("F.b", vec![0.into(), 0.into()]),
+            ("F.s", vec![0.into(), minus_one]),
+        ];
+        let failures = check(&pil, &fixed, &witness).err().unwrap();
+        assert_eq!(failures.len(), 2);
+        assert!(failures[0].to_string().contains("out of bounds"));
+        assert!(failures[1].to_string().contains("does not fit"));
+    }
+}
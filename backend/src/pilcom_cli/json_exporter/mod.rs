@@ -358,7 +358,7 @@ mod test {
 
         let file = std::path::PathBuf::from("../test_data/polygon-hermez/").join(file);
 
-        let analyzed = analyze::<GoldilocksField>(&file);
+        let analyzed = analyze::<GoldilocksField>(&file).unwrap();
         let json_out = export(&analyzed);
 
         let pilcom = std::env::var("PILCOM").expect(
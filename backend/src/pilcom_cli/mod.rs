@@ -1,6 +1,6 @@
 mod json_exporter;
 
-use crate::{BackendImpl, Proof};
+use crate::{BackendImpl, Error, Proof};
 use ast::analyzed::Analyzed;
 use number::{DegreeType, FieldElement};
 
@@ -17,11 +17,11 @@ impl<T: FieldElement> BackendImpl<T> for PilcomCli {
         _fixed: &[(&str, Vec<T>)],
         _witness: &[(&str, Vec<T>)],
         prev_proof: Option<Proof>,
-    ) -> (Option<Proof>, Option<String>) {
+    ) -> Result<(Option<Proof>, Option<String>), Error> {
         if prev_proof.is_some() {
-            unimplemented!("Aggregration is not implemented for Pilcom CLI backend");
+            return Err(Error::NoAggregationAvailable);
         }
 
-        (None, Some(json_exporter::export(pil).to_string()))
+        Ok((None, Some(json_exporter::export(pil).to_string())))
     }
 }
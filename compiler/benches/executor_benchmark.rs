@@ -3,7 +3,7 @@ use asm_utils::compiler::Compiler;
 use ast::analyzed::Analyzed;
 use criterion::{criterion_group, criterion_main, Criterion};
 
-use compiler::inputs_to_query_callback;
+use compiler::Inputs;
 use executor::constant_evaluator;
 use mktemp::Temp;
 use number::{FieldElement, GoldilocksField};
@@ -15,19 +15,19 @@ fn get_pil() -> Analyzed<GoldilocksField> {
     let tmp_dir = Temp::new_dir().unwrap();
     let riscv_asm_files =
         compile_rust_crate_to_riscv_asm("../riscv/tests/riscv_data/keccak/Cargo.toml", &tmp_dir);
-    let contents = Risc::compile(riscv_asm_files);
+    let contents = Risc::compile(riscv_asm_files).unwrap();
     let parsed = parser::parse_asm::<T>(None, &contents).unwrap();
     let analyzed = analyze(parsed).unwrap();
-    let graph = airgen::compile(analyzed);
+    let graph = airgen::compile(analyzed).unwrap();
     let pil = linker::link(graph).unwrap();
-    let analyzed = pil_analyzer::analyze_string(&format!("{pil}"));
+    let analyzed = pil_analyzer::analyze_string(&format!("{pil}")).unwrap();
     pilopt::optimize(analyzed)
 }
 
 fn run_witgen<T: FieldElement>(analyzed: &Analyzed<T>, input: Vec<T>) {
-    let query_callback = Some(inputs_to_query_callback(input));
+    let query_callback = Some(Inputs::from(input));
     let (constants, degree) = constant_evaluator::generate(analyzed);
-    executor::witgen::generate(analyzed, degree, &constants, query_callback);
+    executor::witgen::generate(analyzed, degree, &constants, query_callback).unwrap();
 }
 
 fn criterion_benchmark(c: &mut Criterion) {
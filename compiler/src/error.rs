@@ -0,0 +1,114 @@
+use std::fmt::{self, Display, Formatter};
+
+use parser_util::SourceError;
+
+/// An error that occurred in one of the compilation stages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The source file could not be parsed.
+    Parse(SourceError),
+    /// The asm file failed type checking or could not be reduced to constrained machines.
+    Analysis(Vec<SourceError>),
+    /// The machines could not be turned into a graph of PIL objects.
+    Airgen(Vec<SourceError>),
+    /// The PIL objects could not be linked into a single PIL file.
+    Linker(Vec<SourceError>),
+    /// The PIL file could not be analyzed.
+    PilAnalysis(Vec<SourceError>),
+    /// The PIL could not be optimized with the given settings.
+    Optimization(String),
+    /// Witness generation failed.
+    Witgen(String),
+    /// The program failed when executed directly.
+    Execution(String),
+    /// The generated files do not match the manifest of the compiled program.
+    Manifest(String),
+    /// The backend failed to generate a proof.
+    Proving(String),
+}
+
+impl Error {
+    fn stage(&self) -> &'static str {
+        match self {
+            Error::Parse(_) => "parsing",
+            Error::Analysis(_) => "analysis",
+            Error::Airgen(_) => "airgen",
+            Error::Linker(_) => "linking",
+            Error::PilAnalysis(_) => "PIL analysis",
+            Error::Optimization(_) => "optimization",
+            Error::Witgen(_) => "witness generation",
+            Error::Execution(_) => "execution",
+            Error::Manifest(_) => "manifest validation",
+            Error::Proving(_) => "proving",
+        }
+    }
+
+    /// Returns the errors that refer to the source, if any.
+    pub fn source_errors(&self) -> &[SourceError] {
+        match self {
+            Error::Parse(e) => std::slice::from_ref(e),
+            Error::Analysis(errors)
+            | Error::Airgen(errors)
+            | Error::Linker(errors)
+            | Error::PilAnalysis(errors) => errors,
+            Error::Optimization(_)
+            | Error::Witgen(_)
+            | Error::Execution(_)
+            | Error::Manifest(_)
+            | Error::Proving(_) => &[],
+        }
+    }
+
+    /// Outputs the error to stderr, with excerpts of the source where possible.
+    pub fn output_to_stderr(&self) {
+        eprintln!("Error during {}:", self.stage());
+        match self {
+            Error::Optimization(message)
+            | Error::Witgen(message)
+            | Error::Execution(message)
+            | Error::Manifest(message)
+            | Error::Proving(message) => {
+                eprintln!("{message}")
+            }
+            _ => self
+                .source_errors()
+                .iter()
+                .for_each(|e| e.output_to_stderr()),
+        }
+    }
+
+    /// Sets the file name of all source errors that do not have one yet.
+    pub(crate) fn in_file(self, file_name: &str) -> Self {
+        let in_file = |errors: Vec<SourceError>| -> Vec<SourceError> {
+            errors.into_iter().map(|e| e.in_file(file_name)).collect()
+        };
+        match self {
+            Error::Parse(e) => Error::Parse(e.in_file(file_name)),
+            Error::Analysis(errors) => Error::Analysis(in_file(errors)),
+            Error::Airgen(errors) => Error::Airgen(in_file(errors)),
+            Error::Linker(errors) => Error::Linker(in_file(errors)),
+            e => e,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Error during {}:", self.stage())?;
+        match self {
+            Error::Optimization(message)
+            | Error::Witgen(message)
+            | Error::Execution(message)
+            | Error::Manifest(message)
+            | Error::Proving(message) => {
+                write!(f, "\n{message}")
+            }
+            _ => self
+                .source_errors()
+                .iter()
+                .try_for_each(|e| write!(f, "\n{e}")),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
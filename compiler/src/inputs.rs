@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use executor::witgen::prover_input::{ByteStream, InputList, NamedInputs};
+use executor::witgen::{ProverInput, ProverQuery};
+use number::FieldElement;
+
+/// The inputs provided to the prover.
+#[derive(Clone, Debug)]
+pub enum Inputs<T> {
+    /// A list of values, answering `("input", index)`.
+    List(InputList<T>),
+    /// Lists of values identified by name, answering `("input", "name", index)`.
+    Named(NamedInputs<T>),
+    /// A stream of bytes, answering `("data", index)`.
+    Bytes(ByteStream),
+}
+
+impl<T> Default for Inputs<T> {
+    fn default() -> Self {
+        Inputs::List(InputList::from(vec![]))
+    }
+}
+
+impl<T> From<Vec<T>> for Inputs<T> {
+    fn from(values: Vec<T>) -> Self {
+        Inputs::List(InputList::from(values))
+    }
+}
+
+impl<T> Inputs<T> {
+    /// A stream of bytes, answering `("data", index)`.
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Inputs::Bytes(ByteStream::from(bytes))
+    }
+}
+
+impl<T: FieldElement> Inputs<T> {
+    /// Reads the inputs from a file.
+    /// Files with the extension `.json` contain either an array of values, or an object
+    /// mapping names to arrays of values. Values are numbers or strings of decimal numbers.
+    /// All other files are read as a stream of bytes.
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let error = |e: &dyn std::fmt::Display| format!("Error reading {}: {e}", path.display());
+        if path.extension().is_some_and(|ext| ext == "json") {
+            let contents = fs::read_to_string(path).map_err(|e| error(&e))?;
+            Self::from_json(&contents).map_err(|e| error(&e))
+        } else {
+            ByteStream::from_file(path)
+                .map(Inputs::Bytes)
+                .map_err(|e| error(&e))
+        }
+    }
+
+    fn from_json(contents: &str) -> Result<Self, String> {
+        let value = json::parse(contents).map_err(|e| e.to_string())?;
+        if value.is_array() {
+            Ok(Inputs::from(json_to_values(&value)?))
+        } else if value.is_object() {
+            Ok(Inputs::Named(NamedInputs::from(
+                value
+                    .entries()
+                    .map(|(name, values)| Ok((name.to_string(), json_to_values(values)?)))
+                    .collect::<Result<BTreeMap<_, _>, String>>()?,
+            )))
+        } else {
+            Err("Expected an array of values or an object mapping names to arrays".to_string())
+        }
+    }
+}
+
+fn json_to_values<T: FieldElement>(value: &json::JsonValue) -> Result<Vec<T>, String> {
+    if !value.is_array() {
+        return Err(format!("Expected an array of values, got {value}"));
+    }
+    value
+        .members()
+        .map(|v| {
+            v.as_u64()
+                .map(T::from)
+                .or_else(|| v.as_str().and_then(|s| T::from_str_radix(s, 10).ok()))
+                .ok_or_else(|| format!("Invalid value: {v}"))
+        })
+        .collect()
+}
+
+impl<T: FieldElement> ProverInput<T> for Inputs<T> {
+    fn answer(&mut self, query: &ProverQuery<T>) -> Option<T> {
+        match self {
+            Inputs::List(inputs) => inputs.answer(query),
+            Inputs::Named(inputs) => inputs.answer(query),
+            Inputs::Bytes(inputs) => inputs.answer(query),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use number::GoldilocksField;
+
+    use super::*;
+
+    #[test]
+    fn json_list() {
+        let mut inputs = Inputs::<GoldilocksField>::from_json("[1, \"2\", 3]").unwrap();
+        assert_eq!(inputs.answer(&ProverQuery::Input(1)), Some(2.into()));
+        assert_eq!(inputs.answer(&ProverQuery::Input(3)), None);
+    }
+
+    #[test]
+    fn json_named() {
+        let mut inputs =
+            Inputs::<GoldilocksField>::from_json("{\"a\": [4], \"b\": [5, 6]}").unwrap();
+        assert_eq!(
+            inputs.answer(&ProverQuery::NamedInput("b".to_string(), 1)),
+            Some(6.into())
+        );
+        assert_eq!(inputs.answer(&ProverQuery::Input(0)), None);
+    }
+
+    #[test]
+    fn json_invalid() {
+        assert!(Inputs::<GoldilocksField>::from_json("7").is_err());
+        assert!(Inputs::<GoldilocksField>::from_json("[-1]").is_err());
+        assert!(Inputs::<GoldilocksField>::from_json("{\"a\": 1}").is_err());
+    }
+}
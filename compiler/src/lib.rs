@@ -2,6 +2,7 @@
 
 use std::ffi::OsStr;
 use std::fs;
+use std::io::BufReader;
 use std::io::BufWriter;
 use std::io::Write;
 use std::path::Path;
@@ -10,47 +11,75 @@ use std::time::Instant;
 
 use ast::analyzed::Analyzed;
 
+mod error;
+mod inputs;
+pub mod manifest;
+mod rom_debug_info;
 pub mod util;
 mod verify;
 
-use analysis::analyze;
+use backend::Backend;
 pub use backend::{BackendType, Proof};
-use number::write_polys_file;
+pub use error::Error;
+pub use inputs::Inputs;
+pub use linker::LinkerMode;
+use manifest::{Manifest, Source};
 use number::DegreeType;
-pub use verify::{verify, verify_asm_string};
+use number::{write_polys_file, write_polys_raw};
+pub use verify::{verify, verify_asm_string, verify_asm_string_with_mode};
 
 use ast::parsed::PILFile;
 use executor::constant_evaluator;
+use executor::witgen::{ProverInput, ProverQuery, RomDebugInfo};
 use number::FieldElement;
 
-pub fn no_callback<T>() -> Option<fn(&str) -> Option<T>> {
+#[allow(clippy::type_complexity)]
+pub fn no_callback<T>() -> Option<fn(&ProverQuery<T>) -> Option<T>> {
     None
 }
 
 /// Compiles a .pil or .asm file and runs witness generation.
 /// If the file ends in .asm, converts it to .pil first.
+/// Identities of a degree larger than `max_degree` are split, which defaults to the
+/// maximum degree of the `prove_with` backend.
+/// The proof uses the setup and the keys of `keys` if they are given.
 /// Returns the compilation result if any compilation took place.
+#[allow(clippy::too_many_arguments)]
 pub fn compile_pil_or_asm<T: FieldElement>(
     file_name: &str,
-    inputs: Vec<T>,
+    inputs: Inputs<T>,
     output_dir: &Path,
     force_overwrite: bool,
     prove_with: Option<BackendType>,
-) -> Result<Option<CompilationResult<T>>, Vec<String>> {
+    max_degree: Option<usize>,
+    keys: Option<KeyFiles>,
+    linker_mode: LinkerMode,
+) -> Result<Option<CompilationResult<T>>, Error> {
     if file_name.ends_with(".asm") {
-        compile_asm(file_name, inputs, output_dir, force_overwrite, prove_with)
+        compile_asm(
+            file_name,
+            inputs,
+            output_dir,
+            force_overwrite,
+            prove_with,
+            max_degree,
+            keys,
+            linker_mode,
+        )
     } else {
         Ok(Some(compile_pil(
             Path::new(file_name),
             output_dir,
-            Some(inputs_to_query_callback(inputs)),
+            Some(inputs),
             prove_with,
-        )))
+            max_degree,
+            keys,
+        )?))
     }
 }
 
-pub fn analyze_pil<T: FieldElement>(pil_file: &Path) -> Analyzed<T> {
-    pil_analyzer::analyze(pil_file)
+pub fn analyze_pil<T: FieldElement>(pil_file: &Path) -> Result<Analyzed<T>, Error> {
+    pil_analyzer::analyze(pil_file).map_err(Error::PilAnalysis)
 }
 
 /// Compiles a .pil file to its json form and also tries to generate
@@ -62,52 +91,82 @@ pub fn compile_pil<T: FieldElement, QueryCallback>(
     output_dir: &Path,
     query_callback: Option<QueryCallback>,
     prove_with: Option<BackendType>,
-) -> CompilationResult<T>
+    max_degree: Option<usize>,
+    keys: Option<KeyFiles>,
+) -> Result<CompilationResult<T>, Error>
 where
-    QueryCallback: FnMut(&str) -> Option<T> + Sync + Send,
+    QueryCallback: ProverInput<T> + Sync + Send,
 {
+    let analyzed = analyze_pil(pil_file)?;
+    let contents = fs::read_to_string(pil_file).unwrap();
     compile(
-        pil_analyzer::analyze(pil_file),
+        analyzed,
         pil_file.file_name().unwrap(),
         output_dir,
         query_callback,
         prove_with,
+        max_degree,
+        keys,
+        &[],
+        None,
+        Source {
+            name: pil_file.to_str().unwrap(),
+            contents: &contents,
+        },
     )
 }
 
 /// Compiles a given PIL and tries to generate fixed and witness columns.
+/// Witness generation uses the values in `external_witness_values` for the columns they name.
+/// If the ROM of the main VM is given, witness generation failures describe the failing
+/// statement of the program.
 /// @returns a compilation result, containing witness and fixed columns
+#[allow(clippy::too_many_arguments)]
 pub fn compile_pil_ast<T: FieldElement, QueryCallback>(
     pil: &PILFile<T>,
     file_name: &OsStr,
     output_dir: &Path,
     query_callback: Option<QueryCallback>,
     prove_with: Option<BackendType>,
-) -> CompilationResult<T>
+    max_degree: Option<usize>,
+    keys: Option<KeyFiles>,
+    external_witness_values: &[(&str, Vec<T>)],
+    rom_debug_info: Option<&RomDebugInfo>,
+    source: Source,
+) -> Result<CompilationResult<T>, Error>
 where
-    QueryCallback: FnMut(&str) -> Option<T> + Sync + Send,
+    QueryCallback: ProverInput<T> + Sync + Send,
 {
     // TODO exporting this to string as a hack because the parser
     // is tied into the analyzer due to imports.
     compile(
-        pil_analyzer::analyze_string(&format!("{pil}")),
+        pil_analyzer::analyze_string(&format!("{pil}")).map_err(Error::PilAnalysis)?,
         file_name,
         output_dir,
         query_callback,
         prove_with,
+        max_degree,
+        keys,
+        external_witness_values,
+        rom_debug_info,
+        source,
     )
 }
 
 /// Compiles a .asm file, outputs the PIL on stdout and tries to generate
 /// fixed and witness columns.
 /// @returns a compilation result if any compilation was done.
+#[allow(clippy::too_many_arguments)]
 pub fn compile_asm<T: FieldElement>(
     file_name: &str,
-    inputs: Vec<T>,
+    inputs: Inputs<T>,
     output_dir: &Path,
     force_overwrite: bool,
     prove_with: Option<BackendType>,
-) -> Result<Option<CompilationResult<T>>, Vec<String>> {
+    max_degree: Option<usize>,
+    keys: Option<KeyFiles>,
+    linker_mode: LinkerMode,
+) -> Result<Option<CompilationResult<T>>, Error> {
     let contents = fs::read_to_string(file_name).unwrap();
     Ok(compile_asm_string(
         file_name,
@@ -116,6 +175,9 @@ pub fn compile_asm<T: FieldElement>(
         output_dir,
         force_overwrite,
         prove_with,
+        max_degree,
+        keys,
+        linker_mode,
     )?
     .1)
 }
@@ -124,29 +186,64 @@ pub fn compile_asm<T: FieldElement>(
 /// fixed and witness columns.
 ///
 /// Returns the relative pil file name and the compilation result if any compilation was done.
+#[allow(clippy::too_many_arguments)]
 pub fn compile_asm_string<T: FieldElement>(
     file_name: &str,
     contents: &str,
-    inputs: Vec<T>,
+    inputs: Inputs<T>,
     output_dir: &Path,
     force_overwrite: bool,
     prove_with: Option<BackendType>,
-) -> Result<(PathBuf, Option<CompilationResult<T>>), Vec<String>> {
-    let parsed = parser::parse_asm(Some(file_name), contents).unwrap_or_else(|err| {
-        eprintln!("Error parsing .asm file:");
-        err.output_to_stderr();
-        panic!();
-    });
+    max_degree: Option<usize>,
+    keys: Option<KeyFiles>,
+    linker_mode: LinkerMode,
+) -> Result<(PathBuf, Option<CompilationResult<T>>), Error> {
+    compile_asm_string_with_external_witness(
+        file_name,
+        contents,
+        inputs,
+        output_dir,
+        force_overwrite,
+        prove_with,
+        max_degree,
+        keys,
+        linker_mode,
+        &[],
+    )
+}
+
+/// Like [compile_asm_string], but witness generation uses the values in
+/// `external_witness_values` for the columns they name, e.g. an execution trace.
+#[allow(clippy::too_many_arguments)]
+pub fn compile_asm_string_with_external_witness<T: FieldElement>(
+    file_name: &str,
+    contents: &str,
+    inputs: Inputs<T>,
+    output_dir: &Path,
+    force_overwrite: bool,
+    prove_with: Option<BackendType>,
+    max_degree: Option<usize>,
+    keys: Option<KeyFiles>,
+    linker_mode: LinkerMode,
+    external_witness_values: &[(&str, Vec<T>)],
+) -> Result<(PathBuf, Option<CompilationResult<T>>), Error> {
+    let parsed =
+        parser::parse_asm(Some(file_name), contents).map_err(|err| Error::Parse(err.into()))?;
     log::debug!("Run analysis");
-    let analysed = analyze(parsed).unwrap();
+    let functions =
+        analysis::analyze_functions(parsed).map_err(|e| Error::Analysis(e).in_file(file_name))?;
+    let rom_debug_info = rom_debug_info::rom_debug_info(&functions);
+    let analysed =
+        analysis::reduce_vms(functions).map_err(|e| Error::Analysis(e).in_file(file_name))?;
     log::debug!("Analysis done");
     log::trace!("{analysed}");
     log::debug!("Run airgen");
-    let graph = airgen::compile(analysed);
+    let graph = airgen::compile(analysed).map_err(|e| Error::Airgen(e).in_file(file_name))?;
     log::debug!("Airgen done");
     log::trace!("{graph}");
     log::debug!("Run linker");
-    let pil = linker::link(graph)?;
+    let pil = linker::link_with_mode(graph, linker_mode)
+        .map_err(|e| Error::Linker(e).in_file(file_name))?;
     log::debug!("Linker done");
     log::trace!("{pil}");
 
@@ -172,9 +269,17 @@ pub fn compile_asm_string<T: FieldElement>(
             &pil,
             pil_file_name,
             output_dir,
-            Some(inputs_to_query_callback(inputs)),
+            Some(inputs),
             prove_with,
-        )),
+            max_degree,
+            keys,
+            external_witness_values,
+            rom_debug_info.as_ref(),
+            Source {
+                name: file_name,
+                contents,
+            },
+        )?),
     ))
 }
 
@@ -183,64 +288,134 @@ pub struct CompilationResult<T: FieldElement> {
     pub constants: Vec<(String, Vec<T>)>,
     /// Witness columns, potentially None (if success is false)
     pub witness: Option<Vec<(String, Vec<T>)>>,
+    /// Public values, None if the witness was not generated
+    pub publics: Option<Vec<(String, T)>>,
 }
 
 /// Optimizes a given pil and tries to generate constants and committed polynomials.
+/// Identities of a degree larger than `max_degree`, or the maximum degree of the backend
+/// if it is not given, are split.
+/// Writes a manifest describing the compiled program next to the generated files.
+/// The proof uses the setup and the keys of `keys` if they are given, see [prove].
 /// @returns a compilation result, containing witness and fixed columns, if successful.
+#[allow(clippy::too_many_arguments)]
 fn compile<T: FieldElement, QueryCallback>(
     analyzed: Analyzed<T>,
     file_name: &OsStr,
     output_dir: &Path,
     query_callback: Option<QueryCallback>,
     prove_with: Option<BackendType>,
-) -> CompilationResult<T>
+    max_degree: Option<usize>,
+    keys: Option<KeyFiles>,
+    external_witness_values: &[(&str, Vec<T>)],
+    rom_debug_info: Option<&RomDebugInfo>,
+    source: Source,
+) -> Result<CompilationResult<T>, Error>
 where
-    QueryCallback: FnMut(&str) -> Option<T> + Send + Sync,
+    QueryCallback: ProverInput<T> + Send + Sync,
 {
     log::info!("Optimizing pil...");
     let analyzed = pilopt::optimize(analyzed);
+    // Witness generation recognizes some machines by the shape of their identities,
+    // so it runs on the PIL before the degree reduction. The columns added by the
+    // reduction are computed from the generated witness.
+    let max_degree = max_degree.or_else(|| prove_with.as_ref().and_then(BackendType::max_degree));
+    if let Some(max_degree) = max_degree.filter(|max_degree| *max_degree < 2) {
+        return Err(Error::Optimization(format!(
+            "The maximum degree of the identities has to be at least 2, but is {max_degree}."
+        )));
+    }
+    let reduced = max_degree
+        .map(|max_degree| {
+            log::info!("Reducing the degree of the identities to {max_degree}...");
+            let (reduced, report) = pilopt::reduce_degree(analyzed.clone(), max_degree);
+            log::info!("{report}");
+            if report.max_degree_after() > max_degree {
+                return Err(Error::Optimization(format!(
+                    "Could not reduce the degree of the identities to {max_degree}, the maximum degree is still {}.",
+                    report.max_degree_after()
+                )));
+            }
+            Ok(reduced)
+        })
+        .transpose()?;
+    // The PIL that is written to the output directory and proven.
+    let pil = reduced.as_ref().unwrap_or(&analyzed);
     let optimized_pil_file_name = output_dir.join(format!(
         "{}_opt.pil",
         Path::new(file_name).file_stem().unwrap().to_str().unwrap()
     ));
-    fs::write(optimized_pil_file_name.clone(), format!("{analyzed}")).unwrap();
+    fs::write(optimized_pil_file_name.clone(), format!("{pil}")).unwrap();
     log::info!("Wrote {}.", optimized_pil_file_name.to_str().unwrap());
     let start = Instant::now();
     log::info!("Evaluating fixed columns...");
     let (constants, degree) = constant_evaluator::generate(&analyzed);
     log::info!("Took {}", start.elapsed().as_secs_f32());
+    Manifest::new(source, pil, degree, max_degree)
+        .write_to_fs(output_dir)
+        .map_err(|e| Error::Manifest(e.to_string()))?;
+    log::info!("Wrote {}.", manifest::MANIFEST_FILE_NAME);
+
+    let witness = (analyzed.constant_count() == constants.len())
+        .then(|| {
+            log::info!("Deducing witness columns...");
+            let witness = executor::witgen::generate_with_external_witness(
+                &analyzed,
+                degree,
+                &constants,
+                query_callback,
+                external_witness_values,
+                rom_debug_info,
+            )
+            .map_err(Error::Witgen)?;
+            let witness = match &reduced {
+                Some(reduced) => {
+                    executor::witgen::generate_auxiliary_columns(reduced, &constants, witness)
+                        .map_err(Error::Witgen)?
+                }
+                None => witness,
+            };
 
-    let witness = (analyzed.constant_count() == constants.len()).then(|| {
-        log::info!("Deducing witness columns...");
-        let commits = executor::witgen::generate(&analyzed, degree, &constants, query_callback);
-
-        let witness = commits
-            .into_iter()
-            .map(|(name, c)| (name, c))
-            .collect::<Vec<_>>();
-
-        write_constants_to_fs(&constants, output_dir, degree);
-        write_commits_to_fs(&witness, output_dir, degree);
-
-        witness
-    });
+            write_constants_to_fs(&constants, output_dir, degree);
+            write_commits_to_fs(&witness, output_dir, degree);
+
+            Ok(witness)
+        })
+        .transpose()?;
+    let publics = witness
+        .as_ref()
+        .map(|witness| pil.public_values(witness))
+        .transpose()
+        .map_err(Error::Witgen)?;
+    if let Some(publics) = &publics {
+        write_publics_to_fs(publics, output_dir);
+    }
 
     // Even if we don't have all constants and witnesses, some backends will
     // still output the constraint serialization.
     if let Some(backend) = prove_with {
         let factory = backend.factory::<T>();
-        let backend = factory.create(degree);
-
-        write_proving_results_to_fs(
-            false,
-            backend.prove(
-                &analyzed,
-                &constants,
-                witness.as_deref().unwrap_or_default(),
-                None,
-            ),
-            output_dir,
-        );
+        let backend = match keys {
+            Some(keys) => {
+                let mut params = fs::File::open(keys.params).map_err(|e| {
+                    Error::Proving(format!("Could not open {}: {e}", keys.params.display()))
+                })?;
+                factory
+                    .create_from_setup(&mut BufReader::new(&mut params))
+                    .map_err(|e| Error::Proving(e.to_string()))?
+            }
+            None => factory.create(degree),
+        };
+
+        let result = prove(
+            backend.as_ref(),
+            pil,
+            &constants,
+            witness.as_deref().unwrap_or_default(),
+            None,
+            keys.map(|keys| keys.keys),
+        )?;
+        write_proving_results_to_fs(false, result, output_dir);
     }
 
     let constants = constants
@@ -254,7 +429,64 @@ where
             .collect()
     });
 
-    CompilationResult { constants, witness }
+    Ok(CompilationResult {
+        constants,
+        witness,
+        publics,
+    })
+}
+
+/// The files of the setup parameters and of the keys to prove with. The keys belong
+/// to the setup, so they can only be reused together with it.
+#[derive(Clone, Copy)]
+pub struct KeyFiles<'a> {
+    /// The setup parameters, as written by the `setup` command.
+    pub params: &'a Path,
+    /// The keys, which are generated and written to this file if it does not exist.
+    pub keys: &'a Path,
+}
+
+/// Generates a proof of `pil` with `backend`. If `keys` is given, the keys are read from
+/// it if the file exists. Otherwise, they are generated and written to it after proving,
+/// so that later proofs of the same program can reuse them.
+pub fn prove<T: FieldElement>(
+    backend: &dyn Backend<T>,
+    pil: &Analyzed<T>,
+    fixed: &[(&str, Vec<T>)],
+    witness: &[(&str, Vec<T>)],
+    proof: Option<Proof>,
+    keys: Option<&Path>,
+) -> Result<(Option<Proof>, Option<String>), Error> {
+    let io_error = |path: &Path, e: std::io::Error| {
+        Error::Proving(format!("Could not access {}: {e}", path.display()))
+    };
+    // Keys that do not exist yet are written after proving.
+    let keys_to_write = match keys {
+        Some(keys) if keys.exists() => {
+            let file = fs::File::open(keys).map_err(|e| io_error(keys, e))?;
+            backend
+                .read_keys(pil, fixed, &mut BufReader::new(file))
+                .map_err(|e| Error::Proving(e.to_string()))?;
+            log::info!("Read {}.", keys.display());
+            None
+        }
+        keys => keys,
+    };
+
+    let result = backend
+        .prove(pil, fixed, witness, proof)
+        .map_err(|e| Error::Proving(e.to_string()))?;
+
+    if let Some(keys) = keys_to_write {
+        let file = fs::File::create(keys).map_err(|e| io_error(keys, e))?;
+        let mut writer = BufWriter::new(file);
+        backend
+            .write_keys(pil, fixed, &mut writer)
+            .map_err(|e| Error::Proving(e.to_string()))?;
+        writer.flush().map_err(|e| io_error(keys, e))?;
+        log::info!("Wrote {}.", keys.display());
+    }
+    Ok(result)
 }
 
 pub fn write_proving_results_to_fs(
@@ -266,9 +498,9 @@ pub fn write_proving_results_to_fs(
     match proof {
         Some(proof) => {
             let fname = if is_aggregation {
-                "proof.bin"
-            } else {
                 "proof_aggr.bin"
+            } else {
+                "proof.bin"
             };
 
             // No need to bufferize the writing, because we write the whole
@@ -295,12 +527,19 @@ fn write_constants_to_fs<T: FieldElement>(
     output_dir: &Path,
     degree: DegreeType,
 ) {
-    write_polys_file(
+    write_polys_raw(
         &mut BufWriter::new(&mut fs::File::create(output_dir.join("constants.bin")).unwrap()),
         degree,
         constants,
-    );
+    )
+    .unwrap();
     log::info!("Wrote constants.bin.");
+    write_polys_file(
+        &mut BufWriter::new(&mut fs::File::create(output_dir.join("constants.polys")).unwrap()),
+        constants,
+    )
+    .unwrap();
+    log::info!("Wrote constants.polys.");
 }
 
 fn write_commits_to_fs<T: FieldElement>(
@@ -308,35 +547,28 @@ fn write_commits_to_fs<T: FieldElement>(
     output_dir: &Path,
     degree: DegreeType,
 ) {
-    write_polys_file(
+    write_polys_raw(
         &mut BufWriter::new(&mut fs::File::create(output_dir.join("commits.bin")).unwrap()),
         degree,
         commits,
-    );
+    )
+    .unwrap();
     log::info!("Wrote commits.bin.");
+    write_polys_file(
+        &mut BufWriter::new(&mut fs::File::create(output_dir.join("commits.polys")).unwrap()),
+        commits,
+    )
+    .unwrap();
+    log::info!("Wrote commits.polys.");
 }
 
-pub fn inputs_to_query_callback<T: FieldElement>(inputs: Vec<T>) -> impl Fn(&str) -> Option<T> {
-    move |query: &str| -> Option<T> {
-        let items = query.split(',').map(|s| s.trim()).collect::<Vec<_>>();
-        match items[0] {
-            "\"input\"" => {
-                assert_eq!(items.len(), 2);
-                let index = items[1].parse::<usize>().unwrap();
-                let value = inputs.get(index).cloned();
-                if let Some(value) = value {
-                    log::trace!("Input query: Index {index} -> {value}");
-                } else {
-                    log::warn!("Not enough inputs provided! Index {index} out of bounds")
-                }
-                value
-            }
-            "\"print_char\"" => {
-                assert_eq!(items.len(), 2);
-                print!("{}", items[1].parse::<u8>().unwrap() as char);
-                Some(0.into())
-            }
-            _ => None,
-        }
+fn write_publics_to_fs<T: FieldElement>(publics: &[(String, T)], output_dir: &Path) {
+    if publics.is_empty() {
+        return;
     }
+    util::write_publics(
+        &mut BufWriter::new(&mut fs::File::create(output_dir.join("publics.json")).unwrap()),
+        publics,
+    );
+    log::info!("Wrote publics.json.");
 }
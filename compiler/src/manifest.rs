@@ -0,0 +1,343 @@
+//! The manifest ties the artifacts written into an output directory to the program
+//! they were compiled from, so that files of different compilations are not combined.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    path::Path,
+};
+
+use ast::analyzed::Analyzed;
+use number::{BigInt, DegreeType, FieldElement};
+use sha2::{Digest, Sha256};
+
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// The version of the manifest format, increased with every incompatible change
+/// of the manifest or of the artifacts it describes.
+pub const MANIFEST_VERSION: u64 = 2;
+
+/// The source file a program is compiled from.
+#[derive(Debug, Clone, Copy)]
+pub struct Source<'a> {
+    pub name: &'a str,
+    pub contents: &'a str,
+}
+
+/// Describes the compiled program the artifacts of an output directory belong to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    pub version: u64,
+    /// Identifies the compiled program: the hash of the field, the degree and the PIL.
+    pub id: String,
+    /// The name of the source file.
+    pub source: String,
+    /// The hash of the contents of the source file.
+    pub source_hash: String,
+    /// The modulus of the field, in hexadecimal.
+    pub field: String,
+    pub degree: DegreeType,
+    /// The maximum degree the optimizer reduced the identities to by adding
+    /// auxiliary witness columns, None if the degree was not reduced.
+    pub max_degree: Option<usize>,
+    pub fixed_columns: Vec<String>,
+    pub witness_columns: Vec<String>,
+    /// The hash of the (optimized) PIL the columns were generated for.
+    pub pil_hash: String,
+}
+
+impl Manifest {
+    pub fn new<T: FieldElement>(
+        source: Source,
+        pil: &Analyzed<T>,
+        degree: DegreeType,
+        max_degree: Option<usize>,
+    ) -> Self {
+        let field = field_id::<T>();
+        let pil_hash = pil_hash(pil);
+        Self {
+            version: MANIFEST_VERSION,
+            id: hash(format!("{field}\n{degree}\n{pil_hash}")),
+            source: source.name.to_string(),
+            source_hash: hash(source.contents),
+            field,
+            degree,
+            max_degree,
+            fixed_columns: column_names(pil.constant_polys_in_source_order()),
+            witness_columns: column_names(pil.committed_polys_in_source_order()),
+            pil_hash,
+        }
+    }
+
+    /// Writes the manifest to `manifest.json` in `dir`.
+    pub fn write_to_fs(&self, dir: &Path) -> Result<(), io::Error> {
+        let json = json::object! {
+            version: self.version,
+            id: self.id.as_str(),
+            source: self.source.as_str(),
+            source_hash: self.source_hash.as_str(),
+            field: self.field.as_str(),
+            degree: self.degree,
+            max_degree: self.max_degree,
+            fixed_columns: self.fixed_columns.clone(),
+            witness_columns: self.witness_columns.clone(),
+            pil_hash: self.pil_hash.as_str(),
+        };
+        let mut writer = BufWriter::new(File::create(dir.join(MANIFEST_FILE_NAME))?);
+        json.write_pretty(&mut writer, 4)?;
+        writer.flush()
+    }
+
+    /// Reads the manifest from `manifest.json` in `dir`.
+    /// Fails if the file is missing or was written by an incompatible version.
+    pub fn read_from_fs(dir: &Path) -> Result<Self, io::Error> {
+        let mut contents = String::new();
+        File::open(dir.join(MANIFEST_FILE_NAME))
+            .map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("{MANIFEST_FILE_NAME}: {e}, please compile the program again."),
+                )
+            })?
+            .read_to_string(&mut contents)?;
+        let json = json::parse(&contents).map_err(|e| invalid(&e.to_string()))?;
+
+        let string = |key: &str| {
+            json[key]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| invalid(&format!("{key} is missing")))
+        };
+        let strings = |key: &str| {
+            json[key]
+                .members()
+                .map(|name| name.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+                .filter(|_| json[key].is_array())
+                .ok_or_else(|| invalid(&format!("{key} is missing")))
+        };
+        let version = json["version"]
+            .as_u64()
+            .ok_or_else(|| invalid("version is missing"))?;
+        if version != MANIFEST_VERSION {
+            return Err(invalid(&format!(
+                "The artifacts were written with version {version}, but version {MANIFEST_VERSION} is required. Please compile the program again."
+            )));
+        }
+        Ok(Self {
+            version,
+            id: string("id")?,
+            source: string("source")?,
+            source_hash: string("source_hash")?,
+            field: string("field")?,
+            degree: json["degree"]
+                .as_u64()
+                .ok_or_else(|| invalid("degree is missing"))?,
+            max_degree: match &json["max_degree"] {
+                json::JsonValue::Null => None,
+                max_degree => Some(
+                    max_degree
+                        .as_usize()
+                        .ok_or_else(|| invalid("max_degree is invalid"))?,
+                ),
+            },
+            fixed_columns: strings("fixed_columns")?,
+            witness_columns: strings("witness_columns")?,
+            pil_hash: string("pil_hash")?,
+        })
+    }
+
+    /// Checks that the artifacts were generated for the field `T`.
+    pub fn validate_field<T: FieldElement>(&self) -> Result<(), io::Error> {
+        let field = field_id::<T>();
+        if self.field != field {
+            return Err(invalid(&format!(
+                "The artifacts were generated for the field with modulus {}, not {field}.",
+                self.field
+            )));
+        }
+        Ok(())
+    }
+
+    /// Checks that the artifacts were generated for the field `T` and from `pil`.
+    pub fn validate_pil<T: FieldElement>(&self, pil: &Analyzed<T>) -> Result<(), io::Error> {
+        self.validate_field::<T>()?;
+        if self.pil_hash != pil_hash(pil) {
+            return Err(invalid(&format!(
+                "The artifacts were generated from a different PIL file than the given one, please use the optimized PIL file of {}.",
+                self.source
+            )));
+        }
+        Ok(())
+    }
+
+    /// Checks that the identities were reduced for a backend that limits their degree
+    /// to `backend_max_degree`. The reduction happens during the compilation, so a
+    /// program compiled without it cannot be proven with such a backend.
+    pub fn validate_max_degree(&self, backend_max_degree: Option<usize>) -> Result<(), io::Error> {
+        match (backend_max_degree, self.max_degree) {
+            (Some(backend_max_degree), None) => Err(invalid(&format!(
+                "The backend supports identities of degree at most {backend_max_degree}, but the program was compiled without reducing their degree. Please compile it with --prove-with or --max-degree."
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that a setup of the given size, i.e. for this number of rows, suffices
+    /// to prove the program.
+    pub fn validate_setup_size(&self, size: DegreeType) -> Result<(), io::Error> {
+        if size < self.degree {
+            return Err(invalid(&format!(
+                "The setup size {size} is smaller than the degree {} of the program.",
+                self.degree
+            )));
+        }
+        Ok(())
+    }
+
+    /// Checks that the files written for pilcom in `dir`, which contain the values of
+    /// all fixed or witness columns row by row without any names, have the size
+    /// of the columns of the manifest.
+    pub fn validate_raw_files<T: FieldElement>(&self, dir: &Path) -> Result<(), io::Error> {
+        let value_size = T::zero().to_bytes_le().len() as u64;
+        for (file, columns) in [
+            ("constants.bin", &self.fixed_columns),
+            ("commits.bin", &self.witness_columns),
+        ] {
+            let size = fs::metadata(dir.join(file))
+                .map_err(|e| io::Error::new(e.kind(), format!("{file}: {e}")))?
+                .len();
+            let expected = self.degree * columns.len() as u64 * value_size;
+            if size != expected {
+                return Err(invalid(&format!(
+                    "{file} has {size} bytes, but {} columns of degree {} have {expected} bytes. The file is probably left over from a different compilation.",
+                    columns.len(),
+                    self.degree
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{MANIFEST_FILE_NAME}: {message}"),
+    )
+}
+
+fn hash(data: impl AsRef<[u8]>) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn pil_hash<T: FieldElement>(pil: &Analyzed<T>) -> String {
+    hash(format!("{pil}"))
+}
+
+fn field_id<T: FieldElement>() -> String {
+    format!("0x{:x}", T::modulus().to_arbitrary_integer())
+}
+
+fn column_names<T>(
+    polys: Vec<&(
+        ast::analyzed::Polynomial,
+        Option<ast::analyzed::FunctionValueDefinition<T>>,
+    )>,
+) -> Vec<String> {
+    polys
+        .into_iter()
+        .map(|(poly, _)| poly.absolute_name.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use number::{Bn254Field, GoldilocksField};
+
+    use super::*;
+
+    fn manifest() -> (Analyzed<GoldilocksField>, Manifest) {
+        let pil = pil_analyzer::analyze_string::<GoldilocksField>(
+            "namespace main(8); pol constant FIRST = [1] + [0]*; pol commit x; x' = x + FIRST;",
+        )
+        .unwrap();
+        let source = Source {
+            name: "main.pil",
+            contents: "source",
+        };
+        let manifest = Manifest::new(source, &pil, 8, Some(3));
+        (pil, manifest)
+    }
+
+    #[test]
+    fn write_and_read() {
+        let (pil, manifest) = manifest();
+        let dir = mktemp::Temp::new_dir().unwrap();
+        manifest.write_to_fs(&dir).unwrap();
+        let read = Manifest::read_from_fs(&dir).unwrap();
+        assert_eq!(read, manifest);
+        assert_eq!(read.fixed_columns, ["main.FIRST"]);
+        assert_eq!(read.witness_columns, ["main.x"]);
+        assert_eq!(read.max_degree, Some(3));
+        read.validate_pil(&pil).unwrap();
+    }
+
+    #[test]
+    fn max_degree() {
+        let (_, mut manifest) = manifest();
+        manifest.validate_max_degree(Some(3)).unwrap();
+        manifest.validate_max_degree(None).unwrap();
+        manifest.max_degree = None;
+        assert!(manifest.validate_max_degree(Some(3)).is_err());
+        manifest.validate_max_degree(None).unwrap();
+    }
+
+    #[test]
+    fn raw_files() {
+        let (_, manifest) = manifest();
+        let dir = mktemp::Temp::new_dir().unwrap();
+        let write = |file: &str, rows: usize| {
+            let values = vec![GoldilocksField::from(1); rows];
+            let mut writer = File::create(dir.join(file)).unwrap();
+            number::write_polys_raw(&mut writer, rows as DegreeType, &[("main.x", values)])
+                .unwrap();
+        };
+        write("constants.bin", 8);
+        assert!(manifest
+            .validate_raw_files::<GoldilocksField>(&dir)
+            .is_err());
+        write("commits.bin", 8);
+        manifest
+            .validate_raw_files::<GoldilocksField>(&dir)
+            .unwrap();
+        assert!(manifest.validate_raw_files::<Bn254Field>(&dir).is_err());
+        write("commits.bin", 16);
+        assert!(manifest
+            .validate_raw_files::<GoldilocksField>(&dir)
+            .is_err());
+    }
+
+    #[test]
+    fn stale_artifacts() {
+        let (_, manifest) = manifest();
+        let other = pil_analyzer::analyze_string::<GoldilocksField>(
+            "namespace main(8); pol constant FIRST = [1] + [0]*; pol commit x; x' = x + 2 * FIRST;",
+        )
+        .unwrap();
+        assert!(manifest.validate_pil(&other).is_err());
+        assert!(manifest.validate_field::<Bn254Field>().is_err());
+    }
+
+    #[test]
+    fn missing_manifest() {
+        let dir = mktemp::Temp::new_dir().unwrap();
+        assert!(Manifest::read_from_fs(&dir).is_err());
+        std::fs::write(dir.join(MANIFEST_FILE_NAME), r#"{"version": 0}"#).unwrap();
+        let error = Manifest::read_from_fs(&dir).unwrap_err();
+        assert!(error.to_string().contains("version 0"));
+    }
+}
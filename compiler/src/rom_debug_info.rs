@@ -0,0 +1,51 @@
+//! Extraction of the ROM of the main VM from an analyzed asm file, used to relate
+//! failures of witness generation to the program.
+
+use asm_to_pil::rom_layout::{function_rows, DISPATCHER_ROWS};
+use ast::asm_analysis::{AnalysisASMFile, FunctionStatement};
+use executor::witgen::{RomDebugInfo, RomRow};
+use itertools::Itertools;
+use number::FieldElement;
+
+/// The machine that is linked as the main namespace if there are several machines.
+const MAIN_MACHINE: &str = "Main";
+/// The namespace of the main machine in the linked PIL.
+const MAIN_NAMESPACE: &str = "main";
+
+/// Returns the ROM of the main machine of a file with batched functions, see
+/// [analysis::analyze_functions], with the source location of each row given by
+/// the last `debug loc` directive before it in the same function.
+/// Returns `None` if the main machine is not a VM.
+pub fn rom_debug_info<T: FieldElement>(file: &AnalysisASMFile<T>) -> Option<RomDebugInfo> {
+    let machine = match file.machines.len() {
+        1 => file.machines.values().next(),
+        _ => file.machines.get(MAIN_MACHINE),
+    }?;
+    let pc = format!("{MAIN_NAMESPACE}.{}", machine.pc()?);
+    let dispatcher = (0..DISPATCHER_ROWS).map(|_| RomRow {
+        statements: "<dispatcher>".to_string(),
+        location: None,
+    });
+    // The ROM generation labels the first row of each function with its name.
+    let mut function = None;
+    let functions = function_rows(machine).into_iter().map(|row| {
+        let label = (function != Some(row.function)).then(|| format!("_{}::", row.function));
+        function = Some(row.function);
+        RomRow {
+            statements: label
+                .into_iter()
+                .chain(
+                    row.statements
+                        .iter()
+                        .filter(|s| !matches!(s, FunctionStatement::DebugDirective(_)))
+                        .map(|s| s.to_string()),
+                )
+                .join(" "),
+            location: row.location.map(|location| location.to_string()),
+        }
+    });
+    Some(RomDebugInfo {
+        pc,
+        rows: dispatcher.chain(functions).collect(),
+    })
+}
@@ -1,6 +1,12 @@
 use ast::analyzed::{Analyzed, FunctionValueDefinition, Polynomial};
+use itertools::Itertools;
 use number::{read_polys_file, DegreeType, FieldElement};
-use std::{fs::File, io::BufReader, path::Path};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufReader, Read, Write},
+    path::Path,
+};
 
 pub trait PolySet {
     const FILE_NAME: &'static str;
@@ -11,7 +17,7 @@ pub trait PolySet {
 
 pub struct FixedPolySet;
 impl PolySet for FixedPolySet {
-    const FILE_NAME: &'static str = "constants.bin";
+    const FILE_NAME: &'static str = "constants.polys";
 
     fn get_polys<T: FieldElement>(
         pil: &Analyzed<T>,
@@ -22,7 +28,7 @@ impl PolySet for FixedPolySet {
 
 pub struct WitnessPolySet;
 impl PolySet for WitnessPolySet {
-    const FILE_NAME: &'static str = "commits.bin";
+    const FILE_NAME: &'static str = "commits.polys";
 
     fn get_polys<T: FieldElement>(
         pil: &Analyzed<T>,
@@ -31,17 +37,105 @@ impl PolySet for WitnessPolySet {
     }
 }
 
+/// The values of a set of polynomials, with their names.
+pub type PolySetValues<'a, T> = Vec<(&'a str, Vec<T>)>;
+
+/// Reads the polynomials of the set `P` from the corresponding file in `dir` and
+/// returns them in source order, together with the degree.
+/// Fails if the columns in the file do not match those of the PIL file.
 pub fn read_poly_set<'a, P: PolySet, T: FieldElement>(
     pil: &'a Analyzed<T>,
     dir: &Path,
-) -> (Vec<(&'a str, Vec<T>)>, DegreeType) {
-    let fixed_columns: Vec<&str> = P::get_polys(pil)
+) -> Result<(PolySetValues<'a, T>, DegreeType), io::Error> {
+    let with_file_name = |e: io::Error| io::Error::new(e.kind(), format!("{}: {e}", P::FILE_NAME));
+    let (polys, degree) = read_polys_file::<T>(&mut BufReader::new(
+        File::open(dir.join(P::FILE_NAME)).map_err(with_file_name)?,
+    ))
+    .map_err(with_file_name)?;
+
+    let mut polys = polys.into_iter().collect::<BTreeMap<_, _>>();
+    let result = P::get_polys(pil)
         .iter()
-        .map(|(poly, _)| poly.absolute_name.as_str())
-        .collect();
+        .map(|(poly, _)| {
+            let name = poly.absolute_name.as_str();
+            polys.remove(name).map(|values| (name, values)).ok_or_else(|| {
+                with_file_name(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Column {name} is missing, the file was probably generated from a different PIL file."),
+                ))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if !polys.is_empty() {
+        return Err(with_file_name(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Unexpected columns {}, the file was probably generated from a different PIL file.",
+                polys.keys().join(", ")
+            ),
+        )));
+    }
+    Ok((result, degree))
+}
 
-    read_polys_file(
-        &mut BufReader::new(File::open(dir.join(P::FILE_NAME)).unwrap()),
-        &fixed_columns,
-    )
+/// Writes the public values as a JSON array of `{ "name": ..., "value": ... }` objects.
+pub fn write_publics<T: FieldElement>(file: &mut impl Write, publics: &[(String, T)]) {
+    let json = json::JsonValue::Array(
+        publics
+            .iter()
+            .map(|(name, value)| {
+                json::object! {
+                    name: name.as_str(),
+                    value: value.to_integer().to_string(),
+                }
+            })
+            .collect(),
+    );
+    json.write_pretty(file, 4).unwrap();
+}
+
+/// Reads the public values written by `write_publics` from `publics.json` in `dir`
+/// and returns them in the order in which `pil` declares them.
+/// The file may be missing if `pil` does not declare any publics.
+pub fn read_publics<T: FieldElement>(
+    pil: &Analyzed<T>,
+    dir: &Path,
+) -> Result<Vec<(String, T)>, io::Error> {
+    let declarations = pil.public_declarations_in_source_order();
+    let mut contents = String::new();
+    match File::open(dir.join("publics.json")) {
+        Ok(mut file) => file.read_to_string(&mut contents)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound && declarations.is_empty() => {
+            return Ok(vec![])
+        }
+        Err(e) => return Err(e),
+    };
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let json = json::parse(&contents).map_err(|e| invalid(&e.to_string()))?;
+    let mut values = BTreeMap::new();
+    for public in json.members() {
+        let name = public["name"]
+            .as_str()
+            .ok_or_else(|| invalid("public without name"))?;
+        let value = public["value"]
+            .as_str()
+            .ok_or_else(|| invalid("public without value"))?;
+        let value = T::from_str_radix(value, 10).map_err(|e| invalid(&e))?;
+        if values.insert(name, value).is_some() {
+            return Err(invalid(&format!("duplicate value of public {name}")));
+        }
+    }
+    let publics = declarations
+        .into_iter()
+        .map(|decl| {
+            values
+                .remove(decl.name.as_str())
+                .map(|value| (decl.name.clone(), value))
+                .ok_or_else(|| invalid(&format!("missing value of public {}", decl.name)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    match values.keys().next() {
+        Some(name) => Err(invalid(&format!("{name} is not a public of the PIL"))),
+        None => Ok(publics),
+    }
 }
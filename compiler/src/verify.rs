@@ -2,23 +2,47 @@ use backend::BackendType;
 use number::FieldElement;
 use std::{fs, path::Path, process::Command};
 
-use crate::compile_asm_string;
+use crate::{compile_asm_string, manifest::Manifest, LinkerMode};
 
+/// Compiles the given asm, generates the witness and checks it against
+/// the constraints using the native PIL checker.
 pub fn verify_asm_string<T: FieldElement>(file_name: &str, contents: &str, inputs: Vec<T>) {
+    verify_asm_string_with_mode(file_name, contents, inputs, LinkerMode::Monolithic)
+}
+
+/// Like [verify_asm_string], but links the machines using the given linker mode.
+pub fn verify_asm_string_with_mode<T: FieldElement>(
+    file_name: &str,
+    contents: &str,
+    inputs: Vec<T>,
+    linker_mode: LinkerMode,
+) {
     let temp_dir = mktemp::Temp::new_dir().unwrap();
-    compile_asm_string(
+    let (_, result) = compile_asm_string(
         file_name,
         contents,
-        inputs,
+        inputs.into(),
         &temp_dir,
         true,
-        Some(BackendType::PilcomCli),
+        Some(BackendType::PilCheck),
+        None,
+        None,
+        linker_mode,
     )
     .unwrap();
-    verify(&temp_dir);
+    assert!(
+        result.unwrap().witness.is_some(),
+        "No witness was generated."
+    );
 }
 
-pub fn verify(temp_dir: &Path) {
+/// Verifies the constants and commits in `temp_dir` against `constraints.json`
+/// using the pilcom verifier, which requires `node` and the `PILCOM` environment variable.
+/// The files have to match the manifest of the compiled program in `temp_dir`.
+pub fn verify<T: FieldElement>(temp_dir: &Path) {
+    let manifest = Manifest::read_from_fs(temp_dir).unwrap();
+    manifest.validate_field::<T>().unwrap();
+    manifest.validate_raw_files::<T>(temp_dir).unwrap();
     let pilcom = std::env::var("PILCOM")
         .expect("Please set the PILCOM environment variable to the path to the pilcom repository.");
     let constants_file = format!("{}/constants.bin", temp_dir.to_str().unwrap());
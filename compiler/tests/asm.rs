@@ -1,4 +1,4 @@
-use compiler::verify_asm_string;
+use compiler::{verify_asm_string, verify_asm_string_with_mode, LinkerMode};
 use number::{Bn254Field, FieldElement, GoldilocksField};
 use std::fs;
 use test_log::test;
@@ -10,20 +10,28 @@ fn verify_asm<T: FieldElement>(file_name: &str, inputs: Vec<T>) {
     verify_asm_string(file_name, &contents, inputs)
 }
 
-#[cfg(feature = "halo2")]
 fn gen_halo2_proof(file_name: &str, inputs: Vec<Bn254Field>) {
+    gen_halo2_proof_with_mode(file_name, inputs, LinkerMode::Monolithic)
+}
+
+#[cfg(feature = "halo2")]
+fn gen_halo2_proof_with_mode(file_name: &str, inputs: Vec<Bn254Field>, linker_mode: LinkerMode) {
     compiler::compile_pil_or_asm(
         format!("../test_data/asm/{file_name}").as_str(),
-        inputs,
+        inputs.into(),
         &mktemp::Temp::new_dir().unwrap(),
         true,
         Some(backend::BackendType::Halo2),
+        None,
+        None,
+        linker_mode,
     )
     .unwrap();
 }
 
 #[cfg(not(feature = "halo2"))]
-fn gen_halo2_proof(_file_name: &str, _inputs: Vec<Bn254Field>) {}
+fn gen_halo2_proof_with_mode(_file_name: &str, _inputs: Vec<Bn254Field>, _linker_mode: LinkerMode) {
+}
 
 fn slice_to_vec<T: FieldElement>(arr: &[i32]) -> Vec<T> {
     arr.iter().cloned().map(|x| x.into()).collect()
@@ -80,6 +88,37 @@ fn vm_to_block_unique_interface() {
     gen_halo2_proof(f, slice_to_vec(&i));
 }
 
+#[test]
+fn vm_to_block_to_block() {
+    let f = "vm_to_block_to_block.asm";
+    let i = [];
+    verify_asm::<GoldilocksField>(f, slice_to_vec(&i));
+    gen_halo2_proof(f, slice_to_vec(&i));
+}
+
+#[test]
+fn vm_to_block_different_degrees() {
+    let f = "vm_to_block_different_degrees.asm";
+    let contents = fs::read_to_string(format!("../test_data/asm/{f}")).unwrap();
+    verify_asm_string_with_mode::<GoldilocksField>(f, &contents, vec![], LinkerMode::Composite);
+    gen_halo2_proof_with_mode(f, vec![], LinkerMode::Composite);
+}
+
+#[test]
+fn vm_to_block_larger_submachine() {
+    let f = "vm_to_block_larger_submachine.asm";
+    let contents = fs::read_to_string(format!("../test_data/asm/{f}")).unwrap();
+    verify_asm_string_with_mode::<GoldilocksField>(f, &contents, vec![], LinkerMode::Composite);
+    gen_halo2_proof_with_mode(f, vec![], LinkerMode::Composite);
+}
+
+#[test]
+#[should_panic = "Machine main_arith should have degree 32, found 16"]
+fn vm_to_block_different_degrees_monolithic() {
+    let f = "vm_to_block_different_degrees.asm";
+    verify_asm::<GoldilocksField>(f, vec![]);
+}
+
 #[test]
 #[should_panic = "not implemented: No executor machine matched identity `main.instr_sub { 1, main.X, main.Y, main.Z } in 1 { main_arith.function_id, main_arith.z, main_arith.x, main_arith.y };`"]
 fn vm_to_block_multiple_interfaces() {
@@ -90,7 +129,6 @@ fn vm_to_block_multiple_interfaces() {
 }
 
 #[test]
-#[should_panic = "not implemented: No executor machine matched identity `main.instr_add { 2, main.X, main.Y, main.Z } in main_vm.instr_return { main_vm._function_id, main_vm._input_0, main_vm._input_1, main_vm._output_0 };`"]
 fn vm_to_vm() {
     let f = "vm_to_vm.asm";
     let i = [];
@@ -98,6 +136,14 @@ fn vm_to_vm() {
     gen_halo2_proof(f, slice_to_vec(&i));
 }
 
+#[test]
+fn different_signatures() {
+    let f = "different_signatures.asm";
+    let i = [];
+    verify_asm::<GoldilocksField>(f, slice_to_vec(&i));
+    gen_halo2_proof(f, slice_to_vec(&i));
+}
+
 #[test]
 fn test_mem_read_write() {
     let f = "mem_read_write.asm";
@@ -105,6 +151,13 @@ fn test_mem_read_write() {
     gen_halo2_proof(f, Default::default());
 }
 
+#[test]
+fn test_multiple_memories() {
+    let f = "multiple_memories.asm";
+    verify_asm::<GoldilocksField>(f, Default::default());
+    gen_halo2_proof(f, Default::default());
+}
+
 #[test]
 fn test_multi_assign() {
     let f = "multi_assign.asm";
@@ -156,3 +209,174 @@ fn hello_world_asm_fail() {
     let i = [1];
     verify_asm::<GoldilocksField>(f, slice_to_vec(&i));
 }
+
+#[test]
+fn source_errors() {
+    let contents = r#"
+machine Main {
+    reg pc[@pc];
+    reg X[<=];
+    reg A;
+
+    function main {
+        A <=X= 1;
+        unknown_instr A;
+        jmp nowhere;
+    }
+}
+"#;
+    let temp_dir = mktemp::Temp::new_dir().unwrap();
+    let err = compiler::compile_asm_string::<GoldilocksField>(
+        "errors.asm",
+        contents,
+        Default::default(),
+        &temp_dir,
+        true,
+        None,
+        None,
+        None,
+        compiler::LinkerMode::Monolithic,
+    )
+    .err()
+    .unwrap();
+    let errors = err.source_errors();
+    assert!(!errors.is_empty());
+    for e in errors {
+        assert_eq!(e.file_name.as_deref(), Some("errors.asm"));
+        assert!(e.span.is_some());
+    }
+    assert!(errors.iter().any(|e| e.message.contains("unknown_instr")));
+}
+
+#[test]
+fn external_witness() {
+    let contents = r#"
+machine Main {
+    degree 8;
+
+    reg pc[@pc];
+    reg X[<=];
+    reg A;
+
+    function main {
+        A <=X= 1;
+        A <=X= A + 2;
+        return;
+    }
+}
+"#;
+    let compile = |values: Vec<u64>| {
+        let temp_dir = mktemp::Temp::new_dir().unwrap();
+        compiler::compile_asm_string_with_external_witness::<GoldilocksField>(
+            "external_witness.asm",
+            contents,
+            Default::default(),
+            &temp_dir,
+            true,
+            None,
+            None,
+            None,
+            compiler::LinkerMode::Monolithic,
+            &[("main.A", values.into_iter().map(Into::into).collect())],
+        )
+    };
+    let (_, result) = compile(vec![0, 0, 0, 1, 3]).unwrap();
+    let witness = result.unwrap().witness.unwrap();
+    let (_, a) = witness.iter().find(|(name, _)| name == "main.A").unwrap();
+    assert_eq!(a[..5], [0, 0, 0, 1, 3].map(GoldilocksField::from));
+
+    let Err(compiler::Error::Witgen(message)) = compile(vec![0, 0, 0, 2]) else {
+        panic!("Expected witness generation to fail");
+    };
+    assert!(message.contains("contradicts"));
+}
+
+#[test]
+fn witgen_failure_location() {
+    let contents = r#"
+machine Main {
+    degree 8;
+
+    reg pc[@pc];
+    reg X[<=];
+    reg A;
+
+    instr assert_zero X { X = 0 }
+
+    function main {
+        debug file 1 "src" "main.rs";
+        debug loc 1 3 5;
+        A <=X= 1;
+        debug loc 1 4 5;
+        assert_zero A;
+        return;
+    }
+}
+"#;
+    let temp_dir = mktemp::Temp::new_dir().unwrap();
+    let Err(compiler::Error::Witgen(message)) = compiler::compile_asm_string::<GoldilocksField>(
+        "witgen_failure_location.asm",
+        contents,
+        Default::default(),
+        &temp_dir,
+        true,
+        None,
+        None,
+        None,
+        compiler::LinkerMode::Monolithic,
+    ) else {
+        panic!("Expected witness generation to fail");
+    };
+    assert!(message.contains("At pc 3: assert_zero A; (src/main.rs:4:5)"));
+    assert!(message.contains("2: _main:: A <=X= 1; (src/main.rs:3:5)"));
+}
+
+#[test]
+fn memory_access_to_too_large_address() {
+    let contents = fs::read_to_string("../test_data/asm/mem_read_write.asm")
+        .unwrap()
+        .replacen("ADDR <=X= 4;", "ADDR <=X= 18446744073709551616;", 1);
+    let temp_dir = mktemp::Temp::new_dir().unwrap();
+    let Err(compiler::Error::Witgen(message)) = compiler::compile_asm_string::<Bn254Field>(
+        "mem_read_write.asm",
+        &contents,
+        Default::default(),
+        &temp_dir,
+        true,
+        None,
+        None,
+        None,
+        compiler::LinkerMode::Monolithic,
+    ) else {
+        panic!("Expected witness generation to fail");
+    };
+    assert!(
+        message.contains("Memory access to too large address"),
+        "{message}"
+    );
+}
+
+#[test]
+fn max_degree() {
+    let temp_dir = mktemp::Temp::new_dir().unwrap();
+    let compile = |max_degree| {
+        compiler::compile_pil_or_asm::<GoldilocksField>(
+            "../test_data/asm/simple_sum.asm",
+            slice_to_vec(&[16, 4, 1, 2, 8, 5]).into(),
+            &temp_dir,
+            true,
+            Some(backend::BackendType::PilCheck),
+            max_degree,
+            None,
+            LinkerMode::Monolithic,
+        )
+    };
+    assert!(compile(Some(2)).unwrap().unwrap().witness.is_some());
+    let manifest = compiler::manifest::Manifest::read_from_fs(&temp_dir).unwrap();
+    assert_eq!(manifest.max_degree, Some(2));
+
+    let Err(compiler::Error::Optimization(message)) = compile(Some(1)) else {
+        panic!("Expected the maximum degree to be rejected");
+    };
+    assert!(message.contains("at least 2"));
+}
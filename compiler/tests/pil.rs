@@ -1,9 +1,14 @@
 use backend::BackendType;
-use number::{Bn254Field, GoldilocksField};
+use compiler::Inputs;
+use executor::witgen::ProverQuery;
+use number::{Bn254Field, FieldElement, GoldilocksField};
 use std::path::Path;
 use test_log::test;
 
-pub fn verify_pil(file_name: &str, query_callback: Option<fn(&str) -> Option<GoldilocksField>>) {
+pub fn verify_pil(
+    file_name: &str,
+    query_callback: Option<fn(&ProverQuery<GoldilocksField>) -> Option<GoldilocksField>>,
+) {
     let input_file = Path::new(&format!("../test_data/pil/{file_name}"))
         .canonicalize()
         .unwrap();
@@ -13,21 +18,26 @@ pub fn verify_pil(file_name: &str, query_callback: Option<fn(&str) -> Option<Gol
         &input_file,
         &temp_dir,
         query_callback,
-        Some(BackendType::PilcomCli)
+        Some(BackendType::PilCheck),
+        None,
+        None,
     )
+    .unwrap()
     .witness
     .is_some());
-    compiler::verify(&temp_dir);
 }
 
 #[cfg(feature = "halo2")]
 fn gen_halo2_proof(file_name: &str, inputs: Vec<Bn254Field>) {
     compiler::compile_pil_or_asm(
         format!("../test_data/pil/{file_name}").as_str(),
-        inputs,
+        inputs.into(),
         &mktemp::Temp::new_dir().unwrap(),
         true,
         Some(BackendType::Halo2),
+        None,
+        None,
+        compiler::LinkerMode::Monolithic,
     )
     .unwrap();
 }
@@ -66,14 +76,15 @@ fn test_global() {
 fn test_sum_via_witness_query() {
     verify_pil(
         "sum_via_witness_query.pil",
-        Some(|q| {
-            match q {
-                "\"in\", 0" => Some(7.into()),
-                "\"in\", 1" => Some(8.into()),
-                "\"in\", 2" => Some(2.into()),
-                "\"in\", 3" => None, // This line checks that if we return "None", the system still tries to figure it out on its own.
+        Some(|q| match q {
+            ProverQuery::Other(kind, args) if kind == "in" => match args[0].to_degree() {
+                0 => Some(7.into()),
+                1 => Some(8.into()),
+                2 => Some(2.into()),
+                3 => None, // This line checks that if we return "None", the system still tries to figure it out on its own.
                 _ => None,
-            }
+            },
+            _ => None,
         }),
     );
     // prover query string uses a different convention,
@@ -85,15 +96,43 @@ fn test_witness_lookup() {
     verify_pil(
         "witness_lookup.pil",
         Some(|q| match q {
-            "\"input\", 0" => Some(3.into()),
-            "\"input\", 1" => Some(5.into()),
-            "\"input\", 2" => Some(2.into()),
+            ProverQuery::Input(0) => Some(3.into()),
+            ProverQuery::Input(1) => Some(5.into()),
+            ProverQuery::Input(2) => Some(2.into()),
             _ => Some(7.into()),
         }),
     );
     // halo2 fails with "gates must contain at least one constraint"
 }
 
+#[test]
+fn test_named_inputs() {
+    let input_file = Path::new("../test_data/pil/named_inputs.pil")
+        .canonicalize()
+        .unwrap();
+    let inputs =
+        Inputs::<GoldilocksField>::from_file(Path::new("../test_data/pil/named_inputs.json"))
+            .unwrap();
+    let temp_dir = mktemp::Temp::new_dir().unwrap();
+    compiler::compile_pil(
+        &input_file,
+        &temp_dir,
+        Some(inputs),
+        Some(BackendType::PilCheck),
+        None,
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        compiler::util::read_publics(
+            &compiler::analyze_pil::<GoldilocksField>(&input_file).unwrap(),
+            &temp_dir
+        )
+        .unwrap(),
+        vec![("out".to_string(), 12.into())]
+    );
+}
+
 #[test]
 #[should_panic(expected = "Witness generation failed.")]
 fn test_underdetermined_zero_no_solution() {
@@ -138,3 +177,220 @@ fn test_two_block_machine_functions() {
 fn test_fixed_columns() {
     verify_pil("fixed_columns.pil", None);
 }
+
+#[test]
+fn test_connect() {
+    let f = "connect.pil";
+    verify_pil(f, None);
+    gen_halo2_proof(f, Default::default());
+}
+
+#[test]
+fn test_different_degrees() {
+    let f = "different_degrees.pil";
+    verify_pil(f, None);
+    gen_halo2_proof(f, Default::default());
+}
+
+#[test]
+fn test_connect_backwards() {
+    let f = "connect_backwards.pil";
+    verify_pil(f, None);
+    gen_halo2_proof(f, Default::default());
+}
+
+#[test]
+fn test_connect_in_block_machine() {
+    let f = "connect_in_block_machine.pil";
+    verify_pil(f, None);
+    gen_halo2_proof(f, Default::default());
+}
+
+#[test]
+fn test_publics() {
+    let input_file = Path::new("../test_data/pil/fibonacci.pil")
+        .canonicalize()
+        .unwrap();
+    let temp_dir = mktemp::Temp::new_dir().unwrap();
+    compiler::compile_pil(
+        &input_file,
+        &temp_dir,
+        compiler::no_callback::<GoldilocksField>(),
+        None,
+        None,
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        compiler::util::read_publics(
+            &compiler::analyze_pil::<GoldilocksField>(&input_file).unwrap(),
+            &temp_dir
+        )
+        .unwrap(),
+        vec![("out".to_string(), 1597.into())]
+    );
+}
+
+#[test]
+fn test_public_of_fixed_column() {
+    let temp_dir = mktemp::Temp::new_dir().unwrap();
+    let input_file = temp_dir.join("fixed_public.pil");
+    std::fs::write(
+        &input_file,
+        "namespace main(4); col fixed C = [1, 2, 3, 4]; col witness x; x = C; public out = C(3);",
+    )
+    .unwrap();
+    let Err(error) = compiler::compile_pil(
+        &input_file,
+        &temp_dir,
+        compiler::no_callback::<GoldilocksField>(),
+        None,
+        None,
+        None,
+    ) else {
+        panic!("the public of a fixed column was accepted");
+    };
+    assert!(
+        matches!(&error, compiler::Error::Witgen(message) if message.contains("main.C, which is not a witness column")),
+        "{error}"
+    );
+}
+
+#[test]
+fn test_mixed_degrees() {
+    let temp_dir = mktemp::Temp::new_dir().unwrap();
+    let input_file = temp_dir.join("mixed_degrees.pil");
+    std::fs::write(
+        &input_file,
+        "namespace A(4); col fixed C = [1, 2, 3, 4]; col witness x; x = C; namespace B(8); col witness y; y = A.x;",
+    )
+    .unwrap();
+    let Err(error) = compiler::compile_pil(
+        &input_file,
+        &temp_dir,
+        compiler::no_callback::<GoldilocksField>(),
+        None,
+        None,
+        None,
+    ) else {
+        panic!("columns of different degrees were processed together");
+    };
+    assert!(
+        matches!(&error, compiler::Error::Witgen(message) if message.contains("found degrees")),
+        "{error}"
+    );
+}
+
+#[test]
+fn test_unreducible_degree() {
+    let temp_dir = mktemp::Temp::new_dir().unwrap();
+    let input_file = temp_dir.join("large_exponent.pil");
+    std::fs::write(&input_file, "namespace N(4); col witness x; x ** 100 = 1;").unwrap();
+    let Err(error) = compiler::compile_pil(
+        &input_file,
+        &temp_dir,
+        compiler::no_callback::<GoldilocksField>(),
+        None,
+        Some(2),
+        None,
+    ) else {
+        panic!("an identity of degree 100 was accepted for a maximum degree of 2");
+    };
+    assert!(
+        matches!(&error, compiler::Error::Optimization(message) if message.contains("still 100")),
+        "{error}"
+    );
+}
+
+#[test]
+fn test_read_publics_by_name() {
+    let temp_dir = mktemp::Temp::new_dir().unwrap();
+    let input_file = temp_dir.join("two_publics.pil");
+    std::fs::write(
+        &input_file,
+        "namespace main(4); col fixed C = [1, 2, 3, 4]; col witness x; x = C; public first = x(0); public last = x(3);",
+    )
+    .unwrap();
+    compiler::compile_pil(
+        &input_file,
+        &temp_dir,
+        compiler::no_callback::<GoldilocksField>(),
+        None,
+        None,
+        None,
+    )
+    .unwrap();
+    let pil = compiler::analyze_pil::<GoldilocksField>(&input_file).unwrap();
+
+    let publics_file = temp_dir.join("publics.json");
+    let write_publics = |publics: &[(&str, u64)]| {
+        let publics = publics
+            .iter()
+            .map(|(name, value)| (name.to_string(), GoldilocksField::from(*value)))
+            .collect::<Vec<_>>();
+        compiler::util::write_publics(&mut std::fs::File::create(&publics_file).unwrap(), &publics);
+    };
+
+    write_publics(&[("last", 4), ("first", 1)]);
+    assert_eq!(
+        compiler::util::read_publics(&pil, &temp_dir).unwrap(),
+        vec![
+            ("first".to_string(), 1.into()),
+            ("last".to_string(), 4.into())
+        ]
+    );
+
+    write_publics(&[("first", 1)]);
+    let error = compiler::util::read_publics(&pil, &temp_dir).unwrap_err();
+    assert!(error.to_string().contains("missing value of public last"));
+
+    write_publics(&[("first", 1), ("last", 4), ("other", 5)]);
+    let error = compiler::util::read_publics(&pil, &temp_dir).unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("other is not a public of the PIL"));
+
+    std::fs::write(
+        &publics_file,
+        r#"[{ "name": "first", "value": "1" }, { "name": "last", "value": "0x4" }]"#,
+    )
+    .unwrap();
+    let error = compiler::util::read_publics(&pil, &temp_dir).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[cfg(feature = "halo2")]
+#[test]
+fn test_halo2_keys() {
+    let temp_dir = mktemp::Temp::new_dir().unwrap();
+    let params = temp_dir.join("params.bin");
+    let keys = temp_dir.join("keys.bin");
+    BackendType::Halo2
+        .factory::<Bn254Field>()
+        .create(64)
+        .write_setup(&mut std::fs::File::create(&params).unwrap())
+        .unwrap();
+    let prove = |file_name: &str| {
+        compiler::compile_pil(
+            Path::new(&format!("../test_data/pil/{file_name}")),
+            &temp_dir,
+            compiler::no_callback::<Bn254Field>(),
+            Some(BackendType::Halo2),
+            None,
+            Some(compiler::KeyFiles {
+                params: &params,
+                keys: &keys,
+            }),
+        )
+    };
+
+    // The first proof writes the keys, the second one reads them.
+    prove("fibonacci.pil").unwrap();
+    assert!(keys.exists());
+    prove("fibonacci.pil").unwrap();
+
+    let Err(compiler::Error::Proving(message)) = prove("constant_in_identity.pil") else {
+        panic!("the keys of a different PIL were accepted");
+    };
+    assert!(message.contains("different PIL"), "{message}");
+}
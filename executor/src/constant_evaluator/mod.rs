@@ -8,17 +8,14 @@ use rayon::prelude::{IntoParallelIterator, ParallelIterator};
 
 /// Generates the constant polynomial values for all constant polynomials
 /// that are defined (and not just declared).
-/// @returns the values (in source order) and the degree of the polynomials.
+/// Each polynomial has as many values as the degree of its namespace.
+/// @returns the values (in source order) and the maximum degree of the polynomials.
 pub fn generate<T: FieldElement>(analyzed: &Analyzed<T>) -> (Vec<(&str, Vec<T>)>, DegreeType) {
     let mut degree = None;
     let mut other_constants = HashMap::new();
     for (poly, value) in analyzed.constant_polys_in_source_order() {
         if let Some(value) = value {
-            if let Some(degree) = degree {
-                assert!(degree == poly.degree);
-            } else {
-                degree = Some(poly.degree);
-            }
+            degree = degree.max(Some(poly.degree));
             let values = generate_values(analyzed, poly.degree, value, &other_constants);
             other_constants.insert(&poly.absolute_name, values);
         }
@@ -169,7 +166,7 @@ mod test {
                 _ => 0,
             } };
         "#;
-        let analyzed = analyze_string(src);
+        let analyzed = analyze_string(src).unwrap();
         let (constants, degree) = generate(&analyzed);
         assert_eq!(degree, 8);
         assert_eq!(
@@ -185,7 +182,7 @@ mod test {
             namespace F(%N);
             pol constant EVEN(i) { 2 * (i - 1) };
         "#;
-        let analyzed = analyze_string(src);
+        let analyzed = analyze_string(src).unwrap();
         let (constants, degree) = generate(&analyzed);
         assert_eq!(degree, 8);
         assert_eq!(
@@ -201,7 +198,7 @@ mod test {
             namespace F(%N);
             pol constant X(i) { i ^ (i + 17) | 3 };
         "#;
-        let analyzed = analyze_string(src);
+        let analyzed = analyze_string(src).unwrap();
         let (constants, degree) = generate(&analyzed);
         assert_eq!(degree, 8);
         assert_eq!(
@@ -222,7 +219,7 @@ mod test {
                 _ => 4,
             } + 1 };
         "#;
-        let analyzed = analyze_string(src);
+        let analyzed = analyze_string(src).unwrap();
         let (constants, degree) = generate(&analyzed);
         assert_eq!(degree, 8);
         assert_eq!(
@@ -239,7 +236,7 @@ mod test {
             macro minus_one(X) { X - 1 };
             pol constant EVEN(i) { 2 * minus_one(i) };
         "#;
-        let analyzed = analyze_string(src);
+        let analyzed = analyze_string(src).unwrap();
         let (constants, degree) = generate(&analyzed);
         assert_eq!(degree, 8);
         assert_eq!(
@@ -260,7 +257,7 @@ mod test {
             macro ite(C, T, F) { is_one(C) * T + is_zero(C) * F };
             pol constant TEN(i) { ite(is_equal(i, 10), 1, 0) };
         "#;
-        let analyzed = analyze_string(src);
+        let analyzed = analyze_string(src).unwrap();
         let (constants, degree) = generate(&analyzed);
         assert_eq!(degree, 12);
         assert_eq!(
@@ -282,7 +279,7 @@ mod test {
             col fixed half_nibble(i) { i & 0x7 };
             col fixed doubled_half_nibble(i) { half_nibble(i / 2) };
         "#;
-        let analyzed = analyze_string(src);
+        let analyzed = analyze_string(src).unwrap();
         let (constants, degree) = generate(&analyzed);
         assert_eq!(degree, 10);
         assert_eq!(constants.len(), 4);
@@ -322,7 +319,7 @@ mod test {
             col fixed empty = [] + [0]*;
             col fixed ref_other = [%N-1, alt(1), 8] + [0]*;
         "#;
-        let analyzed = analyze_string(src);
+        let analyzed = analyze_string(src).unwrap();
         let (constants, degree) = generate(&analyzed);
         assert_eq!(degree, 10);
         assert_eq!(constants.len(), 3);
@@ -347,7 +344,7 @@ mod test {
             namespace F(%N);
             col fixed arr = [0, 1, 2]* + [7];
         "#;
-        let analyzed = analyze_string(src);
+        let analyzed = analyze_string(src).unwrap();
         let (constants, degree) = generate(&analyzed);
         assert_eq!(degree, 10);
         assert_eq!(constants.len(), 1);
@@ -62,7 +62,7 @@ where
 
     pub fn assign(&mut self, key: K, value: T) {
         if let Some(coefficient) = self.coefficients.remove(&key) {
-            self.offset -= coefficient * value;
+            self.offset += coefficient * value;
         }
     }
 }
@@ -479,6 +479,22 @@ mod test {
         );
     }
 
+    #[test]
+    pub fn test_affine_assign() {
+        let mut a = AffineExpression::<_, GoldilocksField> {
+            coefficients: convert(vec![2, 3]),
+            offset: 1.into(),
+        };
+        a.assign(1, 5.into());
+        assert_eq!(
+            a,
+            AffineExpression {
+                coefficients: convert(vec![2]),
+                offset: 16.into(),
+            },
+        );
+    }
+
     #[test]
     pub fn test_affine_add() {
         let a = AffineExpression::<_, GoldilocksField> {
@@ -0,0 +1,239 @@
+//! Computation of witness columns that are defined by a polynomial identity,
+//! like the columns added when splitting identities of a large degree.
+
+use std::collections::HashMap;
+
+use ast::analyzed::{
+    Analyzed, BinaryOperator, Expression, FunctionValueDefinition, IdentityKind,
+    PolynomialReference, PolynomialType, UnaryOperator,
+};
+use number::{DegreeType, FieldElement};
+
+/// Completes `witness` with the witness columns of `analyzed` it does not contain.
+/// Each of them has to be defined by a polynomial identity `column = expression`, where
+/// `expression` only references columns that are known or defined before `column`.
+/// Witness generation of the machines is not affected by these identities, because
+/// it runs on the PIL before they are added.
+/// @returns the values of all witness columns, in source order.
+pub fn generate_auxiliary_columns<'a, T: FieldElement>(
+    analyzed: &'a Analyzed<T>,
+    fixed_col_values: &[(&str, Vec<T>)],
+    witness: Vec<(&str, Vec<T>)>,
+) -> Result<Vec<(&'a str, Vec<T>)>, String> {
+    let definitions = analyzed
+        .identities
+        .iter()
+        .filter(|identity| identity.kind == IdentityKind::Polynomial)
+        .filter_map(|identity| match identity.expression_for_poly_id() {
+            Expression::BinaryOperation(left, BinaryOperator::Sub, right) => match left.as_ref() {
+                Expression::PolynomialReference(column) if !column.next => {
+                    Some((column.name.as_str(), right.as_ref()))
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut evaluator = Evaluator {
+        analyzed,
+        fixed: fixed_col_values
+            .iter()
+            .map(|(name, values)| (*name, values.as_slice()))
+            .collect(),
+        witness: witness
+            .into_iter()
+            .map(|(name, values)| (name.to_string(), values))
+            .collect(),
+    };
+    let columns = analyzed.committed_polys_in_source_order();
+    for (poly, _) in &columns {
+        let name = poly.absolute_name.as_str();
+        if evaluator.witness.contains_key(name) {
+            continue;
+        }
+        let definition = definitions.get(name).ok_or_else(|| {
+            format!("Witness column {name} was not generated and is not defined by an identity.")
+        })?;
+        let values = (0..poly.degree)
+            .map(|row| evaluator.evaluate(definition, row))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Could not compute witness column {name}: {e}"))?;
+        evaluator.witness.insert(name.to_string(), values);
+    }
+    Ok(columns
+        .into_iter()
+        .map(|(poly, _)| {
+            let values = evaluator.witness.remove(&poly.absolute_name).unwrap();
+            (poly.absolute_name.as_str(), values)
+        })
+        .collect())
+}
+
+struct Evaluator<'a, 'b, T> {
+    analyzed: &'a Analyzed<T>,
+    fixed: HashMap<&'b str, &'b [T]>,
+    witness: HashMap<String, Vec<T>>,
+}
+
+impl<'a, 'b, T: FieldElement> Evaluator<'a, 'b, T> {
+    fn evaluate(&self, expr: &Expression<T>, row: DegreeType) -> Result<T, String> {
+        Ok(match expr {
+            Expression::Number(n) => *n,
+            Expression::Constant(name) => *self
+                .analyzed
+                .constants
+                .get(name)
+                .ok_or_else(|| format!("Unknown constant {name}"))?,
+            Expression::PolynomialReference(poly) => self.reference_value(poly, row)?,
+            Expression::PublicReference(name) => {
+                let public = self
+                    .analyzed
+                    .public_declarations
+                    .get(name)
+                    .ok_or_else(|| format!("Unknown public {name}"))?;
+                self.reference_value(&public.polynomial, public.index)?
+            }
+            Expression::BinaryOperation(left, op, right) => {
+                let left = self.evaluate(left, row)?;
+                let right = self.evaluate(right, row)?;
+                match op {
+                    BinaryOperator::Add => left + right,
+                    BinaryOperator::Sub => left - right,
+                    BinaryOperator::Mul => left * right,
+                    BinaryOperator::Div => left.integer_div(right),
+                    BinaryOperator::Pow => left.pow(right.to_integer()),
+                    BinaryOperator::Mod => left.integer_mod(right),
+                    BinaryOperator::BinaryAnd => (left.to_integer() & right.to_integer()).into(),
+                    BinaryOperator::BinaryXor => (left.to_integer() ^ right.to_integer()).into(),
+                    BinaryOperator::BinaryOr => (left.to_integer() | right.to_integer()).into(),
+                    BinaryOperator::ShiftLeft | BinaryOperator::ShiftRight => {
+                        shift(left, op, right).ok_or_else(|| {
+                            format!("{left} {op} {right} does not fit into a field element")
+                        })?
+                    }
+                }
+            }
+            Expression::UnaryOperation(op, inner) => {
+                let value = self.evaluate(inner, row)?;
+                match op {
+                    UnaryOperator::Plus => value,
+                    UnaryOperator::Minus => -value,
+                }
+            }
+            _ => return Err(format!("Cannot evaluate {expr}")),
+        })
+    }
+
+    fn reference_value(&self, poly: &PolynomialReference, row: DegreeType) -> Result<T, String> {
+        if poly.index.is_some() {
+            return Err(format!("Array column {poly} is not supported"));
+        }
+        let (definition, value) = self
+            .analyzed
+            .definitions
+            .get(&poly.name)
+            .ok_or_else(|| format!("Unknown column {poly}"))?;
+        let row = if poly.next {
+            (row + 1) % definition.degree
+        } else {
+            row
+        };
+        match (definition.poly_type, value) {
+            (PolynomialType::Intermediate, Some(FunctionValueDefinition::Mapping(e))) => {
+                self.evaluate(e, row)
+            }
+            (PolynomialType::Intermediate, _) => {
+                Err(format!("Intermediate column {poly} has no definition"))
+            }
+            _ => self
+                .witness
+                .get(poly.name.as_str())
+                .map(Vec::as_slice)
+                .or_else(|| self.fixed.get(poly.name.as_str()).copied())
+                .ok_or_else(|| format!("Column {poly} is not known"))?
+                .get(row as usize)
+                .copied()
+                .ok_or_else(|| format!("Row {row} of column {poly} is out of bounds")),
+        }
+    }
+}
+
+/// Shifts `left` by `right` bits, or returns None if the amount or the result
+/// does not fit.
+fn shift<T: FieldElement>(left: T, op: &BinaryOperator, right: T) -> Option<T> {
+    let amount = DegreeType::try_from(right.to_arbitrary_integer()).ok()?;
+    let value = left.to_arbitrary_integer();
+    let value = match op {
+        BinaryOperator::ShiftLeft if left.is_zero() => value,
+        BinaryOperator::ShiftLeft if amount > T::BITS as DegreeType => return None,
+        BinaryOperator::ShiftLeft => value << amount,
+        _ => value >> amount,
+    };
+    T::Integer::try_from(value).ok().map(T::from)
+}
+
+#[cfg(test)]
+mod test {
+    use number::GoldilocksField;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn defined_columns() {
+        let pil = pil_analyzer::analyze_string::<GoldilocksField>(
+            r#"namespace N(4);
+    col fixed FIRST = [1, 0, 0, 0];
+    col witness X;
+    col witness Y;
+    col witness A;
+    col witness B;
+    Y = X * X;
+    A = FIRST * X';
+    B = A * Y + 1;
+"#,
+        )
+        .unwrap();
+        let column = |values: [u64; 4]| values.map(GoldilocksField::from).to_vec();
+        let fixed = [("N.FIRST", column([1, 0, 0, 0]))];
+        // Y is given and not computed from its identity.
+        let witness = vec![("N.X", column([2, 3, 4, 5])), ("N.Y", column([0, 0, 0, 0]))];
+        let result = generate_auxiliary_columns(&pil, &fixed, witness).unwrap();
+        assert_eq!(
+            result,
+            vec![
+                ("N.X", column([2, 3, 4, 5])),
+                ("N.Y", column([0, 0, 0, 0])),
+                ("N.A", column([3, 0, 0, 0])),
+                ("N.B", column([1, 1, 1, 1])),
+            ]
+        );
+    }
+
+    #[test]
+    fn undefined_column() {
+        let pil = pil_analyzer::analyze_string::<GoldilocksField>(
+            "namespace N(4); col witness X; col witness Y; X * Y = 0;",
+        )
+        .unwrap();
+        let witness = vec![("N.X", vec![GoldilocksField::from(0); 4])];
+        let error = generate_auxiliary_columns(&pil, &[], witness).unwrap_err();
+        assert!(error.contains("N.Y"));
+    }
+
+    #[test]
+    fn invalid_values() {
+        let pil = pil_analyzer::analyze_string::<GoldilocksField>(
+            "namespace N(4); col witness X; col witness Y; Y = X << 100;",
+        )
+        .unwrap();
+        let witness = vec![("N.X", vec![GoldilocksField::from(1); 4])];
+        let error = generate_auxiliary_columns(&pil, &[], witness).unwrap_err();
+        assert!(error.contains("does not fit"), "{error}");
+
+        let witness = vec![("N.X", vec![GoldilocksField::from(0); 2])];
+        let error = generate_auxiliary_columns(&pil, &[], witness).unwrap_err();
+        assert!(error.contains("out of bounds"), "{error}");
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use ast::analyzed::{
+    Expression, Identity, IdentityKind, PolyID, PolynomialReference, PolynomialType,
+};
+use number::{DegreeType, FieldElement};
+
+use super::rows::{Row, RowPair};
+use super::{EvalResult, EvalValue, FixedData, IncompleteCause};
+
+/// A cell of the left side of a `connect` identity, given by the index
+/// of the expression on the left side and the row.
+type Cell = (usize, DegreeType);
+
+/// The values of cells, by column and row.
+pub type CellValues<T> = HashMap<(PolyID, DegreeType), T>;
+
+/// The cycles of the permutation defined by a single `connect` identity.
+struct Connection {
+    /// The columns on the left side of the identity.
+    columns: Vec<PolyID>,
+    /// Maps each cell that is connected to at least one other cell to the index of its cycle.
+    cycle_of: HashMap<Cell, usize>,
+    /// The cells of each cycle, in row order.
+    cycles: Vec<Vec<Cell>>,
+}
+
+/// Propagates values along the copy constraints defined by `connect` identities.
+///
+/// The right side of `{ a_0, .., a_k } connect { S_0, .., S_k }` encodes a permutation
+/// of the cells of the left side: If `S_i` has the value `j * N + s` in row `r`
+/// (where `N` is the degree), the cell `a_i` in row `r` is connected to the
+/// cell `a_j` in row `s`. All cells in a cycle of this permutation have to be equal.
+///
+/// A cell is constrained to the value of any other cell of its cycle that is already known,
+/// no matter whether that cell is in an earlier or a later row. Values of cells in rows that
+/// are processed later cannot reach rows that are already finalized, though. Such cells are
+/// reported by [CopyConstraints::late_values], so that witness generation can be repeated with
+/// their values known from the start (see [FixedData::with_copy_constraint_values]).
+pub struct CopyConstraints<'a, T> {
+    /// The cycle data for each `connect` identity (by ID).
+    connections: BTreeMap<u64, Connection>,
+    /// The values of cells that were determined by a previous run of witness generation.
+    known_values: Option<&'a CellValues<T>>,
+    /// The values of the connected cells in the rows that have been finalized,
+    /// see [CopyConstraints::record_row].
+    values: CellValues<T>,
+}
+
+impl<'a, T: FieldElement> CopyConstraints<'a, T> {
+    pub fn new(
+        fixed_data: &'a FixedData<'a, T>,
+        identities: &[&Identity<T>],
+    ) -> Result<Self, String> {
+        let mut connections = BTreeMap::new();
+
+        for identity in identities
+            .iter()
+            .filter(|identity| identity.kind == IdentityKind::Connect)
+        {
+            assert!(
+                identity.left.selector.is_none() && identity.right.selector.is_none(),
+                "Selectors are not supported in connect identities: {identity}"
+            );
+            let left = column_references(identity, &identity.left.expressions);
+            let right = column_references(identity, &identity.right.expressions);
+            let columns = left.iter().map(|l| l.poly_id()).collect::<Vec<_>>();
+            let degree = fixed_data.common_degree(&columns)?;
+            assert!(
+                right
+                    .iter()
+                    .all(|r| r.poly_id().ptype == PolynomialType::Constant),
+                "The right side of a connect identity has to consist of fixed columns: {identity}"
+            );
+
+            let permutation = |(index, row): Cell| -> Cell {
+                let target =
+                    fixed_data.fixed_cols[&right[index].poly_id()].values[row as usize].to_degree();
+                let target = ((target / degree) as usize, target % degree);
+                assert!(
+                    target.0 < left.len(),
+                    "Invalid value in row {row} of {}: {identity}",
+                    right[index]
+                );
+                target
+            };
+
+            let mut visited = HashSet::new();
+            let mut cycle_of = HashMap::new();
+            let mut cycles = vec![];
+            for start in (0..degree).flat_map(|row| (0..left.len()).map(move |index| (index, row)))
+            {
+                if !visited.insert(start) {
+                    continue;
+                }
+                let mut cell = permutation(start);
+                if cell == start {
+                    continue;
+                }
+                let mut cycle = vec![start];
+                while cell != start {
+                    assert!(
+                        visited.insert(cell),
+                        "The right side of a connect identity does not encode a permutation: {identity}"
+                    );
+                    cycle.push(cell);
+                    cell = permutation(cell);
+                }
+                cycle.sort_by_key(|(index, row)| (*row, *index));
+                for cell in &cycle {
+                    cycle_of.insert(*cell, cycles.len());
+                }
+                cycles.push(cycle);
+            }
+            connections.insert(
+                identity.id,
+                Connection {
+                    columns,
+                    cycle_of,
+                    cycles,
+                },
+            );
+        }
+
+        Ok(CopyConstraints {
+            connections,
+            known_values: fixed_data.copy_constraint_values,
+            values: Default::default(),
+        })
+    }
+
+    /// Processes a `connect` identity on the current row, using the values of the
+    /// finalized rows recorded by [CopyConstraints::record_row].
+    pub fn process_identity<'b>(
+        &self,
+        identity: &'b Identity<T>,
+        rows: &RowPair<'_, 'b, T>,
+    ) -> EvalResult<'b, T> {
+        self.process_identity_with(identity, rows, |poly_id, row| {
+            self.values.get(&(poly_id, row)).copied()
+        })
+    }
+
+    /// Processes a `connect` identity on the current row by constraining each cell
+    /// to the value of another cell of its cycle. `value_of` returns the values of
+    /// the cells in other rows, if they are known.
+    /// If no such value is known, the cell is constrained to be equal to the first other
+    /// cell of its cycle in the current row, if there is one.
+    pub fn process_identity_with<'b>(
+        &self,
+        identity: &'b Identity<T>,
+        rows: &RowPair<'_, 'b, T>,
+        value_of: impl Fn(PolyID, DegreeType) -> Option<T>,
+    ) -> EvalResult<'b, T> {
+        let connection = &self.connections[&identity.id];
+        let row = rows.current_row_index;
+
+        let mut updates = EvalValue::complete(vec![]);
+        for (index, expr) in identity.left.expressions.iter().enumerate() {
+            let Some(&cycle) = connection.cycle_of.get(&(index, row)) else {
+                continue;
+            };
+            let cycle = &connection.cycles[cycle];
+            let other_value = self
+                .known_value(connection.columns[index], row)
+                .or_else(|| self.cycle_value(connection, cycle, &value_of, row))
+                .map(|value| Ok(value.into()))
+                .or_else(|| {
+                    cycle
+                        .iter()
+                        .find(|(other_index, other_row)| *other_row == row && *other_index != index)
+                        .map(|(other_index, _)| {
+                            rows.evaluate(&identity.left.expressions[*other_index])
+                        })
+                })
+                .unwrap_or(Err(IncompleteCause::DataNotYetAvailable));
+            match (rows.evaluate(expr), other_value) {
+                (Ok(value), Ok(other_value)) => updates.combine((value - other_value).solve()?),
+                (Err(e), _) | (_, Err(e)) => updates.status = updates.status.combine(e),
+            }
+        }
+        Ok(updates)
+    }
+
+    /// Records the known values of a finalized row that are connected to other cells.
+    pub fn record_row(&mut self, row: DegreeType, values: &Row<'_, T>) {
+        for connection in self.connections.values() {
+            for (index, column) in connection.columns.iter().enumerate() {
+                if !connection.cycle_of.contains_key(&(index, row)) {
+                    continue;
+                }
+                if let Some(value) = Option::<T>::from(&values[column].value) {
+                    self.values.insert((*column, row), value);
+                }
+            }
+        }
+    }
+
+    /// Returns the values of the cells recorded by [CopyConstraints::record_row] without a value
+    /// although another cell of their cycle is known, i.e. the values that were determined only
+    /// after the rows of these cells were finalized.
+    pub fn late_values(&self) -> CellValues<T> {
+        self.late_values_with(|poly_id, row| self.values.get(&(poly_id, row)).copied())
+    }
+
+    /// Like [CopyConstraints::late_values], but `value_of` returns the values of the cells.
+    pub fn late_values_with(
+        &self,
+        value_of: impl Fn(PolyID, DegreeType) -> Option<T>,
+    ) -> CellValues<T> {
+        let mut late_values = CellValues::default();
+        for connection in self.connections.values() {
+            for cycle in &connection.cycles {
+                let cells = cycle
+                    .iter()
+                    .map(|(index, row)| (connection.columns[*index], *row))
+                    .collect::<Vec<_>>();
+                let Some(value) = cells.iter().find_map(|(poly_id, row)| {
+                    value_of(*poly_id, *row).or_else(|| self.known_value(*poly_id, *row))
+                }) else {
+                    continue;
+                };
+                late_values.extend(
+                    cells
+                        .into_iter()
+                        .filter(|(poly_id, row)| {
+                            value_of(*poly_id, *row).is_none()
+                                && self.known_value(*poly_id, *row).is_none()
+                        })
+                        .map(|cell| (cell, value)),
+                );
+            }
+        }
+        late_values
+    }
+
+    /// Returns the value of the cell determined by a previous run of witness generation.
+    fn known_value(&self, poly_id: PolyID, row: DegreeType) -> Option<T> {
+        self.known_values?.get(&(poly_id, row)).copied()
+    }
+
+    /// Returns the value of any cell of the cycle outside the given row, if one is known.
+    fn cycle_value(
+        &self,
+        connection: &Connection,
+        cycle: &[Cell],
+        value_of: &impl Fn(PolyID, DegreeType) -> Option<T>,
+        row: DegreeType,
+    ) -> Option<T> {
+        cycle
+            .iter()
+            .filter(|(_, other_row)| *other_row != row)
+            .find_map(|(index, other_row)| {
+                let poly_id = connection.columns[*index];
+                value_of(poly_id, *other_row).or_else(|| self.known_value(poly_id, *other_row))
+            })
+    }
+}
+
+/// Returns the column references in a list of expressions and
+/// panics if any of them is not a plain (non-shifted) column reference.
+fn column_references<'a, T: FieldElement>(
+    identity: &Identity<T>,
+    expressions: &'a [Expression<T>],
+) -> Vec<&'a PolynomialReference> {
+    expressions
+        .iter()
+        .map(|e| match e {
+            Expression::PolynomialReference(p) if !p.next && p.index.is_none() => p,
+            _ => panic!(
+                "Only plain column references are supported in connect identities: {identity}"
+            ),
+        })
+        .collect()
+}
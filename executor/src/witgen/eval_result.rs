@@ -25,6 +25,8 @@ pub enum IncompleteCause<K = usize> {
     ExponentiationTerm,
     /// No query answer. Example: we ask the prover for `a` and receive no value. Arguments: the query and the column name
     NoQueryAnswer(String, String),
+    /// The query does not follow the query protocol. Example: `("input", "a", "b")`. Arguments: the error and the column name
+    InvalidQuery(String, String),
     /// Query match scrutinee is not constant, so the query fails. Example: evaluate `match x { 1 => 1, _ => 0}` but `x` is not constant.
     NonConstantQueryMatchScrutinee,
     /// Query element is not constant.
@@ -33,12 +35,16 @@ pub enum IncompleteCause<K = usize> {
     NonConstantLeftSelector,
     /// A value to be written is not constant. TODO: should this be covered by another case? it's used for memory
     NonConstantWriteValue,
+    /// The address or step of a memory access is not (yet) constant.
+    NonConstantMemoryAccess,
     /// An expression cannot be evaluated.
     ExpressionEvaluationUnimplemented(String),
     /// A value is not found on the left side of a match. Example: `match x {1 => 2, 3 => 4}` where `x == 0`
     NoMatchArmFound,
     /// A lookup into a block machine was not able to assign all variables in the query. It could be that we just need to re-run it.
     BlockMachineLookupIncomplete,
+    /// A call to a dynamic machine did not reach the latch with all variables in the query assigned.
+    DynamicMachineLookupIncomplete,
     /// We could not (yet) read some data
     DataNotYetAvailable,
     /// Last resort error when all possible solving approaches have failed. TODO: make this more precise or use another variant
@@ -1,4 +1,4 @@
-use ast::analyzed::{Identity, PolyID};
+use ast::analyzed::{Identity, IdentityKind, PolyID};
 use itertools::Itertools;
 use number::{DegreeType, FieldElement};
 use parser_util::lines::indent;
@@ -10,13 +10,14 @@ use crate::witgen::identity_processor::{self, IdentityProcessor};
 use crate::witgen::rows::RowUpdater;
 
 use super::column_map::ColumnMap;
-use super::machines::{KnownMachine, Machine};
+use super::copy_constraints::{CellValues, CopyConstraints};
+use super::machines::{KnownMachine, Machine, Machines};
 use super::query_processor::QueryProcessor;
 use super::range_constraints::RangeConstraint;
 
 use super::machines::FixedLookup;
-use super::rows::{Row, RowFactory, RowPair, UnknownStrategy};
-use super::{EvalError, EvalResult, FixedData};
+use super::rows::{CellValue, Row, RowFactory, RowPair, UnknownStrategy};
+use super::{EvalError, EvalResult, FixedData, ProverInput};
 
 /// Phase in which [Generator::compute_next_row_or_initialize] is called.
 #[derive(Debug, PartialEq)]
@@ -50,6 +51,8 @@ pub struct Generator<'a, 'b, T: FieldElement, QueryCallback: Send + Sync> {
     witnesses: BTreeSet<PolyID>,
     row_factory: RowFactory<'a, T>,
     identity_processor: IdentityProcessor<'a, 'b, T>,
+    /// Propagates values along the copy constraints of `connect` identities
+    copy_constraints: CopyConstraints<'a, T>,
     query_processor: Option<QueryProcessor<'a, T, QueryCallback>>,
     fixed_data: &'a FixedData<'a, T>,
     /// The subset of identities that contains a reference to the next row
@@ -64,6 +67,8 @@ pub struct Generator<'a, 'b, T: FieldElement, QueryCallback: Send + Sync> {
     current: Row<'a, T>,
     /// Values of the witness polynomials in the next row
     next: Row<'a, T>,
+    /// The degree of the namespace of the witness columns
+    degree: DegreeType,
     current_row_index: DegreeType,
     last_report: DegreeType,
     last_report_time: Instant,
@@ -71,7 +76,7 @@ pub struct Generator<'a, 'b, T: FieldElement, QueryCallback: Send + Sync> {
 
 impl<'a, 'b, T: FieldElement, QueryCallback> Generator<'a, 'b, T, QueryCallback>
 where
-    QueryCallback: FnMut(&str) -> Option<T> + Send + Sync,
+    QueryCallback: ProverInput<T> + Send + Sync,
 {
     pub fn new(
         fixed_data: &'a FixedData<'a, T>,
@@ -79,12 +84,15 @@ where
         identities: &'a [&'a Identity<T>],
         witnesses: BTreeSet<PolyID>,
         global_range_constraints: ColumnMap<Option<RangeConstraint<T>>>,
-        machines: Vec<KnownMachine<'a, T>>,
+        machines: &'b mut [KnownMachine<'a, T>],
         query_callback: Option<QueryCallback>,
-    ) -> Self {
+    ) -> Result<Self, String> {
         let query_processor =
             query_callback.map(|query_callback| QueryProcessor::new(fixed_data, query_callback));
-        let identity_processor = IdentityProcessor::new(fixed_data, fixed_lookup, machines);
+        let identity_processor =
+            IdentityProcessor::new(fixed_data, fixed_lookup, Machines::from(machines));
+        let copy_constraints = CopyConstraints::new(fixed_data, identities)?;
+        let degree = fixed_data.common_degree(&witnesses)?;
         let row_factory = RowFactory::new(fixed_data, global_range_constraints);
         let default_row = row_factory.fresh_row();
 
@@ -97,28 +105,37 @@ where
             witnesses,
             query_processor,
             identity_processor,
+            copy_constraints,
             fixed_data,
             identities_with_next_ref: identities_with_next,
             identities_without_next_ref: identities_without_next,
             previous: default_row.clone(),
             current: default_row.clone(),
             next: default_row,
-            current_row_index: fixed_data.degree - 1,
+            degree,
+            current_row_index: degree - 1,
             last_report: 0,
             last_report_time: Instant::now(),
         };
         // For identities like `pc' = (1 - first_step') * <...>`, we need to process the last
         // row before processing the first row.
-        generator
-            .compute_next_row_or_initialize(generator.last_row(), ProcessingPhase::Initialization);
-        generator
+        generator.compute_next_row_or_initialize(
+            generator.last_row(),
+            ProcessingPhase::Initialization,
+        )?;
+        Ok(generator)
+    }
+
+    /// Returns the number of rows to generate.
+    pub fn degree(&self) -> DegreeType {
+        self.degree
     }
 
     fn last_row(&self) -> DegreeType {
-        self.fixed_data.degree - 1
+        self.degree - 1
     }
 
-    pub fn compute_next_row(&mut self, next_row: DegreeType) -> ColumnMap<T> {
+    pub fn compute_next_row(&mut self, next_row: DegreeType) -> Result<ColumnMap<T>, String> {
         self.compute_next_row_or_initialize(next_row, ProcessingPhase::Regular)
     }
 
@@ -126,12 +143,13 @@ where
         &mut self,
         next_row: DegreeType,
         phase: ProcessingPhase,
-    ) -> ColumnMap<T> {
+    ) -> Result<ColumnMap<T>, String> {
         if phase == ProcessingPhase::Initialization {
             assert_eq!(next_row, self.last_row());
             self.current_row_index = next_row;
         } else {
             self.set_next_row_and_log(next_row);
+            self.set_external_values()?;
         }
 
         log::trace!("Row: {}", self.current_row_index);
@@ -145,8 +163,7 @@ where
             CompletableIdentities::new(self.identities_with_next_ref.iter().cloned());
         self.loop_until_no_progress(&mut identities_without_next_ref)
             .and_then(|_| self.loop_until_no_progress(&mut identities_with_next_ref))
-            .map_err(|e| self.report_failure_and_panic_unsatisfiable(e))
-            .unwrap();
+            .map_err(|e| self.report_failure_unsatisfiable(e))?;
 
         // Check that the computed row is "final" by asserting that all unknown values can
         // be set to 0.
@@ -161,8 +178,7 @@ where
                 .and_then(|_| {
                     self.process_identities(&mut identities_with_next_ref, UnknownStrategy::Zero)
                 })
-                .map_err(|e| self.report_failure_and_panic_underconstrained(e))
-                .unwrap();
+                .map_err(|e| self.report_failure_underconstrained(e))?;
         }
 
         log::trace!(
@@ -173,7 +189,37 @@ where
 
         self.shift_rows();
 
-        self.previous.clone().into()
+        if phase == ProcessingPhase::Regular {
+            self.copy_constraints
+                .record_row(self.current_row_index, &self.previous);
+        }
+        Ok(self.previous.clone().into())
+    }
+
+    /// Sets the externally provided values of the witness columns in the current row.
+    /// Fails if a value was already determined differently from the previous row.
+    fn set_external_values(&mut self) -> Result<(), String> {
+        let row = self.current_row_index as usize;
+        for poly_id in &self.witnesses {
+            let Some(value) = self.fixed_data.witness_cols[poly_id]
+                .external_values
+                .and_then(|values| values.get(row))
+            else {
+                continue;
+            };
+            let cell = &mut self.current[poly_id];
+            match cell.value {
+                CellValue::Known(known) if known != *value => {
+                    let name = cell.name;
+                    return Err(format!(
+                        "Witness generation failed. Row {row}: the external value {value} of {name} contradicts the computed value {known}.{}",
+                        self.rom_context()
+                    ));
+                }
+                _ => cell.value = CellValue::Known(*value),
+            }
+        }
+        Ok(())
     }
 
     /// Loops over all identities and queries, until no further progress is made.
@@ -233,12 +279,15 @@ where
                 self.fixed_data,
                 unknown_strategy,
             );
-            let result: EvalResult<'a, T> = self
-                .identity_processor
-                .process_identity(identity, &row_pair)
-                .map_err(|err| {
-                    format!("{identity}:\n{}", indent(&format!("{err}"), "    ")).into()
-                });
+            let result: EvalResult<'a, T> = match identity.kind {
+                IdentityKind::Connect => {
+                    self.copy_constraints.process_identity(identity, &row_pair)
+                }
+                _ => self
+                    .identity_processor
+                    .process_identity(identity, &row_pair),
+            }
+            .map_err(|err| format!("{identity}:\n{}", indent(&format!("{err}"), "    ")).into());
 
             match result {
                 Ok(eval_value) => {
@@ -276,7 +325,7 @@ where
         std::mem::swap(&mut self.next, &mut self.current);
     }
 
-    fn report_failure_and_panic_unsatisfiable(&self, failures: Vec<EvalError<T>>) -> ! {
+    fn report_failure_unsatisfiable(&self, failures: Vec<EvalError<T>>) -> String {
         log::error!(
             "\nError: Row {} failed. Set RUST_LOG=debug for more information.\n",
             self.current_row_index
@@ -285,17 +334,21 @@ where
         log::debug!("{}", self.current.render("Current Row", false));
         log::debug!("{}", self.next.render("Next Row", false));
         log::debug!("Set RUST_LOG=trace to understand why these values were chosen.");
+        let failures = failures
+            .iter()
+            .map(|r| indent(&r.to_string(), "    "))
+            .join("\n");
         log::debug!(
-            "Assuming these values are correct, the following identities fail:\n{}\n",
-            failures
-                .iter()
-                .map(|r| indent(&r.to_string(), "    "))
-                .join("\n")
+            "Assuming these values are correct, the following identities fail:\n{failures}\n"
         );
-        panic!("Witness generation failed.");
+        format!(
+            "Witness generation failed. Row {} is not satisfiable:\n{failures}{}",
+            self.current_row_index,
+            self.rom_context()
+        )
     }
 
-    fn report_failure_and_panic_underconstrained(&self, failures: Vec<EvalError<T>>) -> ! {
+    fn report_failure_underconstrained(&self, failures: Vec<EvalError<T>>) -> String {
         log::error!(
             "\nError: Row {} failed. Set RUST_LOG=debug for more information.\n",
             self.current_row_index
@@ -305,20 +358,43 @@ where
         log::debug!("{}", self.current.render("Current Row", true));
         log::debug!("{}", self.next.render("Next Row", true));
         log::debug!("\nSet RUST_LOG=trace to understand why these values were (not) chosen.");
+        let failures = failures
+            .iter()
+            .map(|r| indent(&r.to_string(), "    "))
+            .join("\n");
         log::debug!(
-            "Assuming zero for unknown values, the following identities fail:\n{}\n",
-            failures
-                .iter()
-                .map(|r| indent(&r.to_string(), "    "))
-                .join("\n")
+            "Assuming zero for unknown values, the following identities fail:\n{failures}\n"
         );
-        panic!("Witness generation failed.");
+        format!(
+            "Witness generation failed. Row {} is underconstrained:\n{failures}{}",
+            self.current_row_index,
+            self.rom_context()
+        )
+    }
+
+    /// Describes the statement of the ROM at the pc of the current row, if the ROM of
+    /// the main VM is known and the pc of the current row was determined.
+    fn rom_context(&self) -> String {
+        let Some(rom) = self.fixed_data.rom_debug_info else {
+            return String::new();
+        };
+        self.witnesses
+            .iter()
+            .map(|poly_id| &self.current[poly_id])
+            .find(|cell| cell.name == rom.pc)
+            .and_then(|cell| Option::<T>::from(&cell.value))
+            .map(|pc| format!("\n{}", rom.describe(pc)))
+            .unwrap_or_default()
     }
 
     /// Verifies the proposed values for the next row.
     /// TODO this is bad for machines because we might introduce rows in the machine that are then
     /// not used.
-    pub fn propose_next_row(&mut self, next_row: DegreeType, values: &ColumnMap<T>) -> bool {
+    pub fn propose_next_row(
+        &mut self,
+        next_row: DegreeType,
+        values: &ColumnMap<T>,
+    ) -> Result<bool, String> {
         self.set_next_row_and_log(next_row);
 
         let proposed_row = self.row_factory.row_from_known_values_dense(values);
@@ -327,6 +403,7 @@ where
             self.check_row_pair(&proposed_row, false) && self.check_row_pair(&proposed_row, true);
 
         if constraints_valid {
+            self.copy_constraints.record_row(next_row, &proposed_row);
             self.previous = proposed_row;
         } else {
             // Note that we never update `current` if proposing a row succeeds (the happy path).
@@ -334,9 +411,9 @@ where
             // correctly forward-propagate values via next references.
             std::mem::swap(&mut self.current, &mut self.previous);
             self.next = self.row_factory.fresh_row();
-            self.compute_next_row(next_row - 1);
+            self.compute_next_row(next_row - 1)?;
         }
-        constraints_valid
+        Ok(constraints_valid)
     }
 
     fn check_row_pair(&mut self, proposed_row: &Row<'a, T>, previous: bool) -> bool {
@@ -371,11 +448,15 @@ where
         };
 
         for identity in identities.iter() {
-            if self
-                .identity_processor
-                .process_identity(identity, &row_pair)
-                .is_err()
-            {
+            let result = match identity.kind {
+                IdentityKind::Connect => {
+                    self.copy_constraints.process_identity(identity, &row_pair)
+                }
+                _ => self
+                    .identity_processor
+                    .process_identity(identity, &row_pair),
+            };
+            if result.is_err() {
                 log::debug!("Previous {:?}", self.previous);
                 log::debug!("Proposed {:?}", proposed_row);
                 log::debug!("Failed on identity: {}", identity);
@@ -386,6 +467,24 @@ where
         true
     }
 
+    /// Finalizes all sub-machines. Each machine can call all other machines in the process.
+    pub fn finalize_machines(&mut self) -> Result<(), String> {
+        let machines = &mut self.identity_processor.machines;
+        for i in 0..machines.len() {
+            machines
+                .call(i, |machine, others| {
+                    machine.finalize(self.identity_processor.fixed_lookup, others)
+                })
+                .map_err(|e| {
+                    format!(
+                        "Failed to finalize machine:\n{}",
+                        indent(&e.to_string(), "    ")
+                    )
+                })?;
+        }
+        Ok(())
+    }
+
     pub fn machine_witness_col_values(&mut self) -> HashMap<PolyID, Vec<T>> {
         let mut result: HashMap<_, _> = Default::default();
         let name_to_id = self
@@ -394,7 +493,7 @@ where
             .iter()
             .map(|(poly_id, col)| (col.name.as_str(), poly_id))
             .collect::<BTreeMap<_, _>>();
-        for m in &mut self.identity_processor.machines {
+        for m in self.identity_processor.machines.iter_mut() {
             for (col_name, col) in
                 m.take_witness_col_values(self.fixed_data, self.identity_processor.fixed_lookup)
             {
@@ -404,6 +503,21 @@ where
         result
     }
 
+    /// Returns the values of cells connected by copy constraints that were determined only
+    /// after their rows had been finalized, in this machine and in all sub-machines.
+    pub fn late_copy_constraint_values(&mut self) -> CellValues<T> {
+        let mut values = self.copy_constraints.late_values();
+        for m in self.identity_processor.machines.iter_mut() {
+            values.extend(m.late_copy_constraint_values());
+        }
+        values
+    }
+
+    /// Consumes the generator and returns the query callback.
+    pub fn into_query_callback(self) -> Option<QueryCallback> {
+        self.query_processor.map(QueryProcessor::into_callback)
+    }
+
     fn set_next_row_and_log(&mut self, next_row: DegreeType) {
         if next_row >= self.last_report + 1000 {
             let duration = self.last_report_time.elapsed();
@@ -422,8 +536,8 @@ where
 
             log::info!(
                 "{next_row} of {} rows ({}%) - {} rows/s, {identities_per_sec}k identities/s, {progress_percentage}% progress",
-                self.fixed_data.degree,
-                next_row * 100 / self.fixed_data.degree,
+                self.degree,
+                next_row * 100 / self.degree,
                 1_000_000_000 / duration.as_micros()
             );
             self.last_report = next_row;
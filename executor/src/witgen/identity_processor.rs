@@ -7,7 +7,7 @@ use number::FieldElement;
 
 use super::{
     affine_expression::AffineExpression,
-    machines::{FixedLookup, KnownMachine, Machine},
+    machines::{FixedLookup, Machine, Machines},
     rows::RowPair,
     EvalResult, EvalValue, FixedData, IncompleteCause,
 };
@@ -16,14 +16,14 @@ use super::{
 pub struct IdentityProcessor<'a, 'b, T: FieldElement> {
     fixed_data: &'a FixedData<'a, T>,
     pub fixed_lookup: &'b mut FixedLookup<T>,
-    pub machines: Vec<KnownMachine<'a, T>>,
+    pub machines: Machines<'a, 'b, T>,
 }
 
 impl<'a, 'b, T: FieldElement> IdentityProcessor<'a, 'b, T> {
     pub fn new(
         fixed_data: &'a FixedData<'a, T>,
         fixed_lookup: &'b mut FixedLookup<T>,
-        machines: Vec<KnownMachine<'a, T>>,
+        machines: Machines<'a, 'b, T>,
     ) -> Self {
         Self {
             fixed_data,
@@ -111,15 +111,19 @@ impl<'a, 'b, T: FieldElement> IdentityProcessor<'a, 'b, T> {
             return result;
         }
 
-        for m in &mut self.machines {
+        for i in 0..self.machines.len() {
+            // The called machine can in turn call all machines except itself.
             // TODO also consider the reasons above.
-            if let Some(result) = m.process_plookup(
-                self.fixed_data,
-                self.fixed_lookup,
-                identity.kind,
-                &left,
-                &identity.right,
-            ) {
+            if let Some(result) = self.machines.call(i, |machine, others| {
+                machine.process_plookup(
+                    self.fixed_data,
+                    self.fixed_lookup,
+                    identity.kind,
+                    &left,
+                    &identity.right,
+                    others,
+                )
+            }) {
                 return result;
             }
         }
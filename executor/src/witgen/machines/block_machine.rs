@@ -1,9 +1,11 @@
 use std::collections::{BTreeMap, HashMap, HashSet};
 
-use super::{EvalResult, FixedData, FixedLookup};
+use super::{EvalResult, FixedData, FixedLookup, Machines};
 use crate::witgen::affine_expression::AffineExpression;
 use crate::witgen::column_map::ColumnMap;
+use crate::witgen::copy_constraints::{CellValues, CopyConstraints};
 use crate::witgen::identity_processor::IdentityProcessor;
+use crate::witgen::machines::machine_extractor::refs_in_identity;
 use crate::witgen::processor::Processor;
 use crate::witgen::rows::{Row, RowFactory, RowPair, RowUpdater, UnknownStrategy};
 use crate::witgen::sequence_iterator::{IdentityInSequence, ProcessingSequenceCache, SequenceStep};
@@ -11,7 +13,8 @@ use crate::witgen::util::try_to_simple_poly;
 use crate::witgen::{machines::Machine, range_constraints::RangeConstraint, EvalError};
 use crate::witgen::{Constraint, EvalValue, IncompleteCause};
 use ast::analyzed::{
-    Expression, Identity, IdentityKind, PolyID, PolynomialReference, SelectedExpressions,
+    Expression, Identity, IdentityKind, PolyID, PolynomialReference, PolynomialType,
+    SelectedExpressions,
 };
 use number::{DegreeType, FieldElement};
 
@@ -43,15 +46,23 @@ pub fn transpose_rows<T: FieldElement>(
 pub struct BlockMachine<'a, T: FieldElement> {
     /// Block size, the period of the selector.
     block_size: usize,
+    /// The degree of the namespace of the machine.
+    degree: DegreeType,
     /// The right-hand side of the connecting identity, needed to identify
     /// when this machine is responsible.
     selected_expressions: SelectedExpressions<T>,
     /// The internal identities
     identities: Vec<&'a Identity<T>>,
+    /// For each internal identity, whether it is a call into another machine.
+    is_call_to_other_machine: Vec<bool>,
+    /// Propagates values along the copy constraints of internal `connect` identities.
+    copy_constraints: CopyConstraints<'a, T>,
     /// The row factory
     row_factory: RowFactory<'a, T>,
     /// The data of the machine.
     data: Vec<Row<'a, T>>,
+    /// The number of rows at each open checkpoint.
+    checkpoints: Vec<usize>,
     /// The set of witness columns that are actually part of this machine.
     witness_cols: HashSet<PolyID>,
     /// Cache that states the order in which to evaluate identities
@@ -73,9 +84,21 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
                 let row_factory = RowFactory::new(fixed_data, global_range_constraints.clone());
                 let mut machine = BlockMachine {
                     block_size: period,
+                    degree: fixed_data.common_degree(witness_cols).ok()?,
                     selected_expressions: id.right.clone(),
                     identities: identities.to_vec(),
+                    is_call_to_other_machine: identities
+                        .iter()
+                        .map(|identity| {
+                            refs_in_identity(identity).iter().any(|poly| {
+                                poly.ptype == PolynomialType::Committed
+                                    && !witness_cols.contains(poly)
+                            })
+                        })
+                        .collect(),
+                    copy_constraints: CopyConstraints::new(fixed_data, identities).ok()?,
                     data: vec![],
+                    checkpoints: vec![],
                     row_factory,
                     witness_cols: witness_cols.clone(),
                     processing_sequence_cache: ProcessingSequenceCache::new(
@@ -85,7 +108,7 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
                 };
                 // Append a block so that we do not have to deal with wrap-around
                 // when storing machine witness data.
-                machine.append_new_block(fixed_data.degree).unwrap();
+                machine.append_new_block(machine.degree).unwrap();
 
                 return Some(machine);
             }
@@ -145,21 +168,46 @@ impl<'a, T: FieldElement> Machine<'a, T> for BlockMachine<'a, T> {
         kind: IdentityKind,
         left: &[AffineExpression<&'a PolynomialReference, T>],
         right: &'a SelectedExpressions<T>,
+        mut machines: Machines<'a, '_, T>,
     ) -> Option<EvalResult<'a, T>> {
         if *right != self.selected_expressions || kind != IdentityKind::Plookup {
             return None;
         }
-        let previous_len = self.rows() as usize;
-        Some({
-            let result = self.process_plookup_internal(fixed_data, fixed_lookup, left, right);
-            if let Ok(assignments) = &result {
-                if !assignments.is_complete() {
-                    // rollback the changes.
-                    self.data.truncate(previous_len);
-                }
-            }
-            result
-        })
+        // If the query cannot be completed, it is repeated later, so the changes of this
+        // and all called machines are rolled back.
+        self.checkpoint();
+        machines.checkpoint();
+        let result = self.process_plookup_internal(
+            fixed_data,
+            fixed_lookup,
+            left,
+            right,
+            machines.reborrow(),
+        );
+        if matches!(&result, Ok(assignments) if assignments.is_complete()) {
+            self.commit();
+            machines.commit();
+        } else {
+            self.rollback();
+            machines.rollback();
+        }
+        Some(result)
+    }
+
+    fn checkpoint(&mut self) {
+        self.checkpoints.push(self.data.len());
+    }
+
+    fn rollback(&mut self) {
+        let len = self
+            .checkpoints
+            .pop()
+            .expect("No checkpoint to roll back to.");
+        self.data.truncate(len);
+    }
+
+    fn commit(&mut self) {
+        self.checkpoints.pop().expect("No checkpoint to commit.");
     }
 
     fn take_witness_col_values(
@@ -178,7 +226,7 @@ impl<'a, T: FieldElement> Machine<'a, T> for BlockMachine<'a, T> {
             .map(|(id, mut values)| {
                 // For all constraints to be satisfied, unused cells have to be filled with valid values.
                 // We do this, we construct a default block, by repeating the first input to the block machine.
-                values.resize(fixed_data.degree as usize, None);
+                values.resize(self.degree as usize, None);
 
                 let second_block_values = values.iter().skip(self.block_size).take(self.block_size);
 
@@ -212,6 +260,11 @@ impl<'a, T: FieldElement> Machine<'a, T> for BlockMachine<'a, T> {
             .map(|(id, values)| (fixed_data.column_name(&id).to_string(), values))
             .collect()
     }
+
+    fn late_copy_constraint_values(&self) -> CellValues<T> {
+        self.copy_constraints
+            .late_values_with(|poly_id, row| self.known_value(poly_id, row))
+    }
 }
 
 impl<'a, T: FieldElement> BlockMachine<'a, T> {
@@ -228,21 +281,35 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
         fixed_lookup: &mut FixedLookup<T>,
     ) {
         // Build a vector of 3 rows: N -2, N - 1 and 0
-        let rows = ((fixed_data.degree - 2)..(fixed_data.degree + 1))
+        let rows = ((self.degree - 2)..(self.degree + 1))
             .map(|row| {
                 self.row_factory.row_from_known_values_sparse(
                     data.iter()
-                        .map(|(col, values)| (*col, values[(row % fixed_data.degree) as usize])),
+                        .map(|(col, values)| (*col, values[(row % self.degree) as usize])),
                 )
             })
             .collect();
 
+        // Identities that call other machines are not checked, because the other machines
+        // might already have been finalized. The default block only repeats calls that
+        // have already been processed, so these identities are satisfied anyway.
+        // If the last row needs to be fixed, this is checked below.
+        // Copy constraints are not checked either, because they are not processed by
+        // the identity processor.
+        let identities = self
+            .identities
+            .iter()
+            .zip(&self.is_call_to_other_machine)
+            .filter(|(identity, is_call)| !**is_call && identity.kind != IdentityKind::Connect)
+            .map(|(identity, _)| *identity)
+            .collect();
+
         // Build the processor. This copies the identities, but it's only done once per block machine instance.
         let mut processor = Processor::new(
-            fixed_data.degree - 2,
+            self.degree - 2,
             rows,
-            IdentityProcessor::new(fixed_data, fixed_lookup, vec![]),
-            self.identities.clone(),
+            IdentityProcessor::new(fixed_data, fixed_lookup, Machines::default()),
+            identities,
             fixed_data,
             self.row_factory.clone(),
         );
@@ -258,10 +325,30 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
                 .expect("Some constraints were not satisfiable when solving for the last row.");
             let last_row = processor.finish().remove(1);
 
+            // The calls to other machines in the last row are only satisfied if the fixed
+            // row does not change any of the values they reference.
+            let last_row_index = self.degree as usize - 1;
+            for (identity, _) in self
+                .identities
+                .iter()
+                .zip(&self.is_call_to_other_machine)
+                .filter(|(_, is_call)| **is_call)
+            {
+                if let Some(poly_id) = refs_in_identity(identity).into_iter().find(|poly_id| {
+                    self.witness_cols.contains(poly_id)
+                        && last_row[poly_id].value.unwrap_or_default()
+                            != data[poly_id][last_row_index]
+                }) {
+                    panic!(
+                        "Fixing the last row changed the value of {}, which is used in a call to another machine: {identity}",
+                        fixed_data.column_name(&poly_id)
+                    );
+                }
+            }
+
             // Copy values into data
             for (poly_id, values) in data.iter_mut() {
-                values[fixed_data.degree as usize - 1] =
-                    last_row[poly_id].value.unwrap_or_default();
+                values[self.degree as usize - 1] = last_row[poly_id].value.unwrap_or_default();
             }
         }
     }
@@ -282,17 +369,27 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
         self.data.len() as DegreeType
     }
 
+    /// Returns the value of a cell of the machine, if it is known.
+    fn known_value(&self, poly_id: PolyID, row: DegreeType) -> Option<T> {
+        self.data
+            .get(row as usize)
+            .and_then(|r| (&r[&poly_id].value).into())
+    }
+
     fn process_plookup_internal(
         &mut self,
         fixed_data: &'a FixedData<T>,
         fixed_lookup: &mut FixedLookup<T>,
         left: &[AffineExpression<&'a PolynomialReference, T>],
         right: &'a SelectedExpressions<T>,
+        machines: Machines<'a, '_, T>,
     ) -> EvalResult<'a, T> {
-        log::trace!("Start processing block machine");
+        let call_depth = machines.call_depth();
+        log::trace!("Start processing block machine (call depth {call_depth})");
 
-        // TODO: Add possibility for machines to call other machines.
-        let mut identity_processor = IdentityProcessor::new(fixed_data, fixed_lookup, vec![]);
+        // Lookups into other machines are processed by the identity processor,
+        // which calls the machines in the registry.
+        let mut identity_processor = IdentityProcessor::new(fixed_data, fixed_lookup, machines);
 
         // First check if we already store the value.
         // This can happen in the loop detection case, where this function is just called
@@ -316,7 +413,7 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
         }
 
         let old_len = self.rows();
-        self.append_new_block(fixed_data.degree)?;
+        self.append_new_block(self.degree)?;
         let mut outer_assignments = EvalValue::complete(vec![]);
 
         // While processing the block, we'll add an additional row which will be
@@ -339,6 +436,9 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
         // TODO: Move this into the processing sequence iterator.
         let mut progress_steps = vec![];
 
+        // The (row, identity index) pairs of internal identities that are complete.
+        let mut completed_identities = HashSet::new();
+
         // A copy of `left` which is mutated by `handle_outer_constraints()`
         let mut left_mut = left.to_vec();
 
@@ -349,8 +449,19 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
                 row_delta,
                 identity,
             } = step;
-            let row = (old_len as i64 + row_delta + fixed_data.degree as i64) as DegreeType
-                % fixed_data.degree;
+            let row = (old_len as i64 + row_delta + self.degree as i64) as DegreeType % self.degree;
+
+            // Identities that are already complete in this row do not need to be processed
+            // again. Calls to other machines in the previous block have already been processed
+            // together with the previous block. Repeating a call would add rows to the other machine.
+            if let IdentityInSequence::Internal(index) = identity {
+                if completed_identities.contains(&(row, index))
+                    || (row_delta < 0 && self.is_call_to_other_machine[index])
+                {
+                    processing_sequence_iterator.report_progress(false);
+                    continue;
+                }
+            }
 
             let progress = match self.compute_updates(
                 row,
@@ -361,6 +472,11 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
                 &mut identity_processor,
             ) {
                 Ok(value) => {
+                    if let (true, IdentityInSequence::Internal(index)) =
+                        (value.is_complete(), identity)
+                    {
+                        completed_identities.insert((row, index));
+                    }
                     if !value.is_empty() {
                         errors.clear();
 
@@ -393,7 +509,7 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
         // Remove the extra row we added at the beginning.
         self.data.pop();
 
-        log::trace!("End processing block machine");
+        log::trace!("End processing block machine (call depth {call_depth})");
 
         // Only succeed if we can assign everything.
         // Otherwise it is messy because we have to find the correct block again.
@@ -426,6 +542,13 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
         identity_processor: &mut IdentityProcessor<'a, '_, T>,
     ) -> EvalResult<'a, T> {
         match identity {
+            IdentityInSequence::Internal(i) if self.identities[i].kind == IdentityKind::Connect => {
+                self.copy_constraints.process_identity_with(
+                    self.identities[i],
+                    &self.get_current_row_pair(row, fixed_data),
+                    |poly_id, row| self.known_value(poly_id, row),
+                )
+            }
             IdentityInSequence::Internal(i) => identity_processor.process_identity(
                 self.identities[i],
                 &self.get_current_row_pair(row, fixed_data),
@@ -1,97 +1,359 @@
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::iter::once;
 
 use itertools::Itertools;
 use num_traits::Zero;
 
-use super::{FixedLookup, Machine};
+use super::journal::Journal;
+use super::{FixedLookup, Machine, Machines};
 use crate::witgen::affine_expression::AffineExpression;
-use crate::witgen::util::is_simple_poly_of_name;
+use crate::witgen::util::try_to_simple_poly;
 use crate::witgen::{EvalResult, FixedData};
 use crate::witgen::{EvalValue, IncompleteCause};
 use number::{DegreeType, FieldElement};
 
 use ast::analyzed::{
-    Expression, Identity, IdentityKind, PolyID, PolynomialReference, SelectedExpressions,
+    BinaryOperator, Expression, Identity, IdentityKind, PolyID, PolynomialReference,
+    SelectedExpressions,
 };
 
-/// TODO make this generic
-
-#[derive(Default)]
+/// A machine that implements a read-write memory.
+/// The roles of the columns are determined from the structure of the identities:
+///  - A lookup `NOTLAST { c * (a' - a) + (1 - c) * (s' - s) } in { POSITIVE }`
+///    sorts the rows by address `a` and then by step `s`, `c` is 1 if and only if
+///    the address changes in the next row. The step difference can be extended to
+///    `s' - s + w' * r`, which allows a read followed by a write in the same step.
+///  - A constraint `(1 - w') * (1 - c) * (v' - v) = 0` identifies the value column `v`
+///    and the write selector `w`.
+///  - All accesses are permutations of the form `{ ... } is sel { a, s, v }` where
+///    `sel` is either `w` or a read selector.
+///
+/// Any further column has to be an "operation flag", i.e. a column `op` with
+/// a constraint `sel * (1 - op) = 0` for one of the selectors.
 pub struct DoubleSortedWitnesses<T> {
     degree: DegreeType,
-    //key_col: String,
-    /// Position of the witness columns in the data.
-    /// The key column has a position of usize::max
-    //witness_positions: HashMap<String, usize>,
-    /// (addr, step) -> value
-    trace: BTreeMap<(T, T), Operation<T>>,
-    data: BTreeMap<T, T>,
-    namespace: String,
+    addr: PolyID,
+    step: PolyID,
+    change: PolyID,
+    value: PolyID,
+    write_selector: PolyID,
+    read_selectors: BTreeSet<PolyID>,
+    operation_flags: BTreeSet<PolyID>,
+    /// (addr, step, is_write) -> value
+    /// A read is ordered before a write in the same step.
+    trace: BTreeMap<(T, T, bool), Operation<T>>,
+    journal: Journal<(T, T, bool), Operation<T>>,
 }
 
+#[derive(Clone)]
 struct Operation<T> {
-    pub is_write: bool,
+    pub selector: PolyID,
     pub value: T,
 }
 
 impl<T: FieldElement> DoubleSortedWitnesses<T> {
-    fn namespaced(&self, name: &str) -> String {
-        format!("{}.{}", self.namespace, name)
-    }
-
     pub fn try_new(
         fixed_data: &FixedData<T>,
-        _identities: &[&Identity<T>],
+        connecting_identities: &[&Identity<T>],
+        identities: &[&Identity<T>],
         witness_cols: &HashSet<PolyID>,
     ) -> Option<Self> {
-        // get the namespaces and column names
-        let (mut namespaces, columns): (HashSet<_>, HashSet<_>) = witness_cols
+        // Only witness sets that are sorted by (address, step) are candidates.
+        let sort_constraint = identities
             .iter()
-            .map(|r| {
-                let mut limbs = fixed_data.column_name(r).split('.');
-                let namespace = limbs.next().unwrap();
-                let col = limbs.next().unwrap();
-                (namespace, col)
-            })
-            .unzip();
+            .filter_map(|id| match_sort_constraint(id))
+            .find(|c| {
+                [c.change, c.addr, c.step]
+                    .into_iter()
+                    .all(|c| witness_cols.contains(&c))
+            })?;
+        let (addr, step) = (sort_constraint.addr, sort_constraint.step);
 
-        if namespaces.len() > 1 {
-            // columns are not in the same namespace, fail
-            return None;
+        Self::try_new_sorted(
+            fixed_data,
+            connecting_identities,
+            identities,
+            witness_cols,
+            sort_constraint,
+        )
+        .map_err(|reason| {
+            log::warn!(
+                "The witness set sorted by address {} and step {} looks like a memory machine, but {reason}.",
+                fixed_data.column_name(&addr),
+                fixed_data.column_name(&step),
+            );
+        })
+        .ok()
+    }
+
+    fn try_new_sorted(
+        fixed_data: &FixedData<T>,
+        connecting_identities: &[&Identity<T>],
+        identities: &[&Identity<T>],
+        witness_cols: &HashSet<PolyID>,
+        SortConstraint {
+            change,
+            addr,
+            step,
+            tie_break,
+        }: SortConstraint,
+    ) -> Result<Self, String> {
+        let (value, write_selector) = identities
+            .iter()
+            .filter(|id| id.kind == IdentityKind::Polynomial)
+            .filter_map(|id| match_value_constraint(id.expression_for_poly_id(), change))
+            .find(|(value, write)| witness_cols.contains(value) && witness_cols.contains(write))
+            .ok_or_else(|| {
+                format!(
+                    "there is no constraint of the form `(1 - w') * (1 - {0}) * (v' - v) = 0` \
+                    that determines the value column and the write selector",
+                    fixed_data.column_name(&change)
+                )
+            })?;
+
+        let mut read_selectors = BTreeSet::new();
+        for id in connecting_identities {
+            let selector = (id.kind == IdentityKind::Permutation)
+                .then_some(id.right.selector.as_ref())
+                .flatten()
+                .and_then(try_to_simple_poly)
+                .map(|p| p.poly_id())
+                .filter(|s| witness_cols.contains(s))
+                .ok_or_else(|| {
+                    format!("it is accessed by `{id}`, which is not a permutation with a selector column")
+                })?;
+            let rhs = id
+                .right
+                .expressions
+                .iter()
+                .map(|e| try_to_simple_poly(e).map(|p| p.poly_id()))
+                .collect::<Option<BTreeSet<_>>>();
+            if id.right.expressions.len() != 3 || rhs != Some([addr, step, value].into()) {
+                return Err(format!(
+                    "the right hand side of `{id}` does not consist of exactly the address, step and value columns ({}, {}, {})",
+                    fixed_data.column_name(&addr),
+                    fixed_data.column_name(&step),
+                    fixed_data.column_name(&value),
+                ));
+            }
+            if selector != write_selector {
+                read_selectors.insert(selector);
+            }
         }
 
-        let namespace = namespaces.drain().next().unwrap().into();
-
-        // TODO check the identities.
-        let expected_witnesses: HashSet<_> = [
-            "m_value",
-            "m_addr",
-            "m_step",
-            "m_change",
-            "m_op",
-            "m_is_write",
-            "m_is_read",
-        ]
-        .into_iter()
-        .collect();
-        if expected_witnesses
-            .symmetric_difference(&columns)
-            .next()
-            .is_none()
-        {
-            Some(Self {
-                // store the namespace
-                namespace,
-                degree: fixed_data.degree,
-                ..Default::default()
-            })
+        if let Some((write, read)) = tie_break {
+            if write != write_selector || !read_selectors.contains(&read) {
+                return Err(format!(
+                    "the step difference in the sort constraint is extended by `{}' * {}`, \
+                    which is not the write selector times a read selector",
+                    fixed_data.column_name(&write),
+                    fixed_data.column_name(&read),
+                ));
+            }
+        }
+
+        let selectors = once(write_selector)
+            .chain(read_selectors.iter().cloned())
+            .collect::<HashSet<_>>();
+        let known = [addr, step, change, value]
+            .into_iter()
+            .chain(selectors.iter().cloned())
+            .collect::<HashSet<_>>();
+        let (operation_flags, unknown): (BTreeSet<_>, BTreeSet<_>) =
+            witness_cols.difference(&known).cloned().partition(|col| {
+                identities
+                    .iter()
+                    .filter(|id| id.kind == IdentityKind::Polynomial)
+                    .any(|id| {
+                        match_operation_flag_constraint(id.expression_for_poly_id(), *col)
+                            .is_some_and(|sel| selectors.contains(&sel))
+                    })
+            });
+        if !unknown.is_empty() {
+            return Err(format!(
+                "the role of the following columns could not be determined: {}",
+                unknown
+                    .iter()
+                    .map(|c| fixed_data.column_name(c))
+                    .sorted()
+                    .join(", ")
+            ));
+        }
+
+        Ok(Self {
+            degree: fixed_data.common_degree(witness_cols)?,
+            addr,
+            step,
+            change,
+            value,
+            write_selector,
+            read_selectors,
+            operation_flags,
+            trace: Default::default(),
+            journal: Default::default(),
+        })
+    }
+}
+
+struct SortConstraint {
+    change: PolyID,
+    addr: PolyID,
+    step: PolyID,
+    /// The write and read selectors `(w, r)` if the step difference is `s' - s + w' * r`.
+    tie_break: Option<(PolyID, PolyID)>,
+}
+
+/// Matches `c * (a' - a) + (1 - c) * (s' - s)` (optionally with `s' - s + w' * r`)
+/// inside a lookup into a single column.
+fn match_sort_constraint<T: FieldElement>(id: &Identity<T>) -> Option<SortConstraint> {
+    if id.kind != IdentityKind::Plookup
+        || id.right.selector.is_some()
+        || id.left.expressions.len() != 1
+        || id.right.expressions.len() != 1
+    {
+        return None;
+    }
+    let Expression::BinaryOperation(left, BinaryOperator::Add, right) = &id.left.expressions[0]
+    else {
+        return None;
+    };
+    let (change, addr) = match_product(left)?;
+    let (one_minus_change, step) = match_product(right)?;
+    let change = witness_column(change)?;
+    let addr = match_next_difference(addr)?;
+    let (step, tie_break) = match_step_difference(step)?;
+    (match_one_minus(one_minus_change).and_then(witness_column) == Some(change)).then_some(
+        SortConstraint {
+            change,
+            addr,
+            step,
+            tie_break,
+        },
+    )
+}
+
+/// Matches `s' - s` or `s' - s + w' * r` and returns the ID of `s` and, in the
+/// second case, the IDs of `w` and `r`.
+fn match_step_difference<T>(expr: &Expression<T>) -> Option<(PolyID, Option<(PolyID, PolyID)>)> {
+    if let Some(step) = match_next_difference(expr) {
+        return Some((step, None));
+    }
+    let Expression::BinaryOperation(difference, BinaryOperator::Add, tie_break) = expr else {
+        return None;
+    };
+    let (write, read) = match_product(tie_break)?;
+    Some((
+        match_next_difference(difference)?,
+        Some((next_witness_column(write)?, witness_column(read)?)),
+    ))
+}
+
+/// Matches `(1 - w') * (1 - change) * (v' - v)` with the factors in any order
+/// and returns the IDs of `v` and `w`.
+fn match_value_constraint<T: FieldElement>(
+    expr: &Expression<T>,
+    change: PolyID,
+) -> Option<(PolyID, PolyID)> {
+    let factors = product_factors(strip_minus_zero(expr));
+    if factors.len() != 3 {
+        return None;
+    }
+    let mut value = None;
+    let mut write = None;
+    let mut has_change = false;
+    for factor in factors {
+        if let Some(v) = match_next_difference(factor) {
+            value = Some(v);
         } else {
-            None
+            let column = match_one_minus(factor)?;
+            if let Some(w) = next_witness_column(column) {
+                write = Some(w);
+            } else {
+                has_change |= witness_column(column) == Some(change);
+            }
+        }
+    }
+    has_change.then_some((value?, write?))
+}
+
+/// Matches `sel * (1 - flag) = 0` for the given flag and returns the ID of `sel`.
+fn match_operation_flag_constraint<T: FieldElement>(
+    expr: &Expression<T>,
+    flag: PolyID,
+) -> Option<PolyID> {
+    let (selector, one_minus_flag) = match_product(strip_minus_zero(expr))?;
+    if match_one_minus(one_minus_flag).and_then(witness_column) == Some(flag) {
+        witness_column(selector)
+    } else {
+        None
+    }
+}
+
+fn strip_minus_zero<T: FieldElement>(expr: &Expression<T>) -> &Expression<T> {
+    match expr {
+        Expression::BinaryOperation(left, BinaryOperator::Sub, right) if is_number(right, 0) => {
+            left
+        }
+        _ => expr,
+    }
+}
+
+fn product_factors<T>(expr: &Expression<T>) -> Vec<&Expression<T>> {
+    match expr {
+        Expression::BinaryOperation(left, BinaryOperator::Mul, right) => product_factors(left)
+            .into_iter()
+            .chain(product_factors(right))
+            .collect(),
+        _ => vec![expr],
+    }
+}
+
+fn match_product<T>(expr: &Expression<T>) -> Option<(&Expression<T>, &Expression<T>)> {
+    match expr {
+        Expression::BinaryOperation(left, BinaryOperator::Mul, right) => Some((left, right)),
+        _ => None,
+    }
+}
+
+fn match_one_minus<T: FieldElement>(expr: &Expression<T>) -> Option<&Expression<T>> {
+    match expr {
+        Expression::BinaryOperation(left, BinaryOperator::Sub, right) if is_number(left, 1) => {
+            Some(right)
+        }
+        _ => None,
+    }
+}
+
+fn is_number<T: FieldElement>(expr: &Expression<T>, n: u64) -> bool {
+    matches!(expr, Expression::Number(v) if *v == n.into())
+}
+
+/// Matches `x' - x` for a witness column `x` and returns its ID.
+fn match_next_difference<T>(expr: &Expression<T>) -> Option<PolyID> {
+    let Expression::BinaryOperation(left, BinaryOperator::Sub, right) = expr else {
+        return None;
+    };
+    let current = witness_column(right)?;
+    (next_witness_column(left)? == current).then_some(current)
+}
+
+/// Returns the ID of a shifted, non-array witness column reference `x'`.
+fn next_witness_column<T>(expr: &Expression<T>) -> Option<PolyID> {
+    match expr {
+        Expression::PolynomialReference(p) if p.next && p.index.is_none() && p.is_witness() => {
+            Some(p.poly_id())
         }
+        _ => None,
     }
 }
 
+/// Returns the ID of a non-shifted, non-array witness column reference.
+fn witness_column<T>(expr: &Expression<T>) -> Option<PolyID> {
+    try_to_simple_poly(expr)
+        .filter(|p| p.is_witness())
+        .map(|p| p.poly_id())
+}
+
 impl<'a, T: FieldElement> Machine<'a, T> for DoubleSortedWitnesses<T> {
     fn process_plookup(
         &mut self,
@@ -100,15 +362,40 @@ impl<'a, T: FieldElement> Machine<'a, T> for DoubleSortedWitnesses<T> {
         kind: IdentityKind,
         left: &[AffineExpression<&'a PolynomialReference, T>],
         right: &'a SelectedExpressions<T>,
+        _machines: Machines<'a, '_, T>,
     ) -> Option<EvalResult<'a, T>> {
-        if kind != IdentityKind::Permutation
-            || !(is_simple_poly_of_name(right.selector.as_ref()?, &self.namespaced("m_is_read"))
-                || is_simple_poly_of_name(right.selector.as_ref()?, &self.namespaced("m_is_write")))
-        {
+        if kind != IdentityKind::Permutation {
+            return None;
+        }
+        let selector = try_to_simple_poly(right.selector.as_ref()?)?.poly_id();
+        if selector != self.write_selector && !self.read_selectors.contains(&selector) {
             return None;
         }
+        let position = |col: PolyID| {
+            right
+                .expressions
+                .iter()
+                .position(|e| try_to_simple_poly(e).map(|p| p.poly_id()) == Some(col))
+        };
+        let positions = (
+            position(self.addr)?,
+            position(self.step)?,
+            position(self.value)?,
+        );
+
+        Some(self.process_plookup_internal(left, right, selector, positions))
+    }
 
-        Some(self.process_plookup_internal(left, right))
+    fn checkpoint(&mut self) {
+        self.journal.checkpoint();
+    }
+
+    fn rollback(&mut self) {
+        self.journal.rollback(&mut self.trace);
+    }
+
+    fn commit(&mut self) {
+        self.journal.commit();
     }
 
     fn take_witness_col_values(
@@ -120,17 +407,14 @@ impl<'a, T: FieldElement> Machine<'a, T> for DoubleSortedWitnesses<T> {
         let mut step = vec![];
         let mut value = vec![];
         let mut op = vec![];
-        let mut is_write = vec![];
-        let mut is_read = vec![];
+        let mut selectors = vec![];
 
-        for ((a, s), o) in std::mem::take(&mut self.trace) {
+        for ((a, s, _), o) in std::mem::take(&mut self.trace) {
             addr.push(a);
             step.push(s);
             value.push(o.value);
             op.push(1.into());
-
-            is_write.push(o.is_write.into());
-            is_read.push((!o.is_write).into());
+            selectors.push(Some(o.selector));
         }
         if addr.is_empty() {
             // No memory access at all - fill a first row with something.
@@ -138,16 +422,14 @@ impl<'a, T: FieldElement> Machine<'a, T> for DoubleSortedWitnesses<T> {
             step.push(0.into());
             value.push(0.into());
             op.push(0.into());
-            is_write.push(0.into());
-            is_read.push(0.into());
+            selectors.push(None);
         }
-        while addr.len() < fixed_data.degree as usize {
+        while addr.len() < self.degree as usize {
             addr.push(*addr.last().unwrap());
             step.push(*step.last().unwrap() + T::from(1));
             value.push(*value.last().unwrap());
             op.push(0.into());
-            is_write.push(0.into());
-            is_read.push(0.into());
+            selectors.push(None);
         }
 
         let change = addr
@@ -158,16 +440,27 @@ impl<'a, T: FieldElement> Machine<'a, T> for DoubleSortedWitnesses<T> {
             .collect::<Vec<_>>();
         assert_eq!(change.len(), addr.len());
 
+        let selector_values = once(self.write_selector)
+            .chain(self.read_selectors.iter().cloned())
+            .map(|col| {
+                let values = selectors
+                    .iter()
+                    .map(|s| (*s == Some(col)).into())
+                    .collect::<Vec<_>>();
+                (col, values)
+            })
+            .collect::<Vec<_>>();
+
         [
-            (self.namespaced("m_value"), value),
-            (self.namespaced("m_addr"), addr),
-            (self.namespaced("m_step"), step),
-            (self.namespaced("m_change"), change),
-            (self.namespaced("m_op"), op),
-            (self.namespaced("m_is_write"), is_write),
-            (self.namespaced("m_is_read"), is_read),
+            (self.value, value),
+            (self.addr, addr),
+            (self.step, step),
+            (self.change, change),
         ]
         .into_iter()
+        .chain(self.operation_flags.iter().map(|&col| (col, op.clone())))
+        .chain(selector_values)
+        .map(|(col, values)| (fixed_data.column_name(&col).to_string(), values))
         .collect()
     }
 }
@@ -177,46 +470,52 @@ impl<T: FieldElement> DoubleSortedWitnesses<T> {
         &mut self,
         left: &[AffineExpression<&'a PolynomialReference, T>],
         right: &SelectedExpressions<T>,
+        selector: PolyID,
+        (addr_index, step_index, value_index): (usize, usize, usize),
     ) -> EvalResult<'a, T> {
-        // We blindly assume the lookup is of the form
-        // OP { ADDR, STEP, X } is m_is_write { m_addr, m_step, m_value }
+        // The lookup is of the form
+        // OP { ADDR, STEP, X } is write_selector { addr, step, value }
         // or
-        // OP { ADDR, STEP, X } is m_is_read { m_addr, m_step, m_value }
+        // OP { ADDR, STEP, X } is read_selector { addr, step, value }
+        // (in any order of the columns).
 
-        let is_write = match &right.selector {
-            Some(Expression::PolynomialReference(p)) => p.name == self.namespaced("m_is_write"),
-            _ => panic!(),
+        let is_write = selector == self.write_selector;
+        // The address and step might depend on other constraints in the same row.
+        let Some(addr) = left[addr_index].constant_value() else {
+            return Ok(EvalValue::incomplete(
+                IncompleteCause::NonConstantMemoryAccess,
+            ));
         };
-        let addr = left[0].constant_value().ok_or_else(|| {
-            format!(
-                "Address must be known: {} = {}",
-                left[0], right.expressions[0]
-            )
-        })?;
-        if addr.to_degree() >= self.degree {
+        if !u64::try_from(addr.to_arbitrary_integer()).is_ok_and(|addr| addr < self.degree) {
             return Err(format!(
                 "Memory access to too large address: 0x{addr:x} (must be less than 0x{:x})",
                 self.degree
             )
             .into());
         }
-        let step = left[1]
-            .constant_value()
-            .ok_or_else(|| format!("Step must be known: {} = {}", left[1], right.expressions[1]))?;
+        let Some(step) = left[step_index].constant_value() else {
+            return Ok(EvalValue::incomplete(
+                IncompleteCause::NonConstantMemoryAccess,
+            ));
+        };
 
         log::trace!(
             "Query addr={:x}, step={step}, write: {is_write}, left: {}",
             addr.to_arbitrary_integer(),
-            left[2]
+            left[value_index]
         );
         if !(addr.clone().to_arbitrary_integer() % 4u32).is_zero() {
-            panic!("UNALIGNED");
+            return Err(format!(
+                "Unaligned memory access to address 0x{addr:x}: {} = {}",
+                left[addr_index], right.expressions[addr_index]
+            )
+            .into());
         }
 
         // TODO this does not check any of the failure modes
         let mut assignments = EvalValue::complete(vec![]);
         if is_write {
-            let value = match left[2].constant_value() {
+            let value = match left[value_index].constant_value() {
                 Some(v) => v,
                 None => {
                     return Ok(EvalValue::incomplete(
@@ -230,24 +529,27 @@ impl<T: FieldElement> DoubleSortedWitnesses<T> {
                 addr,
                 value
             );
-            self.data.insert(addr, value);
+            self.journal.record(&self.trace, &(addr, step, true));
             self.trace
-                .insert((addr, step), Operation { is_write, value });
+                .insert((addr, step, true), Operation { selector, value });
         } else {
-            let value = self.data.entry(addr).or_default();
-            self.trace.insert(
-                (addr, step),
-                Operation {
-                    is_write,
-                    value: *value,
-                },
-            );
+            // The value is the one of the latest access before this one,
+            // which does not depend on the order in which the accesses are processed.
+            let value = self
+                .trace
+                .range((addr, 0.into(), false)..(addr, step, false))
+                .next_back()
+                .map(|(_, o)| o.value)
+                .unwrap_or_default();
+            self.journal.record(&self.trace, &(addr, step, false));
+            self.trace
+                .insert((addr, step, false), Operation { selector, value });
             log::debug!(
                 "Memory read: addr={:x}, step={step}, value={:x}",
                 addr,
                 value
             );
-            let ass = (left[2].clone() - (*value).into()).solve()?;
+            let ass = (left[value_index].clone() - value.into()).solve()?;
             assignments.combine(ass);
         }
         Ok(assignments)
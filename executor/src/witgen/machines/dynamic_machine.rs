@@ -0,0 +1,429 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{EvalResult, FixedData, FixedLookup, Machines};
+use crate::witgen::affine_expression::AffineExpression;
+use crate::witgen::column_map::ColumnMap;
+use crate::witgen::copy_constraints::{CellValues, CopyConstraints};
+use crate::witgen::identity_processor::IdentityProcessor;
+use crate::witgen::rows::{CellValue, Row, RowFactory, RowPair, RowUpdater, UnknownStrategy};
+use crate::witgen::util::try_to_simple_poly;
+use crate::witgen::{machines::Machine, range_constraints::RangeConstraint};
+use crate::witgen::{EvalError, EvalValue, IncompleteCause};
+use ast::analyzed::{
+    Expression, Identity, IdentityKind, PolyID, PolynomialReference, SelectedExpressions,
+};
+use number::{DegreeType, FieldElement};
+
+/// A machine with a program counter (a virtual machine) which is driven by the machine calling it.
+/// Every query is a function call: starting from the row after the previous call, rows are
+/// computed one after the other until the latch (the `return` instruction) is active,
+/// at which point the outputs are read from the latch row.
+///
+/// The machine is detected if all connecting identities are lookups with the same witness column
+/// as selector on the right-hand side, and if the first expression on the right-hand side is
+/// a witness column, the function id.
+/// The function id is assigned on the first row of a call, all other known values of the caller
+/// (i.e. the inputs) from the second row on, whenever they are not determined by the machine itself.
+/// If a call cannot be completed yet, the changes of this machine and of all machines it called
+/// are rolled back, and the call is processed again when it is repeated.
+/// Once all calls are processed, the remaining rows are filled by calling function `0`,
+/// which keeps the machine in its dispatcher.
+pub struct DynamicMachine<'a, T: FieldElement> {
+    /// The degree of the namespace of the machine.
+    degree: DegreeType,
+    /// The right-hand sides of the connecting identities.
+    connecting_rhs: Vec<SelectedExpressions<T>>,
+    /// The function id column.
+    function_id: PolyID,
+    /// The internal identities.
+    identities: Vec<&'a Identity<T>>,
+    /// Propagates values along the copy constraints of internal `connect` identities.
+    copy_constraints: CopyConstraints<'a, T>,
+    /// The set of witness columns that are actually part of this machine.
+    witness_cols: HashSet<PolyID>,
+    fixed_data: &'a FixedData<'a, T>,
+    row_factory: RowFactory<'a, T>,
+    /// The rows computed so far.
+    data: Vec<Row<'a, T>>,
+    /// The row following the last row in `data`, containing the values already known about it.
+    next: Row<'a, T>,
+    /// Whether the values of the first row have been derived from the (wrapping) last row.
+    initialized: bool,
+    /// The latch row of the most recent call.
+    last_latch_row: Option<usize>,
+    /// The state at each open checkpoint.
+    checkpoints: Vec<Checkpoint<'a, T>>,
+}
+
+/// The state of a [DynamicMachine] that is restored when rolling back.
+struct Checkpoint<'a, T: FieldElement> {
+    len: usize,
+    next: Row<'a, T>,
+    initialized: bool,
+    last_latch_row: Option<usize>,
+}
+
+impl<'a, T: FieldElement> DynamicMachine<'a, T> {
+    pub fn try_new(
+        fixed_data: &'a FixedData<'a, T>,
+        connecting_identities: &[&'a Identity<T>],
+        identities: &[&'a Identity<T>],
+        witness_cols: &HashSet<PolyID>,
+        global_range_constraints: &ColumnMap<Option<RangeConstraint<T>>>,
+    ) -> Option<Self> {
+        let witness_column = |expr: &Expression<T>| {
+            try_to_simple_poly(expr)
+                .map(|poly| poly.poly_id())
+                .filter(|id| witness_cols.contains(id))
+        };
+        let (first, rest) = connecting_identities.split_first()?;
+        let latch = witness_column(first.right.selector.as_ref()?)?;
+        let function_id = witness_column(first.right.expressions.first()?)?;
+        let is_call = |id: &&Identity<T>| {
+            id.kind == IdentityKind::Plookup
+                && id.right.selector.as_ref().and_then(witness_column) == Some(latch)
+                && id.right.expressions.first().and_then(witness_column) == Some(function_id)
+        };
+        if !is_call(first) || !rest.iter().all(is_call) {
+            return None;
+        }
+
+        let row_factory = RowFactory::new(fixed_data, global_range_constraints.clone());
+        let next = row_factory.fresh_row();
+        Some(DynamicMachine {
+            degree: fixed_data.common_degree(witness_cols).ok()?,
+            connecting_rhs: connecting_identities
+                .iter()
+                .map(|id| id.right.clone())
+                .collect(),
+            function_id,
+            identities: identities.to_vec(),
+            copy_constraints: CopyConstraints::new(fixed_data, identities).ok()?,
+            witness_cols: witness_cols.clone(),
+            fixed_data,
+            row_factory,
+            data: vec![],
+            next,
+            initialized: false,
+            last_latch_row: None,
+            checkpoints: vec![],
+        })
+    }
+}
+
+impl<'a, T: FieldElement> Machine<'a, T> for DynamicMachine<'a, T> {
+    fn process_plookup(
+        &mut self,
+        _fixed_data: &'a FixedData<T>,
+        fixed_lookup: &mut FixedLookup<T>,
+        kind: IdentityKind,
+        left: &[AffineExpression<&'a PolynomialReference, T>],
+        right: &'a SelectedExpressions<T>,
+        machines: Machines<'a, '_, T>,
+    ) -> Option<EvalResult<'a, T>> {
+        if kind != IdentityKind::Plookup || !self.connecting_rhs.contains(right) {
+            return None;
+        }
+        let mut identity_processor =
+            IdentityProcessor::new(self.fixed_data, fixed_lookup, machines);
+
+        // The same call might be repeated, for example when the caller checks its rows.
+        // Since the connection is a lookup, the latch row of the previous call can be reused.
+        if let Some(result) = self.answer_from_last_call(left, right, &mut identity_processor) {
+            return Some(Ok(result));
+        }
+
+        self.checkpoint();
+        identity_processor.machines.checkpoint();
+        let result = self.process_call(left, right, &mut identity_processor);
+        if matches!(&result, Ok(value) if value.is_complete()) {
+            self.commit();
+            identity_processor.machines.commit();
+        } else {
+            self.rollback();
+            identity_processor.machines.rollback();
+        }
+        Some(result)
+    }
+
+    fn checkpoint(&mut self) {
+        self.checkpoints.push(Checkpoint {
+            len: self.data.len(),
+            next: self.next.clone(),
+            initialized: self.initialized,
+            last_latch_row: self.last_latch_row,
+        });
+    }
+
+    fn rollback(&mut self) {
+        let checkpoint = self
+            .checkpoints
+            .pop()
+            .expect("No checkpoint to roll back to.");
+        self.data.truncate(checkpoint.len);
+        self.next = checkpoint.next;
+        self.initialized = checkpoint.initialized;
+        self.last_latch_row = checkpoint.last_latch_row;
+    }
+
+    fn commit(&mut self) {
+        self.checkpoints.pop().expect("No checkpoint to commit.");
+    }
+
+    fn finalize(
+        &mut self,
+        fixed_lookup: &mut FixedLookup<T>,
+        machines: Machines<'a, '_, T>,
+    ) -> Result<(), EvalError<T>> {
+        let mut identity_processor =
+            IdentityProcessor::new(self.fixed_data, fixed_lookup, machines);
+        self.initialize(&mut identity_processor)?;
+        while (self.data.len() as DegreeType) < self.degree {
+            let row = self.data.len() as DegreeType;
+            let (current, next) = self
+                .compute_row(
+                    row,
+                    &[(self.function_id, T::zero())],
+                    &mut identity_processor,
+                )
+                .map_err(|e| {
+                    EvalError::from(format!(
+                        "Could not fill the unused rows of the machine: {e}"
+                    ))
+                })?;
+            self.data.push(current);
+            self.next = next;
+        }
+        Ok(())
+    }
+
+    fn take_witness_col_values(
+        &mut self,
+        fixed_data: &FixedData<T>,
+        _fixed_lookup: &mut FixedLookup<T>,
+    ) -> HashMap<String, Vec<T>> {
+        let rows = std::mem::take(&mut self.data);
+        self.witness_cols
+            .iter()
+            .map(|poly_id| {
+                (
+                    fixed_data.column_name(poly_id).to_string(),
+                    rows.iter()
+                        .map(|row| row[poly_id].value.unwrap_or_default())
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    fn late_copy_constraint_values(&self) -> CellValues<T> {
+        self.copy_constraints
+            .late_values_with(|poly_id, row| self.known_value(poly_id, row))
+    }
+}
+
+impl<'a, T: FieldElement> DynamicMachine<'a, T> {
+    fn answer_from_last_call(
+        &self,
+        left: &[AffineExpression<&'a PolynomialReference, T>],
+        right: &'a SelectedExpressions<T>,
+        identity_processor: &mut IdentityProcessor<'a, '_, T>,
+    ) -> Option<EvalValue<&'a PolynomialReference, T>> {
+        if !left.iter().all(|l| l.is_constant()) {
+            return None;
+        }
+        let row = self.last_latch_row?;
+        let row_pair = RowPair::new(
+            &self.data[row],
+            self.data.get(row + 1).unwrap_or(&self.next),
+            row as DegreeType,
+            self.fixed_data,
+            UnknownStrategy::Unknown,
+        );
+        identity_processor
+            .process_link(left, right, &row_pair)
+            .ok()
+            .filter(|result| result.is_complete() && result.is_empty())
+    }
+
+    fn process_call(
+        &mut self,
+        left: &[AffineExpression<&'a PolynomialReference, T>],
+        right: &'a SelectedExpressions<T>,
+        identity_processor: &mut IdentityProcessor<'a, '_, T>,
+    ) -> EvalResult<'a, T> {
+        let Some(function_id) = left[0].constant_value() else {
+            return Ok(EvalValue::incomplete(
+                IncompleteCause::DynamicMachineLookupIncomplete,
+            ));
+        };
+        // The known values of the caller, apart from the function id.
+        let arguments = left
+            .iter()
+            .zip(&right.expressions)
+            .skip(1)
+            .filter_map(|(l, r)| {
+                let poly_id = try_to_simple_poly(r)?.poly_id();
+                self.witness_cols
+                    .contains(&poly_id)
+                    .then_some((poly_id, l.constant_value()?))
+            })
+            .collect::<Vec<_>>();
+
+        log::trace!("Start processing dynamic machine call to function {function_id}");
+        self.initialize(identity_processor)?;
+        let start = self.data.len();
+        loop {
+            let row = self.data.len() as DegreeType;
+            if row >= self.degree {
+                return Err(EvalError::RowsExhausted);
+            }
+            let defaults = if self.data.len() == start {
+                vec![(self.function_id, function_id)]
+            } else {
+                arguments.clone()
+            };
+            let (mut current, mut next) = self.compute_row(row, &defaults, identity_processor)?;
+
+            let row_pair = RowPair::new(
+                &current,
+                &next,
+                row,
+                self.fixed_data,
+                UnknownStrategy::Unknown,
+            );
+            let latch = right
+                .selector
+                .as_ref()
+                .and_then(|selector| row_pair.evaluate(selector).ok())
+                .and_then(|value| value.constant_value());
+            match latch {
+                Some(latch) if latch.is_one() => {
+                    let updates = identity_processor
+                        .process_link(left, right, &row_pair)
+                        .map_err(|e| EvalError::from(format!("In row {row}: {e}")))?;
+                    if !updates.is_complete() {
+                        return Ok(EvalValue::incomplete(
+                            IncompleteCause::DynamicMachineLookupIncomplete,
+                        ));
+                    }
+                    let mut outer_assignments = EvalValue::complete(vec![]);
+                    let mut row_updater = RowUpdater::new(&mut current, &mut next, row);
+                    for (poly, constraint) in updates.constraints {
+                        if self.witness_cols.contains(&poly.poly_id()) {
+                            row_updater.apply_update(poly, &constraint);
+                        } else {
+                            outer_assignments.constraints.push((poly, constraint));
+                        }
+                    }
+                    self.data.push(current);
+                    self.next = next;
+                    self.last_latch_row = Some(row as usize);
+                    log::trace!("End processing dynamic machine call in row {row}");
+                    return Ok(outer_assignments);
+                }
+                Some(latch) if latch.is_zero() => {
+                    self.data.push(current);
+                    self.next = next;
+                }
+                _ => {
+                    return Ok(EvalValue::incomplete(
+                        IncompleteCause::DynamicMachineLookupIncomplete,
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Derives the values of the first row from the last row, for identities like
+    /// `pc' = (1 - first_step') * <...>`.
+    fn initialize(
+        &mut self,
+        identity_processor: &mut IdentityProcessor<'a, '_, T>,
+    ) -> Result<(), EvalError<T>> {
+        if !self.initialized {
+            let mut last = self.row_factory.fresh_row();
+            let mut first = self.row_factory.fresh_row();
+            self.solve_row(&mut last, &mut first, self.degree - 1, identity_processor)?;
+            self.next = first;
+            self.initialized = true;
+        }
+        Ok(())
+    }
+
+    /// Computes the row following the last row in `data`. The values in `defaults` are assigned to
+    /// their columns if they cannot be determined otherwise.
+    /// Returns the row and the values known about the row after it.
+    fn compute_row(
+        &self,
+        row: DegreeType,
+        defaults: &[(PolyID, T)],
+        identity_processor: &mut IdentityProcessor<'a, '_, T>,
+    ) -> Result<(Row<'a, T>, Row<'a, T>), EvalError<T>> {
+        let mut current = self.next.clone();
+        let mut next = self.row_factory.fresh_row();
+        self.solve_row(&mut current, &mut next, row, identity_processor)?;
+
+        let mut assigned_default = false;
+        for (poly_id, value) in defaults {
+            let cell = &mut current[poly_id];
+            if !cell.value.is_known() {
+                log::trace!("      => {} (Row {row}) = {value} (default)", cell.name);
+                cell.value = CellValue::Known(*value);
+                assigned_default = true;
+            }
+        }
+        if assigned_default {
+            self.solve_row(&mut current, &mut next, row, identity_processor)?;
+        }
+        Ok((current, next))
+    }
+
+    /// Goes over all identities until no more progress is made.
+    fn solve_row(
+        &self,
+        current: &mut Row<'a, T>,
+        next: &mut Row<'a, T>,
+        row: DegreeType,
+        identity_processor: &mut IdentityProcessor<'a, '_, T>,
+    ) -> Result<(), EvalError<T>> {
+        let mut complete = vec![false; self.identities.len()];
+        loop {
+            let mut progress = false;
+            for (identity, complete) in self.identities.iter().zip(complete.iter_mut()) {
+                if *complete {
+                    continue;
+                }
+                let row_pair = RowPair::new(
+                    current,
+                    next,
+                    row,
+                    self.fixed_data,
+                    UnknownStrategy::Unknown,
+                );
+                let updates = match identity.kind {
+                    IdentityKind::Connect => self.copy_constraints.process_identity_with(
+                        identity,
+                        &row_pair,
+                        |poly_id, row| self.known_value(poly_id, row),
+                    ),
+                    _ => identity_processor.process_identity(identity, &row_pair),
+                }
+                .map_err(|e| EvalError::from(format!("In row {row}: {identity}:\n{e}")))?;
+                *complete = updates.is_complete();
+                progress |= RowUpdater::new(current, next, row)
+                    .apply_updates(&updates, || identity.to_string());
+            }
+            if !progress {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Returns the value of a cell in the rows computed so far, if it is known.
+    fn known_value(&self, poly_id: PolyID, row: DegreeType) -> Option<T> {
+        self.data
+            .get(row as usize)
+            .and_then(|r| (&r[&poly_id].value).into())
+    }
+}
@@ -99,7 +99,13 @@ impl<T: FieldElement> IndexedColumns<T> {
             .map(|id| fixed_data.fixed_cols[id].values)
             .collect::<Vec<_>>();
 
-        let index: BTreeMap<Vec<T>, IndexValue> = (0..fixed_data.degree as usize)
+        // All columns of the lookup belong to the same namespace and thus have the same length.
+        let degree = input_column_values
+            .iter()
+            .chain(&output_column_values)
+            .next()
+            .map_or(fixed_data.degree as usize, |column| column.len());
+        let index: BTreeMap<Vec<T>, IndexValue> = (0..degree)
             .fold(
                 (
                     BTreeMap::<Vec<T>, IndexValue>::default(),
@@ -0,0 +1,75 @@
+use std::collections::BTreeMap;
+
+/// Records the changes of a map since the checkpoints of a machine,
+/// so that they can be undone, see [super::Machine::checkpoint].
+pub struct Journal<K, V> {
+    /// For each open checkpoint, the keys changed since, with their previous values.
+    changes: Vec<Vec<(K, Option<V>)>>,
+}
+
+impl<K, V> Default for Journal<K, V> {
+    fn default() -> Self {
+        Self {
+            changes: Default::default(),
+        }
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Journal<K, V> {
+    pub fn checkpoint(&mut self) {
+        self.changes.push(vec![]);
+    }
+
+    /// Records the value of `key` in `map` before it is changed, if there is an open checkpoint.
+    pub fn record(&mut self, map: &BTreeMap<K, V>, key: &K) {
+        if let Some(changes) = self.changes.last_mut() {
+            changes.push((key.clone(), map.get(key).cloned()));
+        }
+    }
+
+    /// Undoes the changes of `map` since the most recent checkpoint.
+    pub fn rollback(&mut self, map: &mut BTreeMap<K, V>) {
+        let changes = self.changes.pop().expect("No checkpoint to roll back to.");
+        for (key, previous) in changes.into_iter().rev() {
+            match previous {
+                Some(value) => map.insert(key, value),
+                None => map.remove(&key),
+            };
+        }
+    }
+
+    /// Keeps the changes since the most recent checkpoint, as changes of the enclosing one.
+    pub fn commit(&mut self) {
+        let changes = self.changes.pop().expect("No checkpoint to commit.");
+        if let Some(outer) = self.changes.last_mut() {
+            outer.extend(changes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn nested_checkpoints() {
+        let mut map = BTreeMap::from([(1, 'a')]);
+        let mut journal = Journal::default();
+        let insert = |map: &mut BTreeMap<_, _>, journal: &mut Journal<_, _>, key, value| {
+            journal.record(map, &key);
+            map.insert(key, value);
+        };
+
+        journal.checkpoint();
+        insert(&mut map, &mut journal, 1, 'b');
+        journal.checkpoint();
+        insert(&mut map, &mut journal, 2, 'c');
+        journal.commit();
+        journal.checkpoint();
+        insert(&mut map, &mut journal, 3, 'd');
+        journal.rollback(&mut map);
+        assert_eq!(map, BTreeMap::from([(1, 'b'), (2, 'c')]));
+        journal.rollback(&mut map);
+        assert_eq!(map, BTreeMap::from([(1, 'a')]));
+    }
+}
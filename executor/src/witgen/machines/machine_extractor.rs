@@ -2,6 +2,7 @@ use std::collections::HashSet;
 
 use super::block_machine::BlockMachine;
 use super::double_sorted_witness_machine::DoubleSortedWitnesses;
+use super::dynamic_machine::DynamicMachine;
 use super::fixed_lookup_machine::FixedLookup;
 use super::sorted_witness_machine::SortedWitnesses;
 use super::FixedData;
@@ -23,11 +24,12 @@ pub struct ExtractionOutput<'a, T: FieldElement> {
 /// Finds machines in the witness columns and identities
 /// and returns a list of machines and the identities
 /// that are not "internal" to the machines.
+/// Fails if the witness columns of a machine belong to namespaces of different degrees.
 pub fn split_out_machines<'a, T: FieldElement>(
     fixed: &'a FixedData<'a, T>,
     identities: Vec<&'a Identity<T>>,
     global_range_constraints: &ColumnMap<Option<RangeConstraint<T>>>,
-) -> ExtractionOutput<'a, T> {
+) -> Result<ExtractionOutput<'a, T>, String> {
     let fixed_lookup = FixedLookup::try_new(fixed, &[], &Default::default()).unwrap();
 
     let mut machines: Vec<KnownMachine<T>> = vec![];
@@ -46,20 +48,21 @@ pub fn split_out_machines<'a, T: FieldElement>(
         // a fixed row relation.
         let machine_witnesses =
             all_row_connected_witnesses(lookup_witnesses, &remaining_witnesses, &identities);
+        // The machines are processed with the degree of their namespace.
+        fixed.common_degree(&machine_witnesses)?;
 
-        // Split identities into those that only concern the machine
-        // witnesses and those that concern any other witness.
-        let (machine_identities, remaining_identities): (Vec<_>, _) =
-            base_identities.iter().cloned().partition(|i| {
-                // The identity has at least one machine witness, but
-                // all referenced witnesses are machine witnesses.
-                let all_refs = &refs_in_identity(i) & (&all_witnesses);
-                !all_refs.is_empty() && all_refs.is_subset(&machine_witnesses)
-            });
+        // Split identities into those that belong to the machine
+        // and those that concern any other witness.
+        let (machine_identities, remaining_identities): (Vec<_>, _) = base_identities
+            .iter()
+            .cloned()
+            .partition(|i| belongs_to_machine(i, &machine_witnesses, &all_witnesses));
         base_identities = remaining_identities;
         remaining_witnesses = &remaining_witnesses - &machine_witnesses;
 
-        let connecting_identities = base_identities
+        // The identities that call into this machine. These can also be
+        // identities of machines that were extracted before.
+        let connecting_identities = identities
             .iter()
             .cloned()
             .filter(|i| {
@@ -67,6 +70,7 @@ pub fn split_out_machines<'a, T: FieldElement>(
                     .intersection(&machine_witnesses)
                     .next()
                     .is_some()
+                    && !belongs_to_machine(i, &machine_witnesses, &all_witnesses)
             })
             .collect::<Vec<_>>();
 
@@ -90,9 +94,12 @@ pub fn split_out_machines<'a, T: FieldElement>(
         {
             log::info!("Detected machine: sorted witnesses / write-once memory");
             machines.push(KnownMachine::SortedWitnesses(machine));
-        } else if let Some(machine) =
-            DoubleSortedWitnesses::try_new(fixed, &machine_identities, &machine_witnesses)
-        {
+        } else if let Some(machine) = DoubleSortedWitnesses::try_new(
+            fixed,
+            &connecting_identities,
+            &machine_identities,
+            &machine_witnesses,
+        ) {
             log::info!("Detected machine: memory");
             machines.push(KnownMachine::DoubleSortedWitnesses(machine));
         } else if let Some(machine) = BlockMachine::try_new(
@@ -104,6 +111,15 @@ pub fn split_out_machines<'a, T: FieldElement>(
         ) {
             log::info!("Detected machine: block");
             machines.push(KnownMachine::BlockMachine(machine));
+        } else if let Some(machine) = DynamicMachine::try_new(
+            fixed,
+            &connecting_identities,
+            &machine_identities,
+            &machine_witnesses,
+            global_range_constraints,
+        ) {
+            log::info!("Detected machine: dynamic");
+            machines.push(KnownMachine::DynamicMachine(machine));
         } else {
             log::warn!(
                 "Could not find a matching machine to handle a query to the following witness set:\n{}",
@@ -118,11 +134,35 @@ pub fn split_out_machines<'a, T: FieldElement>(
             log::warn!("Will try to continue as is, but this probably requires a specialized machine implementation.");
         }
     }
-    ExtractionOutput {
+    Ok(ExtractionOutput {
         fixed_lookup,
         machines,
         base_identities,
         base_witnesses: remaining_witnesses,
+    })
+}
+
+/// Returns true if the identity is processed by the machine with the given witnesses:
+/// Either all referenced witnesses are machine witnesses, or it is a lookup or
+/// permutation from the machine witnesses into another machine.
+fn belongs_to_machine<T>(
+    identity: &Identity<T>,
+    machine_witnesses: &HashSet<PolyID>,
+    all_witnesses: &HashSet<PolyID>,
+) -> bool {
+    let is_subset = |refs: HashSet<PolyID>| {
+        let refs = refs
+            .into_iter()
+            .filter(|r| all_witnesses.contains(r))
+            .collect::<HashSet<_>>();
+        !refs.is_empty() && refs.is_subset(machine_witnesses)
+    };
+    match identity.kind {
+        IdentityKind::Plookup | IdentityKind::Permutation => {
+            is_subset(refs_in_selected_expressions(&identity.left))
+                || is_subset(refs_in_identity(identity))
+        }
+        _ => is_subset(refs_in_identity(identity)),
     }
 }
 
@@ -7,16 +7,21 @@ use number::FieldElement;
 
 use self::block_machine::BlockMachine;
 use self::double_sorted_witness_machine::DoubleSortedWitnesses;
+use self::dynamic_machine::DynamicMachine;
 pub use self::fixed_lookup_machine::FixedLookup;
 use self::sorted_witness_machine::SortedWitnesses;
 
 use super::affine_expression::AffineExpression;
+use super::copy_constraints::CellValues;
+use super::EvalError;
 use super::EvalResult;
 use super::FixedData;
 
 mod block_machine;
 mod double_sorted_witness_machine;
+mod dynamic_machine;
 mod fixed_lookup_machine;
+mod journal;
 pub mod machine_extractor;
 mod sorted_witness_machine;
 
@@ -28,6 +33,8 @@ pub trait Machine<'a, T: FieldElement>: Send + Sync {
     /// Only return an error if this machine is able to handle the query and
     /// it results in a constraint failure.
     /// If this is not the right machine for the query, return `None`.
+    /// `machines` contains all other machines, which can be called
+    /// while processing the query.
     fn process_plookup(
         &mut self,
         fixed_data: &'a FixedData<T>,
@@ -35,23 +42,58 @@ pub trait Machine<'a, T: FieldElement>: Send + Sync {
         kind: IdentityKind,
         left: &[AffineExpression<&'a PolynomialReference, T>],
         right: &'a SelectedExpressions<T>,
+        machines: Machines<'a, '_, T>,
     ) -> Option<EvalResult<'a, T>>;
 
-    /// Returns the final values of the witness columns.
+    /// Remembers the current state of the machine, so that the changes made by a query
+    /// which turns out to be incomplete can be undone. Checkpoints can be nested: Every
+    /// checkpoint is ended by exactly one call to [Machine::rollback] or [Machine::commit].
+    fn checkpoint(&mut self);
+
+    /// Restores the state of the most recent checkpoint and removes the checkpoint.
+    fn rollback(&mut self);
+
+    /// Removes the most recent checkpoint and keeps the changes made since.
+    /// They are undone if an enclosing checkpoint is rolled back.
+    fn commit(&mut self);
+
+    /// Completes the machine, for example by filling its unused rows, before the values of
+    /// any machine are taken. `machines` contains all other machines, which can be called
+    /// in the process.
+    fn finalize(
+        &mut self,
+        _fixed_lookup: &mut FixedLookup<T>,
+        _machines: Machines<'a, '_, T>,
+    ) -> Result<(), EvalError<T>> {
+        Ok(())
+    }
+
+    /// Returns the final values of the witness columns. Has to be called after
+    /// [Machine::finalize] was called on all machines.
     fn take_witness_col_values(
         &mut self,
         fixed_data: &FixedData<T>,
         fixed_lookup: &mut FixedLookup<T>,
     ) -> HashMap<String, Vec<T>>;
+
+    /// Returns the values of cells connected by copy constraints that were determined
+    /// only after their rows had been finalized. Has to be called before
+    /// [Machine::take_witness_col_values].
+    fn late_copy_constraint_values(&self) -> CellValues<T> {
+        CellValues::default()
+    }
 }
 
 /// All known implementations of [Machine].
 /// This allows us to treat machines uniformly without putting them into a `Box`,
 /// which requires that all lifetime parameters are 'static.
+/// There are only few machines, so the size difference between the variants does not matter.
+#[allow(clippy::large_enum_variant)]
 pub enum KnownMachine<'a, T: FieldElement> {
     SortedWitnesses(SortedWitnesses<T>),
     DoubleSortedWitnesses(DoubleSortedWitnesses<T>),
     BlockMachine(BlockMachine<'a, T>),
+    DynamicMachine(DynamicMachine<'a, T>),
 }
 
 impl<'a, T: FieldElement> KnownMachine<'a, T> {
@@ -60,6 +102,7 @@ impl<'a, T: FieldElement> KnownMachine<'a, T> {
             KnownMachine::SortedWitnesses(m) => m,
             KnownMachine::DoubleSortedWitnesses(m) => m,
             KnownMachine::BlockMachine(m) => m,
+            KnownMachine::DynamicMachine(m) => m,
         }
     }
 }
@@ -72,9 +115,30 @@ impl<'a, T: FieldElement> Machine<'a, T> for KnownMachine<'a, T> {
         kind: IdentityKind,
         left: &[AffineExpression<&'a PolynomialReference, T>],
         right: &'a SelectedExpressions<T>,
+        machines: Machines<'a, '_, T>,
     ) -> Option<crate::witgen::EvalResult<'a, T>> {
         self.get()
-            .process_plookup(fixed_data, fixed_lookup, kind, left, right)
+            .process_plookup(fixed_data, fixed_lookup, kind, left, right, machines)
+    }
+
+    fn checkpoint(&mut self) {
+        self.get().checkpoint()
+    }
+
+    fn rollback(&mut self) {
+        self.get().rollback()
+    }
+
+    fn commit(&mut self) {
+        self.get().commit()
+    }
+
+    fn finalize(
+        &mut self,
+        fixed_lookup: &mut FixedLookup<T>,
+        machines: Machines<'a, '_, T>,
+    ) -> Result<(), EvalError<T>> {
+        self.get().finalize(fixed_lookup, machines)
     }
 
     fn take_witness_col_values(
@@ -84,4 +148,93 @@ impl<'a, T: FieldElement> Machine<'a, T> for KnownMachine<'a, T> {
     ) -> std::collections::HashMap<String, Vec<T>> {
         self.get().take_witness_col_values(fixed_data, fixed_lookup)
     }
+
+    fn late_copy_constraint_values(&self) -> CellValues<T> {
+        match self {
+            KnownMachine::SortedWitnesses(m) => m.late_copy_constraint_values(),
+            KnownMachine::DoubleSortedWitnesses(m) => m.late_copy_constraint_values(),
+            KnownMachine::BlockMachine(m) => m.late_copy_constraint_values(),
+            KnownMachine::DynamicMachine(m) => m.late_copy_constraint_values(),
+        }
+    }
+}
+
+/// A registry of machines that can be called while processing a query.
+/// When a machine is called, it receives all other machines of the registry,
+/// so that it can call them in turn, but not itself.
+/// This prevents cyclic calls and bounds the call depth by the number of machines.
+#[derive(Default)]
+pub struct Machines<'a, 'b, T: FieldElement> {
+    machines: &'b mut [KnownMachine<'a, T>],
+    /// The number of machine calls that led to this registry.
+    depth: usize,
+}
+
+impl<'a, 'b, T: FieldElement> From<&'b mut [KnownMachine<'a, T>]> for Machines<'a, 'b, T> {
+    fn from(machines: &'b mut [KnownMachine<'a, T>]) -> Self {
+        Self { machines, depth: 0 }
+    }
+}
+
+impl<'a, 'b, T: FieldElement> Machines<'a, 'b, T> {
+    pub fn len(&self) -> usize {
+        self.machines.len()
+    }
+
+    /// The number of nested machine calls that led to this registry.
+    pub fn call_depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Calls `f` with the machine at the given index and a registry of all other machines,
+    /// to be passed to the machine when calling it.
+    /// To avoid allocating a new registry, the machine is temporarily moved to the end,
+    /// so that all other machines form a contiguous slice.
+    pub fn call<R>(
+        &mut self,
+        index: usize,
+        f: impl FnOnce(&mut KnownMachine<'a, T>, Machines<'a, '_, T>) -> R,
+    ) -> R {
+        let last = self.machines.len() - 1;
+        self.machines.swap(index, last);
+        let (others, current) = self.machines.split_at_mut(last);
+        let result = f(
+            &mut current[0],
+            Machines {
+                machines: others,
+                depth: self.depth + 1,
+            },
+        );
+        self.machines.swap(index, last);
+        result
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, KnownMachine<'a, T>> {
+        self.machines.iter_mut()
+    }
+
+    /// Returns a registry of the same machines that borrows this one, so that the machines
+    /// can be passed on and used again afterwards.
+    pub fn reborrow(&mut self) -> Machines<'a, '_, T> {
+        Machines {
+            machines: self.machines,
+            depth: self.depth,
+        }
+    }
+
+    /// Creates a checkpoint in all machines, see [Machine::checkpoint].
+    pub fn checkpoint(&mut self) {
+        self.machines.iter_mut().for_each(|m| m.checkpoint());
+    }
+
+    /// Rolls back all machines to their most recent checkpoint, see [Machine::rollback].
+    pub fn rollback(&mut self) {
+        self.machines.iter_mut().for_each(|m| m.rollback());
+    }
+
+    /// Commits the changes of all machines since their most recent checkpoint,
+    /// see [Machine::commit].
+    pub fn commit(&mut self) {
+        self.machines.iter_mut().for_each(|m| m.commit());
+    }
 }
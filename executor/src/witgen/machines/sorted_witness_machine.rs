@@ -4,8 +4,9 @@ use itertools::Itertools;
 
 use super::super::affine_expression::AffineExpression;
 use super::fixed_lookup_machine::FixedLookup;
-use super::Machine;
+use super::journal::Journal;
 use super::{EvalResult, FixedData};
+use super::{Machine, Machines};
 use crate::witgen::{
     expression_evaluator::ExpressionEvaluator, fixed_evaluator::FixedEvaluator,
     symbolic_evaluator::SymbolicEvaluator,
@@ -14,7 +15,7 @@ use crate::witgen::{EvalValue, IncompleteCause};
 use ast::analyzed::{
     Expression, Identity, IdentityKind, PolyID, PolynomialReference, SelectedExpressions,
 };
-use number::FieldElement;
+use number::{DegreeType, FieldElement};
 
 /// A machine that can support a lookup in a set of columns that are sorted
 /// by one specific column and values in that column have to be unique.
@@ -24,10 +25,13 @@ use number::FieldElement;
 ///  - NOTLAST is zero only on the last row
 ///  - POSITIVE has all values from 1 to half of the field size.
 pub struct SortedWitnesses<T> {
+    /// The degree of the namespace of the machine.
+    degree: DegreeType,
     key_col: PolyID,
     /// Position of the witness columns in the data.
     witness_positions: HashMap<PolyID, usize>,
     data: BTreeMap<T, Vec<Option<T>>>,
+    journal: Journal<T, Vec<Option<T>>>,
 }
 
 impl<T: FieldElement> SortedWitnesses<T> {
@@ -39,6 +43,7 @@ impl<T: FieldElement> SortedWitnesses<T> {
         if identities.len() != 1 {
             return None;
         }
+        let degree = fixed_data.common_degree(witnesses).ok()?;
         check_identity(fixed_data, identities.first().unwrap()).map(|key_col| {
             let witness_positions = witnesses
                 .iter()
@@ -49,9 +54,11 @@ impl<T: FieldElement> SortedWitnesses<T> {
                 .collect();
 
             SortedWitnesses {
+                degree,
                 key_col,
                 witness_positions,
                 data: Default::default(),
+                journal: Default::default(),
             }
         })
     }
@@ -74,7 +81,7 @@ fn check_identity<T: FieldElement>(fixed_data: &FixedData<T>, id: &Identity<T>)
 
     // TODO this could be rather slow. We should check the code for identity instead
     // of evaluating it.
-    let degree = fixed_data.degree as usize;
+    let degree = fixed_data.common_degree([&key_column]).ok()? as usize;
     for row in 0..(degree) {
         let ev = ExpressionEvaluator::new(FixedEvaluator::new(fixed_data, row));
         let nl = ev.evaluate(notlast).ok()?.constant_value()?;
@@ -125,6 +132,7 @@ impl<'a, T: FieldElement> Machine<'a, T> for SortedWitnesses<T> {
         kind: IdentityKind,
         left: &[AffineExpression<&'a PolynomialReference, T>],
         right: &'a SelectedExpressions<T>,
+        _machines: Machines<'a, '_, T>,
     ) -> Option<EvalResult<'a, T>> {
         if kind != IdentityKind::Plookup || right.selector.is_some() {
             return None;
@@ -149,6 +157,19 @@ impl<'a, T: FieldElement> Machine<'a, T> for SortedWitnesses<T> {
 
         Some(self.process_plookup_internal(fixed_data, left, right, rhs))
     }
+
+    fn checkpoint(&mut self) {
+        self.journal.checkpoint();
+    }
+
+    fn rollback(&mut self) {
+        self.journal.rollback(&mut self.data);
+    }
+
+    fn commit(&mut self) {
+        self.journal.commit();
+    }
+
     fn take_witness_col_values(
         &mut self,
         fixed_data: &FixedData<T>,
@@ -160,7 +181,7 @@ impl<'a, T: FieldElement> Machine<'a, T> for SortedWitnesses<T> {
             std::mem::take(&mut self.data).into_iter().unzip();
 
         let mut last_key = keys.last().cloned().unwrap_or_default();
-        while keys.len() < fixed_data.degree as usize {
+        while keys.len() < self.degree as usize {
             last_key += 1u64.into();
             keys.push(last_key);
         }
@@ -171,7 +192,7 @@ impl<'a, T: FieldElement> Machine<'a, T> for SortedWitnesses<T> {
                 .iter_mut()
                 .map(|row| std::mem::take(&mut row[i]).unwrap_or_default())
                 .collect::<Vec<_>>();
-            col_values.resize(fixed_data.degree as usize, 0.into());
+            col_values.resize(self.degree as usize, 0.into());
             result.insert(fixed_data.column_name(col).to_string(), col_values);
         }
 
@@ -200,6 +221,7 @@ impl<T: FieldElement> SortedWitnesses<T> {
         })?;
 
         let mut assignments = EvalValue::complete(vec![]);
+        self.journal.record(&self.data, &key_value);
         let stored_values = self
             .data
             .entry(key_value)
@@ -3,19 +3,26 @@ use std::collections::BTreeMap;
 use ast::analyzed::{
     Analyzed, Expression, FunctionValueDefinition, PolyID, PolynomialReference, PolynomialType,
 };
+use itertools::Itertools;
 use num_traits::Zero;
 use number::{DegreeType, FieldElement};
 
+pub use self::auxiliary_columns::generate_auxiliary_columns;
 use self::column_map::ColumnMap;
+use self::copy_constraints::CellValues;
 pub use self::eval_result::{
     Constraint, Constraints, EvalError, EvalResult, EvalStatus, EvalValue, IncompleteCause,
 };
 use self::global_constraints::GlobalConstraints;
 use self::machines::machine_extractor::ExtractionOutput;
+pub use self::prover_input::{ProverInput, ProverQuery};
+pub use self::rom_debug_info::{RomDebugInfo, RomRow};
 use self::util::substitute_constants;
 
 mod affine_expression;
+mod auxiliary_columns;
 mod column_map;
+mod copy_constraints;
 mod eval_result;
 mod expression_evaluator;
 pub mod fixed_evaluator;
@@ -24,8 +31,10 @@ mod global_constraints;
 mod identity_processor;
 mod machines;
 mod processor;
+pub mod prover_input;
 mod query_processor;
 mod range_constraints;
+mod rom_debug_info;
 mod rows;
 mod sequence_iterator;
 pub mod symbolic_evaluator;
@@ -33,20 +42,121 @@ mod symbolic_witness_evaluator;
 mod util;
 
 /// Generates the committed polynomial values
-/// @returns the values (in source order) and the degree of the polynomials.
+/// @param degree the maximum degree of all namespaces. Columns of namespaces with a smaller
+/// degree are generated with their own length.
+/// @returns the values (in source order) of the polynomials,
+/// or a description of the failure if witness generation failed.
 pub fn generate<'a, T: FieldElement, QueryCallback>(
     analyzed: &'a Analyzed<T>,
     degree: DegreeType,
     fixed_col_values: &[(&str, Vec<T>)],
     query_callback: Option<QueryCallback>,
-) -> Vec<(&'a str, Vec<T>)>
+) -> Result<Vec<(&'a str, Vec<T>)>, String>
 where
-    QueryCallback: FnMut(&str) -> Option<T> + Send + Sync,
+    QueryCallback: ProverInput<T> + Send + Sync,
+{
+    generate_with_external_witness(
+        analyzed,
+        degree,
+        fixed_col_values,
+        query_callback,
+        &[],
+        None,
+    )
+}
+
+/// Generates the committed polynomial values like [generate], using externally computed
+/// values of some witness columns, e.g. from an execution trace of the main machine.
+/// The external values are set before the identities of a row are processed, so witness
+/// generation only has to compute the remaining columns. If an external value contradicts
+/// a value derived from the previous row, witness generation fails.
+/// External columns can be shorter than the degree, columns with unknown names are ignored.
+/// If the ROM of the main VM is given, failures describe the statement at the pc of the
+/// failing row, its source location and the statements preceding it.
+pub fn generate_with_external_witness<'a, T: FieldElement, QueryCallback>(
+    analyzed: &'a Analyzed<T>,
+    degree: DegreeType,
+    fixed_col_values: &[(&str, Vec<T>)],
+    query_callback: Option<QueryCallback>,
+    external_witness_values: &[(&str, Vec<T>)],
+    rom_debug_info: Option<&RomDebugInfo>,
+) -> Result<Vec<(&'a str, Vec<T>)>, String>
+where
+    QueryCallback: ProverInput<T> + Send + Sync,
 {
     if degree.is_zero() {
-        panic!("Resulting degree is zero. Please ensure that there is at least one non-constant fixed column to set the degree.");
+        return Err("Resulting degree is zero. Please ensure that there is at least one non-constant fixed column to set the degree.".to_string());
     }
-    let fixed = FixedData::new(analyzed, degree, fixed_col_values);
+
+    // Values that are propagated along copy constraints to rows that were already finalized
+    // can only be taken into account by generating the witness again.
+    let mut copy_constraint_values = CellValues::default();
+    let mut query_callback = query_callback;
+    let columns = loop {
+        let GenerationResult {
+            columns,
+            late_copy_constraint_values,
+            query_callback: callback,
+        } = generate_once(
+            analyzed,
+            degree,
+            fixed_col_values,
+            query_callback,
+            external_witness_values,
+            rom_debug_info,
+            &copy_constraint_values,
+        )?;
+        if late_copy_constraint_values.is_empty() {
+            break columns;
+        }
+        log::info!(
+            "Generating the witness again, because copy constraints determined {} values in rows that were already finalized.",
+            late_copy_constraint_values.len()
+        );
+        copy_constraint_values.extend(late_copy_constraint_values);
+        query_callback = callback;
+    };
+
+    // Map from column id to name
+    let mut col_names = analyzed
+        .committed_polys_in_source_order()
+        .iter()
+        .map(|(p, _)| (p.id, p.absolute_name.as_str()))
+        .collect::<BTreeMap<_, _>>();
+
+    Ok(columns
+        .into_iter()
+        .map(|(id, v)| (col_names.remove(&id.id).unwrap(), v))
+        .collect())
+}
+
+/// The result of a single run of witness generation, see [generate_once].
+struct GenerationResult<T, QueryCallback> {
+    columns: ColumnMap<Vec<T>>,
+    /// The values of cells connected by copy constraints that were only determined
+    /// after their rows had been finalized.
+    late_copy_constraint_values: CellValues<T>,
+    /// The query callback, to generate the witness again.
+    query_callback: Option<QueryCallback>,
+}
+
+/// Generates the witness columns once, assuming the given values of cells connected by
+/// copy constraints.
+fn generate_once<T: FieldElement, QueryCallback>(
+    analyzed: &Analyzed<T>,
+    degree: DegreeType,
+    fixed_col_values: &[(&str, Vec<T>)],
+    query_callback: Option<QueryCallback>,
+    external_witness_values: &[(&str, Vec<T>)],
+    rom_debug_info: Option<&RomDebugInfo>,
+    copy_constraint_values: &CellValues<T>,
+) -> Result<GenerationResult<T, QueryCallback>, String>
+where
+    QueryCallback: ProverInput<T> + Send + Sync,
+{
+    let fixed = FixedData::new(analyzed, degree, fixed_col_values, external_witness_values)
+        .with_rom_debug_info(rom_debug_info)
+        .with_copy_constraint_values(copy_constraint_values);
     let identities = substitute_constants(&analyzed.identities, &analyzed.constants);
 
     let GlobalConstraints {
@@ -58,24 +168,25 @@ where
     } = global_constraints::determine_global_constraints(&fixed, identities.iter().collect());
     let ExtractionOutput {
         mut fixed_lookup,
-        machines,
+        mut machines,
         base_identities,
         base_witnesses,
     } = machines::machine_extractor::split_out_machines(
         &fixed,
         retained_identities,
         &known_witness_constraints,
-    );
+    )?;
     let mut generator = generator::Generator::new(
         &fixed,
         &mut fixed_lookup,
         &base_identities,
         base_witnesses.into_iter().collect(),
         known_witness_constraints,
-        machines,
+        &mut machines,
         query_callback,
-    );
+    )?;
 
+    let main_degree = generator.degree();
     let mut rows: Vec<ColumnMap<T>> = vec![];
 
     let poly_ids = fixed.witness_cols.keys().collect::<Vec<_>>();
@@ -90,7 +201,7 @@ where
 
     // Are we in an infinite loop and can just re-use the old values?
     let mut looping_period = None;
-    for row in 0..degree as DegreeType {
+    for row in 0..main_degree {
         // Check if we are in a loop.
         if looping_period.is_none() && row % 100 == 0 && row > 0 {
             looping_period = rows_are_repeating(&rows, &relevant_witnesses_mask);
@@ -101,7 +212,7 @@ where
         let mut row_values = None;
         if let Some(period) = looping_period {
             let values = &rows[rows.len() - period];
-            if generator.propose_next_row(row, values) {
+            if generator.propose_next_row(row, values)? {
                 row_values = Some(values.clone());
             } else {
                 log::info!("Using loop failed. Trying to generate regularly again.");
@@ -109,7 +220,7 @@ where
             }
         }
         if row_values.is_none() {
-            row_values = Some(generator.compute_next_row(row));
+            row_values = Some(generator.compute_next_row(row)?);
         };
 
         rows.push(row_values.unwrap());
@@ -123,23 +234,19 @@ where
         }
     }
 
+    generator.finalize_machines()?;
+    let late_copy_constraint_values = generator.late_copy_constraint_values();
+
     // Overwrite all machine witness columns
     for (poly_id, data) in generator.machine_witness_col_values() {
         columns[&poly_id] = data;
     }
 
-    // Map from column id to name
-    // We can't used `fixed` here, because the name would have the wrong lifetime.
-    let mut col_names = analyzed
-        .committed_polys_in_source_order()
-        .iter()
-        .map(|(p, _)| (p.id, p.absolute_name.as_str()))
-        .collect::<BTreeMap<_, _>>();
-
-    columns
-        .into_iter()
-        .map(|(id, v)| (col_names.remove(&id.id).unwrap(), v))
-        .collect()
+    Ok(GenerationResult {
+        columns,
+        late_copy_constraint_values,
+        query_callback: generator.into_query_callback(),
+    })
 }
 
 fn zip_relevant<'a, T>(
@@ -177,9 +284,14 @@ fn rows_are_repeating<T: PartialEq>(
 
 /// Data that is fixed for witness generation.
 pub struct FixedData<'a, T> {
+    /// The maximum degree of all namespaces.
     degree: DegreeType,
     fixed_cols: ColumnMap<FixedColumn<'a, T>>,
     witness_cols: ColumnMap<WitnessColumn<'a, T>>,
+    /// The ROM of the main VM, used to describe failures.
+    rom_debug_info: Option<&'a RomDebugInfo>,
+    /// Values of cells connected by copy constraints, determined by a previous run.
+    copy_constraint_values: Option<&'a CellValues<T>>,
 }
 
 impl<'a, T: FieldElement> FixedData<'a, T> {
@@ -187,7 +299,12 @@ impl<'a, T: FieldElement> FixedData<'a, T> {
         analyzed: &'a Analyzed<T>,
         degree: DegreeType,
         fixed_col_values: &'a [(&'a str, Vec<T>)],
+        external_witness_values: &'a [(&'a str, Vec<T>)],
     ) -> Self {
+        let mut external_witness_values = external_witness_values
+            .iter()
+            .map(|(name, values)| (*name, values))
+            .collect::<BTreeMap<_, _>>();
         let witness_cols = ColumnMap::from(
             analyzed
                 .committed_polys_in_source_order()
@@ -198,11 +315,15 @@ impl<'a, T: FieldElement> FixedData<'a, T> {
                         unimplemented!("Committed arrays not implemented.")
                     }
                     assert_eq!(i as u64, poly.id);
-                    let col = WitnessColumn::new(i, &poly.absolute_name, value);
-                    col
+                    let external_values =
+                        external_witness_values.remove(poly.absolute_name.as_str());
+                    WitnessColumn::new(i, &poly.absolute_name, poly.degree, value, external_values)
                 }),
             PolynomialType::Committed,
         );
+        for name in external_witness_values.keys() {
+            log::debug!("Ignoring external values of unknown witness column {name}");
+        }
 
         let fixed_cols = ColumnMap::from(
             fixed_col_values.iter().map(|(n, v)| FixedColumn::new(n, v)),
@@ -212,9 +333,21 @@ impl<'a, T: FieldElement> FixedData<'a, T> {
             degree,
             fixed_cols,
             witness_cols,
+            rom_debug_info: None,
+            copy_constraint_values: None,
         }
     }
 
+    pub fn with_rom_debug_info(mut self, rom_debug_info: Option<&'a RomDebugInfo>) -> Self {
+        self.rom_debug_info = rom_debug_info;
+        self
+    }
+
+    pub fn with_copy_constraint_values(mut self, values: &'a CellValues<T>) -> Self {
+        self.copy_constraint_values = Some(values);
+        self
+    }
+
     fn witness_map_with<V: Clone>(&self, initial_value: V) -> ColumnMap<V> {
         ColumnMap::new(
             initial_value,
@@ -223,6 +356,28 @@ impl<'a, T: FieldElement> FixedData<'a, T> {
         )
     }
 
+    /// Returns the common degree of the namespaces of the given witness columns,
+    /// or the maximum degree if there are no columns.
+    /// Fails if the columns belong to namespaces of different degrees.
+    fn common_degree<'b>(
+        &self,
+        ids: impl IntoIterator<Item = &'b PolyID>,
+    ) -> Result<DegreeType, String> {
+        let degrees = ids
+            .into_iter()
+            .map(|id| self.witness_cols[id].degree)
+            .unique()
+            .collect::<Vec<_>>();
+        match degrees[..] {
+            [] => Ok(self.degree),
+            [degree] => Ok(degree),
+            _ => Err(format!(
+                "Witness columns that are processed together have to be in namespaces of the same degree, found degrees {}.",
+                degrees.iter().join(", ")
+            )),
+        }
+    }
+
     fn column_name(&self, poly_id: &PolyID) -> &str {
         match poly_id.ptype {
             PolynomialType::Committed => &self.witness_cols[poly_id].name,
@@ -255,14 +410,20 @@ pub struct Query<'a, T> {
 #[derive(Debug)]
 pub struct WitnessColumn<'a, T> {
     name: String,
+    /// The degree of the namespace of the column.
+    degree: DegreeType,
     query: Option<Query<'a, T>>,
+    /// Externally computed values of the column, if any.
+    external_values: Option<&'a Vec<T>>,
 }
 
 impl<'a, T> WitnessColumn<'a, T> {
     pub fn new(
         id: usize,
         name: &'a str,
+        degree: DegreeType,
         value: &'a Option<FunctionValueDefinition<T>>,
+        external_values: Option<&'a Vec<T>>,
     ) -> WitnessColumn<'a, T> {
         let query = if let Some(FunctionValueDefinition::Query(query)) = value {
             Some(query)
@@ -285,6 +446,11 @@ impl<'a, T> WitnessColumn<'a, T> {
                 expr: callback,
             }
         });
-        WitnessColumn { name, query }
+        WitnessColumn {
+            name,
+            degree,
+            query,
+            external_values,
+        }
     }
 }
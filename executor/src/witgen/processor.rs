@@ -151,7 +151,9 @@ mod tests {
     use crate::{
         constant_evaluator::generate,
         witgen::{
-            identity_processor::IdentityProcessor, machines::FixedLookup, rows::RowFactory,
+            identity_processor::IdentityProcessor,
+            machines::{FixedLookup, Machines},
+            rows::RowFactory,
             FixedData,
         },
     };
@@ -174,13 +176,13 @@ mod tests {
         src: &str,
         f: impl Fn(&mut Processor<T>, BTreeMap<String, PolyID>) -> R,
     ) -> R {
-        let analyzed = analyze_string(src);
+        let analyzed = analyze_string(src).unwrap();
         let (constants, degree) = generate(&analyzed);
-        let fixed_data = FixedData::new(&analyzed, degree, &constants);
+        let fixed_data = FixedData::new(&analyzed, degree, &constants, &[]);
 
         // No submachines
         let mut fixed_lookup = FixedLookup::default();
-        let machines = vec![];
+        let machines = Machines::default();
 
         // No global range constraints
         let global_range_constraints = fixed_data.witness_map_with(None);
@@ -0,0 +1,283 @@
+//! The protocol between witness generation and the prover: witness columns declared with a query
+//! (e.g. `col witness X(i) query ("input", i);`) ask the prover for their values.
+//! The query tuple is turned into a [ProverQuery], which is answered by a [ProverInput].
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use number::FieldElement;
+
+/// An element of a query tuple, as it appears in PIL.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QueryItem<T> {
+    String(String),
+    Value(T),
+}
+
+/// A query to the prover.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProverQuery<T> {
+    /// `("input", index)`: The value at `index` in the list of inputs.
+    Input(usize),
+    /// `("input", "name", index)`: The value at `index` in the list of inputs called `name`.
+    NamedInput(String, usize),
+    /// `("data", index)`: The byte at `index` in the input byte stream.
+    Data(usize),
+    /// `("print_char", c)`: Prints the character `c`, answered with zero.
+    PrintChar(u8),
+    /// Any other query, with its kind and arguments.
+    Other(String, Vec<T>),
+}
+
+impl<T: FieldElement> ProverQuery<T> {
+    /// Turns the items of a query tuple into a query.
+    /// The first item has to be a string, the kind of the query.
+    pub fn from_items(items: Vec<QueryItem<T>>) -> Result<Self, String> {
+        let mut items = items.into_iter();
+        let Some(QueryItem::String(kind)) = items.next() else {
+            return Err("The first element of a query has to be a string".to_string());
+        };
+        let args = items.collect::<Vec<_>>();
+        match (kind.as_str(), &args[..]) {
+            ("input", [QueryItem::Value(index)]) => Ok(ProverQuery::Input(to_index(index)?)),
+            ("input", [QueryItem::String(name), QueryItem::Value(index)]) => {
+                Ok(ProverQuery::NamedInput(name.clone(), to_index(index)?))
+            }
+            ("data", [QueryItem::Value(index)]) => Ok(ProverQuery::Data(to_index(index)?)),
+            ("print_char", [QueryItem::Value(c)]) => u8::try_from(c.to_arbitrary_integer())
+                .map(ProverQuery::PrintChar)
+                .map_err(|_| format!("Invalid character: {c}")),
+            ("input" | "data" | "print_char", _) => {
+                Err(format!("Invalid arguments for query \"{kind}\""))
+            }
+            _ => args
+                .into_iter()
+                .map(|arg| match arg {
+                    QueryItem::Value(v) => Ok(v),
+                    QueryItem::String(s) => Err(format!(
+                        "Unexpected string argument \"{s}\" in query \"{kind}\""
+                    )),
+                })
+                .collect::<Result<_, _>>()
+                .map(|args| ProverQuery::Other(kind, args)),
+        }
+    }
+}
+
+fn to_index<T: FieldElement>(value: &T) -> Result<usize, String> {
+    usize::try_from(value.to_arbitrary_integer()).map_err(|_| format!("Invalid index: {value}"))
+}
+
+impl<T: fmt::Display> fmt::Display for ProverQuery<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProverQuery::Input(index) => write!(f, "(\"input\", {index})"),
+            ProverQuery::NamedInput(name, index) => write!(f, "(\"input\", \"{name}\", {index})"),
+            ProverQuery::Data(index) => write!(f, "(\"data\", {index})"),
+            ProverQuery::PrintChar(c) => write!(f, "(\"print_char\", {c})"),
+            ProverQuery::Other(kind, args) => {
+                write!(f, "(\"{kind}\"")?;
+                for arg in args {
+                    write!(f, ", {arg}")?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+/// Answers queries of the prover.
+pub trait ProverInput<T> {
+    /// Returns the answer to `query`, or `None` if the query cannot be answered.
+    /// Queries of type [ProverQuery::PrintChar] that are not answered are handled by
+    /// witness generation by printing the character to stdout.
+    fn answer(&mut self, query: &ProverQuery<T>) -> Option<T>;
+}
+
+impl<T, F> ProverInput<T> for F
+where
+    F: FnMut(&ProverQuery<T>) -> Option<T>,
+{
+    fn answer(&mut self, query: &ProverQuery<T>) -> Option<T> {
+        self(query)
+    }
+}
+
+/// Answers `("input", index)` from a list of values.
+#[derive(Clone, Debug, Default)]
+pub struct InputList<T>(Vec<T>);
+
+impl<T> From<Vec<T>> for InputList<T> {
+    fn from(values: Vec<T>) -> Self {
+        InputList(values)
+    }
+}
+
+impl<T: FieldElement> ProverInput<T> for InputList<T> {
+    fn answer(&mut self, query: &ProverQuery<T>) -> Option<T> {
+        let ProverQuery::Input(index) = query else {
+            return None;
+        };
+        let value = self.0.get(*index).cloned();
+        if let Some(value) = value {
+            log::trace!("Input query: Index {index} -> {value}");
+        } else {
+            log::warn!("Not enough inputs provided! Index {index} out of bounds");
+        }
+        value
+    }
+}
+
+/// Answers `("input", "name", index)` from lists of values identified by name.
+#[derive(Clone, Debug, Default)]
+pub struct NamedInputs<T>(BTreeMap<String, Vec<T>>);
+
+impl<T> From<BTreeMap<String, Vec<T>>> for NamedInputs<T> {
+    fn from(inputs: BTreeMap<String, Vec<T>>) -> Self {
+        NamedInputs(inputs)
+    }
+}
+
+impl<T: FieldElement> ProverInput<T> for NamedInputs<T> {
+    fn answer(&mut self, query: &ProverQuery<T>) -> Option<T> {
+        let ProverQuery::NamedInput(name, index) = query else {
+            return None;
+        };
+        let Some(values) = self.0.get(name) else {
+            log::warn!("No input called \"{name}\" provided!");
+            return None;
+        };
+        let value = values.get(*index).cloned();
+        if let Some(value) = value {
+            log::trace!("Input query: \"{name}\", Index {index} -> {value}");
+        } else {
+            log::warn!("Not enough inputs called \"{name}\" provided! Index {index} out of bounds");
+        }
+        value
+    }
+}
+
+/// Answers `("data", index)` from a stream of bytes.
+#[derive(Clone, Debug, Default)]
+pub struct ByteStream(Vec<u8>);
+
+impl ByteStream {
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        fs::read(path).map(ByteStream)
+    }
+}
+
+impl From<Vec<u8>> for ByteStream {
+    fn from(bytes: Vec<u8>) -> Self {
+        ByteStream(bytes)
+    }
+}
+
+impl<T: FieldElement> ProverInput<T> for ByteStream {
+    fn answer(&mut self, query: &ProverQuery<T>) -> Option<T> {
+        let ProverQuery::Data(index) = query else {
+            return None;
+        };
+        let value = self.0.get(*index).map(|b| T::from(*b as u32));
+        if value.is_none() {
+            log::warn!("Not enough data provided! Index {index} out of bounds");
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use number::{Bn254Field, GoldilocksField};
+
+    use super::*;
+
+    fn query(
+        items: Vec<QueryItem<GoldilocksField>>,
+    ) -> Result<ProverQuery<GoldilocksField>, String> {
+        ProverQuery::from_items(items)
+    }
+
+    fn s(s: &str) -> QueryItem<GoldilocksField> {
+        QueryItem::String(s.to_string())
+    }
+
+    fn s_bn254(s: &str) -> QueryItem<Bn254Field> {
+        QueryItem::String(s.to_string())
+    }
+
+    fn v(v: u64) -> QueryItem<GoldilocksField> {
+        QueryItem::Value(v.into())
+    }
+
+    #[test]
+    fn parse_queries() {
+        assert_eq!(query(vec![s("input"), v(3)]), Ok(ProverQuery::Input(3)));
+        assert_eq!(
+            query(vec![s("input"), s("x"), v(1)]),
+            Ok(ProverQuery::NamedInput("x".to_string(), 1))
+        );
+        assert_eq!(query(vec![s("data"), v(7)]), Ok(ProverQuery::Data(7)));
+        assert_eq!(
+            query(vec![s("print_char"), v(65)]),
+            Ok(ProverQuery::PrintChar(b'A'))
+        );
+        assert_eq!(
+            query(vec![s("in"), v(1), v(2)]),
+            Ok(ProverQuery::Other(
+                "in".to_string(),
+                vec![1.into(), 2.into()]
+            ))
+        );
+    }
+
+    #[test]
+    fn invalid_queries() {
+        assert!(query(vec![v(1)]).is_err());
+        assert!(query(vec![s("input")]).is_err());
+        assert!(query(vec![s("print_char"), v(300)]).is_err());
+        assert!(query(vec![s("in"), s("x")]).is_err());
+        // Values that do not fit into 64 bits.
+        let large = || QueryItem::Value(-Bn254Field::from(1));
+        assert!(ProverQuery::from_items(vec![s_bn254("input"), large()]).is_err());
+        assert!(ProverQuery::from_items(vec![s_bn254("print_char"), large()]).is_err());
+    }
+
+    #[test]
+    fn display() {
+        let q = query(vec![s("input"), s("x"), v(1)]).unwrap();
+        assert_eq!(q.to_string(), "(\"input\", \"x\", 1)");
+        let q = query(vec![s("in"), v(1), v(2)]).unwrap();
+        assert_eq!(q.to_string(), "(\"in\", 1, 2)");
+    }
+
+    #[test]
+    fn providers() {
+        let mut list = InputList::from(vec![GoldilocksField::from(5)]);
+        assert_eq!(list.answer(&ProverQuery::Input(0)), Some(5.into()));
+        assert_eq!(list.answer(&ProverQuery::Input(1)), None);
+        assert_eq!(list.answer(&ProverQuery::Data(0)), None);
+
+        let mut named = NamedInputs::from(BTreeMap::from([(
+            "x".to_string(),
+            vec![GoldilocksField::from(2)],
+        )]));
+        assert_eq!(
+            named.answer(&ProverQuery::NamedInput("x".to_string(), 0)),
+            Some(2.into())
+        );
+        assert_eq!(
+            named.answer(&ProverQuery::NamedInput("y".to_string(), 0)),
+            None
+        );
+
+        let mut bytes = ByteStream::from(vec![1, 255]);
+        assert_eq!(
+            ProverInput::<GoldilocksField>::answer(&mut bytes, &ProverQuery::Data(1)),
+            Some(255.into())
+        );
+    }
+}
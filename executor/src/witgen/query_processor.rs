@@ -1,6 +1,7 @@
 use ast::analyzed::{Expression, PolynomialReference};
 use number::FieldElement;
 
+use super::prover_input::{ProverInput, ProverQuery, QueryItem};
 use super::{rows::RowPair, Constraint, EvalValue, FixedData, IncompleteCause, Query};
 
 /// Computes value updates that result from a query.
@@ -11,7 +12,7 @@ pub struct QueryProcessor<'a, T: FieldElement, QueryCallback: Send + Sync> {
 
 impl<'a, T: FieldElement, QueryCallback> QueryProcessor<'a, T, QueryCallback>
 where
-    QueryCallback: FnMut(&str) -> Option<T> + Send + Sync,
+    QueryCallback: ProverInput<T> + Send + Sync,
 {
     pub fn new(fixed_data: &'a FixedData<'a, T>, query_callback: QueryCallback) -> Self {
         Self {
@@ -20,6 +21,10 @@ where
         }
     }
 
+    pub fn into_callback(self) -> QueryCallback {
+        self.query_callback
+    }
+
     pub fn process_queries_on_current_row(
         &mut self,
         rows: &RowPair<T>,
@@ -40,40 +45,56 @@ where
         query: &'a Query<'_, T>,
         rows: &RowPair<T>,
     ) -> EvalValue<&'a PolynomialReference, T> {
-        let query_str = match interpolate_query(query.expr, rows) {
-            Ok(query) => query,
+        let items = match interpolate_query(query.expr, rows) {
+            Ok(items) => items,
             Err(incomplete) => return EvalValue::incomplete(incomplete),
         };
-        if let Some(value) = (self.query_callback)(&query_str) {
+        let prover_query = match ProverQuery::from_items(items) {
+            Ok(prover_query) => prover_query,
+            Err(e) => {
+                return EvalValue::incomplete(IncompleteCause::InvalidQuery(
+                    e,
+                    query.poly.name.to_string(),
+                ))
+            }
+        };
+        let value = self.query_callback.answer(&prover_query).or_else(|| {
+            let ProverQuery::PrintChar(c) = prover_query else {
+                return None;
+            };
+            print!("{}", c as char);
+            Some(0.into())
+        });
+        if let Some(value) = value {
             EvalValue::complete(vec![(&query.poly, Constraint::Assignment(value))])
         } else {
             EvalValue::incomplete(IncompleteCause::NoQueryAnswer(
-                query_str,
+                prover_query.to_string(),
                 query.poly.name.to_string(),
             ))
         }
     }
 }
 
+/// Evaluates the query expression to the items of the query tuple.
 fn interpolate_query<'b, T: FieldElement>(
     query: &'b Expression<T>,
     rows: &RowPair<T>,
-) -> Result<String, IncompleteCause<&'b PolynomialReference>> {
+) -> Result<Vec<QueryItem<T>>, IncompleteCause<&'b PolynomialReference>> {
     // TODO combine that with the constant evaluator and the commit evaluator...
     match query {
         Expression::Tuple(items) => Ok(items
             .iter()
             .map(|i| interpolate_query(i, rows))
             .collect::<Result<Vec<_>, _>>()?
-            .join(", ")),
+            .into_iter()
+            .flatten()
+            .collect()),
         Expression::LocalVariableReference(i) => {
             assert!(*i == 0);
-            Ok(format!("{}", rows.current_row_index))
+            Ok(vec![QueryItem::Value(rows.current_row_index.into())])
         }
-        Expression::String(s) => Ok(format!(
-            "\"{}\"",
-            s.replace('\\', "\\\\").replace('"', "\\\"")
-        )),
+        Expression::String(s) => Ok(vec![QueryItem::String(s.clone())]),
         Expression::MatchExpression(scrutinee, arms) => {
             let v = rows
                 .evaluate(scrutinee)?
@@ -88,7 +109,7 @@ fn interpolate_query<'b, T: FieldElement>(
         _ => rows
             .evaluate(query)?
             .constant_value()
-            .map(|c| c.to_string())
+            .map(|c| vec![QueryItem::Value(c)])
             .ok_or(IncompleteCause::NonConstantQueryElement),
     }
 }
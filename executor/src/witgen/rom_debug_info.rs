@@ -0,0 +1,108 @@
+//! The program of the main VM, used to relate failures of witness generation
+//! to the statements and source locations of the program.
+
+use std::fmt::{self, Display, Formatter, Write};
+
+use number::FieldElement;
+
+/// The number of rows of the ROM shown before the row of a failure.
+const PRECEDING_ROWS: u64 = 5;
+
+/// The ROM of a VM: the statements executed at each pc, together with the
+/// source locations given by the debug directives of the program.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RomDebugInfo {
+    /// The absolute name of the pc column, e.g. `main.pc`.
+    pub pc: String,
+    /// The rows of the ROM, indexed by pc.
+    pub rows: Vec<RomRow>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomRow {
+    /// The statements of the row, including its labels.
+    pub statements: String,
+    /// The source location of the row, as `file:line:column`, if known.
+    pub location: Option<String>,
+}
+
+impl RomDebugInfo {
+    /// Describes the row of the ROM at `pc`, its source location and the rows preceding it.
+    pub fn describe<T: FieldElement>(&self, pc: T) -> String {
+        let Some(row) = u64::try_from(pc.to_arbitrary_integer())
+            .ok()
+            .filter(|pc| *pc < self.rows.len() as u64)
+        else {
+            return format!("The pc {pc} is outside of the ROM.");
+        };
+        let mut description = format!("At pc {row}: {}", self.rows[row as usize]);
+        for pc in row.saturating_sub(PRECEDING_ROWS)..=row {
+            let marker = if pc == row { "=>" } else { "  " };
+            write!(
+                description,
+                "\n  {marker} {pc:>6}: {}",
+                self.rows[pc as usize]
+            )
+            .unwrap();
+        }
+        description
+    }
+}
+
+impl Display for RomRow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.statements)?;
+        if let Some(location) = &self.location {
+            write!(f, " ({location})")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use number::GoldilocksField;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn rom() -> RomDebugInfo {
+        RomDebugInfo {
+            pc: "main.pc".to_string(),
+            rows: (0..8)
+                .map(|i| RomRow {
+                    statements: format!("instr_{i};"),
+                    location: (i > 2).then(|| format!("main.rs:{i}:1")),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn describe() {
+        assert_eq!(
+            rom().describe(GoldilocksField::from(6)),
+            r#"At pc 6: instr_6; (main.rs:6:1)
+          1: instr_1;
+          2: instr_2;
+          3: instr_3; (main.rs:3:1)
+          4: instr_4; (main.rs:4:1)
+          5: instr_5; (main.rs:5:1)
+  =>      6: instr_6; (main.rs:6:1)"#
+        );
+        assert_eq!(
+            rom().describe(GoldilocksField::from(1)),
+            r#"At pc 1: instr_1;
+          0: instr_0;
+  =>      1: instr_1;"#
+        );
+    }
+
+    #[test]
+    fn outside_of_rom() {
+        assert_eq!(
+            rom().describe(GoldilocksField::from(8)),
+            "The pc 8 is outside of the ROM."
+        );
+    }
+}
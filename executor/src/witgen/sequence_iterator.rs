@@ -39,9 +39,11 @@ impl DefaultSequenceIterator {
         DefaultSequenceIterator {
             block_size,
             identities_count,
+            // Without internal identities, only the outer query in the last row is processed.
             row_deltas: (-1..=max_row)
                 .chain((-1..max_row).rev())
                 .chain(0..=max_row)
+                .filter(|row_delta| identities_count > 0 || *row_delta == max_row)
                 .collect(),
             is_first: true,
             progress_in_current_round: false,
@@ -221,3 +223,46 @@ impl ProcessingSequenceCache {
         self.cache.entry(left.into()).or_insert(sequence);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn steps(block_size: usize, identities_count: usize) -> Vec<(i64, Option<usize>)> {
+        let mut iter = DefaultSequenceIterator::new(block_size, identities_count);
+        std::iter::from_fn(|| {
+            let step = iter.next()?;
+            iter.report_progress(false);
+            Some(step)
+        })
+        .map(|step| match step.identity {
+            IdentityInSequence::Internal(index) => (step.row_delta, Some(index)),
+            IdentityInSequence::OuterQuery => (step.row_delta, None),
+        })
+        .collect()
+    }
+
+    #[test]
+    fn no_internal_identities() {
+        assert_eq!(steps(3, 0), vec![(2, None), (2, None)]);
+    }
+
+    #[test]
+    fn internal_identities() {
+        let steps = steps(2, 1);
+        assert_eq!(
+            steps,
+            vec![
+                (-1, Some(0)),
+                (0, Some(0)),
+                (1, Some(0)),
+                (1, None),
+                (0, Some(0)),
+                (-1, Some(0)),
+                (0, Some(0)),
+                (1, Some(0)),
+                (1, None),
+            ]
+        );
+    }
+}
@@ -34,20 +34,6 @@ pub fn try_to_simple_poly_ref<T>(expr: &Expression<T>) -> Option<&PolynomialRefe
     }
 }
 
-pub fn is_simple_poly_of_name<T>(expr: &Expression<T>, poly_name: &str) -> bool {
-    if let Expression::PolynomialReference(PolynomialReference {
-        name,
-        index: None,
-        next: false,
-        ..
-    }) = expr
-    {
-        name == poly_name
-    } else {
-        false
-    }
-}
-
 pub fn substitute_constants<T: Copy>(
     identities: &[Identity<T>],
     constants: &HashMap<String, T>,
@@ -4,7 +4,7 @@ use halo2_proofs::{
         bn256::{Bn256, Fq, Fr, G1Affine},
         ff::Field,
     },
-    plonk::{self, Circuit, ConstraintSystem, Error, VerifyingKey},
+    plonk::{self, Circuit, ConstraintSystem, Error},
     poly::{commitment::ParamsProver, kzg::commitment::ParamsKZG},
 };
 use halo2_wrong_ecc::{
@@ -16,12 +16,7 @@ use halo2_wrong_ecc::{
     EccConfig,
 };
 use snark_verifier::{
-    loader::{
-        self,
-        evm::{self, deploy_and_call, encode_calldata, EvmLoader},
-        halo2::halo2_wrong_ecc,
-        native::NativeLoader,
-    },
+    loader::{self, halo2::halo2_wrong_ecc, native::NativeLoader},
     pcs::{
         kzg::{
             Gwc19, KzgAccumulator, KzgAs, KzgSuccinctVerifyingKey, LimbsEncoding,
@@ -29,17 +24,14 @@ use snark_verifier::{
         },
         AccumulationScheme, AccumulationSchemeProver,
     },
-    system::{
-        self,
-        halo2::{compile, transcript::evm::EvmTranscript, Config},
-    },
+    system,
     util::arithmetic::{fe_to_limbs, PrimeField},
     verifier::{self, plonk::PlonkProtocol, SnarkVerifier},
 };
 
 use itertools::Itertools;
 use rand::rngs::OsRng;
-use std::rc::Rc;
+use std::{collections::HashSet, rc::Rc};
 
 // Comment copied/adjusted from snark-verifier:
 // """
@@ -53,7 +45,7 @@ const BITS: usize = 68;
 
 type As = KzgAs<Bn256, Gwc19>;
 type PlonkSuccinctVerifier = verifier::plonk::PlonkSuccinctVerifier<As, LimbsEncoding<LIMBS, BITS>>;
-type PlonkVerifier = verifier::plonk::PlonkVerifier<As, LimbsEncoding<LIMBS, BITS>>;
+pub(crate) type PlonkVerifier = verifier::plonk::PlonkVerifier<As, LimbsEncoding<LIMBS, BITS>>;
 
 const T: usize = 5;
 const RATE: usize = 4;
@@ -63,6 +55,7 @@ const R_P: usize = 60;
 type Svk = KzgSuccinctVerifyingKey<G1Affine>;
 type BaseFieldEccChip = halo2_wrong_ecc::BaseFieldEccChip<G1Affine, LIMBS, BITS>;
 type Halo2Loader<'a> = loader::halo2::Halo2Loader<'a, G1Affine, BaseFieldEccChip>;
+type Scalar<'a> = loader::halo2::Scalar<'a, G1Affine, BaseFieldEccChip>;
 pub type PoseidonTranscript<L, S> =
     system::halo2::transcript::halo2::PoseidonTranscript<G1Affine, L, S, T, RATE, R_F, R_P>;
 
@@ -82,6 +75,12 @@ impl Snark {
         }
     }
 
+    /// Returns the snark without its proof and instances, which suffices to
+    /// generate the keys of an aggregation circuit.
+    pub fn without_witness(&self) -> Self {
+        Self::new_without_witness(self.protocol.clone())
+    }
+
     pub fn new_without_witness(protocol: PlonkProtocol<G1Affine>) -> Self {
         let instances = protocol
             .num_instance
@@ -135,12 +134,41 @@ impl SnarkWitness {
     }
 }
 
+/// Returns the instances of a snark without those of its accumulator, if it is an
+/// aggregation snark. The accumulator is accumulated into the new one, the other
+/// instances are exposed by the aggregation circuit.
+fn public_instances<T: Clone>(protocol: &PlonkProtocol<G1Affine>, instances: &[Vec<T>]) -> Vec<T> {
+    let accumulator_indices = protocol
+        .accumulator_indices
+        .iter()
+        .flatten()
+        .copied()
+        .collect::<HashSet<_>>();
+    instances
+        .iter()
+        .enumerate()
+        .flat_map(|(column, instances)| {
+            instances
+                .iter()
+                .enumerate()
+                .map(move |(row, instance)| ((column, row), instance))
+        })
+        .filter(|(index, _)| !accumulator_indices.contains(index))
+        .map(|(_, instance)| instance.clone())
+        .collect()
+}
+
+/// Aggregates the snarks and returns the accumulator together with the
+/// (loaded) instances of all snarks, except their accumulators.
 pub fn aggregate<'a>(
     svk: &Svk,
     loader: &Rc<Halo2Loader<'a>>,
     snarks: &[SnarkWitness],
     as_proof: Value<&'_ [u8]>,
-) -> KzgAccumulator<G1Affine, Rc<Halo2Loader<'a>>> {
+) -> (
+    KzgAccumulator<G1Affine, Rc<Halo2Loader<'a>>>,
+    Vec<Scalar<'a>>,
+) {
     let assign_instances = |instances: &[Vec<Value<Fr>>]| {
         instances
             .iter()
@@ -153,11 +181,13 @@ pub fn aggregate<'a>(
             .collect_vec()
     };
 
+    let mut all_instances = vec![];
     let accumulators = snarks
         .iter()
         .flat_map(|snark| {
             let protocol = snark.protocol.loaded(loader);
             let instances = assign_instances(&snark.instances);
+            all_instances.extend(public_instances(&snark.protocol, &instances));
             let mut transcript =
                 PoseidonTranscript::<Rc<Halo2Loader>, _>::new(loader, snark.proof());
             let proof =
@@ -173,7 +203,7 @@ pub fn aggregate<'a>(
         As::verify(&Default::default(), &accumulators, &proof).unwrap()
     };
 
-    accumulator
+    (accumulator, all_instances)
 }
 
 #[derive(Clone)]
@@ -251,10 +281,18 @@ impl AggregationCircuit {
             (accumulator, transcript.finalize())
         };
 
+        // The accumulator limbs are followed by the public instances of the aggregated snarks.
         let KzgAccumulator { lhs, rhs } = accumulator;
         let instances = [lhs.x, lhs.y, rhs.x, rhs.y]
             .map(fe_to_limbs::<_, _, LIMBS, BITS>)
-            .concat();
+            .concat()
+            .into_iter()
+            .chain(
+                snarks
+                    .iter()
+                    .flat_map(|snark| public_instances(&snark.protocol, &snark.instances)),
+            )
+            .collect();
 
         Self {
             svk,
@@ -283,8 +321,13 @@ impl AggregationCircuit {
         (0..4 * LIMBS).map(|idx| (0, idx)).collect()
     }
 
-    pub fn num_instance() -> Vec<usize> {
-        vec![4 * LIMBS]
+    pub fn num_instance(&self) -> Vec<usize> {
+        let snark_instances: usize = self
+            .snarks
+            .iter()
+            .map(|snark| public_instances(&snark.protocol, &snark.instances).len())
+            .sum();
+        vec![4 * LIMBS + snark_instances]
     }
 
     pub fn instances(&self) -> Vec<Vec<Fr>> {
@@ -332,14 +375,15 @@ impl Circuit<Fr> for AggregationCircuit {
 
         range_chip.load_table(&mut layouter)?;
 
-        let accumulator_limbs = layouter.assign_region(
+        let public_cells = layouter.assign_region(
             || "",
             |region| {
                 let ctx = RegionCtx::new(region, 0);
 
                 let ecc_chip = config.ecc_chip();
                 let loader = Halo2Loader::new(ecc_chip, ctx);
-                let accumulator = aggregate(&self.svk, &loader, &self.snarks, self.as_proof());
+                let (accumulator, instances) =
+                    aggregate(&self.svk, &loader, &self.snarks, self.as_proof());
 
                 let accumulator_limbs = [accumulator.lhs, accumulator.rhs]
                     .iter()
@@ -352,46 +396,20 @@ impl Circuit<Fr> for AggregationCircuit {
                     .into_iter()
                     .flatten();
 
-                Ok(accumulator_limbs)
+                Ok(accumulator_limbs
+                    .chain(
+                        instances
+                            .into_iter()
+                            .map(|instance| instance.into_assigned()),
+                    )
+                    .collect_vec())
             },
         )?;
 
-        for (row, limb) in accumulator_limbs.enumerate() {
-            main_gate.expose_public(layouter.namespace(|| ""), limb, row)?;
+        for (row, cell) in public_cells.into_iter().enumerate() {
+            main_gate.expose_public(layouter.namespace(|| ""), cell, row)?;
         }
 
         Ok(())
     }
 }
-
-pub fn gen_aggregation_evm_verifier(
-    params: &ParamsKZG<Bn256>,
-    vk: &VerifyingKey<G1Affine>,
-    num_instance: Vec<usize>,
-    accumulator_indices: Vec<(usize, usize)>,
-) -> Vec<u8> {
-    let protocol = compile(
-        params,
-        vk,
-        Config::kzg()
-            .with_num_instance(num_instance.clone())
-            .with_accumulator_indices(Some(accumulator_indices)),
-    );
-    let vk = (params.get_g()[0], params.g2(), params.s_g2()).into();
-
-    let loader = EvmLoader::new::<Fq, Fr>();
-    let protocol = protocol.loaded(&loader);
-    let mut transcript = EvmTranscript::<_, Rc<EvmLoader>, _, _>::new(&loader);
-
-    let instances = transcript.load_instances(num_instance);
-    let proof = PlonkVerifier::read_proof(&vk, &protocol, &instances, &mut transcript).unwrap();
-    PlonkVerifier::verify(&vk, &protocol, &instances, &proof).unwrap();
-
-    evm::compile_yul(&loader.yul_code())
-}
-
-pub fn evm_verify(deployment_code: Vec<u8>, instances: Vec<Vec<Fr>>, proof: &[u8]) {
-    let calldata = encode_calldata(&instances, proof);
-    let gas_cost = deploy_and_call(deployment_code, calldata).unwrap();
-    dbg!(gas_cost);
-}
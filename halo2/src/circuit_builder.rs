@@ -1,14 +1,22 @@
-use ast::parsed::BinaryOperator;
+use ast::parsed::{BinaryOperator, UnaryOperator};
 use num_bigint::BigUint;
-use polyexen::expr::{ColumnKind, ColumnQuery, Expr, PlonkVar};
+use polyexen::expr::{Column, ColumnKind, ColumnQuery, Expr, PlonkVar};
 use polyexen::plaf::backends::halo2::PlafH2Circuit;
 use polyexen::plaf::{
-    ColumnFixed, ColumnWitness, Columns, Info, Lookup, Plaf, Poly, Shuffle, Witness,
+    ColumnFixed, ColumnPublic, ColumnWitness, Columns, CopyC, Info, Lookup, Plaf, Poly, Shuffle,
+    Witness,
 };
 
-use ast::analyzed::{Analyzed, Expression, IdentityKind, SelectedExpressions};
-use num_traits::One;
-use number::{BigInt, FieldElement};
+use std::collections::{BTreeMap, BTreeSet};
+
+use ast::analyzed::util::expr_any;
+use ast::analyzed::{
+    Analyzed, Expression, FunctionValueDefinition, Identity, IdentityKind, PolynomialReference,
+    PolynomialType, SelectedExpressions,
+};
+use itertools::Itertools;
+use num_traits::{One, Zero};
+use number::{BigInt, DegreeType, FieldElement};
 
 use super::circuit_data::CircuitData;
 
@@ -16,7 +24,7 @@ pub(crate) fn analyzed_to_circuit<T: FieldElement>(
     analyzed: &Analyzed<T>,
     fixed: &[(&str, Vec<T>)],
     witness: &[(&str, Vec<T>)],
-) -> PlafH2Circuit {
+) -> Result<PlafH2Circuit, String> {
     // The structure of the table is as following
     //
     // | constant columns | __enable_cur | __enable_next |  witness columns | \
@@ -36,7 +44,20 @@ pub(crate) fn analyzed_to_circuit<T: FieldElement>(
 
     let query = |column, rotation| Expr::Var(PlonkVar::Query(ColumnQuery { column, rotation }));
 
-    let mut cd = CircuitData::from(fixed.to_owned(), witness, &analyzed.constants);
+    // Columns of namespaces with a smaller degree than the circuit are repeated to fill all
+    // rows. Only the first rows of such a namespace take part in lookups, permutations and
+    // copy constraints, which is enforced by a fixed column that is 1 in these rows.
+    // The repetitions are only needed to satisfy the polynomial identities.
+    let active_column_names = analyzed
+        .definitions
+        .values()
+        .map(|(poly, _)| poly.degree)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|degree| (degree, format!("__active_{degree}")))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut cd = CircuitData::from(fixed.to_owned(), witness, &analyzed.constants)?;
 
     // append two fixed columns:
     // - one that enables constraints that do not have rotations (__enable_cur) in the actual circuit
@@ -57,9 +78,33 @@ pub(crate) fn analyzed_to_circuit<T: FieldElement>(
         0,
     );
 
+    let q_active = active_column_names
+        .iter()
+        .filter(|(degree, _)| (**degree as usize) < num_rows)
+        .map(|(degree, name)| {
+            let rows = *degree as usize;
+            let values = (0..num_rows).map(|row| T::from(if row < rows { 1 } else { 0 }));
+            (*degree, query(cd.insert_constant(name, values), 0))
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    // append a witness column for each public referenced in an identity, which holds the
+    // value of the public in all rows and is connected to the public column below.
+
+    let referenced_publics = referenced_publics(analyzed);
+    let public_columns = analyzed
+        .public_values(witness)?
+        .into_iter()
+        .zip(analyzed.public_declarations_in_source_order())
+        .enumerate()
+        .filter(|(_, (_, decl))| referenced_publics.contains(decl.name.as_str()))
+        .map(|(i, ((_, value), decl))| (i, cd.insert_public(&decl.name, value)))
+        .collect::<Vec<_>>();
+
     let mut lookups = vec![];
     let mut shuffles = vec![];
     let mut polys = vec![];
+    let mut copys = vec![];
 
     // build Plaf columns -------------------------------------------------
 
@@ -74,7 +119,11 @@ pub(crate) fn analyzed_to_circuit<T: FieldElement>(
             .iter()
             .map(|(name, _)| ColumnWitness::new(name.to_string(), 0))
             .collect(),
-        public: vec![],
+        public: if analyzed.public_declarations.is_empty() {
+            vec![]
+        } else {
+            vec![ColumnPublic::new("publics".to_string())]
+        },
     };
 
     // build Plaf info. -------------------------------------------------------------------------
@@ -87,29 +136,38 @@ pub(crate) fn analyzed_to_circuit<T: FieldElement>(
 
     // build Plaf polys. -------------------------------------------------------------------------
 
-    let apply_selectors_to_set = |set: &SelectedExpressions<T>| {
+    let apply_selectors_to_set = |id: &Identity<T>, set: &SelectedExpressions<T>| {
         let selector = set
             .selector
             .clone()
-            .map_or(Expr::Const(BigUint::one()), |expr| {
-                expression_2_expr(&cd, &expr)
-            });
+            .map_or(Ok(Expr::Const(BigUint::one())), |expr| {
+                expression_2_expr(analyzed, &cd, &expr, 0)
+            })?;
 
-        let contains_next_ref = set.expressions.iter().any(|exp| exp.contains_next_ref());
+        let contains_next_ref = set
+            .selector
+            .iter()
+            .chain(set.expressions.iter())
+            .any(|exp| contains_next_ref(analyzed, exp));
 
-        let selector = Expr::Mul(vec![
+        let mut selectors = vec![
             selector,
             if contains_next_ref {
                 q_enable_next.clone()
             } else {
                 q_enable_cur.clone()
             },
-        ]);
+        ];
+        let degree = namespace_degree(analyzed, id, set.selector.iter().chain(&set.expressions))?;
+        if let Some(active) = degree.and_then(|degree| q_active.get(&degree)) {
+            selectors.push(active.clone());
+        }
+        let selector = Expr::Mul(selectors);
 
         set.expressions
             .iter()
-            .map(|expr| selector.clone() * expression_2_expr(&cd, expr))
-            .collect()
+            .map(|expr| Ok(selector.clone() * expression_2_expr(analyzed, &cd, expr, 0)?))
+            .collect::<Result<Vec<_>, String>>()
     };
 
     for id in &analyzed.identities {
@@ -122,9 +180,9 @@ pub(crate) fn analyzed_to_circuit<T: FieldElement>(
                 assert_eq!(id.left.expressions.len(), 0);
 
                 let exp = id.expression_for_poly_id();
-                let contains_next_ref = exp.contains_next_ref();
+                let contains_next_ref = contains_next_ref(analyzed, exp);
 
-                let exp = expression_2_expr(&cd, exp);
+                let exp = expression_2_expr(analyzed, &cd, exp, 0)?;
 
                 // depending whether this polynomial contains a rotation,
                 // enable for all rows or all except the last one.
@@ -143,8 +201,8 @@ pub(crate) fn analyzed_to_circuit<T: FieldElement>(
                 });
             }
             IdentityKind::Plookup => {
-                let left = apply_selectors_to_set(&id.left);
-                let right = apply_selectors_to_set(&id.right);
+                let left = apply_selectors_to_set(id, &id.left)?;
+                let right = apply_selectors_to_set(id, &id.right)?;
 
                 lookups.push(Lookup {
                     name: "".to_string(),
@@ -152,17 +210,36 @@ pub(crate) fn analyzed_to_circuit<T: FieldElement>(
                 });
             }
             IdentityKind::Permutation => {
-                let left = apply_selectors_to_set(&id.left);
-                let right = apply_selectors_to_set(&id.right);
+                let left = apply_selectors_to_set(id, &id.left)?;
+                let right = apply_selectors_to_set(id, &id.right)?;
 
                 shuffles.push(Shuffle {
                     name: "".to_string(),
                     exps: (left, right),
                 });
             }
-            _ => unimplemented!(),
+            IdentityKind::Connect => {
+                // The copy constraints only connect cells in the first rows of the namespace.
+                namespace_degree(
+                    analyzed,
+                    id,
+                    id.left.expressions.iter().chain(&id.right.expressions),
+                )?;
+                copys.extend(connect_to_copy_constraints(&cd, fixed, id)?);
+            }
         }
     }
+    for (_, column) in &public_columns {
+        // the value of a public is the same in all rows.
+        let exp = Expr::Sum(vec![
+            query(*column, 1),
+            Expr::Neg(Box::new(query(*column, 0))),
+        ]);
+        polys.push(Poly {
+            name: "".to_string(),
+            exp: Expr::Mul(vec![exp, q_enable_next.clone()]),
+        });
+    }
     if lookups.is_empty() {
         // TODO something inside halo2 breaks (only in debug mode) if lookups is empty,
         // so just add an empty lookup.
@@ -210,7 +287,39 @@ pub(crate) fn analyzed_to_circuit<T: FieldElement>(
         witness,
     };
 
-    let copys = vec![];
+    // build Plaf copy constraints. -------------------------------------------------------------
+    //
+    // The i-th public is stored in the i-th row of the single instance column
+    // and is constrained to be equal to the witness cell it refers to.
+    // The columns holding the publics referenced in identities are connected to the same row.
+    // The copy constraints of `connect` identities have already been added above.
+
+    copys.extend(
+        analyzed
+            .public_declarations_in_source_order()
+            .into_iter()
+            .enumerate()
+            .map(|(i, decl)| CopyC {
+                columns: (
+                    cd.col(&decl.polynomial.name),
+                    Column {
+                        kind: ColumnKind::Public,
+                        index: 0,
+                    },
+                ),
+                offsets: vec![(decl.index as usize, i)],
+            }),
+    );
+    copys.extend(public_columns.into_iter().map(|(i, column)| CopyC {
+        columns: (
+            column,
+            Column {
+                kind: ColumnKind::Public,
+                index: 0,
+            },
+        ),
+        offsets: vec![(0, i)],
+    }));
 
     // build plaf. -------------------------------------------------------------------------
 
@@ -227,36 +336,316 @@ pub(crate) fn analyzed_to_circuit<T: FieldElement>(
 
     // return circuit description + witness. -------------
 
-    PlafH2Circuit { plaf, wit }
+    Ok(PlafH2Circuit { plaf, wit })
+}
+
+/// Returns the degree of the namespace of the columns referenced in the expressions,
+/// which are part of the identity `id`, or `None` if they do not reference any column.
+/// Fails if the expressions reference namespaces of different degrees.
+fn namespace_degree<'a, T: FieldElement + 'a>(
+    analyzed: &Analyzed<T>,
+    id: &Identity<T>,
+    exprs: impl IntoIterator<Item = &'a Expression<T>>,
+) -> Result<Option<DegreeType>, String> {
+    let mut degrees = BTreeSet::new();
+    for expr in exprs {
+        expr_any(expr, |e| {
+            if let Expression::PolynomialReference(poly) = e {
+                if let Some((definition, _)) = analyzed.definitions.get(&poly.name) {
+                    degrees.insert(definition.degree);
+                }
+            }
+            false
+        });
+    }
+    match degrees.len() {
+        0 | 1 => Ok(degrees.into_iter().next()),
+        _ => Err(format!(
+            "Identity `{id}` combines columns of namespaces with different degrees ({}), which is not supported.",
+            degrees.iter().join(", ")
+        )),
+    }
+}
+
+/// Translates a `connect` identity into copy constraints.
+///
+/// The right side consists of fixed columns encoding a permutation of the cells on the left side:
+/// The value `j * N + s` in row `r` of the `i`-th fixed column connects the cell in row `r`
+/// of the `i`-th left column to the cell in row `s` of the `j`-th left column.
+fn connect_to_copy_constraints<T: FieldElement>(
+    cd: &CircuitData<T>,
+    fixed: &[(&str, Vec<T>)],
+    identity: &Identity<T>,
+) -> Result<Vec<CopyC>, String> {
+    if identity.left.selector.is_some() || identity.right.selector.is_some() {
+        return Err(format!(
+            "Selectors are not supported in connect identities: {identity}"
+        ));
+    }
+    let column_name = |expr: &Expression<T>| match expr {
+        Expression::PolynomialReference(polyref) if polyref.index.is_none() && !polyref.next => {
+            Ok(polyref.name.clone())
+        }
+        _ => Err(format!(
+            "Only plain column references are supported in connect identities: {identity}"
+        )),
+    };
+    let left = identity
+        .left
+        .expressions
+        .iter()
+        .map(column_name)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Group the connected cells by the pair of columns they belong to.
+    let mut offsets: BTreeMap<(usize, usize), Vec<(usize, usize)>> = Default::default();
+    for (i, expr) in identity.right.expressions.iter().enumerate() {
+        let name = column_name(expr)?;
+        let (_, values) = fixed
+            .iter()
+            .find(|(n, _)| *n == name)
+            .ok_or_else(|| format!("Fixed column {name} not found."))?;
+        let degree = values.len();
+        for (row, value) in values.iter().enumerate() {
+            let (j, target_row) = usize::try_from(value.to_arbitrary_integer())
+                .ok()
+                .map(|target| (target / degree, target % degree))
+                .filter(|(j, _)| *j < left.len())
+                .ok_or_else(|| format!("Invalid value {value} in row {row} of {name}."))?;
+            if (i, row) != (j, target_row) {
+                offsets.entry((i, j)).or_default().push((row, target_row));
+            }
+        }
+    }
+
+    Ok(offsets
+        .into_iter()
+        .map(|((i, j), offsets)| CopyC {
+            columns: (cd.col(&left[i]), cd.col(&left[j])),
+            offsets,
+        })
+        .collect())
+}
+
+/// Returns the names of the publics referenced in identities or in the definitions of
+/// intermediate columns.
+fn referenced_publics<T>(analyzed: &Analyzed<T>) -> BTreeSet<&str> {
+    let identity_expressions = analyzed
+        .identities
+        .iter()
+        .flat_map(|id| [&id.left, &id.right])
+        .flat_map(|side| side.selector.iter().chain(side.expressions.iter()));
+    let intermediate_definitions =
+        analyzed
+            .definitions
+            .values()
+            .filter_map(|(poly, definition)| match definition {
+                Some(FunctionValueDefinition::Mapping(e))
+                    if poly.poly_type == PolynomialType::Intermediate =>
+                {
+                    Some(e)
+                }
+                _ => None,
+            });
+    let mut publics = BTreeSet::new();
+    for expr in identity_expressions.chain(intermediate_definitions) {
+        expr_any(expr, |e| {
+            if let Expression::PublicReference(name) = e {
+                publics.insert(name.as_str());
+            }
+            false
+        });
+    }
+    publics
+}
+
+/// Returns the definition of the referenced column if it is an intermediate column.
+fn intermediate_definition<'a, T>(
+    analyzed: &'a Analyzed<T>,
+    polyref: &PolynomialReference,
+) -> Option<&'a Expression<T>> {
+    if polyref.poly_id().ptype != PolynomialType::Intermediate {
+        return None;
+    }
+    match &analyzed.definitions[&polyref.name].1 {
+        Some(FunctionValueDefinition::Mapping(definition)) if polyref.index.is_none() => {
+            Some(definition)
+        }
+        _ => panic!("Intermediate column {polyref} has no definition that can be inlined."),
+    }
+}
+
+/// Returns true if the expression, including the definitions of the intermediate
+/// columns it references, contains a reference to the next row.
+fn contains_next_ref<T>(analyzed: &Analyzed<T>, expr: &Expression<T>) -> bool {
+    expr_any(expr, |e| match e {
+        Expression::PolynomialReference(polyref) => {
+            polyref.next
+                || intermediate_definition(analyzed, polyref)
+                    .map_or(false, |definition| contains_next_ref(analyzed, definition))
+        }
+        _ => false,
+    })
 }
 
-fn expression_2_expr<T: FieldElement>(cd: &CircuitData<T>, expr: &Expression<T>) -> Expr<PlonkVar> {
-    match expr {
+/// Evaluates the expression if it does not reference any column or public.
+fn evaluate_constant<T: FieldElement>(
+    cd: &CircuitData<T>,
+    expr: &Expression<T>,
+) -> Result<Option<T>, String> {
+    Ok(match expr {
+        Expression::Number(n) => Some(*n),
+        Expression::Constant(name) => Some(constant(cd, name)?),
+        Expression::BinaryOperation(left, op, right) => {
+            let (Some(left), Some(right)) =
+                (evaluate_constant(cd, left)?, evaluate_constant(cd, right)?)
+            else {
+                return Ok(None);
+            };
+            Some(match op {
+                BinaryOperator::Add => left + right,
+                BinaryOperator::Sub => left - right,
+                BinaryOperator::Mul => left * right,
+                BinaryOperator::Div if right.is_zero() => {
+                    return Err(format!("Division by zero in {expr}"))
+                }
+                BinaryOperator::Div => left.integer_div(right),
+                BinaryOperator::Pow => left.pow(right.to_integer()),
+                BinaryOperator::Mod if right.is_zero() => {
+                    return Err(format!("Division by zero in {expr}"))
+                }
+                BinaryOperator::Mod => left.integer_mod(right),
+                BinaryOperator::BinaryAnd => (left.to_integer() & right.to_integer()).into(),
+                BinaryOperator::BinaryXor => (left.to_integer() ^ right.to_integer()).into(),
+                BinaryOperator::BinaryOr => (left.to_integer() | right.to_integer()).into(),
+                BinaryOperator::ShiftLeft => {
+                    (left.to_integer() << shift_amount(right, expr)?).into()
+                }
+                BinaryOperator::ShiftRight => {
+                    (left.to_integer() >> shift_amount(right, expr)?).into()
+                }
+            })
+        }
+        Expression::UnaryOperation(op, inner) => {
+            evaluate_constant(cd, inner)?.map(|value| match op {
+                UnaryOperator::Plus => value,
+                UnaryOperator::Minus => -value,
+            })
+        }
+        _ => None,
+    })
+}
+
+fn shift_amount<T: FieldElement>(amount: T, expr: &Expression<T>) -> Result<u64, String> {
+    u64::try_from(amount.to_arbitrary_integer())
+        .map_err(|_| format!("Invalid shift amount {amount} in {expr}"))
+}
+
+fn constant<T: FieldElement>(cd: &CircuitData<T>, name: &str) -> Result<T, String> {
+    cd.constants
+        .get(name)
+        .copied()
+        .ok_or_else(|| format!("Constant {name} not found."))
+}
+
+/// The largest exponent of a power of a non-constant expression. The power is
+/// expanded into a product, so larger exponents only lead to identities of
+/// a degree no circuit supports.
+const MAX_EXPONENT: u64 = 64;
+
+/// Returns `base` to the power of `exponent`, as a balanced product tree.
+fn power(base: Expr<PlonkVar>, exponent: u64) -> Expr<PlonkVar> {
+    match exponent {
+        0 => Expr::Const(BigUint::one()),
+        1 => base,
+        _ => Expr::Mul(vec![
+            power(base.clone(), exponent / 2),
+            power(base, exponent - exponent / 2),
+        ]),
+    }
+}
+
+/// Translates a PIL expression into a polyexen expression, querying the columns
+/// `rotation` rows after the current row. Intermediate columns are inlined.
+fn expression_2_expr<T: FieldElement>(
+    analyzed: &Analyzed<T>,
+    cd: &CircuitData<T>,
+    expr: &Expression<T>,
+    rotation: i32,
+) -> Result<Expr<PlonkVar>, String> {
+    Ok(match expr {
         Expression::Number(n) => Expr::Const(n.to_arbitrary_integer()),
+        Expression::Constant(name) => Expr::Const(constant(cd, name)?.to_arbitrary_integer()),
         Expression::PolynomialReference(polyref) => {
-            assert_eq!(polyref.index, None);
+            let rotation = rotation + polyref.next as i32;
+            if let Some(definition) = intermediate_definition(analyzed, polyref) {
+                return expression_2_expr(analyzed, cd, definition, rotation);
+            }
+            // The constraints are only disabled in the last row, so they can only
+            // refer to the current and the next row.
+            if rotation > 1 {
+                return Err(format!(
+                    "{polyref} refers to a row more than one row ahead (through an intermediate column), which is not supported."
+                ));
+            }
+            let column = match polyref.index {
+                Some(index) => cd.col(&format!("{}[{index}]", polyref.name)),
+                None => cd.col(&polyref.name),
+            };
 
-            let plonkvar = PlonkVar::Query(ColumnQuery {
-                column: cd.col(&polyref.name),
-                rotation: polyref.next as i32,
-            });
+            let plonkvar = PlonkVar::Query(ColumnQuery { column, rotation });
 
             Expr::Var(plonkvar)
         }
-        Expression::BinaryOperation(lhe, op, rhe) => {
-            let lhe = expression_2_expr(cd, lhe);
-            let rhe = expression_2_expr(cd, rhe);
+        // The value of a public is the same in every row of its column.
+        Expression::PublicReference(name) => Expr::Var(PlonkVar::Query(ColumnQuery {
+            column: cd.public(name),
+            rotation: 0,
+        })),
+        Expression::BinaryOperation(lhe, op, rhe) => match op {
+            BinaryOperator::Add | BinaryOperator::Sub | BinaryOperator::Mul => {
+                let lhe = expression_2_expr(analyzed, cd, lhe, rotation)?;
+                let rhe = expression_2_expr(analyzed, cd, rhe, rotation)?;
+                match op {
+                    BinaryOperator::Add => Expr::Sum(vec![lhe, rhe]),
+                    BinaryOperator::Sub => Expr::Sum(vec![lhe, Expr::Neg(Box::new(rhe))]),
+                    _ => Expr::Mul(vec![lhe, rhe]),
+                }
+            }
+            BinaryOperator::Pow => {
+                if let Some(value) = evaluate_constant(cd, expr)? {
+                    return Ok(Expr::Const(value.to_arbitrary_integer()));
+                }
+                let exponent = evaluate_constant(cd, rhe)?.ok_or_else(|| {
+                    format!("The exponent in {expr} is not constant, which cannot be expressed as a polynomial.")
+                })?;
+                let exponent = u64::try_from(exponent.to_arbitrary_integer())
+                    .ok()
+                    .filter(|exponent| *exponent <= MAX_EXPONENT)
+                    .ok_or_else(|| {
+                        format!("The exponent in {expr} is larger than {MAX_EXPONENT}, which is not supported.")
+                    })?;
+                power(expression_2_expr(analyzed, cd, lhe, rotation)?, exponent)
+            }
+            _ => evaluate_constant(cd, expr)?
+                .map(|value| Expr::Const(value.to_arbitrary_integer()))
+                .ok_or_else(|| {
+                    format!("The operator {op} is only supported on constant values, which is not the case in {expr}.")
+                })?,
+        },
+        Expression::UnaryOperation(op, inner) => {
+            let inner = expression_2_expr(analyzed, cd, inner, rotation)?;
             match op {
-                BinaryOperator::Add => Expr::Sum(vec![lhe, rhe]),
-                BinaryOperator::Sub => Expr::Sum(vec![lhe, Expr::Neg(Box::new(rhe))]),
-                BinaryOperator::Mul => Expr::Mul(vec![lhe, rhe]),
-                _ => unimplemented!("{:?}", expr),
+                UnaryOperator::Plus => inner,
+                UnaryOperator::Minus => Expr::Neg(Box::new(inner)),
             }
         }
-        Expression::Constant(constant_name) => {
-            Expr::Const(cd.constants[constant_name].to_arbitrary_integer())
+        Expression::LocalVariableReference(_)
+        | Expression::String(_)
+        | Expression::Tuple(_)
+        | Expression::FunctionCall(_, _)
+        | Expression::MatchExpression(_, _) => {
+            return Err(format!("The expression {expr} cannot be used in an identity."))
         }
-
-        _ => unimplemented!("{:?}", expr),
-    }
+    })
 }
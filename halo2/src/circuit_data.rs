@@ -8,8 +8,10 @@ use polyexen::expr::{Column, ColumnKind};
 
 pub(crate) struct CircuitData<'a, T> {
     pub(crate) fixed: Vec<(&'a str, Vec<T>)>,
-    pub(crate) witness: &'a [(&'a str, Vec<T>)],
+    pub(crate) witness: Vec<(&'a str, Vec<T>)>,
     columns: HashMap<String, Column>,
+    /// The witness columns holding the value of a public in every row, by the name of the public.
+    publics: HashMap<String, Column>,
     pub(crate) constants: &'a HashMap<String, T>,
 }
 
@@ -18,13 +20,16 @@ impl<'a, T: FieldElement> CircuitData<'a, T> {
         fixed: Vec<(&'a str, Vec<T>)>,
         witness: &'a [(&'a str, Vec<T>)],
         constants: &'a HashMap<String, T>,
-    ) -> Self {
-        if !fixed.is_empty() && !witness.is_empty() {
-            assert_eq!(
-                fixed.get(0).unwrap().1.len(),
-                witness.get(0).unwrap().1.len()
-            );
-        }
+    ) -> Result<Self, String> {
+        // Columns of namespaces with a smaller degree are repeated to fill all rows.
+        let len = fixed
+            .iter()
+            .chain(witness)
+            .map(|(_, values)| values.len())
+            .max()
+            .unwrap_or_default();
+        let fixed = repeat_to_len(fixed, len)?;
+        let witness = repeat_to_len(witness.to_vec(), len)?;
 
         let const_cols = fixed.iter().enumerate().map(|(index, (name, _))| {
             (
@@ -48,12 +53,13 @@ impl<'a, T: FieldElement> CircuitData<'a, T> {
 
         let columns = const_cols.chain(witness_cols).collect();
 
-        Self {
+        Ok(Self {
             fixed,
             witness,
             columns,
+            publics: Default::default(),
             constants,
-        }
+        })
     }
 
     pub fn col(&self, name: &str) -> Column {
@@ -63,6 +69,13 @@ impl<'a, T: FieldElement> CircuitData<'a, T> {
             .unwrap_or_else(|| panic!("{name} column not found"))
     }
 
+    pub fn public(&self, name: &str) -> Column {
+        *self
+            .publics
+            .get(name)
+            .unwrap_or_else(|| panic!("column of public {name} not found"))
+    }
+
     pub fn len(&self) -> usize {
         self.fixed.get(0).unwrap().1.len()
     }
@@ -82,4 +95,38 @@ impl<'a, T: FieldElement> CircuitData<'a, T> {
         self.columns.insert(name.to_string(), column);
         column
     }
+
+    /// Inserts a witness column holding `value` in every row, used to refer to the public `name`.
+    pub fn insert_public(&mut self, name: &'a str, value: T) -> Column {
+        self.witness
+            .push((name, itertools::repeat_n(value, self.len()).collect()));
+        let column = Column {
+            kind: ColumnKind::Witness,
+            index: self.witness.len() - 1,
+        };
+        self.publics.insert(name.to_string(), column);
+        column
+    }
+}
+
+/// Repeats the values of each column until it has `len` rows.
+fn repeat_to_len<T: Clone>(
+    columns: Vec<(&str, Vec<T>)>,
+    len: usize,
+) -> Result<Vec<(&str, Vec<T>)>, String> {
+    columns
+        .into_iter()
+        .map(|(name, values)| {
+            if values.len() == len {
+                return Ok((name, values));
+            }
+            if values.is_empty() || len % values.len() != 0 {
+                return Err(format!(
+                    "The degree of column {name} ({}) does not divide the maximum degree ({len}).",
+                    values.len()
+                ));
+            }
+            Ok((name, values.iter().cycle().take(len).cloned().collect()))
+        })
+        .collect()
 }
@@ -0,0 +1,120 @@
+//! Verification of proofs in the EVM: generation of the verifier contract,
+//! encoding of its calldata and execution of the contract in an in-process EVM.
+
+use std::process::{Command, Stdio};
+
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fq, Fr, G1Affine},
+    plonk::VerifyingKey,
+    poly::{commitment::ParamsProver, kzg::commitment::ParamsKZG},
+};
+use snark_verifier::{
+    loader::evm::{self, deploy_and_call, EvmLoader},
+    system::halo2::{compile, transcript::evm::EvmTranscript, Config},
+    verifier::SnarkVerifier,
+};
+use std::rc::Rc;
+
+use crate::aggregation::PlonkVerifier;
+
+/// Returns the Yul source of a contract verifying proofs of the circuit with the
+/// verifying key `vk`, generated with the EVM transcript. If `accumulator_indices`
+/// is given, the contract also checks the accumulator of an aggregation circuit,
+/// which is contained in its instances at these indices.
+pub fn gen_evm_verifier(
+    params: &ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    num_instance: Vec<usize>,
+    accumulator_indices: Option<Vec<(usize, usize)>>,
+) -> String {
+    let protocol = compile(
+        params,
+        vk,
+        Config::kzg()
+            .with_num_instance(num_instance.clone())
+            .with_accumulator_indices(accumulator_indices),
+    );
+    let vk = (params.get_g()[0], params.g2(), params.s_g2()).into();
+
+    let loader = EvmLoader::new::<Fq, Fr>();
+    let protocol = protocol.loaded(&loader);
+    let mut transcript = EvmTranscript::<_, Rc<EvmLoader>, _, _>::new(&loader);
+
+    let instances = transcript.load_instances(num_instance);
+    let proof = PlonkVerifier::read_proof(&vk, &protocol, &instances, &mut transcript).unwrap();
+    PlonkVerifier::verify(&vk, &protocol, &instances, &proof).unwrap();
+
+    loader.yul_code()
+}
+
+/// Compiles the Yul source of a verifier contract to its deployment code.
+/// Returns None if the Solidity compiler `solc` is not installed.
+pub fn compile_yul(yul: &str) -> Option<Vec<u8>> {
+    let solc_installed = Command::new("solc")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false);
+    solc_installed.then(|| evm::compile_yul(yul))
+}
+
+/// Encodes the calldata of a verifier contract: the instances, each as a 32 byte
+/// big-endian number, followed by the proof.
+pub fn encode_calldata(instances: &[Vec<Fr>], proof: &[u8]) -> Vec<u8> {
+    evm::encode_calldata(instances, proof)
+}
+
+/// Deploys the verifier contract in an in-process EVM and calls it with `calldata`.
+/// @returns the gas used by the call if the proof is accepted.
+pub fn evm_verify(deployment_code: Vec<u8>, calldata: Vec<u8>) -> Result<u64, String> {
+    deploy_and_call(deployment_code, calldata)
+}
+
+#[cfg(test)]
+mod test {
+    use ast::analyzed::Analyzed;
+    use number::Bn254Field;
+    use test_log::test;
+
+    use crate::Halo2Prover;
+
+    use super::*;
+
+    #[test]
+    #[ignore = "Requires solc"]
+    fn verify_in_evm() {
+        let content = r#"
+            namespace main(8);
+                pol constant FIRST = [1, 0, 0, 0, 0, 0, 0, 0];
+                pol constant LAST = [0, 0, 0, 0, 0, 0, 0, 1];
+                pol commit x;
+                public out = x(7);
+                FIRST * (x - 1) = 0;
+                (1 - LAST) * (x' - x - 1) = 0;
+        "#;
+        let analyzed: Analyzed<Bn254Field> = pil_analyzer::analyze_string(content).unwrap();
+        let column = |values: [i64; 8]| values.map(Bn254Field::from).to_vec();
+        let fixed = [
+            ("main.FIRST", column([1, 0, 0, 0, 0, 0, 0, 0])),
+            ("main.LAST", column([0, 0, 0, 0, 0, 0, 0, 1])),
+        ];
+        let witness = [("main.x", column([1, 2, 3, 4, 5, 6, 7, 8]))];
+        let publics = [Bn254Field::from(8)];
+
+        let prover = Halo2Prover::new(8);
+        let proof = prover.prove_ast_evm(&analyzed, &fixed, &witness).unwrap();
+        prover
+            .verify_evm(&analyzed, &fixed, &proof, &publics)
+            .unwrap();
+
+        let verifier = prover.evm_verifier(&analyzed, &fixed).unwrap();
+        let deployment_code = compile_yul(&verifier).expect("solc is not installed");
+        let calldata = Halo2Prover::evm_calldata(&proof, &publics);
+        assert!(evm_verify(deployment_code.clone(), calldata).unwrap() > 0);
+
+        let wrong_publics = [Bn254Field::from(9)];
+        let calldata = Halo2Prover::evm_calldata(&proof, &wrong_publics);
+        assert!(evm_verify(deployment_code, calldata).is_err());
+    }
+}
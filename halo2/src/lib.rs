@@ -1,8 +1,10 @@
 pub(crate) mod aggregation;
 pub(crate) mod circuit_builder;
 pub(crate) mod circuit_data;
+pub(crate) mod evm;
 pub(crate) mod mock_prover;
 pub(crate) mod prover;
 
+pub use evm::{compile_yul, evm_verify};
 pub use mock_prover::mock_prove;
 pub use prover::*;
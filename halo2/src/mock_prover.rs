@@ -2,6 +2,7 @@ use ast::analyzed::Analyzed;
 use polyexen::plaf::PlafDisplayBaseTOML;
 
 use super::circuit_builder::analyzed_to_circuit;
+use super::prover::publics_to_instances;
 use halo2_proofs::{dev::MockProver, halo2curves::bn256::Fr};
 use number::{BigInt, FieldElement};
 
@@ -9,12 +10,12 @@ pub fn mock_prove<T: FieldElement>(
     pil: &Analyzed<T>,
     fixed: &[(&str, Vec<T>)],
     witness: &[(&str, Vec<T>)],
-) {
+) -> Result<(), String> {
     if polyexen::expr::get_field_p::<Fr>() != T::modulus().to_arbitrary_integer() {
         panic!("powdr modulus doesn't match halo2 modulus. Make sure you are using Bn254");
     }
 
-    let circuit = analyzed_to_circuit(pil, fixed, witness);
+    let circuit = analyzed_to_circuit(pil, fixed, witness)?;
 
     // double the row count in order to make space for the cells introduced by the backend
     // TODO: use a precise count of the extra rows needed to avoid using so many rows
@@ -25,10 +26,16 @@ pub fn mock_prove<T: FieldElement>(
 
     log::debug!("{}", PlafDisplayBaseTOML(&circuit.plaf));
 
-    let inputs = vec![];
+    let publics = pil
+        .public_values(witness)?
+        .into_iter()
+        .map(|(_, value)| value)
+        .collect::<Vec<_>>();
+    let inputs = publics_to_instances(&publics);
 
     let mock_prover = MockProver::<Fr>::run(expanded_row_count_log, &circuit, inputs).unwrap();
     mock_prover.assert_satisfied();
+    Ok(())
 }
 
 #[cfg(test)]
@@ -36,6 +43,7 @@ mod test {
     use std::fs;
 
     use analysis::analyze;
+    use executor::witgen::ProverQuery;
     use number::Bn254Field;
     use parser::parse_asm;
     use test_log::test;
@@ -48,51 +56,112 @@ mod test {
         let contents = fs::read_to_string(file_name).unwrap();
         let parsed = parse_asm::<Bn254Field>(Some(file_name), &contents).unwrap();
         let analysed = analyze(parsed).unwrap();
-        let graph = airgen::compile(analysed);
+        let graph = airgen::compile(analysed).unwrap();
         let pil = linker::link(graph).unwrap();
 
-        let query_callback = |query: &str| -> Option<Bn254Field> {
-            let items = query.split(',').map(|s| s.trim()).collect::<Vec<_>>();
-            match items[0] {
-                "\"input\"" => {
-                    assert_eq!(items.len(), 2);
-                    let index = items[1].parse::<usize>().unwrap();
-                    let value = inputs.get(index).cloned();
-                    if let Some(value) = value {
-                        log::trace!("Input query: Index {index} -> {value}");
-                    }
-                    value
-                }
-                "\"print\"" => {
-                    log::info!("Print: {}", items[1..].join(", "));
-                    Some(0.into())
-                }
-                "\"print_ch\"" => {
-                    print!("{}", items[1].parse::<u8>().unwrap() as char);
-                    Some(0.into())
-                }
+        let query_callback = |query: &ProverQuery<Bn254Field>| -> Option<Bn254Field> {
+            match query {
+                ProverQuery::Input(index) => inputs.get(*index).cloned(),
                 _ => None,
             }
         };
 
-        let analyzed = pil_analyzer::analyze_string(&format!("{pil}"));
+        let analyzed = pil_analyzer::analyze_string(&format!("{pil}")).unwrap();
 
         let (fixed, degree) = executor::constant_evaluator::generate(&analyzed);
-        let witness = executor::witgen::generate(&analyzed, degree, &fixed, Some(query_callback));
+        let witness =
+            executor::witgen::generate(&analyzed, degree, &fixed, Some(query_callback)).unwrap();
 
-        mock_prove(&analyzed, &fixed, &witness);
+        mock_prove(&analyzed, &fixed, &witness).unwrap();
     }
 
     #[test]
     fn simple_pil_halo2() {
         let content = "namespace Global(8); pol fixed z = [0]*; pol witness a; a = 0;";
-        let analyzed: Analyzed<Bn254Field> = pil_analyzer::analyze_string(content);
+        let analyzed: Analyzed<Bn254Field> = pil_analyzer::analyze_string(content).unwrap();
         let (fixed, degree) = executor::constant_evaluator::generate(&analyzed);
 
-        let query_callback = |_: &str| -> Option<Bn254Field> { None };
+        let query_callback = |_: &ProverQuery<Bn254Field>| -> Option<Bn254Field> { None };
 
-        let witness = executor::witgen::generate(&analyzed, degree, &fixed, Some(query_callback));
-        mock_prove(&analyzed, &fixed, &witness);
+        let witness =
+            executor::witgen::generate(&analyzed, degree, &fixed, Some(query_callback)).unwrap();
+        mock_prove(&analyzed, &fixed, &witness).unwrap();
+    }
+
+    #[test]
+    fn full_expression_language() {
+        let content = r#"
+            namespace main(4);
+                pol constant FIRST = [1, 0, 0, 0];
+                pol constant LAST = [0, 0, 0, 1];
+                pol commit x;
+                pol commit y[2];
+                pol x_inc = x + 1;
+                public out = x(3);
+                FIRST * (x - 2 ** 2) = 0;
+                (1 - LAST) * (x' - x_inc) = 0;
+                LAST * (x - :out) = 0;
+                y[0] = x ** 2;
+                y[1] = -y[0] + 9 / 2;
+        "#;
+        let analyzed: Analyzed<Bn254Field> = pil_analyzer::analyze_string(content).unwrap();
+        let column = |values: [i64; 4]| values.map(Bn254Field::from).to_vec();
+        let fixed = [
+            ("main.FIRST", column([1, 0, 0, 0])),
+            ("main.LAST", column([0, 0, 0, 1])),
+        ];
+        let witness = [
+            ("main.x", column([4, 5, 6, 7])),
+            ("main.y[0]", column([16, 25, 36, 49])),
+            ("main.y[1]", column([-12, -21, -32, -45])),
+        ];
+        mock_prove(&analyzed, &fixed, &witness).unwrap();
+    }
+
+    #[test]
+    fn unsupported_exponents() {
+        let column = |values: [i64; 4]| values.map(Bn254Field::from).to_vec();
+        let fixed = [("main.FIRST", column([1, 0, 0, 0]))];
+        let witness = [("main.x", column([0, 0, 0, 0]))];
+        for identity in ["x ** 100 = 0", "x ** x = 0"] {
+            let content = format!(
+                "namespace main(4); pol constant FIRST = [1, 0, 0, 0]; pol commit x; {identity};"
+            );
+            let analyzed: Analyzed<Bn254Field> = pil_analyzer::analyze_string(&content).unwrap();
+            let error = mock_prove(&analyzed, &fixed, &witness).unwrap_err();
+            assert!(error.contains("exponent"), "{error}");
+        }
+    }
+
+    #[test]
+    fn unsupported_connect() {
+        let column = |values: [i64; 4]| values.map(Bn254Field::from).to_vec();
+        let witness = [("main.x", column([0, 0, 0, 0]))];
+        for (identity, permutation, message) in [
+            (
+                "{ x + 1 } connect { P }",
+                [0, 1, 2, 3],
+                "plain column references",
+            ),
+            (
+                "{ x } connect { P }",
+                [0, 1, 2, 4],
+                "Invalid value 4 in row 3",
+            ),
+            (
+                "{ x } connect { P }",
+                [0, 1, 2, -1],
+                "Invalid value -1 in row 3",
+            ),
+        ] {
+            let content = format!(
+                "namespace main(4); pol constant P = [0, 1, 2, 3]; pol commit x; {identity};"
+            );
+            let analyzed: Analyzed<Bn254Field> = pil_analyzer::analyze_string(&content).unwrap();
+            let fixed = [("main.P", column(permutation))];
+            let error = mock_prove(&analyzed, &fixed, &witness).unwrap_err();
+            assert!(error.contains(message), "{error}");
+        }
     }
 
     #[test]
@@ -2,7 +2,7 @@ use ast::analyzed::Analyzed;
 use halo2_proofs::{
     dev::MockProver,
     halo2curves::bn256::{Fr, G1Affine},
-    plonk::{create_proof, keygen_pk, keygen_vk, verify_proof, Circuit, ProvingKey},
+    plonk::{create_proof, keygen_pk, keygen_vk, verify_proof, Circuit, ProvingKey, VerifyingKey},
     poly::{
         commitment::{Params, ParamsProver},
         kzg::{
@@ -13,31 +13,64 @@ use halo2_proofs::{
         VerificationStrategy,
     },
     transcript::{EncodedChallenge, TranscriptReadBuffer, TranscriptWriterBuffer},
+    SerdeFormat,
 };
 use number::{BigInt, DegreeType, FieldElement};
-use polyexen::plaf::PlafDisplayBaseTOML;
+use polyexen::plaf::{backends::halo2::PlafH2Circuit, PlafDisplayBaseTOML};
+use sha2::{Digest, Sha256};
 use snark_verifier::{
     loader::native::NativeLoader,
     system::halo2::{compile, transcript::evm::EvmTranscript, Config},
+    verifier::plonk::PlonkProtocol,
 };
 
 use crate::aggregation;
 use crate::circuit_builder::analyzed_to_circuit;
+use crate::evm;
 
 use itertools::Itertools;
 use rand::rngs::OsRng;
 use std::{
+    cell::RefCell,
+    collections::HashMap,
     io::{self, Cursor},
+    rc::Rc,
     time::Instant,
 };
 
 pub use halo2_proofs::halo2curves::bn256::Bn256;
 pub use halo2_proofs::poly::kzg::commitment::ParamsKZG;
 
+/// The size of an instance in the calldata of a verifier contract.
+const CALLDATA_INSTANCE_BYTES: usize = 32;
+
+/// A proof generated by `Halo2Prover::prove_ast`, to be aggregated.
+pub struct AggregationInput<'a, F> {
+    pub pil: &'a Analyzed<F>,
+    pub fixed: &'a [(&'a str, Vec<F>)],
+    pub proof: Vec<u8>,
+    pub publics: Vec<F>,
+}
+
+/// The result of `Halo2Prover::aggregate`.
+pub struct AggregatedProof<F> {
+    /// The proof of the root of the aggregation tree, preceded by its accumulator
+    /// as in the calldata of the verifier contract.
+    pub proof: Vec<u8>,
+    /// The instances of the root proof: the accumulator, followed by the public
+    /// values of the aggregated proofs, in their order.
+    pub instances: Vec<F>,
+    /// The Yul source of a contract verifying the root proof.
+    pub verifier: String,
+}
+
 /// Create a halo2 proof for a given PIL, fixed column values and witness column values
 /// We use KZG ([GWC variant](https://eprint.iacr.org/2019/953)) and Keccak256
 pub struct Halo2Prover {
     params: ParamsKZG<Bn256>,
+    /// The loaded or generated proving keys, by the hash of the circuit and the setup
+    /// they were generated for.
+    keys: RefCell<HashMap<Vec<u8>, Rc<ProvingKey<G1Affine>>>>,
 }
 
 impl Halo2Prover {
@@ -45,13 +78,17 @@ impl Halo2Prover {
         let degree = DegreeType::BITS - size.leading_zeros() + 1;
         Self {
             params: ParamsKZG::<Bn256>::new(degree),
+            keys: Default::default(),
         }
     }
 
     pub fn new_from_setup(input: &mut impl io::Read) -> Result<Self, io::Error> {
         let params = ParamsKZG::<Bn256>::read(input)?;
 
-        Ok(Self { params })
+        Ok(Self {
+            params,
+            keys: Default::default(),
+        })
     }
 
     pub fn write_setup(&self, output: &mut impl io::Write) -> Result<(), io::Error> {
@@ -63,111 +100,516 @@ impl Halo2Prover {
         pil: &Analyzed<F>,
         fixed: &[(&str, Vec<F>)],
         witness: &[(&str, Vec<F>)],
-    ) -> Vec<u8> {
-        // TODO this is hacky
-        let degree = usize::BITS - fixed[0].1.len().leading_zeros() + 1;
-        let params = {
-            let mut params = self.params.clone();
-            params.downsize(degree);
-            params
-        };
+    ) -> Result<Vec<u8>, String> {
+        self.prove_with_transcript::<
+            _,
+            _,
+            aggregation::PoseidonTranscript<NativeLoader, _>,
+            aggregation::PoseidonTranscript<NativeLoader, _>,
+        >(pil, fixed, witness)
+    }
+
+    /// Like `prove_ast`, but uses the Keccak256 transcript of the EVM verifier, so that
+    /// the proof can be verified by the contract of `evm_verifier`. Such proofs cannot
+    /// be aggregated.
+    pub fn prove_ast_evm<F: FieldElement>(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(&str, Vec<F>)],
+        witness: &[(&str, Vec<F>)],
+    ) -> Result<Vec<u8>, String> {
+        self.prove_with_transcript::<
+            _,
+            _,
+            EvmTranscript<G1Affine, _, _, _>,
+            EvmTranscript<G1Affine, _, _, _>,
+        >(pil, fixed, witness)
+    }
+
+    fn prove_with_transcript<
+        F: FieldElement,
+        E: EncodedChallenge<G1Affine>,
+        TR: TranscriptReadBuffer<Cursor<Vec<u8>>, G1Affine, E>,
+        TW: TranscriptWriterBuffer<Vec<u8>, G1Affine, E>,
+    >(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(&str, Vec<F>)],
+        witness: &[(&str, Vec<F>)],
+    ) -> Result<Vec<u8>, String> {
+        let params = self.downsized_params(fixed);
 
         log::info!("Starting proof generation...");
 
-        let circuit = analyzed_to_circuit(pil, fixed, witness);
+        let circuit = analyzed_to_circuit(pil, fixed, witness)?;
 
         log::debug!("{}", PlafDisplayBaseTOML(&circuit.plaf));
 
-        log::info!("Generating VK and PK for snark...");
-        let vk = keygen_vk(&params, &circuit).unwrap();
-        let pk = keygen_pk(&params, vk, &circuit).unwrap();
+        let hash = circuit_hash(&params, pil, fixed);
+        let pk = self.proving_key(&params, &hash, &circuit)?;
 
         log::info!("Generating proof...");
         let start = Instant::now();
 
-        let inputs = vec![];
-        let proof = gen_proof::<
-            _,
-            _,
-            aggregation::PoseidonTranscript<NativeLoader, _>,
-            aggregation::PoseidonTranscript<NativeLoader, _>,
-        >(&params, &pk, circuit, inputs);
+        let publics = pil
+            .public_values(witness)?
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect::<Vec<_>>();
+        let proof =
+            gen_proof::<_, _, TR, TW>(&params, &pk, circuit, publics_to_instances(&publics));
 
         let duration = start.elapsed();
         log::info!("Time taken: {:?}", duration);
 
         log::info!("Proof generation done.");
 
-        proof
+        Ok(proof)
     }
 
-    pub fn prove_aggr<F: FieldElement>(
+    /// Verifies a proof generated by `prove_ast`. The verifying key is re-derived
+    /// from the PIL and the fixed columns, unless the keys for them are already loaded.
+    pub fn verify<F: FieldElement>(
         &self,
         pil: &Analyzed<F>,
         fixed: &[(&str, Vec<F>)],
-        witness: &[(&str, Vec<F>)],
-        proof: Vec<u8>,
-    ) -> Vec<u8> {
-        log::info!("Starting proof aggregation...");
+        proof: &[u8],
+        publics: &[F],
+    ) -> Result<(), String> {
+        self.verify_with_transcript::<_, _, aggregation::PoseidonTranscript<NativeLoader, _>>(
+            pil, fixed, proof, publics,
+        )
+    }
 
-        // TODO this is hacky
-        let degree = usize::BITS - fixed[0].1.len().leading_zeros() + 1;
-        let params_app = {
-            let mut params = self.params.clone();
-            params.downsize(degree);
-            params
-        };
+    /// Verifies a proof generated by `prove_ast_evm`.
+    pub fn verify_evm<F: FieldElement>(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(&str, Vec<F>)],
+        proof: &[u8],
+        publics: &[F],
+    ) -> Result<(), String> {
+        self.verify_with_transcript::<_, _, EvmTranscript<G1Affine, _, _, _>>(
+            pil, fixed, proof, publics,
+        )
+    }
 
-        log::info!("Generating circuit for app snark...");
-        let circuit_app = analyzed_to_circuit(pil, fixed, witness);
+    fn verify_with_transcript<
+        F: FieldElement,
+        E: EncodedChallenge<G1Affine>,
+        TR: TranscriptReadBuffer<Cursor<Vec<u8>>, G1Affine, E>,
+    >(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(&str, Vec<F>)],
+        proof: &[u8],
+        publics: &[F],
+    ) -> Result<(), String> {
+        let params = self.downsized_params(fixed);
+        let hash = circuit_hash(&params, pil, fixed);
+        let vk = self.verifying_key(&params, &hash, pil, fixed)?;
+
+        log::info!("Verifying proof...");
+        let start = Instant::now();
 
-        log::debug!("{}", PlafDisplayBaseTOML(&circuit_app.plaf));
+        let instances = publics_to_instances(publics);
+        let instances = instances.iter().map(|i| i.as_slice()).collect_vec();
+        let mut transcript = TR::init(Cursor::new(proof.to_vec()));
+        let accept = VerificationStrategy::<_, VerifierGWC<_>>::finalize(
+            verify_proof::<_, VerifierGWC<_>, _, TR, _>(
+                params.verifier_params(),
+                &vk,
+                AccumulatorStrategy::new(params.verifier_params()),
+                &[instances.as_slice()],
+                &mut transcript,
+            )
+            .map_err(|e| e.to_string())?,
+        );
+
+        let duration = start.elapsed();
+        log::info!("Time taken: {:?}", duration);
+
+        if accept {
+            log::info!("Proof is valid.");
+            Ok(())
+        } else {
+            Err("the accumulator check failed".to_string())
+        }
+    }
+
+    /// Returns the Yul source of a contract verifying the proofs generated by
+    /// `prove_ast_evm` for `pil` and `fixed`.
+    pub fn evm_verifier<F: FieldElement>(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(&str, Vec<F>)],
+    ) -> Result<String, String> {
+        let params = self.downsized_params(fixed);
+        let hash = circuit_hash(&params, pil, fixed);
+        let vk = self.verifying_key(&params, &hash, pil, fixed)?;
 
-        log::info!("Generating VK for app snark...");
-        let vk_app = keygen_vk(&params_app, &circuit_app).unwrap();
+        log::info!("Generating snark verifier...");
+        Ok(evm::gen_evm_verifier(&params, &vk, num_instance(pil), None))
+    }
 
-        log::info!("Generating circuit for compression snark...");
-        let protocol_app = compile(
+    /// Returns the Yul source of a contract verifying the proofs generated by
+    /// `prove_aggr` for `pil` and `fixed`.
+    pub fn aggregation_evm_verifier<F: FieldElement>(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(&str, Vec<F>)],
+    ) -> Result<String, String> {
+        let params_app = self.downsized_params(fixed);
+        let hash = circuit_hash(&params_app, pil, fixed);
+        let protocol_app = self.app_protocol(&params_app, &hash, pil, fixed)?;
+        let agg_circuit = aggregation::AggregationCircuit::new_without_witness(
             &params_app,
-            &vk_app,
-            Config::kzg().with_num_instance(vec![]),
+            [aggregation::Snark::new_without_witness(protocol_app)],
         );
-        let empty_snark = aggregation::Snark::new_without_witness(protocol_app.clone());
-        let agg_circuit =
-            aggregation::AggregationCircuit::new_without_witness(&params_app, [empty_snark]);
 
-        log::info!("Generating VK and PK for compression snark...");
-        let vk_aggr = keygen_vk(&self.params, &agg_circuit).unwrap();
-        let pk_aggr = keygen_pk(&self.params, vk_aggr, &agg_circuit).unwrap();
+        log::info!("Generating VK for compression snark...");
+        let vk_aggr = keygen_vk(&self.params, &agg_circuit).map_err(|e| e.to_string())?;
+
+        log::info!("Generating compressed snark verifier...");
+        Ok(evm::gen_evm_verifier(
+            &self.params,
+            &vk_aggr,
+            agg_circuit.num_instance(),
+            Some(aggregation::AggregationCircuit::accumulator_indices()),
+        ))
+    }
+
+    /// Encodes the calldata of the contract of `evm_verifier` for a proof generated by
+    /// `prove_ast_evm` with the given public values.
+    pub fn evm_calldata<F: FieldElement>(proof: &[u8], publics: &[F]) -> Vec<u8> {
+        evm::encode_calldata(&publics_to_instances(publics), proof)
+    }
+
+    /// Encodes the calldata of the contract of `aggregation_evm_verifier` for a proof
+    /// generated by `prove_aggr`, given the public values of the aggregated proof.
+    pub fn aggregation_evm_calldata<F: FieldElement>(
+        proof: &[u8],
+        publics: &[F],
+    ) -> Result<Vec<u8>, String> {
+        // The instances of the aggregation circuit are the accumulator, which
+        // precedes the proof, followed by the public values of the aggregated proof.
+        let accumulator_len =
+            CALLDATA_INSTANCE_BYTES * aggregation::AggregationCircuit::accumulator_indices().len();
+        if proof.len() < accumulator_len {
+            return Err("The proof is too short to be an aggregated proof.".to_string());
+        }
+        let (accumulator, proof) = proof.split_at(accumulator_len);
+        Ok([
+            accumulator,
+            &evm::encode_calldata(&publics_to_instances(publics), proof),
+        ]
+        .concat())
+    }
+
+    /// Writes the proving key of the circuit of `pil` and `fixed`, which contains the
+    /// verifying key, so that it can be loaded by `read_keys` instead of being generated
+    /// again. The keys are generated if they have not been generated or loaded before.
+    pub fn write_keys<F: FieldElement>(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(&str, Vec<F>)],
+        output: &mut impl io::Write,
+    ) -> Result<(), io::Error> {
+        let params = self.downsized_params(fixed);
+        let circuit = circuit_without_witness(pil, fixed)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let hash = circuit_hash(&params, pil, fixed);
+        let pk = self
+            .proving_key(&params, &hash, &circuit)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        // The keys are preceded by the hash of the circuit and the setup they belong to.
+        output.write_all(&hash)?;
+        pk.write(output, SerdeFormat::Processed)
+    }
+
+    /// Loads keys written by `write_keys`, to be used when proving and verifying the
+    /// circuit of `pil` and `fixed`. Fails if the keys were generated for a different PIL,
+    /// different fixed columns or a different setup.
+    pub fn read_keys<F: FieldElement>(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(&str, Vec<F>)],
+        input: &mut impl io::Read,
+    ) -> Result<(), String> {
+        let params = self.downsized_params(fixed);
+        let hash = circuit_hash(&params, pil, fixed);
+        let mut keys_hash = vec![0; hash.len()];
+        input
+            .read_exact(&mut keys_hash)
+            .map_err(|e| format!("Could not read the keys: {e}"))?;
+        if keys_hash != hash {
+            return Err(
+                "The keys were generated for a different PIL, different fixed columns or a different setup."
+                    .to_string(),
+            );
+        }
+        let pk = ProvingKey::<G1Affine>::read::<_, PlafH2Circuit>(
+            input,
+            SerdeFormat::Processed,
+            circuit_without_witness(pil, fixed)?.params(),
+        )
+        .map_err(|e| format!("Could not read the keys: {e}"))?;
+        self.keys.borrow_mut().insert(hash, Rc::new(pk));
+        Ok(())
+    }
+
+    /// Returns the proving key for `circuit`, whose `circuit_hash` is `hash`.
+    /// The loaded or previously generated key is reused if it belongs to the same circuit.
+    fn proving_key(
+        &self,
+        params: &ParamsKZG<Bn256>,
+        hash: &[u8],
+        circuit: &PlafH2Circuit,
+    ) -> Result<Rc<ProvingKey<G1Affine>>, String> {
+        if let Some(pk) = self.keys.borrow().get(hash) {
+            log::info!("Using the loaded VK and PK for snark.");
+            return Ok(pk.clone());
+        }
+
+        log::info!("Generating VK and PK for snark...");
+        let vk = keygen_vk(params, circuit).map_err(|e| e.to_string())?;
+        let pk = Rc::new(keygen_pk(params, vk, circuit).map_err(|e| e.to_string())?);
+        self.keys.borrow_mut().insert(hash.to_vec(), pk.clone());
+        Ok(pk)
+    }
+
+    /// Returns the verifying key for the circuit of `pil` and `fixed`, whose
+    /// `circuit_hash` is `hash`, reusing the loaded or previously generated keys
+    /// if they belong to the same circuit.
+    fn verifying_key<F: FieldElement>(
+        &self,
+        params: &ParamsKZG<Bn256>,
+        hash: &[u8],
+        pil: &Analyzed<F>,
+        fixed: &[(&str, Vec<F>)],
+    ) -> Result<VerifyingKey<G1Affine>, String> {
+        match self.keys.borrow().get(hash) {
+            Some(pk) => {
+                log::info!("Using the loaded VK for snark.");
+                Ok(pk.get_vk().clone())
+            }
+            None => {
+                log::info!("Generating VK for snark...");
+                keygen_vk(params, &circuit_without_witness(pil, fixed)?).map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    /// Returns the protocol of the circuit of `pil` and `fixed`, whose `circuit_hash`
+    /// is `hash`, which is verified by the aggregation circuit.
+    fn app_protocol<F: FieldElement>(
+        &self,
+        params_app: &ParamsKZG<Bn256>,
+        hash: &[u8],
+        pil: &Analyzed<F>,
+        fixed: &[(&str, Vec<F>)],
+    ) -> Result<PlonkProtocol<G1Affine>, String> {
+        let vk_app = self.verifying_key(params_app, hash, pil, fixed)?;
+        Ok(compile(
+            params_app,
+            &vk_app,
+            Config::kzg().with_num_instance(num_instance(pil)),
+        ))
+    }
+
+    fn downsized_params<F>(&self, fixed: &[(&str, Vec<F>)]) -> ParamsKZG<Bn256> {
+        // TODO this is hacky
+        let degree = usize::BITS - num_rows(fixed).leading_zeros() + 1;
+        let mut params = self.params.clone();
+        params.downsize(degree);
+        params
+    }
+
+    /// Aggregates a proof generated by `prove_ast` for `pil` and `fixed`.
+    /// @returns the proof of `aggregate`.
+    pub fn prove_aggr<F: FieldElement>(
+        &self,
+        pil: &Analyzed<F>,
+        fixed: &[(&str, Vec<F>)],
+        witness: &[(&str, Vec<F>)],
+        proof: Vec<u8>,
+    ) -> Result<Vec<u8>, String> {
+        let publics = pil
+            .public_values(witness)?
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect::<Vec<_>>();
+        let input = AggregationInput {
+            pil,
+            fixed,
+            proof,
+            publics,
+        };
+        let (_, instances, proof) = self.aggregate_tree(vec![input], 2)?;
+        Ok(Self::encode_aggregated_proof(&instances, &proof))
+    }
+
+    /// Aggregates proofs generated by `prove_ast`, possibly of different circuits, in a
+    /// tree. Every node of the tree aggregates at most `arity` proofs, which are given
+    /// proofs or the aggregated proofs of the level below. The proof of the root uses the
+    /// EVM transcript, so that it can be verified by the contract of the result.
+    pub fn aggregate<F: FieldElement>(
+        &self,
+        proofs: Vec<AggregationInput<F>>,
+        arity: usize,
+    ) -> Result<AggregatedProof<F>, String> {
+        let (pk, instances, proof) = self.aggregate_tree(proofs, arity)?;
 
         log::info!("Generating compressed snark verifier...");
-        let deployment_code = aggregation::gen_aggregation_evm_verifier(
+        let verifier = evm::gen_evm_verifier(
             &self.params,
-            pk_aggr.get_vk(),
-            aggregation::AggregationCircuit::num_instance(),
-            aggregation::AggregationCircuit::accumulator_indices(),
+            pk.get_vk(),
+            instances.iter().map(Vec::len).collect(),
+            Some(aggregation::AggregationCircuit::accumulator_indices()),
         );
 
-        log::info!("Generating aggregated proof...");
-        let start = Instant::now();
-        let snark = aggregation::Snark::new(protocol_app, vec![], proof);
-        let agg_circuit_with_proof = aggregation::AggregationCircuit::new(&params_app, [snark]);
-        let proof =
-            gen_proof::<_, _, EvmTranscript<G1Affine, _, _, _>, EvmTranscript<G1Affine, _, _, _>>(
-                &self.params,
-                &pk_aggr,
-                agg_circuit_with_proof.clone(),
-                agg_circuit_with_proof.instances(),
+        Ok(AggregatedProof {
+            proof: Self::encode_aggregated_proof(&instances, &proof),
+            instances: instances[0]
+                .iter()
+                .map(|instance| F::from_bytes_le(&instance.to_bytes()))
+                .collect(),
+            verifier,
+        })
+    }
+
+    /// Aggregates the proofs in a tree, see `aggregate`.
+    /// @returns the proving key of the root, its instances and its proof.
+    #[allow(clippy::type_complexity)]
+    fn aggregate_tree<F: FieldElement>(
+        &self,
+        proofs: Vec<AggregationInput<F>>,
+        arity: usize,
+    ) -> Result<(Rc<ProvingKey<G1Affine>>, Vec<Vec<Fr>>, Vec<u8>), String> {
+        if arity < 2 {
+            return Err(format!(
+                "The arity of the aggregation tree has to be at least 2, but it is {arity}."
+            ));
+        }
+        if proofs.is_empty() {
+            return Err("There are no proofs to aggregate.".to_string());
+        }
+        log::info!("Starting proof aggregation...");
+
+        // Each snark is paired with the hash of its circuit. Aggregation circuits with
+        // the same hash, i.e. of the same snark circuits, share their keys.
+        let mut snarks = proofs
+            .into_iter()
+            .map(|input| {
+                let params_app = self.downsized_params(input.fixed);
+                let hash = circuit_hash(&params_app, input.pil, input.fixed);
+                let protocol = self.app_protocol(&params_app, &hash, input.pil, input.fixed)?;
+                let snark = aggregation::Snark::new(
+                    protocol,
+                    publics_to_instances(&input.publics),
+                    input.proof,
+                );
+                Ok((hash, snark))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let mut keys = HashMap::new();
+
+        while snarks.len() > arity {
+            log::info!(
+                "Aggregating {} proofs into {}...",
+                snarks.len(),
+                (snarks.len() + arity - 1) / arity
             );
-        let duration = start.elapsed();
-        log::info!("Time taken: {:?}", duration);
+            snarks = snarks
+                .chunks(arity)
+                .map(|chunk| match chunk {
+                    [snark] => Ok(snark.clone()),
+                    _ => {
+                        let (hash, pk, instances, proof) = self.aggregate_snarks::<
+                            _,
+                            aggregation::PoseidonTranscript<NativeLoader, _>,
+                            aggregation::PoseidonTranscript<NativeLoader, _>,
+                        >(chunk, &mut keys)?;
+                        let protocol = compile(
+                            &self.params,
+                            pk.get_vk(),
+                            Config::kzg()
+                                .with_num_instance(instances.iter().map(Vec::len).collect())
+                                .with_accumulator_indices(Some(
+                                    aggregation::AggregationCircuit::accumulator_indices(),
+                                )),
+                        );
+                        Ok((hash, aggregation::Snark::new(protocol, instances, proof)))
+                    }
+                })
+                .collect::<Result<_, String>>()?;
+        }
 
-        log::info!("Verifying aggregated proof in the EVM...");
-        aggregation::evm_verify(deployment_code, agg_circuit_with_proof.instances(), &proof);
+        let (_, pk, instances, proof) = self.aggregate_snarks::<
+            _,
+            EvmTranscript<G1Affine, _, _, _>,
+            EvmTranscript<G1Affine, _, _, _>,
+        >(&snarks, &mut keys)?;
 
         log::info!("Proof aggregation done.");
 
-        proof
+        Ok((pk, instances, proof))
+    }
+
+    /// Stores the accumulator, which is needed to verify the aggregated proof, in
+    /// front of it.
+    fn encode_aggregated_proof(instances: &[Vec<Fr>], proof: &[u8]) -> Vec<u8> {
+        let accumulator =
+            instances[0][..aggregation::AggregationCircuit::accumulator_indices().len()].to_vec();
+        evm::encode_calldata(&[accumulator], proof)
+    }
+
+    /// Generates a proof of the aggregation circuit of `snarks`, each paired with the
+    /// hash of its circuit. The keys of the aggregation circuit are taken from `keys`
+    /// or generated and added to it.
+    /// @returns the hash of the aggregation circuit, its proving key, the instances and the proof.
+    #[allow(clippy::type_complexity)]
+    fn aggregate_snarks<
+        E: EncodedChallenge<G1Affine>,
+        TR: TranscriptReadBuffer<Cursor<Vec<u8>>, G1Affine, E>,
+        TW: TranscriptWriterBuffer<Vec<u8>, G1Affine, E>,
+    >(
+        &self,
+        snarks: &[(Vec<u8>, aggregation::Snark)],
+        keys: &mut HashMap<Vec<u8>, Rc<ProvingKey<G1Affine>>>,
+    ) -> Result<(Vec<u8>, Rc<ProvingKey<G1Affine>>, Vec<Vec<Fr>>, Vec<u8>), String> {
+        let mut hasher = Sha256::new();
+        for (hash, _) in snarks {
+            hasher.update(hash);
+        }
+        let hash = hasher.finalize().to_vec();
+        let pk = match keys.get(&hash) {
+            Some(pk) => pk.clone(),
+            None => {
+                log::info!("Generating VK and PK for compression snark...");
+                let circuit = aggregation::AggregationCircuit::new_without_witness(
+                    &self.params,
+                    snarks.iter().map(|(_, snark)| snark.without_witness()),
+                );
+                let vk = keygen_vk(&self.params, &circuit).map_err(|e| e.to_string())?;
+                let pk = Rc::new(keygen_pk(&self.params, vk, &circuit).map_err(|e| e.to_string())?);
+                keys.insert(hash.clone(), pk.clone());
+                pk
+            }
+        };
+
+        log::info!("Generating aggregated proof...");
+        let start = Instant::now();
+        let circuit = aggregation::AggregationCircuit::new(
+            &self.params,
+            snarks.iter().map(|(_, snark)| snark.clone()),
+        );
+        let instances = circuit.instances();
+        let proof = gen_proof::<_, _, TR, TW>(&self.params, &pk, circuit, instances.clone());
+        let duration = start.elapsed();
+        log::info!("Time taken: {:?}", duration);
+
+        Ok((hash, pk, instances, proof))
     }
 
     pub fn assert_field_is_compatible<F: FieldElement>() {
@@ -177,6 +619,71 @@ impl Halo2Prover {
     }
 }
 
+fn field_to_fr<F: FieldElement>(value: &F) -> Fr {
+    let bytes: [u8; 32] = value.to_bytes_le().try_into().unwrap();
+    Fr::from_bytes(&bytes).unwrap()
+}
+
+/// Builds the circuit of `pil` and `fixed` with zeros as witness values.
+/// The keys do not depend on the witness. Each element of an array is a column of its own.
+fn circuit_without_witness<F: FieldElement>(
+    pil: &Analyzed<F>,
+    fixed: &[(&str, Vec<F>)],
+) -> Result<PlafH2Circuit, String> {
+    let names = pil
+        .committed_polys_in_source_order()
+        .into_iter()
+        .flat_map(|(poly, _)| match poly.length {
+            Some(length) => (0..length)
+                .map(|i| format!("{}[{i}]", poly.absolute_name))
+                .collect(),
+            None => vec![poly.absolute_name.clone()],
+        })
+        .collect::<Vec<_>>();
+    let witness = names
+        .iter()
+        .map(|name| (name.as_str(), vec![F::zero(); num_rows(fixed)]))
+        .collect::<Vec<_>>();
+    analyzed_to_circuit(pil, fixed, &witness)
+}
+
+/// Returns the hash identifying the keys of the circuit of `pil` and `fixed`
+/// for the setup `params`.
+fn circuit_hash<F: FieldElement>(
+    params: &ParamsKZG<Bn256>,
+    pil: &Analyzed<F>,
+    fixed: &[(&str, Vec<F>)],
+) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{}\n{:?}\n{pil}", params.k(), params.s_g2()));
+    for (name, values) in fixed {
+        hasher.update(name);
+        for value in values {
+            hasher.update(value.to_bytes_le());
+        }
+    }
+    hasher.finalize().to_vec()
+}
+
+/// Returns the number of instances of the circuit built by `analyzed_to_circuit`
+/// for each instance column, see `publics_to_instances`.
+fn num_instance<F>(pil: &Analyzed<F>) -> Vec<usize> {
+    match pil.public_declarations.len() {
+        0 => vec![],
+        count => vec![count],
+    }
+}
+
+/// Converts the public values to the instances of the circuit built by
+/// `analyzed_to_circuit`, which has a single instance column if there are publics.
+pub(crate) fn publics_to_instances<F: FieldElement>(publics: &[F]) -> Vec<Vec<Fr>> {
+    if publics.is_empty() {
+        vec![]
+    } else {
+        vec![publics.iter().map(field_to_fr).collect()]
+    }
+}
+
 fn gen_proof<
     C: Circuit<Fr>,
     E: EncodedChallenge<G1Affine>,
@@ -227,3 +734,130 @@ fn gen_proof<
 
     proof
 }
+
+/// Returns the number of rows of the circuit, i.e. the length of the longest fixed column.
+/// Shorter columns belong to namespaces of a smaller degree and are repeated to fill the circuit.
+fn num_rows<F>(fixed: &[(&str, Vec<F>)]) -> usize {
+    fixed.iter().map(|(_, values)| values.len()).max().unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use number::Bn254Field;
+    use test_log::test;
+
+    use super::*;
+
+    /// A PIL whose column `x` increments by one in every row.
+    const COUNTER: &str = r#"
+        namespace main(8);
+            pol constant LAST = [0, 0, 0, 0, 0, 0, 0, 1];
+            pol commit x;
+            public out = x(7);
+            (1 - LAST) * (x' - x - 1) = 0;
+    "#;
+
+    /// A PIL whose column `x` doubles in every row.
+    const DOUBLER: &str = r#"
+        namespace main(8);
+            pol constant LAST = [0, 0, 0, 0, 0, 0, 0, 1];
+            pol commit x;
+            public out = x(7);
+            (1 - LAST) * (x' - 2 * x) = 0;
+    "#;
+
+    fn fixed() -> Vec<(&'static str, Vec<Bn254Field>)> {
+        vec![(
+            "main.LAST",
+            [0, 0, 0, 0, 0, 0, 0, 1].map(Bn254Field::from).to_vec(),
+        )]
+    }
+
+    /// Proves `pil` for the column `x` starting at `first`, in which every value is
+    /// computed from the previous one by `next`.
+    fn prove<'a>(
+        prover: &Halo2Prover,
+        pil: &'a Analyzed<Bn254Field>,
+        fixed: &'a [(&'a str, Vec<Bn254Field>)],
+        first: u64,
+        next: fn(u64) -> u64,
+    ) -> AggregationInput<'a, Bn254Field> {
+        let x = std::iter::successors(Some(first), |x| Some(next(*x)))
+            .take(8)
+            .map(Bn254Field::from)
+            .collect::<Vec<_>>();
+        let publics = vec![x[7]];
+        let proof = prover.prove_ast(pil, fixed, &[("main.x", x)]).unwrap();
+        AggregationInput {
+            pil,
+            fixed,
+            proof,
+            publics,
+        }
+    }
+
+    #[test]
+    fn aggregate_different_circuits() {
+        let counter = pil_analyzer::analyze_string(COUNTER).unwrap();
+        let doubler = pil_analyzer::analyze_string(DOUBLER).unwrap();
+        let fixed = fixed();
+        // The aggregation circuit needs a larger setup than the aggregated circuits.
+        let prover = Halo2Prover::new(1 << 20);
+
+        // With an arity of 2, the first two proofs are aggregated before the result
+        // is aggregated with the third one.
+        let proofs = vec![
+            prove(&prover, &counter, &fixed, 1, |x| x + 1),
+            prove(&prover, &doubler, &fixed, 1, |x| 2 * x),
+            prove(&prover, &counter, &fixed, 10, |x| x + 1),
+        ];
+        let (pk, instances, proof) = prover.aggregate_tree(proofs, 2).unwrap();
+
+        // The accumulator is followed by the public values of the proofs, in their order.
+        let accumulator_len = aggregation::AggregationCircuit::accumulator_indices().len();
+        assert_eq!(
+            instances[0][accumulator_len..],
+            [8u64, 128, 17].map(Fr::from)
+        );
+
+        let verify = |instances: &[Vec<Fr>]| {
+            let instances = instances.iter().map(Vec::as_slice).collect_vec();
+            let mut transcript =
+                EvmTranscript::<G1Affine, _, _, _>::init(Cursor::new(proof.clone()));
+            verify_proof::<_, VerifierGWC<_>, _, EvmTranscript<G1Affine, _, _, _>, _>(
+                prover.params.verifier_params(),
+                pk.get_vk(),
+                AccumulatorStrategy::new(prover.params.verifier_params()),
+                &[instances.as_slice()],
+                &mut transcript,
+            )
+            .map(VerificationStrategy::<_, VerifierGWC<_>>::finalize)
+            .unwrap_or(false)
+        };
+        assert!(verify(&instances));
+        let mut wrong_instances = instances.clone();
+        *wrong_instances[0].last_mut().unwrap() = Fr::from(18u64);
+        assert!(!verify(&wrong_instances));
+    }
+
+    #[test]
+    fn aggregate_invalid_arity() {
+        let counter = pil_analyzer::analyze_string(COUNTER).unwrap();
+        let fixed = fixed();
+        let prover = Halo2Prover::new(8);
+        let proofs = vec![prove(&prover, &counter, &fixed, 1, |x| x + 1)];
+        let Err(error) = prover.aggregate(proofs, 1) else {
+            panic!("an arity of 1 was accepted");
+        };
+        assert!(error.contains("at least 2"), "{error}");
+    }
+
+    #[test]
+    fn aggregate_no_proofs() {
+        let prover = Halo2Prover::new(8);
+        let Err(error) = prover.aggregate::<Bn254Field>(vec![], 2) else {
+            panic!("an empty aggregation was accepted");
+        };
+        assert!(error.contains("no proofs"), "{error}");
+    }
+}
@@ -2,20 +2,45 @@ use std::iter::once;
 
 use analysis::utils::parse_pil_statement;
 use ast::{
-    object::{Location, PILGraph},
+    object::{Function, Location, PILGraph},
     parsed::{
         build::{direct_reference, namespaced_reference},
         Expression, PILFile, PilStatement, SelectedExpressions,
     },
 };
 use number::FieldElement;
+use parser_util::SourceError;
+use strum::{Display, EnumString, EnumVariantNames};
 
 const DEFAULT_DEGREE: u64 = 1024;
 const MAIN_FUNCTION_NAME: &str = "main";
 
+/// How the degrees of the machines are determined when linking.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, EnumString, EnumVariantNames, Display)]
+pub enum LinkerMode {
+    /// All machines have the degree of the main machine.
+    #[default]
+    #[strum(serialize = "monolithic")]
+    Monolithic,
+    /// Each machine is a separate namespace with its own degree, connected to the
+    /// other machines through lookups. Machines without an explicit degree
+    /// have the degree of the main machine.
+    #[strum(serialize = "composite")]
+    Composite,
+}
+
 /// a monolithic linker which outputs a single AIR
 /// It sets the degree of submachines to the degree of the main machine, and errors out if a submachine has an explicit degree which doesn't match the main one
-pub fn link<T: FieldElement>(graph: PILGraph<T>) -> Result<PILFile<T>, Vec<String>> {
+pub fn link<T: FieldElement>(graph: PILGraph<T>) -> Result<PILFile<T>, Vec<SourceError>> {
+    link_with_mode(graph, LinkerMode::Monolithic)
+}
+
+/// Links the machines of the graph into a single PIL file, with one namespace per machine.
+/// See [LinkerMode] for how the degrees of the namespaces are determined.
+pub fn link_with_mode<T: FieldElement>(
+    graph: PILGraph<T>,
+    mode: LinkerMode,
+) -> Result<PILFile<T>, Vec<SourceError>> {
     let main_machine = graph.main;
     let main_degree = graph
         .objects
@@ -32,20 +57,26 @@ pub fn link<T: FieldElement>(graph: PILGraph<T>) -> Result<PILFile<T>, Vec<Strin
         .flat_map(|(location, object)| {
             let mut pil = vec![];
 
-            if let Some(degree) = object.degree {
-                if degree != main_degree {
-                    errors.push(format!(
-                        "Machine {location} should have degree {main_degree}, found {}",
-                        degree
-                    ))
+            let degree = match (mode, object.degree) {
+                (LinkerMode::Composite, Some(degree)) => degree,
+                (LinkerMode::Monolithic, Some(degree)) if degree != main_degree => {
+                    errors.push(
+                        format!(
+                            "Machine {location} should have degree {main_degree}, found {}",
+                            degree
+                        )
+                        .into(),
+                    );
+                    main_degree
                 }
-            }
+                _ => main_degree,
+            };
 
             // create a namespace for this object
             pil.push(PilStatement::Namespace(
                 0,
                 location.to_string(),
-                Expression::Number(T::from(main_degree)),
+                Expression::Number(T::from(degree)),
             ));
             pil.extend(object.pil);
             for link in object.links {
@@ -108,21 +139,28 @@ pub fn link<T: FieldElement>(graph: PILGraph<T>) -> Result<PILFile<T>, Vec<Strin
             }
 
             if location == Location::main() {
-                if let Some(main_function) = graph
-                    .entry_points
-                    .iter()
-                    .find(|f| f.name == MAIN_FUNCTION_NAME)
-                {
-                    let main_function_id = main_function.id;
-                    let function_id = main_machine.function_id.clone();
-                    // call the main function by initialising `function_id` to that of the main function
-                    let linker_first_step = "_linker_first_step";
-                    pil.extend([
-                        parse_pil_statement(&format!("col fixed {linker_first_step} = [1] + [0]*")),
-                        parse_pil_statement(&format!(
-                            "{linker_first_step} * ({function_id} - {main_function_id}) = 0"
-                        )),
-                    ]);
+                match entry_point(&graph.entry_points) {
+                    Ok(Some(entry_point)) => {
+                        let entry_point_id = entry_point.id;
+                        let function_id = main_machine.function_id.clone();
+                        let latch = main_machine.latch.clone();
+                        // call the entry point by initialising `function_id` to that of the entry point
+                        let linker_first_step = "_linker_first_step";
+                        pil.extend([
+                            parse_pil_statement(&format!(
+                                "col fixed {linker_first_step} = [1] + [0]*"
+                            )),
+                            parse_pil_statement(&format!(
+                                "{linker_first_step} * ({function_id} - {entry_point_id}) = 0"
+                            )),
+                            // once the entry point returns, call function `0`, which keeps the machine idle
+                            parse_pil_statement(&format!(
+                                "(1 - {linker_first_step}') * {latch} * {function_id}' = 0"
+                            )),
+                        ]);
+                    }
+                    Ok(None) => {}
+                    Err(e) => errors.push(e),
                 }
             }
 
@@ -137,6 +175,24 @@ pub fn link<T: FieldElement>(graph: PILGraph<T>) -> Result<PILFile<T>, Vec<Strin
     }
 }
 
+/// The function of the main machine which is called when the program starts:
+/// the function called `main`, or the only function if there is a single one.
+fn entry_point<T>(functions: &[Function<T>]) -> Result<Option<&Function<T>>, SourceError> {
+    match functions.iter().find(|f| f.name == MAIN_FUNCTION_NAME) {
+        Some(main) => Ok(Some(main)),
+        None if functions.len() <= 1 => Ok(functions.first()),
+        None => Err(format!(
+            "The main machine has functions {} but none of them is called {MAIN_FUNCTION_NAME}",
+            functions
+                .iter()
+                .map(|f| f.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+        .into()),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::fs;
@@ -152,10 +208,10 @@ mod test {
 
     use pretty_assertions::assert_eq;
 
-    use crate::{link, DEFAULT_DEGREE};
+    use crate::{link, link_with_mode, LinkerMode, DEFAULT_DEGREE};
 
     fn parse_analyse_and_compile<T: FieldElement>(input: &str) -> PILGraph<T> {
-        airgen::compile(analyze(parse_asm(None, input).unwrap()).unwrap())
+        airgen::compile(analyze(parse_asm(None, input).unwrap()).unwrap()).unwrap()
     }
 
     #[test]
@@ -201,38 +257,77 @@ mod test {
         assert_eq!(
             link(default_no_match),
             Err(vec![
-                "Machine main_foo should have degree 1024, found 8".to_string()
+                "Machine main_foo should have degree 1024, found 8".into()
             ])
         );
     }
 
+    #[test]
+    fn composite_degree() {
+        let graph = |foo_degree| PILGraph::<Bn254Field> {
+            main: ast::object::Machine {
+                location: Location::main(),
+                function_id: "function_id".into(),
+                latch: "latch".into(),
+            },
+            entry_points: vec![],
+            objects: [
+                (Location::main(), Object::default().with_degree(Some(16))),
+                (
+                    Location::main().join("foo"),
+                    Object::default().with_degree(foo_degree),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        let namespace_degrees = |f: PILFile<Bn254Field>| {
+            f.0.into_iter()
+                .filter_map(|s| match s {
+                    ast::parsed::PilStatement::Namespace(_, name, e) => Some((name, e)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            namespace_degrees(link_with_mode(graph(Some(4)), LinkerMode::Composite).unwrap()),
+            vec![
+                ("main".to_string(), Expression::Number(16.into())),
+                ("main_foo".to_string(), Expression::Number(4.into()))
+            ]
+        );
+        // machines without a degree inherit the degree of the main machine
+        assert_eq!(
+            namespace_degrees(link_with_mode(graph(None), LinkerMode::Composite).unwrap()),
+            vec![
+                ("main".to_string(), Expression::Number(16.into())),
+                ("main_foo".to_string(), Expression::Number(16.into()))
+            ]
+        );
+    }
+
     #[test]
     pub fn compile_empty_vm() {
         let expectation = r#"
         namespace main(8);
 pol commit _function_id;
-pol commit _sigma;
-pol constant _romgen_first_step = [1] + [0]*;
-_sigma' = ((1 - _romgen_first_step') * (_sigma + instr_return));
-(_sigma * (_function_id - 2)) = 0;
 pol commit pc;
 pol commit instr__jump_to_operation;
 pol commit instr__reset;
-pol commit instr__loop;
 pol commit instr_return;
 pol constant first_step = [1] + [0]*;
-pc' = ((1 - first_step') * ((((instr__jump_to_operation * _function_id) + (instr__loop * pc)) + (instr_return * 0)) + ((1 - ((instr__jump_to_operation + instr__loop) + instr_return)) * (pc + 1))));
-pol constant p_line = [0, 1, 2] + [2]*;
-pol constant p_instr__jump_to_operation = [0, 1, 0] + [0]*;
-pol constant p_instr__loop = [0, 0, 1] + [1]*;
-pol constant p_instr__reset = [1, 0, 0] + [0]*;
-pol constant p_instr_return = [0, 0, 0] + [0]*;
-{ pc, instr__jump_to_operation, instr__reset, instr__loop, instr_return } in { p_line, p_instr__jump_to_operation, p_instr__reset, p_instr__loop, p_instr_return };
+pc' = ((1 - first_step') * (((instr__jump_to_operation * _function_id) + (instr_return * 0)) + ((1 - (instr__jump_to_operation + instr_return)) * (pc + 1))));
+pol constant p_line = [0, 1] + [1]*;
+pol constant p_instr__jump_to_operation = [0, 1] + [1]*;
+pol constant p_instr__reset = [1, 0] + [0]*;
+pol constant p_instr_return = [0, 0] + [0]*;
+{ pc, instr__jump_to_operation, instr__reset, instr_return } in { p_line, p_instr__jump_to_operation, p_instr__reset, p_instr_return };
 pol constant _block_enforcer_last_step = [0]* + [1];
 pol commit _function_id_no_change;
 _function_id_no_change = ((1 - _block_enforcer_last_step) * (1 - instr_return));
 (_function_id_no_change * (_function_id' - _function_id)) = 0;
-        "#;
+"#;
 
         let file_name = "../test_data/asm/empty_vm.asm";
         let contents = fs::read_to_string(file_name).unwrap();
@@ -246,10 +341,6 @@ _function_id_no_change = ((1 - _block_enforcer_last_step) * (1 - instr_return));
         let expectation = r#"
         namespace main(16);
 pol commit _function_id;
-pol commit _sigma;
-pol constant _romgen_first_step = [1] + [0]*;
-_sigma' = ((1 - _romgen_first_step') * (_sigma + instr_return));
-(_sigma * (_function_id - 4)) = 0;
 pol commit pc;
 pol commit X;
 pol commit Y;
@@ -261,7 +352,6 @@ pol commit instr_one;
 pol commit instr_nothing;
 pol commit instr__jump_to_operation;
 pol commit instr__reset;
-pol commit instr__loop;
 pol commit instr_return;
 pol commit X_const;
 pol commit X_read_free;
@@ -275,28 +365,27 @@ pol commit read_Y_pc;
 Y = ((((read_Y_A * A) + (read_Y_pc * pc)) + Y_const) + (Y_read_free * Y_free_value));
 pol constant first_step = [1] + [0]*;
 A' = ((((reg_write_X_A * X) + (reg_write_Y_A * Y)) + (instr__reset * 0)) + ((1 - ((reg_write_X_A + reg_write_Y_A) + instr__reset)) * A));
-pc' = ((1 - first_step') * ((((instr__jump_to_operation * _function_id) + (instr__loop * pc)) + (instr_return * 0)) + ((1 - ((instr__jump_to_operation + instr__loop) + instr_return)) * (pc + 1))));
-pol constant p_line = [0, 1, 2, 3, 4] + [4]*;
+pc' = ((1 - first_step') * (((instr__jump_to_operation * _function_id) + (instr_return * 0)) + ((1 - (instr__jump_to_operation + instr_return)) * (pc + 1))));
+pol constant p_line = [0, 1, 2, 3] + [3]*;
 pol commit X_free_value(i) query match pc {  };
 pol commit Y_free_value(i) query match pc {  };
-pol constant p_X_const = [0, 0, 0, 0, 0] + [0]*;
-pol constant p_X_read_free = [0, 0, 0, 0, 0] + [0]*;
-pol constant p_Y_const = [0, 0, 0, 0, 0] + [0]*;
-pol constant p_Y_read_free = [0, 0, 1, 0, 0] + [0]*;
-pol constant p_instr__jump_to_operation = [0, 1, 0, 0, 0] + [0]*;
-pol constant p_instr__loop = [0, 0, 0, 0, 1] + [1]*;
-pol constant p_instr__reset = [1, 0, 0, 0, 0] + [0]*;
-pol constant p_instr_identity = [0, 0, 0, 0, 0] + [0]*;
-pol constant p_instr_nothing = [0, 0, 0, 0, 0] + [0]*;
-pol constant p_instr_one = [0, 0, 1, 0, 0] + [0]*;
-pol constant p_instr_return = [0, 0, 0, 1, 0] + [0]*;
-pol constant p_read_X_A = [0, 0, 0, 0, 0] + [0]*;
-pol constant p_read_X_pc = [0, 0, 0, 0, 0] + [0]*;
-pol constant p_read_Y_A = [0, 0, 0, 0, 0] + [0]*;
-pol constant p_read_Y_pc = [0, 0, 0, 0, 0] + [0]*;
-pol constant p_reg_write_X_A = [0, 0, 0, 0, 0] + [0]*;
-pol constant p_reg_write_Y_A = [0, 0, 1, 0, 0] + [0]*;
-{ pc, reg_write_X_A, reg_write_Y_A, instr_identity, instr_one, instr_nothing, instr__jump_to_operation, instr__reset, instr__loop, instr_return, X_const, X_read_free, read_X_A, read_X_pc, Y_const, Y_read_free, read_Y_A, read_Y_pc } in { p_line, p_reg_write_X_A, p_reg_write_Y_A, p_instr_identity, p_instr_one, p_instr_nothing, p_instr__jump_to_operation, p_instr__reset, p_instr__loop, p_instr_return, p_X_const, p_X_read_free, p_read_X_A, p_read_X_pc, p_Y_const, p_Y_read_free, p_read_Y_A, p_read_Y_pc };
+pol constant p_X_const = [0, 0, 0, 0] + [0]*;
+pol constant p_X_read_free = [0, 0, 0, 0] + [0]*;
+pol constant p_Y_const = [0, 0, 0, 0] + [0]*;
+pol constant p_Y_read_free = [0, 0, 1, 0] + [0]*;
+pol constant p_instr__jump_to_operation = [0, 1, 0, 0] + [0]*;
+pol constant p_instr__reset = [1, 0, 0, 0] + [0]*;
+pol constant p_instr_identity = [0, 0, 0, 0] + [0]*;
+pol constant p_instr_nothing = [0, 0, 0, 0] + [0]*;
+pol constant p_instr_one = [0, 0, 1, 0] + [0]*;
+pol constant p_instr_return = [0, 0, 0, 1] + [1]*;
+pol constant p_read_X_A = [0, 0, 0, 0] + [0]*;
+pol constant p_read_X_pc = [0, 0, 0, 0] + [0]*;
+pol constant p_read_Y_A = [0, 0, 0, 0] + [0]*;
+pol constant p_read_Y_pc = [0, 0, 0, 0] + [0]*;
+pol constant p_reg_write_X_A = [0, 0, 0, 0] + [0]*;
+pol constant p_reg_write_Y_A = [0, 0, 1, 0] + [0]*;
+{ pc, reg_write_X_A, reg_write_Y_A, instr_identity, instr_one, instr_nothing, instr__jump_to_operation, instr__reset, instr_return, X_const, X_read_free, read_X_A, read_X_pc, Y_const, Y_read_free, read_Y_A, read_Y_pc } in { p_line, p_reg_write_X_A, p_reg_write_Y_A, p_instr_identity, p_instr_one, p_instr_nothing, p_instr__jump_to_operation, p_instr__reset, p_instr_return, p_X_const, p_X_read_free, p_read_X_A, p_read_X_pc, p_Y_const, p_Y_read_free, p_read_Y_A, p_read_Y_pc };
 pol constant _block_enforcer_last_step = [0]* + [1];
 pol commit _function_id_no_change;
 _function_id_no_change = ((1 - _block_enforcer_last_step) * (1 - instr_return));
@@ -306,18 +395,14 @@ instr_one { 3, Y } in main_sub.instr_return { main_sub._function_id, main_sub._o
 instr_nothing { 4 } in main_sub.instr_return { main_sub._function_id };
 pol constant _linker_first_step = [1] + [0]*;
 (_linker_first_step * (_function_id - 2)) = 0;
+(((1 - _linker_first_step') * instr_return) * _function_id') = 0;
 namespace main_sub(16);
 pol commit _function_id;
-pol commit _sigma;
-pol constant _romgen_first_step = [1] + [0]*;
-_sigma' = ((1 - _romgen_first_step') * (_sigma + instr_return));
-(_sigma * (_function_id - 5)) = 0;
 pol commit pc;
 pol commit _input_0;
 pol commit _output_0;
 pol commit instr__jump_to_operation;
 pol commit instr__reset;
-pol commit instr__loop;
 pol commit instr_return;
 pol commit _output_0_const;
 pol commit _output_0_read_free;
@@ -326,23 +411,22 @@ pol commit read__output_0__input_0;
 _output_0 = ((((read__output_0_pc * pc) + (read__output_0__input_0 * _input_0)) + _output_0_const) + (_output_0_read_free * _output_0_free_value));
 pol constant first_step = [1] + [0]*;
 ((1 - instr__reset) * _input_0') = ((1 - instr__reset) * _input_0);
-pc' = ((1 - first_step') * ((((instr__jump_to_operation * _function_id) + (instr__loop * pc)) + (instr_return * 0)) + ((1 - ((instr__jump_to_operation + instr__loop) + instr_return)) * (pc + 1))));
-pol constant p_line = [0, 1, 2, 3, 4, 5] + [5]*;
+pc' = ((1 - first_step') * (((instr__jump_to_operation * _function_id) + (instr_return * 0)) + ((1 - (instr__jump_to_operation + instr_return)) * (pc + 1))));
+pol constant p_line = [0, 1, 2, 3, 4] + [4]*;
 pol commit _output_0_free_value(i) query match pc {  };
-pol constant p__output_0_const = [0, 0, 0, 1, 0, 0] + [0]*;
-pol constant p__output_0_read_free = [0, 0, 0, 0, 0, 0] + [0]*;
-pol constant p_instr__jump_to_operation = [0, 1, 0, 0, 0, 0] + [0]*;
-pol constant p_instr__loop = [0, 0, 0, 0, 0, 1] + [1]*;
-pol constant p_instr__reset = [1, 0, 0, 0, 0, 0] + [0]*;
-pol constant p_instr_return = [0, 0, 1, 1, 1, 0] + [0]*;
-pol constant p_read__output_0__input_0 = [0, 0, 1, 0, 0, 0] + [0]*;
-pol constant p_read__output_0_pc = [0, 0, 0, 0, 0, 0] + [0]*;
-{ pc, instr__jump_to_operation, instr__reset, instr__loop, instr_return, _output_0_const, _output_0_read_free, read__output_0_pc, read__output_0__input_0 } in { p_line, p_instr__jump_to_operation, p_instr__reset, p_instr__loop, p_instr_return, p__output_0_const, p__output_0_read_free, p_read__output_0_pc, p_read__output_0__input_0 };
+pol constant p__output_0_const = [0, 0, 0, 1, 0] + [0]*;
+pol constant p__output_0_read_free = [0, 0, 0, 0, 0] + [0]*;
+pol constant p_instr__jump_to_operation = [0, 1, 0, 0, 0] + [0]*;
+pol constant p_instr__reset = [1, 0, 0, 0, 0] + [0]*;
+pol constant p_instr_return = [0, 0, 1, 1, 1] + [1]*;
+pol constant p_read__output_0__input_0 = [0, 0, 1, 0, 0] + [0]*;
+pol constant p_read__output_0_pc = [0, 0, 0, 0, 0] + [0]*;
+{ pc, instr__jump_to_operation, instr__reset, instr_return, _output_0_const, _output_0_read_free, read__output_0_pc, read__output_0__input_0 } in { p_line, p_instr__jump_to_operation, p_instr__reset, p_instr_return, p__output_0_const, p__output_0_read_free, p_read__output_0_pc, p_read__output_0__input_0 };
 pol constant _block_enforcer_last_step = [0]* + [1];
 pol commit _function_id_no_change;
 _function_id_no_change = ((1 - _block_enforcer_last_step) * (1 - instr_return));
-(_function_id_no_change * (_function_id' - _function_id)) = 0;        
-        "#;
+(_function_id_no_change * (_function_id' - _function_id)) = 0;
+"#;
 
         let file_name = "../test_data/asm/different_signatures.asm";
         let contents = fs::read_to_string(file_name).unwrap();
@@ -361,10 +445,6 @@ XIsZero = (1 - (X * XInv));
 (XIsZero * X) = 0;
 (XIsZero * (1 - XIsZero)) = 0;
 pol commit _function_id;
-pol commit _sigma;
-pol constant _romgen_first_step = [1] + [0]*;
-_sigma' = ((1 - _romgen_first_step') * (_sigma + instr_return));
-(_sigma * (_function_id - 10)) = 0;
 pol commit pc;
 pol commit X;
 pol commit reg_write_X_A;
@@ -380,7 +460,6 @@ pol commit instr_assert_zero;
 (instr_assert_zero * (XIsZero - 1)) = 0;
 pol commit instr__jump_to_operation;
 pol commit instr__reset;
-pol commit instr__loop;
 pol commit instr_return;
 pol commit X_const;
 pol commit X_read_free;
@@ -391,34 +470,33 @@ X = (((((read_X_A * A) + (read_X_CNT * CNT)) + (read_X_pc * pc)) + X_const) + (X
 pol constant first_step = [1] + [0]*;
 A' = (((reg_write_X_A * X) + (instr__reset * 0)) + ((1 - (reg_write_X_A + instr__reset)) * A));
 CNT' = ((((reg_write_X_CNT * X) + (instr_dec_CNT * (CNT - 1))) + (instr__reset * 0)) + ((1 - ((reg_write_X_CNT + instr_dec_CNT) + instr__reset)) * CNT));
-pc' = ((1 - first_step') * ((((((instr_jmpz * ((XIsZero * instr_jmpz_param_l) + ((1 - XIsZero) * (pc + 1)))) + (instr_jmp * instr_jmp_param_l)) + (instr__jump_to_operation * _function_id)) + (instr__loop * pc)) + (instr_return * 0)) + ((1 - ((((instr_jmpz + instr_jmp) + instr__jump_to_operation) + instr__loop) + instr_return)) * (pc + 1))));
-pol constant p_line = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10] + [10]*;
+pc' = ((1 - first_step') * (((((instr_jmpz * ((XIsZero * instr_jmpz_param_l) + ((1 - XIsZero) * (pc + 1)))) + (instr_jmp * instr_jmp_param_l)) + (instr__jump_to_operation * _function_id)) + (instr_return * 0)) + ((1 - (((instr_jmpz + instr_jmp) + instr__jump_to_operation) + instr_return)) * (pc + 1))));
+pol constant p_line = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9] + [9]*;
 pol commit X_free_value(i) query match pc { 2 => ("input", 1), 4 => ("input", (CNT + 1)), 7 => ("input", 0), };
-pol constant p_X_const = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] + [0]*;
-pol constant p_X_read_free = [0, 0, 1, 0, 1, 0, 0, -1, 0, 0, 0] + [0]*;
-pol constant p_instr__jump_to_operation = [0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0] + [0]*;
-pol constant p_instr__loop = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1] + [1]*;
-pol constant p_instr__reset = [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] + [0]*;
-pol constant p_instr_assert_zero = [0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0] + [0]*;
-pol constant p_instr_dec_CNT = [0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0] + [0]*;
-pol constant p_instr_jmp = [0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0] + [0]*;
-pol constant p_instr_jmp_param_l = [0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 0] + [0]*;
-pol constant p_instr_jmpz = [0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0] + [0]*;
-pol constant p_instr_jmpz_param_l = [0, 0, 0, 7, 0, 0, 0, 0, 0, 0, 0] + [0]*;
-pol constant p_instr_return = [0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0] + [0]*;
-pol constant p_read_X_A = [0, 0, 0, 0, 1, 0, 0, 1, 1, 0, 0] + [0]*;
-pol constant p_read_X_CNT = [0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0] + [0]*;
-pol constant p_read_X_pc = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] + [0]*;
-pol constant p_reg_write_X_A = [0, 0, 0, 0, 1, 0, 0, 1, 0, 0, 0] + [0]*;
-pol constant p_reg_write_X_CNT = [0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0] + [0]*;
-{ pc, reg_write_X_A, reg_write_X_CNT, instr_jmpz, instr_jmpz_param_l, instr_jmp, instr_jmp_param_l, instr_dec_CNT, instr_assert_zero, instr__jump_to_operation, instr__reset, instr__loop, instr_return, X_const, X_read_free, read_X_A, read_X_CNT, read_X_pc } in { p_line, p_reg_write_X_A, p_reg_write_X_CNT, p_instr_jmpz, p_instr_jmpz_param_l, p_instr_jmp, p_instr_jmp_param_l, p_instr_dec_CNT, p_instr_assert_zero, p_instr__jump_to_operation, p_instr__reset, p_instr__loop, p_instr_return, p_X_const, p_X_read_free, p_read_X_A, p_read_X_CNT, p_read_X_pc };
+pol constant p_X_const = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0] + [0]*;
+pol constant p_X_read_free = [0, 0, 1, 0, 1, 0, 0, -1, 0, 0] + [0]*;
+pol constant p_instr__jump_to_operation = [0, 1, 0, 0, 0, 0, 0, 0, 0, 0] + [0]*;
+pol constant p_instr__reset = [1, 0, 0, 0, 0, 0, 0, 0, 0, 0] + [0]*;
+pol constant p_instr_assert_zero = [0, 0, 0, 0, 0, 0, 0, 0, 1, 0] + [0]*;
+pol constant p_instr_dec_CNT = [0, 0, 0, 0, 0, 1, 0, 0, 0, 0] + [0]*;
+pol constant p_instr_jmp = [0, 0, 0, 0, 0, 0, 1, 0, 0, 0] + [0]*;
+pol constant p_instr_jmp_param_l = [0, 0, 0, 0, 0, 0, 3, 0, 0, 0] + [0]*;
+pol constant p_instr_jmpz = [0, 0, 0, 1, 0, 0, 0, 0, 0, 0] + [0]*;
+pol constant p_instr_jmpz_param_l = [0, 0, 0, 7, 0, 0, 0, 0, 0, 0] + [0]*;
+pol constant p_instr_return = [0, 0, 0, 0, 0, 0, 0, 0, 0, 1] + [1]*;
+pol constant p_read_X_A = [0, 0, 0, 0, 1, 0, 0, 1, 1, 0] + [0]*;
+pol constant p_read_X_CNT = [0, 0, 0, 1, 0, 0, 0, 0, 0, 0] + [0]*;
+pol constant p_read_X_pc = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0] + [0]*;
+pol constant p_reg_write_X_A = [0, 0, 0, 0, 1, 0, 0, 1, 0, 0] + [0]*;
+pol constant p_reg_write_X_CNT = [0, 0, 1, 0, 0, 0, 0, 0, 0, 0] + [0]*;
+{ pc, reg_write_X_A, reg_write_X_CNT, instr_jmpz, instr_jmpz_param_l, instr_jmp, instr_jmp_param_l, instr_dec_CNT, instr_assert_zero, instr__jump_to_operation, instr__reset, instr_return, X_const, X_read_free, read_X_A, read_X_CNT, read_X_pc } in { p_line, p_reg_write_X_A, p_reg_write_X_CNT, p_instr_jmpz, p_instr_jmpz_param_l, p_instr_jmp, p_instr_jmp_param_l, p_instr_dec_CNT, p_instr_assert_zero, p_instr__jump_to_operation, p_instr__reset, p_instr_return, p_X_const, p_X_read_free, p_read_X_A, p_read_X_CNT, p_read_X_pc };
 pol constant _block_enforcer_last_step = [0]* + [1];
 pol commit _function_id_no_change;
 _function_id_no_change = ((1 - _block_enforcer_last_step) * (1 - instr_return));
 (_function_id_no_change * (_function_id' - _function_id)) = 0;
 pol constant _linker_first_step = [1] + [0]*;
 (_linker_first_step * (_function_id - 2)) = 0;
-
+(((1 - _linker_first_step') * instr_return) * _function_id') = 0;
 "#;
         let file_name = "../test_data/asm/simple_sum.asm";
         let contents = fs::read_to_string(file_name).unwrap();
@@ -447,10 +525,6 @@ machine Machine {
         let expectation = r#"
 namespace main(1024);
 pol commit _function_id;
-pol commit _sigma;
-pol constant _romgen_first_step = [1] + [0]*;
-_sigma' = ((1 - _romgen_first_step') * (_sigma + instr_return));
-(_sigma * (_function_id - 4)) = 0;
 pol commit pc;
 pol commit fp;
 pol commit instr_inc_fp;
@@ -460,28 +534,27 @@ pol commit instr_adjust_fp_param_amount;
 pol commit instr_adjust_fp_param_t;
 pol commit instr__jump_to_operation;
 pol commit instr__reset;
-pol commit instr__loop;
 pol commit instr_return;
 pol constant first_step = [1] + [0]*;
 fp' = ((((instr_inc_fp * (fp + instr_inc_fp_param_amount)) + (instr_adjust_fp * (fp + instr_adjust_fp_param_amount))) + (instr__reset * 0)) + ((1 - ((instr_inc_fp + instr_adjust_fp) + instr__reset)) * fp));
-pc' = ((1 - first_step') * (((((instr_adjust_fp * label) + (instr__jump_to_operation * _function_id)) + (instr__loop * pc)) + (instr_return * 0)) + ((1 - (((instr_adjust_fp + instr__jump_to_operation) + instr__loop) + instr_return)) * (pc + 1))));
-pol constant p_line = [0, 1, 2, 3, 4] + [4]*;
-pol constant p_instr__jump_to_operation = [0, 1, 0, 0, 0] + [0]*;
-pol constant p_instr__loop = [0, 0, 0, 0, 1] + [1]*;
-pol constant p_instr__reset = [1, 0, 0, 0, 0] + [0]*;
-pol constant p_instr_adjust_fp = [0, 0, 0, 1, 0] + [0]*;
-pol constant p_instr_adjust_fp_param_amount = [0, 0, 0, -2, 0] + [0]*;
-pol constant p_instr_adjust_fp_param_t = [0, 0, 0, 3, 0] + [0]*;
-pol constant p_instr_inc_fp = [0, 0, 1, 0, 0] + [0]*;
-pol constant p_instr_inc_fp_param_amount = [0, 0, 7, 0, 0] + [0]*;
-pol constant p_instr_return = [0, 0, 0, 0, 0] + [0]*;
-{ pc, instr_inc_fp, instr_inc_fp_param_amount, instr_adjust_fp, instr_adjust_fp_param_amount, instr_adjust_fp_param_t, instr__jump_to_operation, instr__reset, instr__loop, instr_return } in { p_line, p_instr_inc_fp, p_instr_inc_fp_param_amount, p_instr_adjust_fp, p_instr_adjust_fp_param_amount, p_instr_adjust_fp_param_t, p_instr__jump_to_operation, p_instr__reset, p_instr__loop, p_instr_return };
+pc' = ((1 - first_step') * ((((instr_adjust_fp * label) + (instr__jump_to_operation * _function_id)) + (instr_return * 0)) + ((1 - ((instr_adjust_fp + instr__jump_to_operation) + instr_return)) * (pc + 1))));
+pol constant p_line = [0, 1, 2, 3] + [3]*;
+pol constant p_instr__jump_to_operation = [0, 1, 0, 0] + [0]*;
+pol constant p_instr__reset = [1, 0, 0, 0] + [0]*;
+pol constant p_instr_adjust_fp = [0, 0, 0, 1] + [1]*;
+pol constant p_instr_adjust_fp_param_amount = [0, 0, 0, -2] + [-2]*;
+pol constant p_instr_adjust_fp_param_t = [0, 0, 0, 3] + [3]*;
+pol constant p_instr_inc_fp = [0, 0, 1, 0] + [0]*;
+pol constant p_instr_inc_fp_param_amount = [0, 0, 7, 0] + [0]*;
+pol constant p_instr_return = [0, 0, 0, 0] + [0]*;
+{ pc, instr_inc_fp, instr_inc_fp_param_amount, instr_adjust_fp, instr_adjust_fp_param_amount, instr_adjust_fp_param_t, instr__jump_to_operation, instr__reset, instr_return } in { p_line, p_instr_inc_fp, p_instr_inc_fp_param_amount, p_instr_adjust_fp, p_instr_adjust_fp_param_amount, p_instr_adjust_fp_param_t, p_instr__jump_to_operation, p_instr__reset, p_instr_return };
 pol constant _block_enforcer_last_step = [0]* + [1];
 pol commit _function_id_no_change;
 _function_id_no_change = ((1 - _block_enforcer_last_step) * (1 - instr_return));
 (_function_id_no_change * (_function_id' - _function_id)) = 0;
 pol constant _linker_first_step = [1] + [0]*;
 (_linker_first_step * (_function_id - 2)) = 0;
+(((1 - _linker_first_step') * instr_return) * _function_id') = 0;
 "#;
         let graph = parse_analyse_and_compile::<GoldilocksField>(source);
         let pil = link(graph).unwrap();
@@ -506,4 +579,45 @@ machine NegativeForUnsigned {
         let graph = parse_analyse_and_compile::<GoldilocksField>(source);
         let _ = link(graph);
     }
+
+    #[test]
+    pub fn entry_point_is_main_function() {
+        let file_name = "../test_data/asm/book/function_params.asm";
+        let contents = fs::read_to_string(file_name).unwrap();
+        let graph = parse_analyse_and_compile::<GoldilocksField>(&contents);
+        let main_id = graph
+            .entry_points
+            .iter()
+            .find(|f| f.name == "main")
+            .unwrap()
+            .id;
+        let pil = link(graph).unwrap();
+        assert!(format!("{pil}").contains(&format!(
+            "(_linker_first_step * (_function_id - {main_id})) = 0;"
+        )));
+    }
+
+    #[test]
+    pub fn ambiguous_entry_point() {
+        let source = r#"
+machine Machine {
+    reg pc[@pc];
+
+    function foo {
+        return;
+    }
+
+    function bar {
+        return;
+    }
+}
+"#;
+        let graph = parse_analyse_and_compile::<GoldilocksField>(source);
+        assert_eq!(
+            link(graph),
+            Err(vec![
+                "The main machine has functions foo, bar but none of them is called main".into()
+            ])
+        );
+    }
 }
@@ -7,7 +7,10 @@ mod goldilocks;
 mod serialize;
 mod traits;
 
-pub use serialize::{read_polys_file, write_polys_file};
+pub use serialize::{
+    read_polys_file, write_polys_file, write_polys_raw, PolysReader, PolysWriter,
+    POLYS_FILE_VERSION,
+};
 
 pub use bn254::Bn254Field;
 pub use goldilocks::GoldilocksField;
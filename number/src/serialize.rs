@@ -1,77 +1,382 @@
-use std::io::{Read, Write};
+//! Serialization of fixed and witness columns.
+//!
+//! The file starts with a header, followed by the columns and a checksum.
+//! Columns of namespaces with a smaller degree have fewer values than the others.
+//! All integers are stored in little-endian byte order.
+//!
+//! | Content                                   | Size                       |
+//! |-------------------------------------------|----------------------------|
+//! | magic bytes `POWDRPOL`                    | 8                          |
+//! | format version                            | 4                          |
+//! | length of the field modulus in bytes      | 4                          |
+//! | field modulus                             | variable                   |
+//! | size of a field element in bytes (`w`)    | 4                          |
+//! | number of columns                         | 4                          |
+//! | for each column: length of the name       | 4                          |
+//! | for each column: name in UTF-8            | variable                   |
+//! | for each column: number of values (`d`)   | 8                          |
+//! | for each column: values                   | `d * w`                    |
+//! | CRC32 checksum of all previous bytes      | 4                          |
 
-use crate::{DegreeType, FieldElement};
+use std::{
+    io::{self, Read, Write},
+    marker::PhantomData,
+};
+
+use crate::{BigInt, DegreeType, FieldElement};
+
+const MAGIC: &[u8; 8] = b"POWDRPOL";
+
+/// The version of the file format written by [PolysWriter].
+pub const POLYS_FILE_VERSION: u32 = 1;
+
+/// Polynomials with their names.
+type NamedPolys<T> = Vec<(String, Vec<T>)>;
 
 fn ceil_div(num: usize, div: usize) -> usize {
     (num + div - 1) / div
 }
 
-pub fn write_polys_file<T: FieldElement>(
-    file: &mut impl Write,
-    degree: DegreeType,
-    polys: &[(&str, Vec<T>)],
-) {
-    let width = ceil_div(T::BITS as usize, 64) * 8;
+fn element_width<T: FieldElement>() -> usize {
+    ceil_div(T::BITS as usize, 64) * 8
+}
 
-    for i in 0..degree as usize {
-        for (_name, constant) in polys {
-            let bytes = constant[i].to_bytes_le();
-            assert_eq!(bytes.len(), width);
-            file.write_all(&bytes).unwrap();
+fn modulus_bytes<T: FieldElement>() -> Vec<u8> {
+    T::modulus().to_arbitrary_integer().to_bytes_le()
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Writes polynomials one after the other, preceded by a header and followed by a checksum.
+pub struct PolysWriter<W: Write, T> {
+    writer: W,
+    hasher: crc32fast::Hasher,
+    remaining_columns: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<W: Write, T: FieldElement> PolysWriter<W, T> {
+    /// Writes the header for `column_count` columns.
+    pub fn new(writer: W, column_count: usize) -> io::Result<Self> {
+        let mut polys_writer = PolysWriter {
+            writer,
+            hasher: crc32fast::Hasher::new(),
+            remaining_columns: column_count,
+            _marker: PhantomData,
+        };
+        let modulus = modulus_bytes::<T>();
+        polys_writer.write(MAGIC)?;
+        polys_writer.write(&POLYS_FILE_VERSION.to_le_bytes())?;
+        polys_writer.write(&(modulus.len() as u32).to_le_bytes())?;
+        polys_writer.write(&modulus)?;
+        polys_writer.write(&(element_width::<T>() as u32).to_le_bytes())?;
+        polys_writer.write(&(column_count as u32).to_le_bytes())?;
+        Ok(polys_writer)
+    }
+
+    /// Writes the next column.
+    pub fn write_column(&mut self, name: &str, values: &[T]) -> io::Result<()> {
+        assert!(
+            self.remaining_columns > 0,
+            "All columns have already been written."
+        );
+        self.remaining_columns -= 1;
+        self.write(&(name.len() as u32).to_le_bytes())?;
+        self.write(name.as_bytes())?;
+        self.write(&(values.len() as DegreeType).to_le_bytes())?;
+        for value in values {
+            self.write(&value.to_bytes_le())?;
         }
+        Ok(())
+    }
+
+    /// Writes the checksum after all columns have been written and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        assert_eq!(
+            self.remaining_columns, 0,
+            "Not all columns have been written before finishing."
+        );
+        let checksum = self.hasher.clone().finalize();
+        self.writer.write_all(&checksum.to_le_bytes())?;
+        Ok(self.writer)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.hasher.update(bytes);
+        self.writer.write_all(bytes)
     }
 }
 
-pub fn read_polys_file<'a, T: FieldElement>(
-    file: &mut impl Read,
-    columns: &[&'a str],
-) -> (Vec<(&'a str, Vec<T>)>, DegreeType) {
-    let width = ceil_div(T::BITS as usize, 64) * 8;
+/// Reads polynomials written by [PolysWriter] one after the other.
+pub struct PolysReader<R: Read, T> {
+    reader: R,
+    hasher: crc32fast::Hasher,
+    remaining_columns: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<R: Read, T: FieldElement> PolysReader<R, T> {
+    /// Reads and checks the header.
+    /// Fails if the file is not a polynomial file of a supported version
+    /// or if it was written for a different field.
+    pub fn new(reader: R) -> io::Result<Self> {
+        let mut polys_reader = PolysReader {
+            reader,
+            hasher: crc32fast::Hasher::new(),
+            remaining_columns: 0,
+            _marker: PhantomData,
+        };
+        if &polys_reader.read_bytes(MAGIC.len())?[..] != MAGIC {
+            return Err(invalid_data("Not a polynomial file (wrong magic bytes)."));
+        }
+        let version = polys_reader.read_u32()?;
+        if version != POLYS_FILE_VERSION {
+            return Err(invalid_data(format!(
+                "Unsupported polynomial file version {version}, expected {POLYS_FILE_VERSION}."
+            )));
+        }
+        let modulus_len = polys_reader.read_u32()? as usize;
+        let modulus = polys_reader.read_bytes(modulus_len)?;
+        let width = polys_reader.read_u32()? as usize;
+        if modulus != modulus_bytes::<T>() || width != element_width::<T>() {
+            return Err(invalid_data(format!(
+                "The polynomial file was written for the field with modulus 0x{}, but the field with modulus 0x{:x} was requested.",
+                modulus.iter().rev().map(|b| format!("{b:02x}")).collect::<String>(),
+                T::modulus().to_arbitrary_integer()
+            )));
+        }
+        polys_reader.remaining_columns = polys_reader.read_u32()? as usize;
+        Ok(polys_reader)
+    }
 
-    let bytes_to_read = width * columns.len();
+    /// The number of columns that have not been read yet.
+    pub fn remaining_columns(&self) -> usize {
+        self.remaining_columns
+    }
 
-    let mut result: Vec<(_, Vec<T>)> = columns.iter().map(|name| (*name, vec![])).collect();
-    let mut degree = 0;
+    /// Reads the next column and its name.
+    /// After the last column, verifies the checksum and returns None.
+    pub fn read_column(&mut self) -> io::Result<Option<(String, Vec<T>)>> {
+        if self.remaining_columns == 0 {
+            let expected = self.hasher.clone().finalize();
+            let mut checksum = [0u8; 4];
+            self.reader.read_exact(&mut checksum)?;
+            if u32::from_le_bytes(checksum) != expected {
+                return Err(invalid_data(
+                    "Checksum mismatch, the polynomial file is corrupted.",
+                ));
+            }
+            return Ok(None);
+        }
+        self.remaining_columns -= 1;
+        let name_len = self.read_u32()? as usize;
+        let name = String::from_utf8(self.read_bytes(name_len)?)
+            .map_err(|_| invalid_data("Column name is not valid UTF-8."))?;
+        let len = self.read_u64()?;
+        // The number of values is not trusted before the checksum is verified,
+        // so the vector only grows with the values actually read.
+        let mut values = vec![];
+        let mut buf = vec![0u8; element_width::<T>()];
+        for _ in 0..len {
+            self.read_exact(&mut buf)?;
+            values.push(T::from_bytes_le(&buf));
+        }
+        Ok(Some((name, values)))
+    }
 
-    loop {
-        let mut buf = vec![0u8; bytes_to_read];
-        match file.read_exact(&mut buf) {
-            Ok(()) => {}
-            Err(_) => return (result, degree),
+    /// Reads `len` bytes, without allocating more than the reader provides.
+    fn read_bytes(&mut self, len: usize) -> io::Result<Vec<u8>> {
+        let mut buf = vec![];
+        (&mut self.reader).take(len as u64).read_to_end(&mut buf)?;
+        if buf.len() != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
         }
-        degree += 1;
-        result
-            .iter_mut()
-            .zip(buf.chunks(width))
-            .for_each(|((_, values), bytes)| {
-                values.push(T::from_bytes_le(bytes));
-            });
+        self.hasher.update(&buf);
+        Ok(buf)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        self.reader.read_exact(buf)?;
+        self.hasher.update(buf);
+        Ok(())
+    }
+
+    fn read_u32(&mut self) -> io::Result<u32> {
+        let mut buf = [0u8; 4];
+        self.read_exact(&mut buf)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn read_u64(&mut self) -> io::Result<u64> {
+        let mut buf = [0u8; 8];
+        self.read_exact(&mut buf)?;
+        Ok(u64::from_le_bytes(buf))
     }
 }
 
+/// Writes the polynomials, each with its own number of values.
+pub fn write_polys_file<T: FieldElement>(
+    file: &mut impl Write,
+    polys: &[(&str, Vec<T>)],
+) -> io::Result<()> {
+    let mut writer = PolysWriter::new(file, polys.len())?;
+    for (name, values) in polys {
+        writer.write_column(name, values)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Reads all polynomials from a file written by [write_polys_file], together
+/// with the degree, i.e. the maximum number of values of a polynomial.
+pub fn read_polys_file<T: FieldElement>(
+    file: &mut impl Read,
+) -> io::Result<(NamedPolys<T>, DegreeType)> {
+    let mut reader = PolysReader::new(file)?;
+    let mut result = vec![];
+    while let Some(column) = reader.read_column()? {
+        result.push(column);
+    }
+    let degree = result
+        .iter()
+        .map(|(_, values)| values.len() as DegreeType)
+        .max()
+        .unwrap_or_default();
+    Ok((result, degree))
+}
+
+/// Writes the polynomials row by row without header or checksum,
+/// which is the format expected by pilcom.
+/// Polynomials with fewer than `degree` values (because they belong to a namespace
+/// of a smaller degree) are repeated until they have `degree` values.
+pub fn write_polys_raw<T: FieldElement>(
+    file: &mut impl Write,
+    degree: DegreeType,
+    polys: &[(&str, Vec<T>)],
+) -> io::Result<()> {
+    for i in 0..degree as usize {
+        for (_, values) in polys {
+            file.write_all(&values[i % values.len()].to_bytes_le())?;
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::Bn254Field;
+    use crate::{Bn254Field, GoldilocksField};
     use std::io::Cursor;
 
     use super::*;
     use test_log::test;
 
+    fn test_polys() -> Vec<(&'static str, Vec<Bn254Field>)> {
+        vec![
+            ("a", (0..4).map(Bn254Field::from).collect()),
+            ("b", vec![Bn254Field::from(1); 4]),
+        ]
+    }
+
     #[test]
     fn write_read() {
         let mut buf: Vec<u8> = vec![];
 
-        let degree = 4;
+        let polys = test_polys();
+        write_polys_file(&mut buf, &polys).unwrap();
+        let (read_polys, read_degree) =
+            read_polys_file::<Bn254Field>(&mut Cursor::new(buf)).unwrap();
+
+        assert_eq!(
+            read_polys,
+            polys
+                .into_iter()
+                .map(|(name, values)| (name.to_string(), values))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(read_degree, 4);
+    }
+
+    #[test]
+    fn different_degrees() {
+        let mut buf: Vec<u8> = vec![];
+
         let polys = vec![
-            ("a", vec![Bn254Field::from(0); degree]),
-            ("b", vec![Bn254Field::from(1); degree]),
+            ("a", [1, 2, 3, 4].map(GoldilocksField::from).to_vec()),
+            ("b", [7, 8].map(GoldilocksField::from).to_vec()),
         ];
+        write_polys_file(&mut buf, &polys).unwrap();
+        let (read_polys, degree) =
+            read_polys_file::<GoldilocksField>(&mut Cursor::new(buf)).unwrap();
 
-        write_polys_file(&mut buf, degree as u64, &polys);
-        let (read_polys, read_degree) =
-            read_polys_file::<Bn254Field>(&mut Cursor::new(buf), &["a", "b"]);
+        assert_eq!(read_polys[1].1, [7, 8].map(GoldilocksField::from));
+        assert_eq!(degree, 4);
+    }
 
-        assert_eq!(read_polys, polys);
-        assert_eq!(read_degree, degree as u64);
+    #[test]
+    fn raw_repeats_shorter_columns() {
+        let mut buf: Vec<u8> = vec![];
+
+        let polys = vec![("a", [7, 8].map(GoldilocksField::from).to_vec())];
+        write_polys_raw(&mut buf, 4, &polys).unwrap();
+
+        assert_eq!(
+            buf.chunks(8)
+                .map(GoldilocksField::from_bytes_le)
+                .collect::<Vec<_>>(),
+            [7, 8, 7, 8].map(GoldilocksField::from)
+        );
+    }
+
+    #[test]
+    fn wrong_field() {
+        let mut buf: Vec<u8> = vec![];
+        write_polys_file(&mut buf, &test_polys()).unwrap();
+
+        let error = read_polys_file::<GoldilocksField>(&mut Cursor::new(buf)).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("but the field with modulus 0xffffffff00000001 was requested"));
+    }
+
+    #[test]
+    fn corrupted() {
+        let mut buf: Vec<u8> = vec![];
+        write_polys_file(&mut buf, &test_polys()).unwrap();
+        let len = buf.len();
+        buf[len - 10] ^= 1;
+
+        let error = read_polys_file::<Bn254Field>(&mut Cursor::new(buf)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Checksum mismatch, the polynomial file is corrupted."
+        );
+    }
+
+    #[test]
+    fn truncated_with_large_lengths() {
+        let mut buf: Vec<u8> = vec![];
+        write_polys_file(&mut buf, &[("a", vec![GoldilocksField::from(1)])]).unwrap();
+        let header_len = buf.len() - 4 - 4 - 1 - 8 - 8;
+
+        let mut large_name = buf[..header_len].to_vec();
+        large_name.extend(u32::MAX.to_le_bytes());
+        let error = read_polys_file::<GoldilocksField>(&mut Cursor::new(large_name)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut many_values = buf[..header_len + 4 + 1].to_vec();
+        many_values.extend(u64::MAX.to_le_bytes());
+        let error = read_polys_file::<GoldilocksField>(&mut Cursor::new(many_values)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn not_a_polys_file() {
+        let error = read_polys_file::<Bn254Field>(&mut Cursor::new(vec![0u8; 100])).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Not a polynomial file (wrong magic bytes)."
+        );
     }
 }
@@ -2,6 +2,9 @@
 
 pub mod lines;
 
+use std::fmt::{self, Display, Formatter};
+use std::ops::Range;
+
 #[derive(Debug)]
 pub struct ParseError<'a> {
     start: usize,
@@ -13,20 +16,127 @@ pub struct ParseError<'a> {
 
 impl<'a> ParseError<'a> {
     pub fn output_to_stderr(&self) {
-        use codespan_reporting::diagnostic::{Diagnostic, Label};
-        use codespan_reporting::files::SimpleFiles;
-        use codespan_reporting::term;
-        use codespan_reporting::term::termcolor::{ColorChoice, StandardStream};
-
-        let config = term::Config::default();
-        let mut files = SimpleFiles::new();
-        let file_id = files.add(&self.file_name, self.contents);
-        let diagnostic = Diagnostic::error()
-            .with_message(&self.message)
-            .with_labels(vec![Label::primary(file_id, self.start..self.end)]);
-        let mut writer = StandardStream::stderr(ColorChoice::Always);
-        term::emit(&mut writer, &config, &files, &diagnostic).unwrap()
+        output_to_stderr(
+            Some(&self.file_name),
+            Some(self.contents),
+            Some(self.start..self.end),
+            &self.message,
+        )
+    }
+}
+
+/// An error in a source file, reported by any of the compilation stages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceError {
+    /// The name of the file the error refers to, if known.
+    pub file_name: Option<String>,
+    /// The range of bytes in the file the error refers to, if known.
+    pub span: Option<Range<usize>>,
+    pub message: String,
+}
+
+impl SourceError {
+    pub fn new(message: impl Into<String>) -> Self {
+        SourceError {
+            file_name: None,
+            span: None,
+            message: message.into(),
+        }
+    }
+
+    /// Creates an error that refers to the statement starting at byte `start`.
+    pub fn at(start: usize, message: impl Into<String>) -> Self {
+        SourceError {
+            span: Some(start..start),
+            ..Self::new(message)
+        }
+    }
+
+    /// Sets the file name of the error unless it is already set.
+    pub fn in_file(mut self, file_name: &str) -> Self {
+        self.file_name.get_or_insert_with(|| file_name.to_string());
+        self
+    }
+
+    /// Outputs the error to stderr, including an excerpt of the source
+    /// if the file it refers to can be read.
+    pub fn output_to_stderr(&self) {
+        let contents = self
+            .file_name
+            .as_ref()
+            .and_then(|file_name| std::fs::read_to_string(file_name).ok());
+        self.output_to_stderr_with_contents(contents.as_deref())
+    }
+
+    /// Outputs the error to stderr, using `contents` as the contents of the file it refers to.
+    pub fn output_to_stderr_with_contents(&self, contents: Option<&str>) {
+        output_to_stderr(
+            self.file_name.as_deref(),
+            contents,
+            self.span.clone(),
+            &self.message,
+        )
+    }
+}
+
+impl Display for SourceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(file_name) = &self.file_name {
+            write!(f, "{file_name}: ")?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<String> for SourceError {
+    fn from(message: String) -> Self {
+        SourceError::new(message)
+    }
+}
+
+impl From<&str> for SourceError {
+    fn from(message: &str) -> Self {
+        SourceError::new(message)
+    }
+}
+
+impl From<ParseError<'_>> for SourceError {
+    fn from(err: ParseError<'_>) -> Self {
+        SourceError {
+            file_name: Some(err.file_name),
+            span: Some(err.start..err.end),
+            message: err.message,
+        }
+    }
+}
+
+/// Outputs an error message to stderr, labelling `span` in the source if it is known.
+fn output_to_stderr(
+    file_name: Option<&str>,
+    contents: Option<&str>,
+    span: Option<Range<usize>>,
+    message: &str,
+) {
+    use codespan_reporting::diagnostic::{Diagnostic, Label};
+    use codespan_reporting::files::SimpleFiles;
+    use codespan_reporting::term;
+    use codespan_reporting::term::termcolor::{ColorChoice, StandardStream};
+
+    let config = term::Config::default();
+    let mut files = SimpleFiles::new();
+    let mut diagnostic = Diagnostic::error().with_message(message);
+    match (file_name, contents, span) {
+        (Some(file_name), Some(contents), Some(span)) if span.end <= contents.len() => {
+            let file_id = files.add(file_name, contents);
+            diagnostic = diagnostic.with_labels(vec![Label::primary(file_id, span)]);
+        }
+        (Some(file_name), _, _) => {
+            diagnostic = diagnostic.with_notes(vec![format!("in {file_name}")]);
+        }
+        (None, _, _) => {}
     }
+    let mut writer = StandardStream::stderr(ColorChoice::Always);
+    term::emit(&mut writer, &config, &files, &diagnostic).unwrap()
 }
 
 pub fn handle_parse_error<'a>(
@@ -4,11 +4,12 @@ use std::path::Path;
 
 use ast::analyzed::Analyzed;
 use number::FieldElement;
+use parser_util::SourceError;
 
-pub fn analyze<T: FieldElement>(path: &Path) -> Analyzed<T> {
+pub fn analyze<T: FieldElement>(path: &Path) -> Result<Analyzed<T>, Vec<SourceError>> {
     pil_analyzer::process_pil_file(path)
 }
 
-pub fn analyze_string<T: FieldElement>(contents: &str) -> Analyzed<T> {
+pub fn analyze_string<T: FieldElement>(contents: &str) -> Result<Analyzed<T>, Vec<SourceError>> {
     pil_analyzer::process_pil_file_contents(contents)
 }
@@ -1,3 +1,4 @@
+use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -8,6 +9,7 @@ use ast::parsed::{
     UnaryOperator,
 };
 use number::{BigInt, DegreeType, FieldElement};
+use parser_util::SourceError;
 
 use ast::analyzed::util::previsit_expressions_in_pil_file_mut;
 use ast::analyzed::{
@@ -16,16 +18,18 @@ use ast::analyzed::{
     SourceRef, StatementIdentifier,
 };
 
-pub fn process_pil_file<T: FieldElement>(path: &Path) -> Analyzed<T> {
+pub fn process_pil_file<T: FieldElement>(path: &Path) -> Result<Analyzed<T>, Vec<SourceError>> {
     let mut ctx = PILContext::new();
     ctx.process_file(path);
-    ctx.into()
+    ctx.into_analyzed()
 }
 
-pub fn process_pil_file_contents<T: FieldElement>(contents: &str) -> Analyzed<T> {
+pub fn process_pil_file_contents<T: FieldElement>(
+    contents: &str,
+) -> Result<Analyzed<T>, Vec<SourceError>> {
     let mut ctx = PILContext::new();
     ctx.process_file_contents(Path::new("input"), contents);
-    ctx.into()
+    ctx.into_analyzed()
 }
 
 #[derive(Default)]
@@ -43,6 +47,9 @@ struct PILContext<T> {
     included_files: HashSet<PathBuf>,
     line_starts: Vec<usize>,
     current_file: PathBuf,
+    /// The start of the statement currently being processed, used to report errors.
+    statement_start: usize,
+    errors: Vec<SourceError>,
     commit_poly_counter: u64,
     constant_poly_counter: u64,
     intermediate_poly_counter: u64,
@@ -51,81 +58,93 @@ struct PILContext<T> {
     macro_expander: MacroExpander<T>,
 }
 
-impl<T> From<PILContext<T>> for Analyzed<T> {
-    fn from(
+impl<T: FieldElement> PILContext<T> {
+    pub fn new() -> PILContext<T> {
         PILContext {
+            namespace: "Global".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Resolves the column references and returns the analyzed file,
+    /// or all the errors encountered during the analysis.
+    fn into_analyzed(self) -> Result<Analyzed<T>, Vec<SourceError>> {
+        let PILContext {
             constants,
             definitions,
             public_declarations,
             identities,
             source_order,
+            mut errors,
             ..
-        }: PILContext<T>,
-    ) -> Self {
+        } = self;
         let ids = definitions
             .iter()
             .map(|(name, (poly, _))| (name.clone(), poly.clone()))
             .collect::<HashMap<_, _>>();
-        let mut result = Self {
+        let mut result = Analyzed {
             constants,
             definitions,
             public_declarations,
             identities,
             source_order,
         };
-        let assign_id = |reference: &mut PolynomialReference| {
-            let poly = ids
+        let assign_id =
+            |reference: &mut PolynomialReference, errors: &mut Vec<SourceError>| match ids
                 .get(&reference.name)
-                .unwrap_or_else(|| panic!("Column {} not found.", reference.name));
-            reference.poly_id = Some(poly.into());
-        };
+            {
+                Some(poly) => reference.poly_id = Some(poly.into()),
+                None => errors.push(format!("Column {} not found.", reference.name).into()),
+            };
         previsit_expressions_in_pil_file_mut(&mut result, &mut |e| {
             if let Expression::PolynomialReference(reference) = e {
-                assign_id(reference);
+                assign_id(reference, &mut errors);
             }
             std::ops::ControlFlow::Continue::<()>(())
         });
         result
             .public_declarations
             .values_mut()
-            .for_each(|public_decl| assign_id(&mut public_decl.polynomial));
-        result
-    }
-}
-
-impl<T: FieldElement> PILContext<T> {
-    pub fn new() -> PILContext<T> {
-        PILContext {
-            namespace: "Global".to_string(),
-            ..Default::default()
+            .for_each(|public_decl| assign_id(&mut public_decl.polynomial, &mut errors));
+        if errors.is_empty() {
+            Ok(result)
+        } else {
+            Err(errors)
         }
     }
 
     pub fn process_file(&mut self, path: &Path) {
-        let path = path
-            .canonicalize()
-            .unwrap_or_else(|e| panic!("File {path:?} not found: {e}"));
+        let path = match path.canonicalize() {
+            Ok(path) => path,
+            Err(e) => {
+                self.error(format!("File {path:?} not found: {e}"));
+                return;
+            }
+        };
         if !self.included_files.insert(path.clone()) {
             return;
         }
-        let contents = fs::read_to_string(path.clone()).unwrap();
-
-        self.process_file_contents(&path, &contents);
+        match fs::read_to_string(&path) {
+            Ok(contents) => self.process_file_contents(&path, &contents),
+            Err(e) => self.error(format!("Could not read file {path:?}: {e}")),
+        }
     }
 
     pub fn process_file_contents(&mut self, path: &Path, contents: &str) {
+        let pil_file = match parser::parse(Some(path.to_str().unwrap()), contents) {
+            Ok(pil_file) => pil_file,
+            Err(err) => {
+                self.errors.push(err.into());
+                return;
+            }
+        };
+
         let old_current_file = std::mem::take(&mut self.current_file);
         let old_line_starts = std::mem::take(&mut self.line_starts);
 
         // TODO make this work for other line endings
         self.line_starts = parser_util::lines::compute_line_starts(contents);
         self.current_file = path.to_path_buf();
-        let pil_file =
-            parser::parse(Some(path.to_str().unwrap()), contents).unwrap_or_else(|err| {
-                eprintln!("Error parsing .pil file:");
-                err.output_to_stderr();
-                panic!();
-            });
 
         for statement in pil_file.0 {
             for statement in self.macro_expander.expand_macros(vec![statement]) {
@@ -137,7 +156,18 @@ impl<T: FieldElement> PILContext<T> {
         self.line_starts = old_line_starts;
     }
 
+    /// Records an error at the statement currently being processed.
+    fn error(&mut self, message: String) {
+        let error = SourceError::at(self.statement_start, message);
+        self.errors
+            .push(match self.current_file.to_str().filter(|f| !f.is_empty()) {
+                Some(file_name) => error.in_file(file_name),
+                None => error,
+            });
+    }
+
     fn handle_statement(&mut self, statement: PilStatement<T>) {
+        self.statement_start = statement.start();
         match statement {
             PilStatement::Include(_, include) => self.handle_include(include),
             PilStatement::Namespace(_, name, degree) => self.handle_namespace(name, degree),
@@ -266,7 +296,7 @@ impl<T: FieldElement> PILContext<T> {
 
     fn handle_namespace(&mut self, name: String, degree: ::ast::parsed::Expression<T>) {
         // TODO: the polynomial degree should be handled without going through a field element. This requires having types in Expression
-        self.polynomial_degree = self.evaluate_expression(&degree).unwrap().to_degree();
+        self.polynomial_degree = self.evaluate_constant_expression(&degree).to_degree();
         self.namespace = name;
     }
 
@@ -296,9 +326,7 @@ impl<T: FieldElement> PILContext<T> {
         value: Option<FunctionDefinition<T>>,
     ) -> u64 {
         let have_array_size = array_size.is_some();
-        let length = array_size
-            .map(|l| self.evaluate_expression(&l).unwrap())
-            .map(|l| l.to_degree());
+        let length = array_size.map(|l| self.evaluate_constant_expression(&l).to_degree());
         if length.is_some() {
             assert!(value.is_none());
         }
@@ -344,13 +372,13 @@ impl<T: FieldElement> PILContext<T> {
                 FunctionValueDefinition::Array(expression)
             }
         });
-        let is_new = self
-            .definitions
-            .insert(name.clone(), (poly, value))
-            .is_none();
-        assert!(is_new, "{name} aready defined.");
-        self.source_order
-            .push(StatementIdentifier::Definition(name));
+        if self.definitions.contains_key(&name) {
+            self.error(format!("{name} already defined."));
+        } else {
+            self.definitions.insert(name.clone(), (poly, value));
+            self.source_order
+                .push(StatementIdentifier::Definition(name));
+        }
         id
     }
 
@@ -378,14 +406,16 @@ impl<T: FieldElement> PILContext<T> {
         index: ::ast::parsed::Expression<T>,
     ) {
         let id = self.public_declarations.len() as u64;
+        let polynomial = self.process_namespaced_polynomial_reference(poly);
+        let index = self.evaluate_constant_expression(&index).to_degree();
         self.public_declarations.insert(
             name.to_string(),
             PublicDeclaration {
                 id,
                 source,
                 name: name.to_string(),
-                polynomial: self.process_namespaced_polynomial_reference(poly),
-                index: self.evaluate_expression(&index).unwrap().to_degree(),
+                polynomial,
+                index,
             },
         );
         self.source_order
@@ -394,11 +424,16 @@ impl<T: FieldElement> PILContext<T> {
 
     fn handle_constant_definition(&mut self, name: String, value: ::ast::parsed::Expression<T>) {
         // TODO does the order matter here?
-        let is_new = self
-            .constants
-            .insert(name.to_string(), self.evaluate_expression(&value).unwrap())
-            .is_none();
-        assert!(is_new, "Constant {name} was defined twice.");
+        let value = self.evaluate_constant_expression(&value);
+        match self.constants.entry(name) {
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+            }
+            Entry::Occupied(entry) => {
+                let name = entry.key().clone();
+                self.error(format!("Constant {name} was defined twice."));
+            }
+        }
     }
 
     fn dispense_id(&mut self, kind: IdentityKind) -> u64 {
@@ -512,7 +547,10 @@ impl<T: FieldElement> PILContext<T> {
                         (
                             n.map(|n| {
                                 self.evaluate_expression(&n).unwrap_or_else(|| {
-                                    panic!("Left side of match arm must be a constant, found {n}")
+                                    self.error(format!(
+                                        "Left side of match arm must be a constant, found {n}"
+                                    ));
+                                    0.into()
                                 })
                             }),
                             self.process_expression(e),
@@ -525,14 +563,13 @@ impl<T: FieldElement> PILContext<T> {
     }
 
     fn process_namespaced_polynomial_reference(
-        &self,
+        &mut self,
         poly: ::ast::parsed::NamespacedPolynomialReference<T>,
     ) -> PolynomialReference {
         let index = poly
             .index()
             .as_ref()
-            .map(|i| self.evaluate_expression(i).unwrap())
-            .map(|i| i.to_degree());
+            .map(|i| self.evaluate_constant_expression(i).to_degree());
         let name = self.namespaced_ref(poly.namespace(), poly.name());
         PolynomialReference {
             name,
@@ -543,7 +580,7 @@ impl<T: FieldElement> PILContext<T> {
     }
 
     fn process_shifted_polynomial_reference(
-        &self,
+        &mut self,
         poly: ::ast::parsed::ShiftedPolynomialReference<T>,
     ) -> PolynomialReference {
         PolynomialReference {
@@ -552,15 +589,22 @@ impl<T: FieldElement> PILContext<T> {
         }
     }
 
-    fn evaluate_expression(&self, expr: &::ast::parsed::Expression<T>) -> Option<T> {
+    /// Evaluates an expression that has to be constant, recording an error
+    /// (and returning zero) if it is not.
+    fn evaluate_constant_expression(&mut self, expr: &::ast::parsed::Expression<T>) -> T {
+        self.evaluate_expression(expr).unwrap_or_else(|| {
+            self.error(format!("Expected a constant expression, found {expr}"));
+            0.into()
+        })
+    }
+
+    fn evaluate_expression(&mut self, expr: &::ast::parsed::Expression<T>) -> Option<T> {
         use ::ast::parsed::Expression::*;
         match expr {
-            Constant(name) => Some(
-                *self
-                    .constants
-                    .get(name)
-                    .unwrap_or_else(|| panic!("Constant {name} not found.")),
-            ),
+            Constant(name) => Some(self.constants.get(name).cloned().unwrap_or_else(|| {
+                self.error(format!("Constant {name} not found."));
+                0.into()
+            })),
             PolynomialReference(_) => None,
             PublicReference(_) => None,
             Number(n) => Some(*n),
@@ -575,7 +619,7 @@ impl<T: FieldElement> PILContext<T> {
     }
 
     fn evaluate_binary_operation(
-        &self,
+        &mut self,
         left: &::ast::parsed::Expression<T>,
         op: BinaryOperator,
         right: &::ast::parsed::Expression<T>,
@@ -617,7 +661,7 @@ impl<T: FieldElement> PILContext<T> {
     }
 
     fn evaluate_unary_operation(
-        &self,
+        &mut self,
         op: UnaryOperator,
         value: &::ast::parsed::Expression<T>,
     ) -> Option<T> {
@@ -680,7 +724,9 @@ namespace T(65536);
     col fixed p_reg_write_X_CNT = [1, 0, 0, 0, 0, 0, 0, 0, 0] + [0]*;
     { T.pc, T.reg_write_X_A, T.reg_write_X_CNT } in (1 - T.first_step) { T.line, T.p_reg_write_X_A, T.p_reg_write_X_CNT };
 "#;
-        let formatted = process_pil_file_contents::<GoldilocksField>(input).to_string();
+        let formatted = process_pil_file_contents::<GoldilocksField>(input)
+            .unwrap()
+            .to_string();
         if input != formatted {
             for (i, f) in input.split('\n').zip(formatted.split('\n')) {
                 assert_eq!(i, f);
@@ -688,4 +734,28 @@ namespace T(65536);
         }
         assert_eq!(input, formatted);
     }
+
+    #[test]
+    fn report_errors() {
+        let input = r#"constant %N = 16;
+namespace T(%N);
+    col witness x;
+    col witness x;
+    col fixed f(i) { i * %M };
+    x = y;
+"#;
+        let errors = process_pil_file_contents::<GoldilocksField>(input)
+            .err()
+            .unwrap();
+        let messages = errors.iter().map(|e| &e.message[..]).collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "T.x already defined.",
+                "Constant %M not found.",
+                "Column T.y not found."
+            ]
+        );
+        assert_eq!(errors[0].span, Some(58..58));
+    }
 }
@@ -0,0 +1,438 @@
+//! Reduces the degree of identities by introducing auxiliary witness columns.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Display, Formatter};
+
+use ast::analyzed::{
+    build::{build_binary_expr, build_mul, build_number, build_sub},
+    Analyzed, BinaryOperator, Expression, FunctionValueDefinition, IdentityKind,
+    PolynomialReference, PolynomialType, SelectedExpressions, SourceRef,
+};
+use itertools::Itertools;
+use number::FieldElement;
+
+/// The degrees of the identities before and after the degree reduction.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DegreeReport {
+    /// The number of identities by their degree, before the reduction.
+    pub before: BTreeMap<usize, usize>,
+    /// The number of identities by their degree, after the reduction.
+    pub after: BTreeMap<usize, usize>,
+    /// The number of witness columns added.
+    pub added_columns: usize,
+}
+
+impl DegreeReport {
+    pub fn max_degree_before(&self) -> usize {
+        self.before.keys().last().cloned().unwrap_or_default()
+    }
+
+    pub fn max_degree_after(&self) -> usize {
+        self.after.keys().last().cloned().unwrap_or_default()
+    }
+}
+
+impl Display for DegreeReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let format_degrees = |degrees: &BTreeMap<usize, usize>| {
+            degrees
+                .iter()
+                .map(|(degree, count)| format!("{count} of degree {degree}"))
+                .join(", ")
+        };
+        writeln!(
+            f,
+            "Reduced the maximum degree of the identities from {} to {} by adding {} witness columns.",
+            self.max_degree_before(),
+            self.max_degree_after(),
+            self.added_columns
+        )?;
+        writeln!(f, "Identities before: {}", format_degrees(&self.before))?;
+        write!(f, "Identities after: {}", format_degrees(&self.after))
+    }
+}
+
+/// Splits the identities whose degree is larger than `max_degree` by replacing
+/// products with new witness columns, which are constrained to be equal to the products.
+/// The new columns are computed by witness generation like any other column.
+/// For lookups and permutations, the degree of a side is the degree of its selector
+/// plus the largest degree of its expressions.
+/// Intermediate columns count with the degree of their definition and are replaced
+/// by a witness column if their degree is too large.
+/// Powers with a non-constant or too large exponent cannot be reduced, so the
+/// maximum degree in the returned report can still be larger than `max_degree`.
+pub fn reduce_degree<T: FieldElement>(
+    mut pil_file: Analyzed<T>,
+    max_degree: usize,
+) -> (Analyzed<T>, DegreeReport) {
+    assert!(
+        max_degree >= 2,
+        "The maximum degree has to be at least 2, but is {max_degree}."
+    );
+    let mut reducer = DegreeReducer {
+        pil_file: &mut pil_file,
+        max_degree,
+        columns: Default::default(),
+        added_columns: 0,
+    };
+    let before = reducer.identity_degrees();
+    // The identities added during the reduction already have a small enough degree.
+    for index in 0..reducer.pil_file.identities.len() {
+        reducer.reduce_identity(index);
+    }
+    let after = reducer.identity_degrees();
+    let report = DegreeReport {
+        before,
+        after,
+        added_columns: reducer.added_columns,
+    };
+    (pil_file, report)
+}
+
+/// The largest exponent of a power that is expanded into a product to reduce its degree.
+/// The product has as many factors as the exponent, and no backend supports identities
+/// of a larger degree anyway.
+const MAX_EXPANDED_EXPONENT: u64 = 64;
+
+struct DegreeReducer<'a, T> {
+    pil_file: &'a mut Analyzed<T>,
+    max_degree: usize,
+    /// The columns added for products and intermediate columns, by the expression
+    /// (or the name of the intermediate column) they are equal to.
+    columns: HashMap<String, PolynomialReference>,
+    added_columns: usize,
+}
+
+impl<'a, T: FieldElement> DegreeReducer<'a, T> {
+    fn identity_degrees(&self) -> BTreeMap<usize, usize> {
+        let mut degrees = BTreeMap::new();
+        for identity in &self.pil_file.identities {
+            let degree = match identity.kind {
+                IdentityKind::Polynomial => self.degree(identity.expression_for_poly_id()),
+                IdentityKind::Plookup | IdentityKind::Permutation => self
+                    .side_degree(&identity.left)
+                    .max(self.side_degree(&identity.right)),
+                IdentityKind::Connect => 1,
+            };
+            *degrees.entry(degree).or_default() += 1;
+        }
+        degrees
+    }
+
+    fn side_degree(&self, side: &SelectedExpressions<T>) -> usize {
+        side.selector.as_ref().map_or(0, |s| self.degree(s))
+            + side
+                .expressions
+                .iter()
+                .map(|e| self.degree(e))
+                .max()
+                .unwrap_or_default()
+    }
+
+    fn degree(&self, e: &Expression<T>) -> usize {
+        match e {
+            Expression::PolynomialReference(poly) => self
+                .intermediate_definition(poly)
+                .map_or(1, |definition| self.degree(definition)),
+            Expression::BinaryOperation(left, op, right) => {
+                let (left_degree, right_degree) = (self.degree(left), self.degree(right));
+                match op {
+                    BinaryOperator::Mul => left_degree + right_degree,
+                    BinaryOperator::Pow => match self.constant_exponent(right) {
+                        Some(exponent) => left_degree
+                            .saturating_mul(usize::try_from(exponent).unwrap_or(usize::MAX)),
+                        None => left_degree.max(right_degree),
+                    },
+                    _ => left_degree.max(right_degree),
+                }
+            }
+            Expression::UnaryOperation(_, inner) => self.degree(inner),
+            _ => 0,
+        }
+    }
+
+    fn intermediate_definition(&self, poly: &PolynomialReference) -> Option<&Expression<T>> {
+        match self.pil_file.definitions.get(&poly.name) {
+            Some((p, Some(FunctionValueDefinition::Mapping(definition))))
+                if p.poly_type == PolynomialType::Intermediate && poly.index.is_none() =>
+            {
+                Some(definition)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the exponent if it is a constant that fits into a `u64`.
+    fn constant_exponent(&self, e: &Expression<T>) -> Option<u64> {
+        let value = match e {
+            Expression::Number(n) => n,
+            Expression::Constant(name) => self.pil_file.constants.get(name)?,
+            _ => return None,
+        };
+        u64::try_from(value.to_arbitrary_integer()).ok()
+    }
+
+    fn reduce_identity(&mut self, index: usize) {
+        let mut identity = self.pil_file.identities[index].clone();
+        match identity.kind {
+            IdentityKind::Polynomial => {
+                let expr = identity.left.selector.take().unwrap();
+                identity.left.selector = Some(self.reduce(expr, self.max_degree, &identity.source));
+            }
+            IdentityKind::Plookup | IdentityKind::Permutation => {
+                for side in [&mut identity.left, &mut identity.right] {
+                    self.reduce_side(side, &identity.source);
+                }
+            }
+            IdentityKind::Connect => {}
+        }
+        self.pil_file.identities[index] = identity;
+    }
+
+    fn reduce_side(&mut self, side: &mut SelectedExpressions<T>, source: &SourceRef) {
+        if self.side_degree(side) <= self.max_degree {
+            return;
+        }
+        let mut selector_degree = 0;
+        if let Some(selector) = side.selector.take() {
+            let selector = self.reduce(selector, 1, source);
+            selector_degree = self.degree(&selector);
+            side.selector = Some(selector);
+        }
+        side.expressions = std::mem::take(&mut side.expressions)
+            .into_iter()
+            .map(|e| self.reduce(e, self.max_degree - selector_degree, source))
+            .collect();
+    }
+
+    /// Returns an expression equal to `e` of degree at most `target`, if possible.
+    fn reduce(&mut self, e: Expression<T>, target: usize, source: &SourceRef) -> Expression<T> {
+        if self.degree(&e) <= target {
+            return e;
+        }
+        match e {
+            Expression::PolynomialReference(poly) => {
+                // Only intermediate columns can have a degree larger than one.
+                let column = self.intermediate_column(&poly, source);
+                Expression::PolynomialReference(PolynomialReference {
+                    next: poly.next,
+                    ..column
+                })
+            }
+            Expression::BinaryOperation(left, BinaryOperator::Mul, right) => {
+                let (left_degree, right_degree) = (self.degree(&left), self.degree(&right));
+                let (left_target, right_target) = if left_degree < target {
+                    (left_degree, target - left_degree)
+                } else if right_degree < target {
+                    (target - right_degree, right_degree)
+                } else if target == 1 {
+                    return self.column_for(build_mul(*left, *right), source);
+                } else {
+                    (target / 2, target - target / 2)
+                };
+                build_mul(
+                    self.reduce(*left, left_target, source),
+                    self.reduce(*right, right_target, source),
+                )
+            }
+            Expression::BinaryOperation(base, BinaryOperator::Pow, exponent) => {
+                match self.constant_exponent(&exponent) {
+                    Some(exponent) if exponent <= MAX_EXPANDED_EXPONENT => {
+                        self.reduce(power(*base, exponent), target, source)
+                    }
+                    _ => build_binary_expr(*base, BinaryOperator::Pow, *exponent),
+                }
+            }
+            Expression::BinaryOperation(left, op, right) => build_binary_expr(
+                self.reduce(*left, target, source),
+                op,
+                self.reduce(*right, target, source),
+            ),
+            Expression::UnaryOperation(op, inner) => {
+                Expression::UnaryOperation(op, Box::new(self.reduce(*inner, target, source)))
+            }
+            e => e,
+        }
+    }
+
+    /// Returns a reference to a new witness column constrained to be equal to `e`.
+    /// Re-uses the column if the same expression was replaced before.
+    fn column_for(&mut self, e: Expression<T>, source: &SourceRef) -> Expression<T> {
+        let key = e.to_string();
+        if let Some(column) = self.columns.get(&key) {
+            return Expression::PolynomialReference(column.clone());
+        }
+        let (namespace, degree) = self.location_of(&e);
+        let column = self.add_column(&namespace, degree, e, source);
+        self.columns.insert(key, column.clone());
+        Expression::PolynomialReference(column)
+    }
+
+    /// Returns the witness column that replaces the intermediate column `poly`.
+    fn intermediate_column(
+        &mut self,
+        poly: &PolynomialReference,
+        source: &SourceRef,
+    ) -> PolynomialReference {
+        if let Some(column) = self.columns.get(&poly.name) {
+            return column.clone();
+        }
+        let definition = self.intermediate_definition(poly).unwrap().clone();
+        let (intermediate, _) = &self.pil_file.definitions[&poly.name];
+        let degree = intermediate.degree;
+        let namespace = namespace_of(&poly.name).to_string();
+        let column = self.add_column(&namespace, degree, definition, source);
+        self.columns.insert(poly.name.clone(), column.clone());
+        column
+    }
+
+    /// Adds a witness column to `namespace` and constrains it to be equal to `e`.
+    fn add_column(
+        &mut self,
+        namespace: &str,
+        degree: u64,
+        e: Expression<T>,
+        source: &SourceRef,
+    ) -> PolynomialReference {
+        let e = self.reduce(e, self.max_degree, source);
+        let name = format!("{namespace}.__aux_{}", self.added_columns);
+        self.added_columns += 1;
+        let column = self
+            .pil_file
+            .append_witness_column(name, degree, source.clone());
+        self.pil_file.append_polynomial_identity(
+            build_sub(Expression::PolynomialReference(column.clone()), e),
+            source.clone(),
+        );
+        column
+    }
+
+    /// Returns the namespace and degree of the first column referenced in `e`.
+    fn location_of(&self, e: &Expression<T>) -> (String, u64) {
+        let mut location = None;
+        ast::analyzed::util::expr_any(e, |e| {
+            if let Expression::PolynomialReference(poly) = e {
+                let (definition, _) = &self.pil_file.definitions[&poly.name];
+                location = Some((namespace_of(&poly.name).to_string(), definition.degree));
+                true
+            } else {
+                false
+            }
+        });
+        location.expect("Products of constants have degree zero.")
+    }
+}
+
+/// Returns `base ** exponent` as a balanced product.
+fn power<T: FieldElement>(base: Expression<T>, exponent: u64) -> Expression<T> {
+    match exponent {
+        0 => build_number(1),
+        1 => base,
+        _ => build_mul(
+            power(base.clone(), exponent / 2),
+            power(base, exponent - exponent / 2),
+        ),
+    }
+}
+
+fn namespace_of(name: &str) -> &str {
+    name.find('.').map_or("Global", |dot| &name[..dot])
+}
+
+#[cfg(test)]
+mod test {
+    use number::GoldilocksField;
+    use pil_analyzer::pil_analyzer::process_pil_file_contents;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn split_products() {
+        let input = r#"namespace N(8);
+    col fixed FIRST = [1] + [0]*;
+    col witness X;
+    col witness Y;
+    col witness Z;
+    col XY = X * Y;
+    FIRST * X * Y * Z = 0;
+    Z = X ** 4;
+    { X * XY } in { Y };
+"#;
+        let expectation = r#"namespace N(8);
+    col fixed FIRST = [1] + [0]*;
+    col witness X;
+    col witness Y;
+    col witness Z;
+    col XY(i) { (N.X * N.Y) };
+    (N.__aux_1 * N.Z) = 0;
+    N.Z = (N.__aux_2 * N.__aux_2);
+    { (N.X * N.__aux_3) } in { N.Y };
+    col witness __aux_0;
+    N.__aux_0 = (N.FIRST * N.X);
+    col witness __aux_1;
+    N.__aux_1 = (N.__aux_0 * N.Y);
+    col witness __aux_2;
+    N.__aux_2 = (N.X * N.X);
+    col witness __aux_3;
+    N.__aux_3 = (N.X * N.Y);
+"#;
+        let (reduced, report) = reduce_degree(
+            process_pil_file_contents::<GoldilocksField>(input).unwrap(),
+            2,
+        );
+        assert_eq!(reduced.to_string(), expectation);
+        assert_eq!(report.max_degree_before(), 4);
+        assert_eq!(report.max_degree_after(), 2);
+        assert_eq!(report.added_columns, 4);
+    }
+
+    #[test]
+    fn columns_in_namespace_of_identity() {
+        let input = r#"namespace A(8);
+    col witness X;
+    X * X * X = 1;
+namespace B(4);
+    col witness Y;
+    Y = 1;
+"#;
+        let expectation = r#"namespace A(8);
+    col witness X;
+    (A.__aux_0 * A.X) = 1;
+namespace B(4);
+    col witness Y;
+    B.Y = 1;
+namespace A(8);
+    col witness __aux_0;
+    A.__aux_0 = (A.X * A.X);
+"#;
+        let (reduced, _) = reduce_degree(
+            process_pil_file_contents::<GoldilocksField>(input).unwrap(),
+            2,
+        );
+        assert_eq!(reduced.to_string(), expectation);
+        let reparsed = process_pil_file_contents::<GoldilocksField>(expectation).unwrap();
+        assert_eq!(reparsed.to_string(), expectation);
+    }
+
+    #[test]
+    fn large_exponents() {
+        // The second exponent is -1 in the field.
+        let input = r#"namespace N(8);
+    col witness X;
+    X ** 100 = 1;
+    X ** 18446744069414584320 = 1;
+"#;
+        let (reduced, report) = reduce_degree(
+            process_pil_file_contents::<GoldilocksField>(input).unwrap(),
+            2,
+        );
+        assert_eq!(report.added_columns, 0);
+        assert_eq!(report.max_degree_before(), 18446744069414584320);
+        assert_eq!(report.max_degree_after(), 18446744069414584320);
+        assert_eq!(
+            reduced.to_string(),
+            "namespace N(8);\n    col witness X;\n    (N.X ** 100) = 1;\n    (N.X ** -1) = 1;\n"
+        );
+    }
+}
@@ -1,5 +1,9 @@
 //! PIL-based optimizer
 
+mod degree_reduction;
+
+pub use degree_reduction::{reduce_degree, DegreeReport};
+
 use std::collections::{BTreeMap, HashSet};
 use std::ops::ControlFlow;
 
@@ -377,7 +381,8 @@ mod test {
     N.X = N.Y;
     N.Y = (7 * N.X);
 "#;
-        let optimized = optimize(process_pil_file_contents::<GoldilocksField>(input)).to_string();
+        let optimized =
+            optimize(process_pil_file_contents::<GoldilocksField>(input).unwrap()).to_string();
         assert_eq!(optimized, expectation);
     }
 
@@ -411,7 +416,8 @@ mod test {
     N.A = (1 + N.A);
     N.Z = (1 + N.A);
 "#;
-        let optimized = optimize(process_pil_file_contents::<GoldilocksField>(input)).to_string();
+        let optimized =
+            optimize(process_pil_file_contents::<GoldilocksField>(input).unwrap()).to_string();
         assert_eq!(optimized, expectation);
     }
 }
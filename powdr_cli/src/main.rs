@@ -2,16 +2,17 @@
 
 mod util;
 
-use backend::{Backend, BackendType};
+use backend::{AggregationInput, Backend, BackendType};
 use clap::{CommandFactory, Parser, Subcommand};
-use compiler::util::{read_poly_set, FixedPolySet, WitnessPolySet};
-use compiler::{compile_pil_or_asm, write_proving_results_to_fs};
+use compiler::manifest::Manifest;
+use compiler::util::{read_poly_set, read_publics, write_publics, FixedPolySet, WitnessPolySet};
+use compiler::{compile_pil_or_asm, write_proving_results_to_fs, Inputs, LinkerMode};
 use env_logger::fmt::Color;
 use env_logger::{Builder, Target};
 use log::LevelFilter;
 use number::{Bn254Field, FieldElement, GoldilocksField};
-use riscv::{compile_riscv_asm, compile_rust};
-use std::io::{self, BufWriter, Read};
+use riscv::{compile_elf, compile_riscv_asm, compile_rust, ExecutionMode};
+use std::io::{self, BufReader, BufWriter, Read};
 use std::{borrow::Cow, collections::HashSet, fs, io::Write, path::Path};
 use strum::{Display, EnumString, EnumVariantNames};
 
@@ -63,8 +64,11 @@ enum Commands {
         #[arg(default_value_t = String::from("."))]
         output_directory: String,
 
-        /// Comma-separated list of free inputs (numbers). Assumes queries to have the form
-        /// ("input", <index>).
+        /// Comma-separated list of free inputs (numbers), answering queries of the form
+        /// ("input", <index>). Alternatively, the path to a JSON file containing an array of
+        /// values, or an object mapping names to arrays of values for queries of the form
+        /// ("input", "<name>", <index>). Any other file is read as a stream of bytes, answering
+        /// queries of the form ("data", <index>).
         #[arg(short, long)]
         #[arg(default_value_t = String::new())]
         inputs: String,
@@ -79,6 +83,12 @@ enum Commands {
         #[arg(value_parser = clap_enum_variants!(BackendType))]
         prove_with: Option<BackendType>,
 
+        /// Split identities of a larger degree by adding witness columns. Defaults to
+        /// the maximum degree of the backend given by --prove-with, if it is limited.
+        #[arg(long)]
+        #[arg(value_parser = clap::value_parser!(u64).range(2..))]
+        max_degree: Option<u64>,
+
         /// Generate a CSV file containing the fixed and witness column values. Useful for debugging purposes.
         #[arg(long)]
         #[arg(default_value_t = false)]
@@ -89,6 +99,13 @@ enum Commands {
         #[arg(default_value_t = CsvRenderMode::Hex)]
         #[arg(value_parser = clap_enum_variants!(CsvRenderMode))]
         csv_mode: CsvRenderMode,
+
+        /// How to link the machines of an .asm file: "monolithic" uses the degree of the main
+        /// machine everywhere, "composite" keeps the degree of each machine.
+        #[arg(long)]
+        #[arg(default_value_t = LinkerMode::Monolithic)]
+        #[arg(value_parser = clap_enum_variants!(LinkerMode))]
+        linker_mode: LinkerMode,
     },
     /// Compiles (no-std) rust code to riscv assembly, then to powdr assembly
     /// and finally to PIL and generates fixed and witness columns.
@@ -103,7 +120,8 @@ enum Commands {
         #[arg(value_parser = clap_enum_variants!(FieldArgument))]
         field: FieldArgument,
 
-        /// Comma-separated list of free inputs (numbers).
+        /// Comma-separated list of free inputs (numbers), or the path to a file containing
+        /// the inputs (see the `pil` command).
         #[arg(short, long)]
         #[arg(default_value_t = String::new())]
         inputs: String,
@@ -122,6 +140,15 @@ enum Commands {
         #[arg(short, long)]
         #[arg(value_parser = clap_enum_variants!(BackendType))]
         prove_with: Option<BackendType>,
+
+        /// How to run the program: "witgen" generates the witness from the constraints,
+        /// "execute" only executes the program and reports its cycles, "trace" executes
+        /// the program first and uses its trace for witness generation, "profile" executes
+        /// the program and reports the cycles spent in each function and source line.
+        #[arg(long)]
+        #[arg(default_value_t = ExecutionMode::Witgen)]
+        #[arg(value_parser = clap_enum_variants!(ExecutionMode))]
+        mode: ExecutionMode,
     },
 
     /// Compiles riscv assembly to powdr assembly and then to PIL
@@ -137,7 +164,8 @@ enum Commands {
         #[arg(value_parser = clap_enum_variants!(FieldArgument))]
         field: FieldArgument,
 
-        /// Comma-separated list of free inputs (numbers).
+        /// Comma-separated list of free inputs (numbers), or the path to a file containing
+        /// the inputs (see the `pil` command).
         #[arg(short, long)]
         #[arg(default_value_t = String::new())]
         inputs: String,
@@ -156,6 +184,58 @@ enum Commands {
         #[arg(short, long)]
         #[arg(value_parser = clap_enum_variants!(BackendType))]
         prove_with: Option<BackendType>,
+
+        /// How to run the program: "witgen" generates the witness from the constraints,
+        /// "execute" only executes the program and reports its cycles, "trace" executes
+        /// the program first and uses its trace for witness generation, "profile" executes
+        /// the program and reports the cycles spent in each function and source line.
+        #[arg(long)]
+        #[arg(default_value_t = ExecutionMode::Witgen)]
+        #[arg(value_parser = clap_enum_variants!(ExecutionMode))]
+        mode: ExecutionMode,
+    },
+
+    /// Compiles a statically linked RV32IM ELF binary to powdr assembly
+    /// and then to PIL and generates fixed and witness columns.
+    Elf {
+        /// Input file (ELF binary)
+        file: String,
+
+        /// The field to use
+        #[arg(long)]
+        #[arg(default_value_t = FieldArgument::Gl)]
+        #[arg(value_parser = clap_enum_variants!(FieldArgument))]
+        field: FieldArgument,
+
+        /// Comma-separated list of free inputs (numbers), or the path to a file containing
+        /// the inputs (see the `pil` command).
+        #[arg(short, long)]
+        #[arg(default_value_t = String::new())]
+        inputs: String,
+
+        /// Directory for output files.
+        #[arg(short, long)]
+        #[arg(default_value_t = String::from("."))]
+        output_directory: String,
+
+        /// Force overwriting of files in output directory.
+        #[arg(short, long)]
+        #[arg(default_value_t = false)]
+        force: bool,
+
+        /// Generate a proof with a given backend.
+        #[arg(short, long)]
+        #[arg(value_parser = clap_enum_variants!(BackendType))]
+        prove_with: Option<BackendType>,
+
+        /// How to run the program: "witgen" generates the witness from the constraints,
+        /// "execute" only executes the program and reports its cycles, "trace" executes
+        /// the program first and uses its trace for witness generation, "profile" executes
+        /// the program and reports the cycles spent in each function and source line.
+        #[arg(long)]
+        #[arg(default_value_t = ExecutionMode::Witgen)]
+        #[arg(value_parser = clap_enum_variants!(ExecutionMode))]
+        mode: ExecutionMode,
     },
 
     Prove {
@@ -185,6 +265,131 @@ enum Commands {
         /// File containing previously generated setup parameters.
         #[arg(long)]
         params: Option<String>,
+
+        /// File containing the proving and verifying keys for the PIL, the fixed columns
+        /// and the setup parameters. If it does not exist, the keys are generated and
+        /// written to it, so that later proofs of the same program can reuse them.
+        #[arg(long)]
+        #[arg(requires = "params")]
+        keys: Option<String>,
+    },
+
+    /// Verifies a proof generated by the `prove` command.
+    Verify {
+        /// Input PIL file
+        file: String,
+
+        /// Directory to find the fixed values, the public values and the proof
+        #[arg(short, long)]
+        #[arg(default_value_t = String::from("."))]
+        dir: String,
+
+        /// The field to use
+        #[arg(long)]
+        #[arg(default_value_t = FieldArgument::Gl)]
+        #[arg(value_parser = clap_enum_variants!(FieldArgument))]
+        field: FieldArgument,
+
+        /// The backend the proof was generated with.
+        #[arg(short, long)]
+        #[arg(value_parser = clap_enum_variants!(BackendType))]
+        backend: BackendType,
+
+        /// File containing the proof.
+        #[arg(long)]
+        #[arg(default_value_t = String::from("proof.bin"))]
+        proof: String,
+
+        /// File containing the setup parameters the proof was generated with.
+        #[arg(long)]
+        params: String,
+
+        /// File containing the keys written by the `prove` command.
+        /// If not given, the verifying key is generated.
+        #[arg(long)]
+        keys: Option<String>,
+    },
+
+    /// Exports a contract verifying proofs in the EVM, as Yul source (verifier.yul) and,
+    /// if solc is installed, as deployment code (verifier.bin). With the halo2 backend,
+    /// aggregated proofs can be verified; use halo2-evm for proofs without aggregation.
+    ExportVerifier {
+        /// Input PIL file
+        file: String,
+
+        /// Directory to find the fixed values and the public values, and to write the verifier to
+        #[arg(short, long)]
+        #[arg(default_value_t = String::from("."))]
+        dir: String,
+
+        /// The field to use
+        #[arg(long)]
+        #[arg(default_value_t = FieldArgument::Gl)]
+        #[arg(value_parser = clap_enum_variants!(FieldArgument))]
+        field: FieldArgument,
+
+        /// The backend the proofs are generated with.
+        #[arg(short, long)]
+        #[arg(value_parser = clap_enum_variants!(BackendType))]
+        backend: BackendType,
+
+        /// File containing the setup parameters the proofs are generated with.
+        #[arg(long)]
+        params: String,
+
+        /// File containing the keys written by the `prove` command.
+        /// If not given, the verifying key is generated.
+        #[arg(long)]
+        keys: Option<String>,
+
+        /// File containing a proof. If given, the calldata to verify it together with
+        /// the public values is written to calldata.bin.
+        #[arg(long)]
+        proof: Option<String>,
+    },
+
+    /// Aggregates proofs generated by the `prove` command, possibly of different programs,
+    /// into a single proof in a tree. Writes the proof (proof_aggr.bin), its public
+    /// instances (instances.json) and a contract verifying it in the EVM (verifier.yul and,
+    /// if solc is installed, verifier.bin).
+    Aggregate {
+        /// The PIL file of each proof, in the order of the proofs. The fixed values and
+        /// the public values are read from the directory of the PIL file.
+        #[arg(long = "pil", required = true)]
+        pils: Vec<String>,
+
+        /// The proofs to aggregate, one for every PIL file.
+        #[arg(long = "proof", required = true)]
+        proofs: Vec<String>,
+
+        /// The keys written by the `prove` command, either none or one for every PIL file.
+        #[arg(long)]
+        keys: Vec<String>,
+
+        /// Directory to find the setup parameters and to write the results to
+        #[arg(short, long)]
+        #[arg(default_value_t = String::from("."))]
+        dir: String,
+
+        /// The field to use
+        #[arg(long)]
+        #[arg(default_value_t = FieldArgument::Gl)]
+        #[arg(value_parser = clap_enum_variants!(FieldArgument))]
+        field: FieldArgument,
+
+        /// The backend the proofs were generated with.
+        #[arg(short, long)]
+        #[arg(value_parser = clap_enum_variants!(BackendType))]
+        backend: BackendType,
+
+        /// File containing the setup parameters the proofs were generated with.
+        #[arg(long)]
+        params: String,
+
+        /// The maximum number of proofs aggregated by a node of the tree.
+        #[arg(long)]
+        #[arg(default_value_t = 2)]
+        arity: usize,
     },
 
     Setup {
@@ -227,13 +432,37 @@ enum Commands {
     },
 }
 
-fn split_inputs<T: FieldElement>(inputs: &str) -> Vec<T> {
+/// Prints the outputs committed by a RISC-V program, if there are any.
+fn print_committed_outputs(outputs: Option<Vec<u8>>) {
+    if let Some(outputs) = outputs.filter(|outputs| !outputs.is_empty()) {
+        println!(
+            "Committed outputs: 0x{}",
+            outputs
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<String>()
+        );
+    }
+}
+
+/// Parses the inputs argument: either the path to a file containing the inputs,
+/// or a comma-separated list of numbers.
+fn parse_inputs<T: FieldElement>(inputs: &str) -> Result<Inputs<T>, String> {
+    let path = Path::new(inputs);
+    if path.is_file() {
+        return Inputs::from_file(path);
+    }
     inputs
         .split(',')
         .map(|x| x.trim())
         .filter(|x| !x.is_empty())
-        .map(|x| x.parse::<u64>().unwrap().into())
-        .collect()
+        .map(|x| {
+            x.parse::<u64>()
+                .map(T::from)
+                .map_err(|e| format!("Invalid input {x}: {e}"))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Inputs::from)
 }
 
 fn main() -> Result<(), io::Error> {
@@ -269,14 +498,16 @@ fn main() -> Result<(), io::Error> {
         clap_markdown::print_help_markdown::<Cli>();
         Ok(())
     } else if let Some(command) = args.command {
-        run_command(command);
+        if run_command(command).is_err() {
+            std::process::exit(1);
+        }
         Ok(())
     } else {
         Cli::command().print_help()
     }
 }
 
-fn run_command(command: Commands) {
+fn run_command(command: Commands) -> Result<(), ()> {
     match command {
         Commands::Rust {
             file,
@@ -285,19 +516,22 @@ fn run_command(command: Commands) {
             output_directory,
             force,
             prove_with,
+            mode,
         } => {
-            if let Err(errors) = call_with_field!(compile_rust::<field>(
+            match call_with_field!(compile_rust::<field>(
                 &file,
-                split_inputs(&inputs),
+                parse_inputs(&inputs).map_err(|e| eprintln!("Error: {e}"))?,
                 Path::new(&output_directory),
                 force,
-                prove_with
+                prove_with,
+                mode
             )) {
-                eprintln!("Errors:");
-                for e in errors {
-                    eprintln!("{e}");
+                Ok(outputs) => print_committed_outputs(outputs),
+                Err(error) => {
+                    error.output_to_stderr();
+                    return Err(());
                 }
-            };
+            }
         }
         Commands::RiscvAsm {
             files,
@@ -306,6 +540,7 @@ fn run_command(command: Commands) {
             output_directory,
             force,
             prove_with,
+            mode,
         } => {
             assert!(!files.is_empty());
             let name = if files.len() == 1 {
@@ -314,29 +549,58 @@ fn run_command(command: Commands) {
                 Cow::Borrowed("output")
             };
 
-            if let Err(errors) = call_with_field!(compile_riscv_asm::<field>(
+            match call_with_field!(compile_riscv_asm::<field>(
                 &name,
                 files.into_iter(),
-                split_inputs(&inputs),
+                parse_inputs(&inputs).map_err(|e| eprintln!("Error: {e}"))?,
                 Path::new(&output_directory),
                 force,
-                prove_with
+                prove_with,
+                mode
             )) {
-                eprintln!("Errors:");
-                for e in errors {
-                    eprintln!("{e}");
+                Ok(outputs) => print_committed_outputs(outputs),
+                Err(error) => {
+                    error.output_to_stderr();
+                    return Err(());
                 }
-            };
+            }
+        }
+        Commands::Elf {
+            file,
+            field,
+            inputs,
+            output_directory,
+            force,
+            prove_with,
+            mode,
+        } => {
+            match call_with_field!(compile_elf::<field>(
+                &file,
+                parse_inputs(&inputs).map_err(|e| eprintln!("Error: {e}"))?,
+                Path::new(&output_directory),
+                force,
+                prove_with,
+                mode
+            )) {
+                Ok(outputs) => print_committed_outputs(outputs),
+                Err(error) => {
+                    error.output_to_stderr();
+                    return Err(());
+                }
+            }
         }
         Commands::Reformat { file } => {
             let contents = fs::read_to_string(&file).unwrap();
             match parser::parse::<GoldilocksField>(Some(&file), &contents) {
                 Ok(ast) => println!("{ast}"),
-                Err(err) => err.output_to_stderr(),
+                Err(err) => {
+                    err.output_to_stderr();
+                    return Err(());
+                }
             }
         }
         Commands::OptimizePIL { file, field } => {
-            call_with_field!(optimize_and_output::<field>(&file))
+            call_with_field!(optimize_and_output::<field>(&file))?
         }
         Commands::Pil {
             file,
@@ -345,26 +609,28 @@ fn run_command(command: Commands) {
             inputs,
             force,
             prove_with,
+            max_degree,
             export_csv,
             csv_mode,
+            linker_mode,
         } => {
             match call_with_field!(compile_with_csv_export::<field>(
                 file,
                 output_directory,
-                inputs,
+                parse_inputs(&inputs).map_err(|e| eprintln!("Error: {e}"))?,
                 force,
                 prove_with,
+                max_degree.map(|max_degree| max_degree as usize),
                 export_csv,
-                csv_mode
+                csv_mode,
+                linker_mode
             )) {
                 Ok(()) => {}
-                Err(errors) => {
-                    eprintln!("Errors:");
-                    for e in errors {
-                        eprintln!("{e}");
-                    }
+                Err(error) => {
+                    error.output_to_stderr();
+                    return Err(());
                 }
-            };
+            }
         }
         Commands::Prove {
             file,
@@ -373,10 +639,70 @@ fn run_command(command: Commands) {
             backend,
             proof,
             params,
+            keys,
+        } => {
+            let pil = Path::new(&file);
+            let dir = Path::new(&dir);
+            if let Err(error) = call_with_field!(read_and_prove::<field>(
+                pil, dir, &backend, proof, params, keys
+            )) {
+                eprintln!("Error: {error}");
+                return Err(());
+            }
+        }
+        Commands::Verify {
+            file,
+            dir,
+            field,
+            backend,
+            proof,
+            params,
+            keys,
         } => {
             let pil = Path::new(&file);
             let dir = Path::new(&dir);
-            call_with_field!(read_and_prove::<field>(pil, dir, &backend, proof, params));
+            if let Err(error) = call_with_field!(read_and_verify::<field>(
+                pil, dir, &backend, proof, params, keys
+            )) {
+                eprintln!("Error: {error}");
+                return Err(());
+            }
+        }
+        Commands::ExportVerifier {
+            file,
+            dir,
+            field,
+            backend,
+            params,
+            keys,
+            proof,
+        } => {
+            let pil = Path::new(&file);
+            let dir = Path::new(&dir);
+            if let Err(error) = call_with_field!(export_verifier::<field>(
+                pil, dir, &backend, params, keys, proof
+            )) {
+                eprintln!("Error: {error}");
+                return Err(());
+            }
+        }
+        Commands::Aggregate {
+            pils,
+            proofs,
+            keys,
+            dir,
+            field,
+            backend,
+            params,
+            arity,
+        } => {
+            let dir = Path::new(&dir);
+            if let Err(error) = call_with_field!(aggregate::<field>(
+                &pils, &proofs, &keys, dir, &backend, params, arity
+            )) {
+                eprintln!("Error: {error}");
+                return Err(());
+            }
         }
         Commands::Setup {
             size,
@@ -384,16 +710,36 @@ fn run_command(command: Commands) {
             field,
             backend,
         } => {
-            call_with_field!(setup::<field>(size, dir, backend));
+            if let Err(error) = call_with_field!(setup::<field>(size, dir, backend)) {
+                eprintln!("Error: {error}");
+                return Err(());
+            }
         }
     }
+    Ok(())
 }
 
-fn setup<F: FieldElement>(size: u64, dir: String, backend_type: BackendType) {
+fn setup<F: FieldElement>(
+    size: u64,
+    dir: String,
+    backend_type: BackendType,
+) -> Result<(), io::Error> {
     let dir = Path::new(&dir);
 
+    // The setup does not depend on the program, but if the directory contains
+    // a compiled program, the setup has to fit it.
+    match Manifest::read_from_fs(dir) {
+        Ok(manifest) => {
+            manifest.validate_field::<F>()?;
+            manifest.validate_setup_size(size)?;
+        }
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+        Err(error) => return Err(error),
+    }
+
     let backend = backend_type.factory::<F>().create(size);
     write_backend_to_fs(backend.as_ref(), dir);
+    Ok(())
 }
 
 fn write_backend_to_fs<F: FieldElement>(be: &dyn Backend<F>, output_dir: &Path) {
@@ -404,39 +750,75 @@ fn write_backend_to_fs<F: FieldElement>(be: &dyn Backend<F>, output_dir: &Path)
     log::info!("Wrote params.bin.");
 }
 
+#[allow(clippy::too_many_arguments)]
 fn compile_with_csv_export<T: FieldElement>(
     file: String,
     output_directory: String,
-    inputs: String,
+    inputs: Inputs<T>,
     force: bool,
     prove_with: Option<BackendType>,
+    max_degree: Option<usize>,
     export_csv: bool,
     csv_mode: CsvRenderMode,
-) -> Result<(), Vec<String>> {
+    linker_mode: LinkerMode,
+) -> Result<(), compiler::Error> {
     let result = compile_pil_or_asm::<T>(
         &file,
-        split_inputs(&inputs),
+        inputs,
         Path::new(&output_directory),
         force,
         prove_with,
+        max_degree,
+        None,
+        linker_mode,
     )?;
 
     if export_csv {
         // Compilation result is None if the ASM file has not been compiled
-        // (e.g. it has been compiled before and the force flag is not set)
-        if let Some(compilation_result) = result {
-            let csv_path = Path::new(&output_directory).join("columns.csv");
-            export_columns_to_csv::<T>(
-                compilation_result.constants,
-                compilation_result.witness,
-                &csv_path,
-                csv_mode,
-            );
-        }
+        // (e.g. it has been compiled before and the force flag is not set),
+        // the columns are then read from the output directory.
+        let (fixed, witness) = match result {
+            Some(result) => (result.constants, result.witness),
+            None => read_columns_from_fs::<T>(&file, Path::new(&output_directory))?,
+        };
+        let csv_path = Path::new(&output_directory).join("columns.csv");
+        export_columns_to_csv::<T>(fixed, witness, &csv_path, csv_mode);
     }
     Ok(())
 }
 
+type Columns<T> = Vec<(String, Vec<T>)>;
+
+/// Reads the fixed and, if they were generated, the witness columns of the program
+/// compiled from `file` from `dir`, after checking them against the manifest.
+fn read_columns_from_fs<T: FieldElement>(
+    file: &str,
+    dir: &Path,
+) -> Result<(Columns<T>, Option<Columns<T>>), compiler::Error> {
+    let optimized_pil_file = dir.join(format!(
+        "{}_opt.pil",
+        Path::new(file).file_stem().unwrap().to_str().unwrap()
+    ));
+    let pil = compiler::analyze_pil::<T>(&optimized_pil_file)?;
+    let manifest_error = |e: io::Error| compiler::Error::Manifest(e.to_string());
+    Manifest::read_from_fs(dir)
+        .and_then(|manifest| manifest.validate_pil(&pil))
+        .map_err(manifest_error)?;
+    let to_owned = |columns: Vec<(&str, Vec<T>)>| {
+        columns
+            .into_iter()
+            .map(|(name, values)| (name.to_string(), values))
+            .collect::<Vec<_>>()
+    };
+    let (fixed, _) = read_poly_set::<FixedPolySet, T>(&pil, dir).map_err(manifest_error)?;
+    let witness = match read_poly_set::<WitnessPolySet, T>(&pil, dir) {
+        Ok((witness, _)) => Some(to_owned(witness)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(manifest_error(e)),
+    };
+    Ok((to_owned(fixed), witness))
+}
+
 fn export_columns_to_csv<T: FieldElement>(
     fixed: Vec<(String, Vec<T>)>,
     witness: Option<Vec<(String, Vec<T>)>>,
@@ -495,12 +877,22 @@ fn read_and_prove<T: FieldElement>(
     backend_type: &BackendType,
     proof_path: Option<String>,
     params: Option<String>,
-) {
-    let pil = compiler::analyze_pil::<T>(file);
-    let fixed = read_poly_set::<FixedPolySet, T>(&pil, dir);
-    let witness = read_poly_set::<WitnessPolySet, T>(&pil, dir);
-
-    assert_eq!(fixed.1, witness.1);
+    keys: Option<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pil = compiler::analyze_pil::<T>(file)?;
+    let manifest = Manifest::read_from_fs(dir)?;
+    manifest.validate_pil(&pil)?;
+    manifest.validate_max_degree(backend_type.max_degree())?;
+    let fixed = read_poly_set::<FixedPolySet, T>(&pil, dir)?;
+    let witness = read_poly_set::<WitnessPolySet, T>(&pil, dir)?;
+
+    if fixed.1 != witness.1 {
+        return Err(format!(
+            "The degree of the fixed columns ({}) does not match the degree of the witness columns ({}).",
+            fixed.1, witness.1
+        )
+        .into());
+    }
 
     let builder = backend_type.factory::<T>();
     let backend = if let Some(filename) = params {
@@ -520,24 +912,234 @@ fn read_and_prove<T: FieldElement>(
         buf
     });
 
-    write_proving_results_to_fs(
-        proof.is_some(),
-        backend.prove(&pil, &fixed.0, &witness.0, proof),
-        dir,
-    );
+    let is_aggregation = proof.is_some();
+    let keys = keys.map(|filename| dir.join(filename));
+    let result = compiler::prove(
+        backend.as_ref(),
+        &pil,
+        &fixed.0,
+        &witness.0,
+        proof,
+        keys.as_deref(),
+    )?;
+    write_proving_results_to_fs(is_aggregation, result, dir);
+    Ok(())
+}
+
+fn read_and_verify<T: FieldElement>(
+    file: &Path,
+    dir: &Path,
+    backend_type: &BackendType,
+    proof_path: String,
+    params: String,
+    keys: Option<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pil = compiler::analyze_pil::<T>(file)?;
+    let manifest = Manifest::read_from_fs(dir)?;
+    manifest.validate_pil(&pil)?;
+    let (fixed, _) = read_poly_set::<FixedPolySet, T>(&pil, dir)?;
+
+    let mut params_file = fs::File::open(dir.join(params))?;
+    let backend = backend_type
+        .factory::<T>()
+        .create_from_setup(&mut params_file)?;
+    if let Some(keys) = keys {
+        backend.read_keys(
+            &pil,
+            &fixed,
+            &mut BufReader::new(fs::File::open(dir.join(keys))?),
+        )?;
+    }
+
+    let mut proof = Vec::new();
+    fs::File::open(dir.join(proof_path))?.read_to_end(&mut proof)?;
+
+    let publics = read_publics(&pil, dir)?
+        .into_iter()
+        .map(|(_, value)| value)
+        .collect::<Vec<_>>();
+
+    backend.verify(&pil, &fixed, &proof, &publics)?;
+    log::info!("Proof verified successfully.");
+    Ok(())
+}
+
+fn export_verifier<T: FieldElement>(
+    file: &Path,
+    dir: &Path,
+    backend_type: &BackendType,
+    params: String,
+    keys: Option<String>,
+    proof_path: Option<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pil = compiler::analyze_pil::<T>(file)?;
+    let manifest = Manifest::read_from_fs(dir)?;
+    manifest.validate_pil(&pil)?;
+    let (fixed, _) = read_poly_set::<FixedPolySet, T>(&pil, dir)?;
+
+    let mut params_file = fs::File::open(dir.join(params))?;
+    let backend = backend_type
+        .factory::<T>()
+        .create_from_setup(&mut params_file)?;
+    if let Some(keys) = keys {
+        backend.read_keys(
+            &pil,
+            &fixed,
+            &mut BufReader::new(fs::File::open(dir.join(keys))?),
+        )?;
+    }
+
+    let verifier = backend.export_evm_verifier(&pil, &fixed)?;
+    fs::write(dir.join("verifier.yul"), verifier.yul)?;
+    log::info!("Wrote verifier.yul.");
+    match verifier.bytecode {
+        Some(bytecode) => {
+            fs::write(dir.join("verifier.bin"), bytecode)?;
+            log::info!("Wrote verifier.bin.");
+        }
+        None => log::warn!("solc is not installed, the verifier was not compiled."),
+    }
+
+    if let Some(proof_path) = proof_path {
+        let proof = fs::read(dir.join(proof_path))?;
+        let publics = read_publics(&pil, dir)?
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect::<Vec<_>>();
+        fs::write(
+            dir.join("calldata.bin"),
+            backend.evm_calldata(&proof, &publics)?,
+        )?;
+        log::info!("Wrote calldata.bin.");
+    }
+    Ok(())
+}
+
+fn aggregate<T: FieldElement>(
+    pils: &[String],
+    proofs: &[String],
+    keys: &[String],
+    dir: &Path,
+    backend_type: &BackendType,
+    params: String,
+    arity: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if pils.len() != proofs.len() {
+        return Err(format!(
+            "There are {} PIL files, but {} proofs, expected one proof for every PIL file.",
+            pils.len(),
+            proofs.len()
+        )
+        .into());
+    }
+    if !keys.is_empty() && keys.len() != pils.len() {
+        return Err(format!(
+            "There are {} PIL files, but {} keys, expected none or one for every PIL file.",
+            pils.len(),
+            keys.len()
+        )
+        .into());
+    }
+    let proofs = pils
+        .iter()
+        .zip(proofs)
+        .enumerate()
+        .map(|(i, (pil, proof))| (Path::new(pil), Path::new(proof), keys.get(i).map(Path::new)))
+        .collect::<Vec<_>>();
+
+    let mut params_file = fs::File::open(dir.join(params))?;
+    let backend = backend_type
+        .factory::<T>()
+        .create_from_setup(&mut params_file)?;
+
+    // The inputs borrow the PILs and the fixed columns, so all of them are read first.
+    let pils = proofs
+        .iter()
+        .map(|(pil, ..)| compiler::analyze_pil::<T>(pil))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut fixed = vec![];
+    let mut publics = vec![];
+    for (pil, (pil_path, _, keys)) in pils.iter().zip(&proofs) {
+        let pil_dir = pil_path.parent().unwrap_or(Path::new("."));
+        let manifest = Manifest::read_from_fs(pil_dir)?;
+        manifest.validate_pil(pil)?;
+        let (columns, _) = read_poly_set::<FixedPolySet, T>(pil, pil_dir)?;
+        if let Some(keys) = keys {
+            backend.read_keys(pil, &columns, &mut BufReader::new(fs::File::open(keys)?))?;
+        }
+        fixed.push(columns);
+        publics.push(read_publics(pil, pil_dir)?);
+    }
+
+    let inputs = proofs
+        .iter()
+        .zip(&pils)
+        .zip(&fixed)
+        .zip(&publics)
+        .map(|((((_, proof, _), pil), fixed), publics)| {
+            Ok(AggregationInput {
+                pil,
+                fixed,
+                proof: fs::read(proof)?,
+                publics: publics.iter().map(|(_, value)| *value).collect(),
+            })
+        })
+        .collect::<Result<Vec<_>, io::Error>>()?;
+
+    let aggregated = backend.aggregate(inputs, arity)?;
+    fs::write(dir.join("proof_aggr.bin"), aggregated.proof)?;
+    log::info!("Wrote proof_aggr.bin.");
+
+    // The instances start with the accumulator, followed by the public values of the proofs.
+    let public_names = publics
+        .iter()
+        .enumerate()
+        .flat_map(|(i, publics)| publics.iter().map(move |(name, _)| format!("{i}.{name}")))
+        .collect::<Vec<_>>();
+    let accumulator_len = aggregated.instances.len() - public_names.len();
+    let instances = (0..accumulator_len)
+        .map(|i| format!("accumulator.{i}"))
+        .chain(public_names)
+        .zip(aggregated.instances)
+        .collect::<Vec<_>>();
+    let mut writer = BufWriter::new(fs::File::create(dir.join("instances.json"))?);
+    write_publics(&mut writer, &instances);
+    writer.flush()?;
+    log::info!("Wrote instances.json.");
+
+    fs::write(dir.join("verifier.yul"), aggregated.verifier.yul)?;
+    log::info!("Wrote verifier.yul.");
+    match aggregated.verifier.bytecode {
+        Some(bytecode) => {
+            fs::write(dir.join("verifier.bin"), bytecode)?;
+            log::info!("Wrote verifier.bin.");
+        }
+        None => log::warn!("solc is not installed, the verifier was not compiled."),
+    }
+    Ok(())
 }
 
-fn optimize_and_output<T: FieldElement>(file: &str) {
-    println!(
-        "{}",
-        pilopt::optimize(compiler::analyze_pil::<T>(Path::new(file)))
-    );
+fn optimize_and_output<T: FieldElement>(file: &str) -> Result<(), ()> {
+    match compiler::analyze_pil::<T>(Path::new(file)) {
+        Ok(pil) => {
+            println!("{}", pilopt::optimize(pil));
+            Ok(())
+        }
+        Err(error) => {
+            error.output_to_stderr();
+            Err(())
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{run_command, Commands, CsvRenderMode, FieldArgument};
+    use crate::{
+        aggregate, parse_inputs, run_command, Cli, Commands, CsvRenderMode, FieldArgument,
+    };
     use backend::BackendType;
+    use clap::Parser;
+    use number::GoldilocksField;
 
     #[test]
     fn test_simple_sum() {
@@ -551,10 +1153,13 @@ mod test {
             inputs: "3,2,1,2".into(),
             force: false,
             prove_with: Some(BackendType::PilcomCli),
+            // The identities are reduced for proving with halo2 below.
+            max_degree: Some(3),
             export_csv: true,
             csv_mode: CsvRenderMode::Hex,
+            linker_mode: compiler::LinkerMode::Monolithic,
         };
-        run_command(pil_command);
+        run_command(pil_command).unwrap();
 
         #[cfg(feature = "halo2")]
         {
@@ -570,8 +1175,343 @@ mod test {
                 backend: BackendType::Halo2Mock,
                 proof: None,
                 params: None,
+                keys: None,
             };
-            run_command(prove_command);
+            run_command(prove_command).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_export_csv_without_compilation() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let pil_command = || Commands::Pil {
+            file: "../test_data/asm/simple_sum.asm".into(),
+            field: FieldArgument::Gl,
+            output_directory: output_dir.path().to_string_lossy().to_string(),
+            inputs: "3,2,1,2".into(),
+            force: false,
+            prove_with: Some(BackendType::PilCheck),
+            max_degree: None,
+            export_csv: true,
+            csv_mode: CsvRenderMode::Hex,
+            linker_mode: compiler::LinkerMode::Monolithic,
+        };
+        let csv_file = output_dir.path().join("columns.csv");
+        run_command(pil_command()).unwrap();
+        let csv = std::fs::read_to_string(&csv_file).unwrap();
+
+        // The program is not compiled again, the columns are read from the files.
+        std::fs::remove_file(&csv_file).unwrap();
+        run_command(pil_command()).unwrap();
+        assert_eq!(std::fs::read_to_string(&csv_file).unwrap(), csv);
+
+        // Columns of a different program are rejected.
+        let pil_file = output_dir.path().join("simple_sum_opt.pil");
+        let pil = std::fs::read_to_string(&pil_file).unwrap();
+        std::fs::write(&pil_file, format!("{pil}\npol commit extra;\n")).unwrap();
+        assert!(run_command(pil_command()).is_err());
+    }
+
+    #[test]
+    fn test_inputs_file() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let output_dir_str = output_dir.path().to_string_lossy().to_string();
+        let inputs_file = output_dir.path().join("inputs.json");
+        std::fs::write(&inputs_file, "[3, 2, 1, \"2\"]").unwrap();
+
+        let pil_command = Commands::Pil {
+            file: "../test_data/asm/simple_sum.asm".into(),
+            field: FieldArgument::Gl,
+            output_directory: output_dir_str,
+            inputs: inputs_file.to_string_lossy().to_string(),
+            force: false,
+            prove_with: Some(BackendType::PilCheck),
+            max_degree: None,
+            export_csv: false,
+            csv_mode: CsvRenderMode::Hex,
+            linker_mode: compiler::LinkerMode::Monolithic,
+        };
+        run_command(pil_command).unwrap();
+        assert!(output_dir.path().join("commits.bin").exists());
+    }
+
+    #[test]
+    fn test_invalid_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let inputs_file = dir.path().join("inputs.json");
+        std::fs::write(&inputs_file, "[3, 2,").unwrap();
+        let error = parse_inputs::<GoldilocksField>(inputs_file.to_str().unwrap()).unwrap_err();
+        assert!(error.starts_with("Error reading"), "{error}");
+        assert!(parse_inputs::<GoldilocksField>("1,x").is_err());
+        assert!(parse_inputs::<GoldilocksField>("1, 2,").is_ok());
+    }
+
+    #[test]
+    fn test_aggregate_arguments() {
+        let parse = |flags: &[&str]| {
+            let args = [
+                "powdr",
+                "aggregate",
+                "--backend",
+                "pil-check",
+                "--params",
+                "p.bin",
+            ];
+            Cli::try_parse_from(args.iter().chain(flags)).map(|cli| cli.command)
+        };
+        let Ok(Some(Commands::Aggregate {
+            pils, proofs, keys, ..
+        })) = parse(&[
+            "--pil",
+            "a/a.pil",
+            "--proof",
+            "a/proof.bin",
+            "--pil",
+            "b/b.pil",
+            "--proof",
+            "b/proof.bin",
+        ])
+        else {
+            panic!("the repeated flags were not accepted");
+        };
+        assert_eq!(pils, ["a/a.pil", "b/b.pil"]);
+        assert_eq!(proofs, ["a/proof.bin", "b/proof.bin"]);
+        assert!(keys.is_empty());
+        assert!(parse(&["--proof", "a/proof.bin"]).is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let aggregate = |proofs: &[String], keys: &[String]| {
+            aggregate::<GoldilocksField>(
+                &["a/a.pil".to_string(), "b/b.pil".to_string()],
+                proofs,
+                keys,
+                dir.path(),
+                &BackendType::PilCheck,
+                "p.bin".into(),
+                2,
+            )
+            .unwrap_err()
+            .to_string()
+        };
+        let error = aggregate(&["a/proof.bin".to_string()], &[]);
+        assert!(error.contains("2 PIL files, but 1 proofs"), "{error}");
+        let error = aggregate(
+            &["a/proof.bin".to_string(), "b/proof.bin".to_string()],
+            &["a/keys.bin".to_string()],
+        );
+        assert!(error.contains("2 PIL files, but 1 keys"), "{error}");
+    }
+
+    #[test]
+    fn test_prove_with_stale_artifacts() {
+        let compile = |file: &str| {
+            let output_dir = tempfile::tempdir().unwrap();
+            run_command(Commands::Pil {
+                file: format!("../test_data/asm/{file}.asm"),
+                field: FieldArgument::Gl,
+                output_directory: output_dir.path().to_string_lossy().to_string(),
+                inputs: "3,2,1,2".into(),
+                force: false,
+                prove_with: None,
+                max_degree: None,
+                export_csv: false,
+                csv_mode: CsvRenderMode::Hex,
+                linker_mode: compiler::LinkerMode::Monolithic,
+            })
+            .unwrap();
+            let pil = output_dir.path().join(format!("{file}_opt.pil"));
+            (output_dir, pil)
+        };
+        let prove = |pil: &std::path::Path, dir: &std::path::Path| {
+            crate::read_and_prove::<number::GoldilocksField>(
+                pil,
+                dir,
+                &BackendType::PilCheck,
+                None,
+                None,
+                None,
+            )
+        };
+        let (sum_dir, sum_pil) = compile("simple_sum");
+        let (palindrome_dir, palindrome_pil) = compile("palindrome");
+
+        assert!(prove(&sum_pil, sum_dir.path()).is_ok());
+        let error = prove(&palindrome_pil, sum_dir.path()).unwrap_err();
+        assert!(error.to_string().contains("different PIL file"));
+
+        std::fs::remove_file(palindrome_dir.path().join("manifest.json")).unwrap();
+        assert!(prove(&palindrome_pil, palindrome_dir.path()).is_err());
+        assert!(crate::read_and_prove::<number::Bn254Field>(
+            &sum_pil,
+            sum_dir.path(),
+            &BackendType::PilCheck,
+            None,
+            None,
+            None,
+        )
+        .is_err());
+
+        // halo2 needs identities of a reduced degree.
+        #[cfg(feature = "halo2")]
+        {
+            let output_dir = tempfile::tempdir().unwrap();
+            run_command(Commands::Pil {
+                file: "../test_data/asm/simple_sum.asm".into(),
+                field: FieldArgument::Bn254,
+                output_directory: output_dir.path().to_string_lossy().to_string(),
+                inputs: "3,2,1,2".into(),
+                force: false,
+                prove_with: None,
+                max_degree: None,
+                export_csv: false,
+                csv_mode: CsvRenderMode::Hex,
+                linker_mode: compiler::LinkerMode::Monolithic,
+            })
+            .unwrap();
+            let error = crate::read_and_prove::<number::Bn254Field>(
+                &output_dir.path().join("simple_sum_opt.pil"),
+                output_dir.path(),
+                &BackendType::Halo2Mock,
+                None,
+                None,
+                None,
+            )
+            .unwrap_err();
+            assert!(error.to_string().contains("without reducing"));
         }
     }
+
+    #[cfg(feature = "halo2")]
+    #[test]
+    fn test_prove_and_verify() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let output_dir_str = output_dir.path().to_string_lossy().to_string();
+
+        let pil_command = Commands::Pil {
+            file: "../test_data/asm/simple_sum.asm".into(),
+            field: FieldArgument::Bn254,
+            output_directory: output_dir_str.clone(),
+            inputs: "3,2,1,2".into(),
+            force: false,
+            prove_with: None,
+            max_degree: Some(3),
+            export_csv: false,
+            csv_mode: CsvRenderMode::Hex,
+            linker_mode: compiler::LinkerMode::Monolithic,
+        };
+        run_command(pil_command).unwrap();
+
+        let setup_command = Commands::Setup {
+            size: 1024,
+            dir: output_dir_str.clone(),
+            field: FieldArgument::Bn254,
+            backend: BackendType::Halo2,
+        };
+        run_command(setup_command).unwrap();
+
+        let file = output_dir
+            .path()
+            .join("simple_sum_opt.pil")
+            .to_string_lossy()
+            .to_string();
+        let prove_command = || Commands::Prove {
+            file: file.clone(),
+            dir: output_dir_str.clone(),
+            field: FieldArgument::Bn254,
+            backend: BackendType::Halo2,
+            proof: None,
+            params: Some("params.bin".into()),
+            keys: Some("keys.bin".into()),
+        };
+        run_command(prove_command()).unwrap();
+        assert!(output_dir.path().join("keys.bin").exists());
+
+        // The second proof uses the keys written by the first one.
+        std::fs::remove_file(output_dir.path().join("proof.bin")).unwrap();
+        run_command(prove_command()).unwrap();
+
+        let verify = |keys: Option<&str>| {
+            crate::read_and_verify::<number::Bn254Field>(
+                std::path::Path::new(&file),
+                output_dir.path(),
+                &BackendType::Halo2,
+                "proof.bin".into(),
+                "params.bin".into(),
+                keys.map(String::from),
+            )
+        };
+        assert!(verify(None).is_ok());
+        assert!(verify(Some("keys.bin")).is_ok());
+
+        // Keys of a different setup are rejected.
+        let setup_command = Commands::Setup {
+            size: 1024,
+            dir: output_dir_str,
+            field: FieldArgument::Bn254,
+            backend: BackendType::Halo2,
+        };
+        run_command(setup_command).unwrap();
+        let error = verify(Some("keys.bin")).unwrap_err();
+        assert!(error.to_string().contains("different setup"));
+    }
+
+    #[cfg(feature = "halo2")]
+    #[test]
+    #[ignore = "Requires solc"]
+    fn test_export_evm_verifier() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let output_dir_str = output_dir.path().to_string_lossy().to_string();
+
+        run_command(Commands::Pil {
+            file: "../test_data/asm/simple_sum.asm".into(),
+            field: FieldArgument::Bn254,
+            output_directory: output_dir_str.clone(),
+            inputs: "3,2,1,2".into(),
+            force: false,
+            prove_with: None,
+            max_degree: Some(3),
+            export_csv: false,
+            csv_mode: CsvRenderMode::Hex,
+            linker_mode: compiler::LinkerMode::Monolithic,
+        })
+        .unwrap();
+        run_command(Commands::Setup {
+            size: 1024,
+            dir: output_dir_str.clone(),
+            field: FieldArgument::Bn254,
+            backend: BackendType::Halo2Evm,
+        })
+        .unwrap();
+
+        let file = output_dir
+            .path()
+            .join("simple_sum_opt.pil")
+            .to_string_lossy()
+            .to_string();
+        run_command(Commands::Prove {
+            file: file.clone(),
+            dir: output_dir_str.clone(),
+            field: FieldArgument::Bn254,
+            backend: BackendType::Halo2Evm,
+            proof: None,
+            params: Some("params.bin".into()),
+            keys: None,
+        })
+        .unwrap();
+        run_command(Commands::ExportVerifier {
+            file,
+            dir: output_dir_str,
+            field: FieldArgument::Bn254,
+            backend: BackendType::Halo2Evm,
+            params: "params.bin".into(),
+            keys: None,
+            proof: Some("proof.bin".into()),
+        })
+        .unwrap();
+
+        assert!(output_dir.path().join("verifier.yul").exists());
+        let calldata = std::fs::read(output_dir.path().join("calldata.bin")).unwrap();
+        let deployment_code = std::fs::read(output_dir.path().join("verifier.bin")).unwrap();
+        assert!(halo2::evm_verify(deployment_code, calldata).is_ok());
+    }
 }
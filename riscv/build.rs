@@ -21,6 +21,8 @@ fn build_instruction_tests() {
     let mut test_file = File::create(&destination).unwrap();
 
     let generated_path = "./tests/instruction_tests/generated/";
+    // Also rerun if tests are added.
+    println!("cargo:rerun-if-changed={generated_path}");
     for file in read_dir(generated_path).unwrap() {
         let file = file.unwrap();
         if let Some(file_name) = file
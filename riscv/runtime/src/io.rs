@@ -0,0 +1,119 @@
+//! Reading prover input and committing outputs.
+//!
+//! All requests to the prover use `ecall`, with the syscall number in `a7`.
+//! The input byte stream and the outputs are accessed sequentially.
+
+use core::arch::asm;
+
+use alloc::vec::Vec;
+
+const SYSCALL_INPUT: u32 = 0;
+const SYSCALL_DATA: u32 = 1;
+const SYSCALL_COMMIT: u32 = 2;
+
+/// The number of bytes that can be committed.
+/// This needs to match `OUTPUT_CAPACITY` in the compiler.
+const OUTPUT_CAPACITY: u32 = 64;
+/// The value committed after the last output byte.
+const OUTPUT_END: u32 = 256;
+
+static mut DATA_POSITION: u32 = 0;
+static mut OUTPUT_POSITION: u32 = 0;
+
+/// Returns the prover input at `index`, answering the query `("input", index)`.
+#[inline]
+pub fn get_prover_input(index: u32) -> u32 {
+    let mut value: u32;
+    unsafe {
+        asm!("ecall", lateout("a0") value, in("a0") index, in("a7") SYSCALL_INPUT);
+    }
+    value
+}
+
+/// Fills `buf` with the next bytes of the input byte stream.
+pub fn read_bytes(buf: &mut [u8]) {
+    for b in buf {
+        *b = read_byte();
+    }
+}
+
+/// Reads the next value from the input byte stream.
+pub fn read<T: Read>() -> T {
+    T::read()
+}
+
+fn read_byte() -> u8 {
+    let mut value: u32;
+    unsafe {
+        let index = DATA_POSITION;
+        DATA_POSITION += 1;
+        asm!("ecall", lateout("a0") value, in("a0") index, in("a7") SYSCALL_DATA);
+    }
+    value as u8
+}
+
+/// Types that can be read from the input byte stream.
+/// Integers are encoded in little endian, vectors are prefixed by their length as `u32`.
+pub trait Read {
+    fn read() -> Self;
+}
+
+macro_rules! impl_read_for_int {
+    ($($t:ty),*) => {
+        $(
+            impl Read for $t {
+                fn read() -> Self {
+                    let mut bytes = [0; core::mem::size_of::<$t>()];
+                    read_bytes(&mut bytes);
+                    <$t>::from_le_bytes(bytes)
+                }
+            }
+        )*
+    };
+}
+
+impl_read_for_int!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+impl Read for bool {
+    fn read() -> Self {
+        u8::read() != 0
+    }
+}
+
+impl<T: Read, const N: usize> Read for [T; N] {
+    fn read() -> Self {
+        core::array::from_fn(|_| T::read())
+    }
+}
+
+impl<T: Read> Read for Vec<T> {
+    fn read() -> Self {
+        let len = u32::read();
+        (0..len).map(|_| T::read()).collect()
+    }
+}
+
+/// Appends `data` to the committed outputs, which are public values of the proof.
+/// At most `OUTPUT_CAPACITY` bytes can be committed in total.
+pub fn commit(data: &[u8]) {
+    for b in data {
+        assert!(
+            unsafe { OUTPUT_POSITION } < OUTPUT_CAPACITY,
+            "Cannot commit more than {OUTPUT_CAPACITY} bytes."
+        );
+        commit_value(*b as u32);
+    }
+}
+
+/// Ends the committed outputs. Called by the runtime after `main` returns.
+pub(crate) fn finish_outputs() {
+    commit_value(OUTPUT_END);
+}
+
+fn commit_value(value: u32) {
+    unsafe {
+        let position = OUTPUT_POSITION;
+        OUTPUT_POSITION += 1;
+        asm!("ecall", in("a0") position, in("a1") value, in("a7") SYSCALL_COMMIT);
+    }
+}
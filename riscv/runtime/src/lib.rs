@@ -6,6 +6,8 @@
     round_char_boundary
 )]
 
+extern crate alloc;
+
 use core::arch::asm;
 use core::panic::PanicInfo;
 
@@ -14,6 +16,9 @@ use crate::fmt::print_str;
 mod allocator;
 pub mod coprocessors;
 pub mod fmt;
+pub mod io;
+
+pub use io::get_prover_input;
 
 #[panic_handler]
 unsafe fn panic(panic: &PanicInfo<'_>) -> ! {
@@ -31,15 +36,6 @@ unsafe fn panic(panic: &PanicInfo<'_>) -> ! {
     loop {}
 }
 
-#[inline]
-pub fn get_prover_input(index: u32) -> u32 {
-    let mut value: u32;
-    unsafe {
-        asm!("ecall", lateout("a0") value, in("a0") index);
-    }
-    value
-}
-
 extern "Rust" {
     fn main();
 }
@@ -49,4 +45,5 @@ pub unsafe extern "C" fn __runtime_start() {
     unsafe {
         main();
     }
+    io::finish_outputs();
 }
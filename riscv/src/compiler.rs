@@ -10,6 +10,7 @@ use asm_utils::{
     reachability,
 };
 use itertools::Itertools;
+use number::FieldElement;
 
 use crate::disambiguator;
 use crate::parser::RiscParser;
@@ -20,8 +21,8 @@ pub struct Register {
     value: u8,
 }
 
-pub fn machine_decls() -> Vec<&'static str> {
-    vec![
+pub fn machine_decls() -> Vec<String> {
+    [
         r#"
 // ================= binary/bitwise instructions =================
 
@@ -121,6 +122,71 @@ machine Shift(latch, function_id) {
 }
 "#,
     ]
+    .into_iter()
+    .map(|decl| decl.to_string())
+    .chain([output_machine()])
+    .collect()
+}
+
+/// The number of bytes a program can commit to the public outputs.
+/// This needs to match `OUTPUT_CAPACITY` in the runtime.
+pub const OUTPUT_CAPACITY: usize = 64;
+/// The value committed after the last output byte.
+const OUTPUT_END: u32 = 256;
+
+/// The machine storing the committed outputs. Every call stores a value in the next row
+/// and returns its position. The value at position `i` is stored in row `i + 1`, because
+/// the first block of a block machine is a dummy block.
+/// The positions up to and including `OUTPUT_CAPACITY` are exposed as public values.
+fn output_machine() -> String {
+    format!(
+        r#"
+// ================= committed outputs =================
+
+machine Output(latch, function_id) {{
+    degree 262144;
+
+    function write<0> value -> position {{
+    }}
+
+    constraints{{
+        col witness function_id;
+        col fixed latch = [1]*;
+        col fixed position(i) {{ i - 1 }};
+        col witness value;
+{}
+    }}
+}}
+"#,
+        (0..=OUTPUT_CAPACITY)
+            .map(|i| format!("        public output_{i} = value({});", i + 1))
+            .join("\n")
+    )
+}
+
+/// Decodes the outputs committed by a program from the public values.
+/// Returns `None` if the program did not end its outputs, which happens if it
+/// does not use the runtime.
+pub fn committed_outputs<T: FieldElement>(publics: &[(String, T)]) -> Option<Vec<u8>> {
+    decode_outputs((0..=OUTPUT_CAPACITY).map(|i| {
+        let name = format!("output_{i}");
+        publics
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, value)| *value)
+    }))
+}
+
+/// Decodes the outputs committed by a program from the values at the output positions,
+/// which are `None` if the position was not written.
+/// Returns `None` if the program did not end its outputs.
+pub fn decode_outputs<T: FieldElement>(values: impl Iterator<Item = Option<T>>) -> Option<Vec<u8>> {
+    values
+        .map(|value| value.map(|v| v.to_degree()))
+        .take_while(|value| *value != Some(OUTPUT_END as u64))
+        .map(|value| value.and_then(|v| u8::try_from(v).ok()))
+        .collect::<Option<Vec<_>>>()
+        .filter(|outputs| outputs.len() <= OUTPUT_CAPACITY)
 }
 
 impl Register {
@@ -145,6 +211,8 @@ impl fmt::Display for Register {
 pub enum FunctionKind {
     HiDataRef,
     LoDataRef,
+    PcRelHi,
+    PcRelLo,
 }
 
 impl asm_utils::ast::FunctionOpKind for FunctionKind {}
@@ -154,6 +222,8 @@ impl fmt::Display for FunctionKind {
         match self {
             FunctionKind::HiDataRef => write!(f, "%hi"),
             FunctionKind::LoDataRef => write!(f, "%lo"),
+            FunctionKind::PcRelHi => write!(f, "%pcrel_hi"),
+            FunctionKind::PcRelLo => write!(f, "%pcrel_lo"),
         }
     }
 }
@@ -163,7 +233,7 @@ pub struct Risc {}
 
 impl asm_utils::compiler::Compiler for Risc {
     /// Compiles riscv assembly to POWDR assembly. Adds required library routines.
-    fn compile(mut assemblies: BTreeMap<String, String>) -> String {
+    fn compile(mut assemblies: BTreeMap<String, String>) -> Result<String, String> {
         // stack grows towards zero
         let stack_start = 0x10000;
         // data grows away from zero
@@ -215,10 +285,17 @@ impl asm_utils::compiler::Compiler for Risc {
             .collect::<Vec<_>>();
         let (data_code, data_positions) = store_data_objects(&sorted_objects, data_start);
 
-        riscv_machine(
+        let statements = substitute_symbols_with_values(statements, &data_positions);
+        check_instruction_forms(&statements)?;
+
+        Ok(riscv_machine(
             &machine_decls(),
             &preamble(),
-            &[("binary", "Binary"), ("shift", "Shift")],
+            &[
+                ("binary", "Binary"),
+                ("shift", "Shift"),
+                ("output", "Output"),
+            ],
             file_ids
                 .into_iter()
                 .map(|(id, dir, file)| format!("debug file {id} {} {};", quote(&dir), quote(&file)))
@@ -229,19 +306,53 @@ impl asm_utils::compiler::Compiler for Risc {
                     "call __runtime_start;".to_string(),
                     "return;".to_string(), // This is not "riscv ret", but "return from powdr asm function".
                 ])
-                .chain(
-                    substitute_symbols_with_values(statements, &data_positions)
-                        .into_iter()
-                        .flat_map(process_statement),
-                )
+                .chain(statements.into_iter().flat_map(process_statement))
+                .chain(syscall_routine())
+                .chain(unaligned_access_routines())
                 .chain(["// This is the data initialization routine.\n__data_init::".to_string()])
                 .chain(data_code)
                 .chain(["// This is the end of the data initialization routine.\nret;".to_string()])
                 .collect(),
-        )
+        ))
     }
 }
 
+/// Fails if an instruction is used in a form that cannot be translated. Code addresses
+/// are not byte addresses, so offsets relative to code addresses, e.g. in `jalr` or in
+/// `auipc` without `%pcrel_hi`, cannot be translated.
+fn check_instruction_forms(statements: &[Statement]) -> Result<(), String> {
+    for statement in statements {
+        let Statement::Instruction(instr, args) = statement else {
+            continue;
+        };
+        let error = match (instr.as_str(), &args[..]) {
+            ("jalr", [_, Argument::RegOffset(_, offset)] | [_, _, Argument::Expression(offset)])
+                if !matches!(offset, Expression::Number(0)) =>
+            {
+                "jalr is only supported with a zero offset, because code addresses are not byte addresses"
+            }
+            (
+                "auipc",
+                [Argument::Register(_), Argument::Expression(Expression::FunctionOp(FunctionKind::PcRelHi, target))],
+            ) => match target.as_ref() {
+                Expression::Number(_) | Expression::Symbol(_) => continue,
+                _ => "auipc is only supported with %pcrel_hi of a symbol",
+            },
+            ("auipc", _) => {
+                "auipc is only supported with %pcrel_hi, because code addresses are not byte addresses"
+            }
+            ("csrr" | "csrrw" | "csrrs" | "csrrc" | "csrrwi" | "csrrsi" | "csrrci", args)
+                if !matches!(args, [Argument::Register(_), ..]) =>
+            {
+                "expected a destination register"
+            }
+            _ => continue,
+        };
+        return Err(format!("{error}: {}", statement.to_string().trim()));
+    }
+    Ok(())
+}
+
 /// Replace certain patterns of references to code labels by
 /// special instructions. We ignore any references to data objects
 /// because they will be handled differently.
@@ -448,7 +559,7 @@ fn store_data_objects<'a>(
     (code, positions)
 }
 
-fn call_every_submachine() -> Vec<String> {
+pub(crate) fn call_every_submachine() -> Vec<String> {
     // TODO This is a hacky snippet to ensure that every submachine in the RISCV machine
     // is called at least once. This is needed for witgen until it can do default blocks
     // automatically.
@@ -460,6 +571,88 @@ fn call_every_submachine() -> Vec<String> {
     ]
 }
 
+/// The routine `ecall` jumps to. It dispatches on the syscall number in x17 (a7)
+/// and returns to the address in tmp1:
+/// 0: x10 is set to the prover input at index x10.
+/// 1: x10 is set to the byte at index x10 of the input byte stream.
+/// 2: The value x11 is committed to the outputs at position x10, which has to be the
+///    number of values committed before. The position must not exceed `OUTPUT_CAPACITY`,
+///    which is the position of the value that ends the outputs if all bytes are used.
+pub(crate) fn syscall_routine() -> Vec<String> {
+    vec![
+        "// This is the syscall routine.\n__ecall::".to_string(),
+        "branch_if_zero x17, __ecall_input;".to_string(),
+        "branch_if_zero x17 - 1, __ecall_data;".to_string(),
+        "branch_if_zero x17 - 2, __ecall_commit;".to_string(),
+        "__ecall_fail::\nfail;".to_string(),
+        "__ecall_input::".to_string(),
+        "x10 <=X= ${ (\"input\", x10) };".to_string(),
+        "jump_dyn tmp1;".to_string(),
+        "__ecall_data::".to_string(),
+        "x10 <=X= ${ (\"data\", x10) };".to_string(),
+        "jump_dyn tmp1;".to_string(),
+        "__ecall_commit::".to_string(),
+        format!("branch_if_positive x10 - {OUTPUT_CAPACITY}, __ecall_fail;"),
+        "tmp2 <=X= write_output(x11);".to_string(),
+        "branch_if_nonzero tmp2 - x10, __ecall_fail;".to_string(),
+        "// This is the end of the syscall routine.\njump_dyn tmp1;".to_string(),
+    ]
+}
+
+/// The routines the memory instructions jump to for misaligned word accesses
+/// and half-word accesses that cross a word boundary. They access the bytes one
+/// by one and return to the address in tmp4.
+/// Loads have one routine per destination register, which holds the byte address.
+/// Stores have a single routine, which expects the byte address in tmp1 and the
+/// value in tmp2.
+pub(crate) fn unaligned_access_routines() -> Vec<String> {
+    let loads = (1..32).map(Register::new).flat_map(|r| {
+        [
+            format!("__unaligned_lw_{r}::"),
+            format!("tmp1 <== load_byte_unsigned({r}, 3);"),
+            format!("tmp2 <== load_byte_unsigned({r}, 2);"),
+            "tmp1 <=X= tmp1 * 0x100 + tmp2;".to_string(),
+            format!("tmp2 <== load_byte_unsigned({r}, 1);"),
+            "tmp1 <=X= tmp1 * 0x100 + tmp2;".to_string(),
+            format!("tmp2 <== load_byte_unsigned({r}, 0);"),
+            format!("{r} <=X= tmp1 * 0x100 + tmp2;"),
+            "jump_dyn tmp4;".to_string(),
+            format!("__unaligned_lhu_{r}::"),
+            format!("tmp1 <== load_byte_unsigned({r}, 1);"),
+            format!("tmp2 <== load_byte_unsigned({r}, 0);"),
+            format!("{r} <=X= tmp1 * 0x100 + tmp2;"),
+            "jump_dyn tmp4;".to_string(),
+            format!("__unaligned_lh_{r}::"),
+            format!("tmp1 <== load_byte_signed({r}, 1);"),
+            "tmp1 <== shl(tmp1, 8);".to_string(),
+            format!("tmp2 <== load_byte_unsigned({r}, 0);"),
+            format!("{r} <=X= tmp1 + tmp2;"),
+            "jump_dyn tmp4;".to_string(),
+        ]
+    });
+    ["// These are the routines for misaligned memory accesses.".to_string()]
+        .into_iter()
+        .chain(loads)
+        .chain([
+            "__unaligned_sw::".to_string(),
+            "store_byte tmp1, 0, tmp2;".to_string(),
+            "tmp2 <== shr(tmp2, 8);".to_string(),
+            "store_byte tmp1, 1, tmp2;".to_string(),
+            "tmp2 <== shr(tmp2, 8);".to_string(),
+            "store_byte tmp1, 2, tmp2;".to_string(),
+            "tmp2 <== shr(tmp2, 8);".to_string(),
+            "store_byte tmp1, 3, tmp2;".to_string(),
+            "jump_dyn tmp4;".to_string(),
+            "__unaligned_sh::".to_string(),
+            "store_byte tmp1, 0, tmp2;".to_string(),
+            "tmp2 <== shr(tmp2, 8);".to_string(),
+            "store_byte tmp1, 1, tmp2;".to_string(),
+            "// This is the end of the routines for misaligned memory accesses.\njump_dyn tmp4;"
+                .to_string(),
+        ])
+        .collect()
+}
+
 fn next_multiple_of_four(x: usize) -> usize {
     ((x + 3) / 4) * 4
 }
@@ -507,11 +700,20 @@ fn substitute_symbols_with_values(
                         *expression = Expression::Number(result);
                     }
                 }
+                Expression::FunctionOp(FunctionKind::PcRelHi, _) => {
+                    // Resolved by `auipc`, which loads the full address.
+                }
+                Expression::FunctionOp(FunctionKind::PcRelLo, _) => {
+                    // The full address has already been loaded by the
+                    // corresponding `auipc`, so there is nothing to add.
+                    *expression = Expression::Number(0);
+                }
                 Expression::FunctionOp(op, subexpr) => {
                     if let Expression::Number(num) = subexpr.as_ref() {
                         let result = match op {
                             FunctionKind::HiDataRef => num >> 12,
                             FunctionKind::LoDataRef => num & 0xfff,
+                            FunctionKind::PcRelHi | FunctionKind::PcRelLo => unreachable!(),
                         };
                         *expression = Expression::Number(result);
                     };
@@ -522,8 +724,8 @@ fn substitute_symbols_with_values(
     statements
 }
 
-fn riscv_machine(
-    machines: &[&str],
+pub(crate) fn riscv_machine(
+    machines: &[String],
     preamble: &str,
     submachines: &[(&str, &str)],
     program: Vec<String>,
@@ -556,7 +758,7 @@ machine Main {{
     )
 }
 
-fn preamble() -> String {
+pub(crate) fn preamble() -> String {
     r#"
     degree 262144;
     reg pc[@pc];
@@ -566,6 +768,7 @@ fn preamble() -> String {
     reg tmp1;
     reg tmp2;
     reg tmp3;
+    reg tmp4;
 "#
     .to_string()
         + &(0..32)
@@ -613,8 +816,9 @@ fn preamble() -> String {
         (m_addr' - m_addr) * (1 - m_change) = 0;
 
         // Except for the last row, if m_change is 1, then m_addr has to increase,
-        // if it is zero, m_step has to increase.
-        (1 - LAST) { m_change * (m_addr' - m_addr) + (1 - m_change) * (m_step' - m_step) } in POSITIVE;
+        // if it is zero, m_step has to increase. The only exception is a read
+        // followed by a write in the same step, which is used by sub-word stores.
+        (1 - LAST) { m_change * (m_addr' - m_addr) + (1 - m_change) * (m_step' - m_step + m_is_write' * m_is_read) } in POSITIVE;
 
         m_op * (1 - m_op) = 0;
         m_is_write * (1 - m_is_write) = 0;
@@ -636,15 +840,28 @@ fn preamble() -> String {
 
     // ============== memory instructions ==============
 
-    instr mstore X { { addr, STEP, X } is m_is_write { m_addr, m_step, m_value } }
-    instr mload -> X { { addr, STEP, X } is m_is_read { m_addr, m_step, m_value } }
+    // All memory instructions access the aligned word at mem_addr. Since every
+    // permutation is a separate argument, there is a single permutation for reads
+    // and one for writes, selected by the flags of the instructions that use them.
+    // A sub-word store reads mem_word and writes mem_new_word in the same step.
+    constraints {
+        col witness mem_addr;
+        col witness mem_word;
+        col witness mem_new_word;
+        (instr_mload + instr_load_word + instr_load_byte_unsigned + instr_load_byte_signed + instr_load_half_unsigned + instr_load_half_signed + instr_store_word + instr_store_byte + instr_store_half) { mem_addr, STEP, mem_word } is m_is_read { m_addr, m_step, m_value };
+        (instr_mstore + instr_store_word + instr_store_byte + instr_store_half) { mem_addr, STEP, mem_new_word } is m_is_write { m_addr, m_step, m_value };
+    }
+
+    instr mstore X { mem_addr = addr, mem_new_word = X }
+    instr mload -> X { mem_addr = addr, X = mem_word }
 
     // ============== control-flow instructions ==============
 
     instr jump l: label { pc' = l }
     instr load_label l: label -> X { X = l }
     instr jump_dyn X { pc' = X }
-    instr jump_and_link_dyn X { pc' = X, x1' = pc + 1 }
+    instr jump_and_link l: label -> Y { pc' = l, Y = pc + 1 }
+    instr jump_and_link_dyn X -> Y { pc' = X, Y = pc + 1 }
     instr call l: label { pc' = l, x1' = pc + 1 }
     // TODO x6 actually stores some relative address, but only part of it.
     instr tail l: label { pc' = l, x6' = l }
@@ -692,6 +909,10 @@ fn preamble() -> String {
 
     instr shr Y, Z -> X = shift.shr
 
+    // ================= committed outputs =================
+
+    instr write_output Y -> X = output.write
+
     // ================== wrapping instructions ==============
 
     // Wraps a value in Y to 32 bits.
@@ -713,18 +934,146 @@ fn preamble() -> String {
         wrap_bit * (1 - wrap_bit) = 0;
     }
 
-    // Input is a 32 bit unsigned number. We check the 7th bit and set all higher bits to that value.
-    instr sign_extend_byte Y -> X {
-        // wrap_bit is used as sign_bit here.
-        Y = Y_7bit + wrap_bit * 0x80 + X_b2 * 0x100 + X_b3 * 0x10000 + X_b4 * 0x1000000,
-        X = Y_7bit + wrap_bit * 0xffffff80
+    // ============== sub-word memory access ==============
+
+    // Memory is word-addressed, byte-addressed accesses read the aligned word that contains
+    // the byte address Y + Z (mem_word) and select the bytes inside the instruction,
+    // so that they only take a single step.
+    // The byte address is decomposed into X_b1..X_b4 (dropping the wrap_bit)
+    // and X_b1 is further split into the offset inside the word (offset_b1, offset_b0)
+    // and the remaining six bits. The aligned word is decomposed into mem_b1..mem_b4.
+    // Misaligned word accesses and half-word accesses that cross a word boundary
+    // cannot be done in a single step. In that case, the instructions do not modify
+    // memory, store the return address in tmp4 and jump to the label l instead,
+    // which is a routine that does the access byte by byte:
+    // - loads return the byte address in X,
+    // - stores put the byte address in tmp1 and the value in tmp2.
+    constraints {
+        col fixed six_bit(i) { i & 0x3f };
+        col witness addr_6bit;
+        { addr_6bit } in { six_bit };
+        col witness offset_b0;
+        col witness offset_b1;
+        col witness offset_both;
+        offset_b0 * (1 - offset_b0) = 0;
+        offset_b1 * (1 - offset_b1) = 0;
+        col witness mem_b1;
+        col witness mem_b2;
+        col witness mem_b3;
+        col witness mem_b4;
+        { mem_b1 } in { bytes };
+        { mem_b2 } in { bytes };
+        { mem_b3 } in { bytes };
+        { mem_b4 } in { bytes };
+        col witness mem_sign;
+        mem_sign * (1 - mem_sign) = 0;
+    }
+
+    instr load_word Y, Z, l: label -> X {
+        Y + Z = X_b1 + X_b2 * 0x100 + X_b3 * 0x10000 + X_b4 * 0x1000000 + wrap_bit * 2**32,
+        X_b1 = addr_6bit * 4 + offset_b1 * 2 + offset_b0,
+        offset_both = offset_b0 * offset_b1,
+        mem_addr = addr_6bit * 4 + X_b2 * 0x100 + X_b3 * 0x10000 + X_b4 * 0x1000000,
+        X = mem_word + (offset_b0 + offset_b1 - offset_both) * (mem_addr + offset_b1 * 2 + offset_b0 - mem_word),
+        tmp4' = pc + 1,
+        pc' = (offset_b0 + offset_b1 - offset_both) * l + (1 - offset_b0 - offset_b1 + offset_both) * (pc + 1)
+    }
+
+    instr load_byte_unsigned Y, Z -> X {
+        Y + Z = X_b1 + X_b2 * 0x100 + X_b3 * 0x10000 + X_b4 * 0x1000000 + wrap_bit * 2**32,
+        X_b1 = addr_6bit * 4 + offset_b1 * 2 + offset_b0,
+        offset_both = offset_b0 * offset_b1,
+        mem_addr = addr_6bit * 4 + X_b2 * 0x100 + X_b3 * 0x10000 + X_b4 * 0x1000000,
+        mem_word = mem_b1 + mem_b2 * 0x100 + mem_b3 * 0x10000 + mem_b4 * 0x1000000,
+        X = (1 - offset_b0 - offset_b1 + offset_both) * mem_b1 + (offset_b0 - offset_both) * mem_b2 + (offset_b1 - offset_both) * mem_b3 + offset_both * mem_b4
+    }
+
+    instr load_byte_signed Y, Z -> X {
+        Y + Z = X_b1 + X_b2 * 0x100 + X_b3 * 0x10000 + X_b4 * 0x1000000 + wrap_bit * 2**32,
+        X_b1 = addr_6bit * 4 + offset_b1 * 2 + offset_b0,
+        offset_both = offset_b0 * offset_b1,
+        mem_addr = addr_6bit * 4 + X_b2 * 0x100 + X_b3 * 0x10000 + X_b4 * 0x1000000,
+        mem_word = mem_b1 + mem_b2 * 0x100 + mem_b3 * 0x10000 + mem_b4 * 0x1000000,
+        (1 - offset_b0 - offset_b1 + offset_both) * mem_b1 + (offset_b0 - offset_both) * mem_b2 + (offset_b1 - offset_both) * mem_b3 + offset_both * mem_b4 = Y_7bit + mem_sign * 0x80,
+        X = Y_7bit + mem_sign * 0xffffff80
+    }
+
+    instr load_half_unsigned Y, Z, l: label -> X {
+        Y + Z = X_b1 + X_b2 * 0x100 + X_b3 * 0x10000 + X_b4 * 0x1000000 + wrap_bit * 2**32,
+        X_b1 = addr_6bit * 4 + offset_b1 * 2 + offset_b0,
+        offset_both = offset_b0 * offset_b1,
+        mem_addr = addr_6bit * 4 + X_b2 * 0x100 + X_b3 * 0x10000 + X_b4 * 0x1000000,
+        mem_word = mem_b1 + mem_b2 * 0x100 + mem_b3 * 0x10000 + mem_b4 * 0x1000000,
+        X = (1 - offset_b0 - offset_b1 + offset_both) * (mem_b1 + mem_b2 * 0x100) + (offset_b0 - offset_both) * (mem_b2 + mem_b3 * 0x100) + (offset_b1 - offset_both) * (mem_b3 + mem_b4 * 0x100) + offset_both * (mem_addr + 3),
+        tmp4' = pc + 1,
+        pc' = offset_both * l + (1 - offset_both) * (pc + 1)
+    }
+
+    instr load_half_signed Y, Z, l: label -> X {
+        Y + Z = X_b1 + X_b2 * 0x100 + X_b3 * 0x10000 + X_b4 * 0x1000000 + wrap_bit * 2**32,
+        X_b1 = addr_6bit * 4 + offset_b1 * 2 + offset_b0,
+        offset_both = offset_b0 * offset_b1,
+        mem_addr = addr_6bit * 4 + X_b2 * 0x100 + X_b3 * 0x10000 + X_b4 * 0x1000000,
+        mem_word = mem_b1 + mem_b2 * 0x100 + mem_b3 * 0x10000 + mem_b4 * 0x1000000,
+        (1 - offset_b0 - offset_b1 + offset_both) * (mem_b1 + mem_b2 * 0x100) + (offset_b0 - offset_both) * (mem_b2 + mem_b3 * 0x100) + (offset_b1 - offset_both) * (mem_b3 + mem_b4 * 0x100) = Y_15bit + mem_sign * 0x8000,
+        X = Y_15bit + mem_sign * 0xffff8000 + offset_both * (mem_addr + 3),
+        tmp4' = pc + 1,
+        pc' = offset_both * l + (1 - offset_both) * (pc + 1)
     }
+
+    // Stores X at address Y + Z.
+    // The word is read and written in the same step, the read is ordered first.
+    instr store_word Y, Z, X, l: label {
+        Y + Z = X_b1 + X_b2 * 0x100 + X_b3 * 0x10000 + X_b4 * 0x1000000 + wrap_bit * 2**32,
+        X_b1 = addr_6bit * 4 + offset_b1 * 2 + offset_b0,
+        offset_both = offset_b0 * offset_b1,
+        mem_addr = addr_6bit * 4 + X_b2 * 0x100 + X_b3 * 0x10000 + X_b4 * 0x1000000,
+        mem_new_word = mem_word + (1 - offset_b0 - offset_b1 + offset_both) * (X - mem_word),
+        tmp1' = mem_addr + offset_b1 * 2 + offset_b0,
+        tmp2' = X,
+        tmp4' = pc + 1,
+        pc' = (offset_b0 + offset_b1 - offset_both) * l + (1 - offset_b0 - offset_b1 + offset_both) * (pc + 1)
+    }
+
+    // Stores the lowest byte of X at address Y + Z.
+    // The word is read and written in the same step, the read is ordered first.
+    instr store_byte Y, Z, X {
+        Y + Z = X_b1 + X_b2 * 0x100 + X_b3 * 0x10000 + X_b4 * 0x1000000 + wrap_bit * 2**32,
+        X_b1 = addr_6bit * 4 + offset_b1 * 2 + offset_b0,
+        offset_both = offset_b0 * offset_b1,
+        mem_addr = addr_6bit * 4 + X_b2 * 0x100 + X_b3 * 0x10000 + X_b4 * 0x1000000,
+        mem_word = mem_b1 + mem_b2 * 0x100 + mem_b3 * 0x10000 + mem_b4 * 0x1000000,
+        X = Y_b5 + Y_b6 * 0x100 + Y_b7 * 0x10000 + Y_b8 * 0x1000000,
+        mem_new_word = mem_word + (1 - offset_b0 - offset_b1 + offset_both) * (Y_b5 - mem_b1) + (offset_b0 - offset_both) * (Y_b5 - mem_b2) * 0x100 + (offset_b1 - offset_both) * (Y_b5 - mem_b3) * 0x10000 + offset_both * (Y_b5 - mem_b4) * 0x1000000
+    }
+
+    // Stores the lowest two bytes of X at address Y + Z.
+    instr store_half Y, Z, X, l: label {
+        Y + Z = X_b1 + X_b2 * 0x100 + X_b3 * 0x10000 + X_b4 * 0x1000000 + wrap_bit * 2**32,
+        X_b1 = addr_6bit * 4 + offset_b1 * 2 + offset_b0,
+        offset_both = offset_b0 * offset_b1,
+        mem_addr = addr_6bit * 4 + X_b2 * 0x100 + X_b3 * 0x10000 + X_b4 * 0x1000000,
+        mem_word = mem_b1 + mem_b2 * 0x100 + mem_b3 * 0x10000 + mem_b4 * 0x1000000,
+        X = Y_b5 + Y_b6 * 0x100 + Y_b7 * 0x10000 + Y_b8 * 0x1000000,
+        mem_new_word = mem_word + (1 - offset_b0 - offset_b1 + offset_both) * (Y_b5 + Y_b6 * 0x100 - mem_b1 - mem_b2 * 0x100) + (offset_b0 - offset_both) * (Y_b5 + Y_b6 * 0x100 - mem_b2 - mem_b3 * 0x100) * 0x100 + (offset_b1 - offset_both) * (Y_b5 + Y_b6 * 0x100 - mem_b3 - mem_b4 * 0x100) * 0x10000,
+        tmp1' = mem_addr + 3,
+        tmp2' = X,
+        tmp4' = pc + 1,
+        pc' = offset_both * l + (1 - offset_both) * (pc + 1)
+    }
+
     constraints{
         col fixed seven_bit(i) { i & 0x7f };
         col witness Y_7bit;
         { Y_7bit } in { seven_bit };
     }
 
+    constraints{
+        col fixed fifteen_bit(i) { i & 0x7fff };
+        col witness Y_15bit;
+        { Y_15bit } in { fifteen_bit };
+    }
+
     // Input is a 32 but unsigned number (0 <= Y < 2**32) interpreted as a two's complement numbers.
     // Returns a signed number (-2**31 <= X < 2**31).
     instr to_signed Y -> X {
@@ -751,6 +1100,7 @@ fn preamble() -> String {
         { Y_b8 } in { bytes };
 
         col witness remainder; 
+        col witness quotient;
 
         col witness REM_b1;
         col witness REM_b2;
@@ -784,6 +1134,27 @@ fn preamble() -> String {
         Z = X_b1 + X_b2 * 0x100 + X_b3 * 0x10000 + X_b4 * 0x1000000
     }
 
+    // implements Z = Y % X, stores quotient in `quotient`.
+    instr remu Y, X -> Z {
+        // Y is the known dividend
+        // X is the known divisor
+        // Z is the unknown remainder
+        X * quotient + Z = Y,
+
+        // remainder >= 0:
+        Z = REM_b1 + REM_b2 * 0x100 + REM_b3 * 0x10000 + REM_b4 * 0x1000000,
+
+        // remainder < divisor, conditioned to X not being 0:
+        (1 - XIsZero) * (X - Z - 1 - Y_b5 - Y_b6 * 0x100 - Y_b7 * 0x10000 - Y_b8 * 0x1000000) = 0,
+
+        // in case X is zero, the quotient is set according to RISC-V specification,
+        // so the remainder is the dividend:
+        XIsZero * (quotient - 0xffffffff) = 0,
+
+        // quotient is 32 bits:
+        quotient = X_b1 + X_b2 * 0x100 + X_b3 * 0x10000 + X_b4 * 0x1000000
+    }
+
     // Removes up to 32 bits beyond 32
     // TODO is this really safe?
     instr mul Y, Z -> X {
@@ -849,7 +1220,7 @@ poseidon_coprocessor:
 "#
 }
 
-fn process_statement(s: Statement) -> Vec<String> {
+pub(crate) fn process_statement(s: Statement) -> Vec<String> {
     match &s {
         Statement::Label(l) => vec![format!("{}::", escape_label(l))],
         Statement::Directive(directive, args) => match (directive.as_str(), &args[..]) {
@@ -982,10 +1353,31 @@ fn only_if_no_write_to_zero_vec(statements: Vec<String>, reg: Register) -> Vec<S
     }
 }
 
+/// Returns statements that store the absolute value of the signed 32-bit value `rs`
+/// in `abs` and 1 in `sign` if it is negative, zero otherwise.
+fn abs_with_sign_bit(abs: &str, sign: &str, rs: Register) -> Vec<String> {
+    vec![
+        format!("{sign} <== to_signed({rs});"),
+        format!("{sign} <== is_positive(0 - {sign});"),
+        format!("{abs} <=X= {sign} * 0xffffffff;"),
+        format!("{abs} <== xor({abs}, {rs});"),
+        format!("{abs} <== wrap({abs} + {sign});"),
+    ]
+}
+
+/// Returns statements that store the two's complement negation of `value`
+/// in `rd` if `sign` is 1 and `value` itself if `sign` is 0.
+fn negate_if_sign_bit(rd: Register, value: &str, sign: &str) -> Vec<String> {
+    vec![
+        format!("{value} <== xor({value}, {sign} * 0xffffffff);"),
+        format!("{rd} <== wrap({value} + {sign});"),
+    ]
+}
+
 static COPROCESSOR_SUBSTITUTIONS: &[(&str, &str)] =
     &[("poseidon_coprocessor", "x10 <== poseidon(x10, x11);")];
 
-fn try_coprocessor_substitution(label: &str) -> Option<String> {
+pub(crate) fn try_coprocessor_substitution(label: &str) -> Option<String> {
     COPROCESSOR_SUBSTITUTIONS
         .iter()
         .find(|(l, _)| *l == label)
@@ -1004,14 +1396,49 @@ fn process_instruction(instr: &str, args: &[Argument]) -> Vec<String> {
             let (rd, imm) = ri(args);
             only_if_no_write_to_zero(format!("{rd} <=X= {};", imm << 12), rd)
         }
-        "la" => {
-            let (rd, addr) = ri(args);
-            only_if_no_write_to_zero(format!("{rd} <=X= {};", addr), rd)
-        }
+        "la" => match args {
+            // Data references have already been replaced by their address,
+            // so this is a reference to a code label.
+            [Argument::Register(rd), Argument::Expression(Expression::Symbol(label))] => {
+                only_if_no_write_to_zero(
+                    format!("{rd} <== load_label({});", escape_label(label)),
+                    *rd,
+                )
+            }
+            _ => {
+                let (rd, addr) = ri(args);
+                only_if_no_write_to_zero(format!("{rd} <=X= {};", addr), rd)
+            }
+        },
         "mv" => {
             let (rd, rs) = rr(args);
             only_if_no_write_to_zero(format!("{rd} <=X= {rs};"), rd)
         }
+        "auipc" => {
+            // We do not have byte addresses for code, so instead of adding an offset
+            // to the pc, we load the full address of the symbol here and
+            // the corresponding `%pcrel_lo` is replaced by zero.
+            // The form of the arguments is checked by `check_instruction_forms`.
+            let (rd, target) = match args {
+                [Argument::Register(rd), Argument::Expression(Expression::FunctionOp(FunctionKind::PcRelHi, target))] => {
+                    (*rd, target.as_ref())
+                }
+                _ => panic!(
+                    "auipc is only supported with %pcrel_hi, got: {}",
+                    args.iter().map(|a| a.to_string()).join(", ")
+                ),
+            };
+            let statement = match target {
+                // Reference to a data object, already replaced by its address.
+                Expression::Number(addr) => format!("{rd} <=X= {};", *addr as u32),
+                // Reference to a code label.
+                Expression::Symbol(label) => {
+                    format!("{rd} <== load_label({});", escape_label(label))
+                }
+                _ => panic!("Unsupported argument to %pcrel_hi: {target}"),
+            };
+            only_if_no_write_to_zero(statement, rd)
+        }
 
         // Arithmetic
         "add" => {
@@ -1038,10 +1465,88 @@ fn process_instruction(instr: &str, args: &[Argument]) -> Vec<String> {
             let (rd, r1, r2) = rrr(args);
             only_if_no_write_to_zero(format!("{rd} <== mulhu({r1}, {r2});"), rd)
         }
+        "mulh" => {
+            // Signed high multiplication, using the equivalence
+            // mulh(a, b) = mulhu(a, b) - (a < 0 ? b : 0) - (b < 0 ? a : 0) (mod 2**32)
+            let (rd, r1, r2) = rrr(args);
+            only_if_no_write_to_zero_vec(
+                vec![
+                    format!("tmp1 <== to_signed({r1});"),
+                    "tmp1 <== is_positive(0 - tmp1);".to_string(),
+                    format!("tmp2 <== to_signed({r2});"),
+                    "tmp2 <== is_positive(0 - tmp2);".to_string(),
+                    // Here, tmp1 (tmp2) is 1 if r1 (r2) is negative and zero otherwise.
+                    format!("tmp1 <== mul(tmp1, {r2});"),
+                    format!("tmp2 <== mul(tmp2, {r1});"),
+                    format!("tmp3 <== mulhu({r1}, {r2});"),
+                    "tmp3 <== wrap_signed(tmp3 - tmp1);".to_string(),
+                    format!("{rd} <== wrap_signed(tmp3 - tmp2);"),
+                ],
+                rd,
+            )
+        }
+        "mulhsu" => {
+            // High multiplication of a signed and an unsigned value, using the equivalence
+            // mulhsu(a, b) = mulhu(a, b) - (a < 0 ? b : 0) (mod 2**32)
+            let (rd, r1, r2) = rrr(args);
+            only_if_no_write_to_zero_vec(
+                vec![
+                    format!("tmp1 <== to_signed({r1});"),
+                    "tmp1 <== is_positive(0 - tmp1);".to_string(),
+                    format!("tmp1 <== mul(tmp1, {r2});"),
+                    format!("tmp2 <== mulhu({r1}, {r2});"),
+                    format!("{rd} <== wrap_signed(tmp2 - tmp1);"),
+                ],
+                rd,
+            )
+        }
         "divu" => {
             let (rd, r1, r2) = rrr(args);
             only_if_no_write_to_zero(format!("{rd} <=Z= divu({r1}, {r2});"), rd)
         }
+        "remu" => {
+            let (rd, r1, r2) = rrr(args);
+            only_if_no_write_to_zero(format!("{rd} <=Z= remu({r1}, {r2});"), rd)
+        }
+        "div" => {
+            // Signed division, performed as unsigned division of the absolute values.
+            // The quotient is negated if exactly one of the operands is negative,
+            // unless the divisor is zero, in which case the result is -1.
+            // The overflow case -2**31 / -1 results in -2**31 as required.
+            let (rd, r1, r2) = rrr(args);
+            only_if_no_write_to_zero_vec(
+                [
+                    abs_with_sign_bit("tmp3", "tmp1", r1),
+                    abs_with_sign_bit("tmp4", "tmp2", r2),
+                    vec![
+                        "tmp3 <=Z= divu(tmp3, tmp4);".to_string(),
+                        "tmp1 <== xor(tmp1, tmp2);".to_string(),
+                        format!("tmp2 <=Y= is_not_equal_zero({r2});"),
+                        "tmp1 <== and(tmp1, tmp2);".to_string(),
+                    ],
+                    negate_if_sign_bit(rd, "tmp3", "tmp1"),
+                ]
+                .concat(),
+                rd,
+            )
+        }
+        "rem" => {
+            // Signed remainder, performed as unsigned remainder of the absolute values.
+            // The remainder has the sign of the dividend, which also covers
+            // division by zero (result is the dividend) and the overflow case
+            // -2**31 % -1 (result is zero).
+            let (rd, r1, r2) = rrr(args);
+            only_if_no_write_to_zero_vec(
+                [
+                    abs_with_sign_bit("tmp3", "tmp1", r1),
+                    abs_with_sign_bit("tmp4", "tmp2", r2),
+                    vec!["tmp3 <=Z= remu(tmp3, tmp4);".to_string()],
+                    negate_if_sign_bit(rd, "tmp3", "tmp1"),
+                ]
+                .concat(),
+                rd,
+            )
+        }
 
         // bitwise
         "xor" => {
@@ -1138,6 +1643,23 @@ fn process_instruction(instr: &str, args: &[Argument]) -> Vec<String> {
             )
         }
 
+        "sra" => {
+            // arithmetic shift right, see "srai"
+            let (rd, r1, r2) = rrr(args);
+            only_if_no_write_to_zero_vec(
+                vec![
+                    format!("tmp1 <== to_signed({r1});"),
+                    "tmp1 <== is_positive(0 - tmp1);".to_string(),
+                    "tmp1 <=X= tmp1 * 0xffffffff;".to_string(),
+                    format!("tmp2 <== and({r2}, 0x1f);"),
+                    format!("{rd} <== xor(tmp1, {r1});"),
+                    format!("{rd} <== shr({rd}, tmp2);"),
+                    format!("{rd} <== xor(tmp1, {rd});"),
+                ],
+                rd,
+            )
+        }
+
         // comparison
         "seqz" => {
             let (rd, rs) = rr(args);
@@ -1270,13 +1792,31 @@ fn process_instruction(instr: &str, args: &[Argument]) -> Vec<String> {
             vec![format!("jump_dyn {rs};")]
         }
         "jal" => {
-            let (_rd, _label) = rl(args);
-            todo!();
+            let (rd, label) = match args {
+                [label] => (Register::new(1), argument_to_escaped_symbol(label)),
+                _ => rl(args),
+            };
+            if rd.is_zero() {
+                vec![format!("jump {label};")]
+            } else {
+                vec![format!("{rd} <=Y= jump_and_link({label});")]
+            }
         }
         "jalr" => {
-            // TODO there is also a form that takes more arguments
-            let rs = r(args);
-            vec![format!("jump_and_link_dyn {rs};")]
+            let (rd, rs, off) = match args {
+                [Argument::Register(rs)] => (Register::new(1), *rs, 0),
+                [Argument::Register(rd), Argument::Register(rs)] => (*rd, *rs, 0),
+                [_, Argument::RegOffset(_, _)] => rro(args),
+                _ => rri(args),
+            };
+            // Code addresses are not byte addresses, so offsets cannot be supported.
+            // This is checked by `check_instruction_forms` before the translation.
+            assert_eq!(off, 0, "jalr is only supported with a zero offset");
+            if rd.is_zero() {
+                vec![format!("jump_dyn {rs};")]
+            } else {
+                vec![format!("{rd} <=Y= jump_and_link_dyn({rs});")]
+            }
         }
         "call" | "tail" => {
             // Depending on what symbol is called, the call is replaced by a
@@ -1297,7 +1837,7 @@ fn process_instruction(instr: &str, args: &[Argument]) -> Vec<String> {
         }
         "ecall" => {
             assert!(args.is_empty());
-            vec!["x10 <=X= ${ (\"input\", x10) };".to_string()]
+            vec!["tmp1 <=Y= jump_and_link(__ecall);".to_string()]
         }
         "ebreak" => {
             assert!(args.is_empty());
@@ -1312,107 +1852,69 @@ fn process_instruction(instr: &str, args: &[Argument]) -> Vec<String> {
 
         // memory access
         "lw" => {
+            // Misaligned loads jump to the routine for {rd}, which loads the bytes
+            // from the address stored in {rd}.
             let (rd, rs, off) = rro(args);
-            // TODO we need to consider misaligned loads / stores
-            only_if_no_write_to_zero_vec(
-                vec![
-                    format!("addr <== wrap({rs} + {off});"),
-                    format!("{rd} <== mload();"),
-                ],
+            only_if_no_write_to_zero(
+                format!("{rd} <== load_word({rs}, {off}, __unaligned_lw_{rd});"),
                 rd,
             )
         }
         "lb" => {
             // load byte and sign-extend. the memory is little-endian.
             let (rd, rs, off) = rro(args);
-            only_if_no_write_to_zero_vec(
-                vec![
-                    format!("tmp1 <== wrap({rs} + {off});"),
-                    "addr <== and(tmp1, 0xfffffffc);".to_string(),
-                    "tmp2 <== and(tmp1, 0x3);".to_string(),
-                    format!("{rd} <== mload();"),
-                    format!("{rd} <== shr({rd}, 8 * tmp2);"),
-                    format!("{rd} <== sign_extend_byte({rd});"),
-                ],
+            only_if_no_write_to_zero(format!("{rd} <== load_byte_signed({rs}, {off});"), rd)
+        }
+        "lh" => {
+            // Load two bytes and sign-extend.
+            let (rd, rs, off) = rro(args);
+            only_if_no_write_to_zero(
+                format!("{rd} <== load_half_signed({rs}, {off}, __unaligned_lh_{rd});"),
                 rd,
             )
         }
         "lbu" => {
             // load byte and zero-extend. the memory is little-endian.
             let (rd, rs, off) = rro(args);
-            only_if_no_write_to_zero_vec(
-                vec![
-                    format!("tmp1 <== wrap({rs} + {off});"),
-                    "addr <== and(tmp1, 0xfffffffc);".to_string(),
-                    "tmp2 <== and(tmp1, 0x3);".to_string(),
-                    format!("{rd} <== mload();"),
-                    format!("{rd} <== shr({rd}, 8 * tmp2);"),
-                    format!("{rd} <== and({rd}, 0xff);"),
-                ],
-                rd,
-            )
+            only_if_no_write_to_zero(format!("{rd} <== load_byte_unsigned({rs}, {off});"), rd)
         }
         "lhu" => {
             // Load two bytes and zero-extend.
-            // Assumes the address is a multiple of two.
             let (rd, rs, off) = rro(args);
-            only_if_no_write_to_zero_vec(
-                vec![
-                    format!("tmp1 <== wrap({rs} + {off});"),
-                    "addr <== and(tmp1, 0xfffffffc);".to_string(),
-                    "tmp2 <== and(tmp1, 0x3);".to_string(),
-                    format!("{rd} <== mload();"),
-                    format!("{rd} <== shr({rd}, 8 * tmp2);"),
-                    format!("{rd} <== and({rd}, 0x0000ffff);"),
-                ],
+            only_if_no_write_to_zero(
+                format!("{rd} <== load_half_unsigned({rs}, {off}, __unaligned_lhu_{rd});"),
                 rd,
             )
         }
         "sw" => {
             let (r1, r2, off) = rro(args);
-            vec![
-                format!("addr <== wrap({r2} + {off});"),
-                format!("mstore {r1};"),
-            ]
+            vec![format!("store_word {r2}, {off}, {r1}, __unaligned_sw;")]
         }
         "sh" => {
             // store half word (two bytes)
-            // TODO this code assumes it is at least aligned on
-            // a two-byte boundary
-
             let (rs, rd, off) = rro(args);
-            vec![
-                format!("tmp1 <== wrap({rd} + {off});"),
-                "addr <== and(tmp1, 0xfffffffc);".to_string(),
-                "tmp2 <== and(tmp1, 0x3);".to_string(),
-                "tmp1 <== mload();".to_string(),
-                "tmp3 <== shl(0xffff, 8 * tmp2);".to_string(),
-                "tmp3 <== xor(tmp3, 0xffffffff);".to_string(),
-                "tmp1 <== and(tmp1, tmp3);".to_string(),
-                format!("tmp3 <== and({rs}, 0xffff);"),
-                "tmp3 <== shl(tmp3, 8 * tmp2);".to_string(),
-                "tmp1 <== or(tmp1, tmp3);".to_string(),
-                "mstore tmp1;".to_string(),
-            ]
+            vec![format!("store_half {rd}, {off}, {rs}, __unaligned_sh;")]
         }
         "sb" => {
             // store byte
             let (rs, rd, off) = rro(args);
-            vec![
-                format!("tmp1 <== wrap({rd} + {off});"),
-                "addr <== and(tmp1, 0xfffffffc);".to_string(),
-                "tmp2 <== and(tmp1, 0x3);".to_string(),
-                "tmp1 <== mload();".to_string(),
-                "tmp3 <== shl(0xff, 8 * tmp2);".to_string(),
-                "tmp3 <== xor(tmp3, 0xffffffff);".to_string(),
-                "tmp1 <== and(tmp1, tmp3);".to_string(),
-                format!("tmp3 <== and({rs}, 0xff);"),
-                "tmp3 <== shl(tmp3, 8 * tmp2);".to_string(),
-                "tmp1 <== or(tmp1, tmp3);".to_string(),
-                "mstore tmp1;".to_string(),
-            ]
+            vec![format!("store_byte {rd}, {off}, {rs};")]
         }
         "nop" => vec![],
+        // There is no concurrency and no instruction cache, so fences are no-ops.
+        "fence" | "fence.i" => vec![],
+        // There are no control and status registers: reads return zero and writes are ignored.
+        "csrr" | "csrrw" | "csrrs" | "csrrc" | "csrrwi" | "csrrsi" | "csrrci" => {
+            // Checked by `check_instruction_forms`.
+            let [Argument::Register(rd), ..] = args else {
+                panic!(
+                    "Expected destination register, got: {}",
+                    args.iter().map(|a| a.to_string()).join(", ")
+                )
+            };
+            only_if_no_write_to_zero(format!("{rd} <=X= 0;"), *rd)
+        }
+        "csrw" | "csrs" | "csrc" | "csrwi" | "csrsi" | "csrci" => vec![],
         "unimp" => vec!["fail;".to_string()],
 
         // Special instruction that is inserted to allow dynamic label references
@@ -1429,6 +1931,8 @@ fn process_instruction(instr: &str, args: &[Argument]) -> Vec<String> {
 
 #[cfg(test)]
 mod test {
+    use number::GoldilocksField;
+
     use super::*;
 
     #[test]
@@ -1459,4 +1963,24 @@ mod test {
             vec!["chausson aux pommes"]
         );
     }
+
+    #[test]
+    fn test_committed_outputs() {
+        let publics = |values: &[u32]| {
+            values
+                .iter()
+                .enumerate()
+                .map(|(i, v)| (format!("output_{i}"), GoldilocksField::from(*v)))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            committed_outputs(&publics(&[7, 255, OUTPUT_END, 3])),
+            Some(vec![7, 255])
+        );
+        assert_eq!(committed_outputs(&publics(&[OUTPUT_END])), Some(vec![]));
+        // No terminator.
+        assert_eq!(committed_outputs(&publics(&[1, 2])), None);
+        // Not a byte.
+        assert_eq!(committed_outputs(&publics(&[300, OUTPUT_END])), None);
+    }
 }
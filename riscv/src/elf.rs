@@ -0,0 +1,911 @@
+//! Translation of statically linked RV32IM ELF binaries to powdr assembly.
+//!
+//! Instead of going through textual assembly, the instructions are decoded
+//! directly from the executable sections of the binary and the contents of
+//! the other allocated sections are used to initialize the memory.
+//!
+//! Code addresses in powdr asm are not byte addresses, so every value that is
+//! known to be the address of an instruction is replaced by a reference to a
+//! label: targets of branches and jumps, addresses computed by `lui`/`auipc`
+//! and `addi` and words in data sections that point into code.
+//! Whether a constant or a data word is an address cannot be decided from its
+//! value, so the binary has to be linked with `--emit-relocs` and only values
+//! with relocations are treated as addresses.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use goblin::elf::{
+    header::{EM_RISCV, ET_EXEC},
+    program_header::{PF_X, PT_DYNAMIC, PT_LOAD},
+    reloc::{
+        Reloc, R_RISCV_32, R_RISCV_HI20, R_RISCV_LO12_I, R_RISCV_PCREL_HI20, R_RISCV_PCREL_LO12_I,
+    },
+    section_header::{SHF_ALLOC, SHF_EXECINSTR, SHT_NOBITS},
+    Elf,
+};
+
+use crate::compiler::{
+    call_every_submachine, machine_decls, preamble, process_statement, riscv_machine,
+    syscall_routine, try_coprocessor_substitution, unaligned_access_routines, Register,
+};
+use crate::{Argument, Expression, Statement};
+
+/// The `e_flags` bit that marks a binary containing compressed instructions.
+const EF_RISCV_RVC: u32 = 0x1;
+/// The `e_flags` bits that specify the floating point ABI.
+const EF_RISCV_FLOAT_ABI: u32 = 0x6;
+
+/// Translates a statically linked RV32IM ELF binary to powdr assembly.
+/// Returns an error message if the binary is not supported.
+pub fn translate(elf_bytes: &[u8]) -> Result<String, String> {
+    let program = ElfProgram::load(elf_bytes)?;
+    let mut translator = Translator::new(&program);
+    let code = translator.translate_code()?;
+    let data_code = translator.data_initialization();
+
+    // The stack grows towards zero, starting right below the lowest loaded address.
+    let stack_start = program.lowest_address & !0xf;
+
+    Ok(riscv_machine(
+        &machine_decls(),
+        &preamble(),
+        &[
+            ("binary", "Binary"),
+            ("shift", "Shift"),
+            ("output", "Output"),
+        ],
+        ["call __data_init;".to_string()]
+            .into_iter()
+            .chain(call_every_submachine())
+            .chain([
+                format!("// Set stack pointer\nx2 <=X= {stack_start};"),
+                format!("call {};", code_label(program.entry)),
+                "return;".to_string(), // This is not "riscv ret", but "return from powdr asm function".
+            ])
+            .chain(code)
+            .chain(syscall_routine())
+            .chain(unaligned_access_routines())
+            .chain(["// This is the data initialization routine.\n__data_init::".to_string()])
+            .chain(data_code)
+            .chain(["// This is the end of the data initialization routine.\nret;".to_string()])
+            .collect(),
+    ))
+}
+
+/// The parts of an ELF binary relevant for the translation.
+struct ElfProgram {
+    entry: u32,
+    /// The executable regions, as start address and contents.
+    code: Vec<(u32, Vec<u8>)>,
+    /// The initial memory contents outside the executable regions, as a map
+    /// from word-aligned addresses to the (non-zero) word at that address.
+    data: BTreeMap<u32, u32>,
+    /// The addresses of data words that point into code.
+    code_pointers_in_data: BTreeSet<u32>,
+    /// The addresses of the instructions that compute (a part of) an address,
+    /// according to the relocations.
+    address_instructions: BTreeSet<u32>,
+    /// Names of function symbols by address.
+    functions: BTreeMap<u32, String>,
+    lowest_address: u32,
+}
+
+impl ElfProgram {
+    fn load(elf_bytes: &[u8]) -> Result<Self, String> {
+        let elf = Elf::parse(elf_bytes).map_err(|e| format!("Invalid ELF file: {e}"))?;
+        if elf.is_64 || elf.header.e_machine != EM_RISCV {
+            return Err("Expected a 32-bit RISC-V ELF binary.".to_string());
+        }
+        if !elf.little_endian {
+            return Err("Expected a little-endian ELF binary.".to_string());
+        }
+        if elf.header.e_type != ET_EXEC
+            || elf.interpreter.is_some()
+            || elf.program_headers.iter().any(|p| p.p_type == PT_DYNAMIC)
+        {
+            return Err("Expected a statically linked executable.".to_string());
+        }
+        if elf.header.e_flags & EF_RISCV_RVC != 0 {
+            return Err(
+                "Compressed instructions are not supported, please compile for RV32IM.".to_string(),
+            );
+        }
+        if elf.header.e_flags & EF_RISCV_FLOAT_ABI != 0 {
+            return Err(
+                "Floating point instructions are not supported, please compile for RV32IM."
+                    .to_string(),
+            );
+        }
+
+        let regions = loaded_regions(&elf, elf_bytes)?;
+        let lowest_address = regions
+            .iter()
+            .map(|(addr, _, _)| *addr)
+            .min()
+            .ok_or_else(|| "The ELF file does not contain any loadable code.".to_string())?;
+
+        let mut code = vec![];
+        let mut data = BTreeMap::new();
+        for (addr, bytes, executable) in regions {
+            if executable {
+                code.push((addr, bytes));
+            } else {
+                // The memory is little-endian and can be assumed to be zero-initialized.
+                for (i, b) in bytes.into_iter().enumerate().filter(|(_, b)| *b != 0) {
+                    let byte_addr = addr + i as u32;
+                    *data.entry(byte_addr & !0x3).or_default() |=
+                        (b as u32) << (8 * (byte_addr & 0x3));
+                }
+            }
+        }
+        code.sort_by_key(|(addr, _)| *addr);
+
+        if elf.shdr_relocs.is_empty() {
+            return Err(
+                "The ELF file does not contain relocations, please link with --emit-relocs."
+                    .to_string(),
+            );
+        }
+        let address_instructions = relocations(&elf, true)
+            .filter(|reloc| {
+                matches!(
+                    reloc.r_type,
+                    R_RISCV_HI20 | R_RISCV_LO12_I | R_RISCV_PCREL_HI20 | R_RISCV_PCREL_LO12_I
+                )
+            })
+            .map(|reloc| reloc.r_offset as u32)
+            .collect();
+
+        let functions = elf
+            .syms
+            .iter()
+            .filter(|sym| sym.is_function() && sym.st_value != 0)
+            .filter_map(|sym| {
+                let name = elf.strtab.get_at(sym.st_name)?;
+                Some((sym.st_value as u32, name.to_string()))
+            })
+            .collect();
+
+        let mut program = ElfProgram {
+            entry: elf.entry as u32,
+            code,
+            data,
+            code_pointers_in_data: Default::default(),
+            address_instructions,
+            functions,
+            lowest_address,
+        };
+        if !program.is_code_address(program.entry) {
+            return Err(format!(
+                "The entry point 0x{:x} is not in an executable section.",
+                program.entry
+            ));
+        }
+
+        program.code_pointers_in_data = program.find_code_pointers_in_data(&elf);
+        Ok(program)
+    }
+
+    /// Returns the addresses of data words that point into code.
+    fn find_code_pointers_in_data(&self, elf: &Elf) -> BTreeSet<u32> {
+        relocations(elf, false)
+            .filter(|reloc| reloc.r_type == R_RISCV_32)
+            .map(|reloc| reloc.r_offset as u32)
+            .filter(|addr| {
+                self.data
+                    .get(addr)
+                    .is_some_and(|value| self.is_code_address(*value))
+            })
+            .collect()
+    }
+
+    fn instructions(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.code.iter().flat_map(|(start, bytes)| {
+            bytes.chunks_exact(4).enumerate().map(move |(i, word)| {
+                (
+                    start + 4 * i as u32,
+                    u32::from_le_bytes(word.try_into().unwrap()),
+                )
+            })
+        })
+    }
+
+    fn is_code_address(&self, addr: u32) -> bool {
+        addr & 0x3 == 0
+            && self
+                .code
+                .iter()
+                .any(|(start, bytes)| *start <= addr && addr < start + bytes.len() as u32)
+    }
+}
+
+/// Returns the relocations that apply to the allocated executable sections
+/// if `executable` is true and to the other allocated sections otherwise.
+fn relocations<'a>(elf: &'a Elf, executable: bool) -> impl Iterator<Item = Reloc> + 'a {
+    elf.shdr_relocs
+        .iter()
+        .filter(move |(idx, _)| {
+            let target = &elf.section_headers[elf.section_headers[*idx].sh_info as usize];
+            target.sh_flags as u32 & SHF_ALLOC != 0
+                && (target.sh_flags as u32 & SHF_EXECINSTR != 0) == executable
+        })
+        .flat_map(|(_, relocs)| relocs.iter())
+}
+
+/// Returns the allocated regions of the ELF file as start address, contents
+/// and whether the region is executable.
+/// Uses the section headers if present and the program headers otherwise.
+fn loaded_regions(elf: &Elf, elf_bytes: &[u8]) -> Result<Vec<(u32, Vec<u8>, bool)>, String> {
+    let file_contents = |offset: u64, size: u64| {
+        elf_bytes
+            .get(offset as usize..(offset + size) as usize)
+            .map(|b| b.to_vec())
+            .ok_or_else(|| "Section or segment exceeds the ELF file.".to_string())
+    };
+    if elf.section_headers.iter().any(|s| s.sh_addr != 0) {
+        elf.section_headers
+            .iter()
+            .filter(|s| s.sh_flags as u32 & SHF_ALLOC != 0 && s.sh_size != 0)
+            .map(|s| {
+                let contents = if s.sh_type == SHT_NOBITS {
+                    vec![]
+                } else {
+                    file_contents(s.sh_offset, s.sh_size)?
+                };
+                Ok((
+                    s.sh_addr as u32,
+                    contents,
+                    s.sh_flags as u32 & SHF_EXECINSTR != 0,
+                ))
+            })
+            .collect()
+    } else {
+        elf.program_headers
+            .iter()
+            .filter(|p| p.p_type == PT_LOAD && p.p_memsz != 0)
+            .map(|p| {
+                Ok((
+                    p.p_vaddr as u32,
+                    file_contents(p.p_offset, p.p_filesz)?,
+                    p.p_flags & PF_X != 0,
+                ))
+            })
+            .collect()
+    }
+}
+
+struct Translator<'a> {
+    program: &'a ElfProgram,
+    /// Code addresses that are referenced and thus need a label.
+    referenced: BTreeSet<u32>,
+    /// Values of the registers that are known at the current instruction
+    /// because they were computed from constants.
+    known_values: [Option<KnownValue>; 32],
+}
+
+/// The value of a register computed from constants.
+#[derive(Clone, Copy)]
+struct KnownValue {
+    value: u32,
+    /// Whether all instructions computing the value have address relocations,
+    /// i.e. the value is an address and not a plain constant.
+    is_address: bool,
+}
+
+impl<'a> Translator<'a> {
+    fn new(program: &'a ElfProgram) -> Self {
+        let mut translator = Translator {
+            program,
+            referenced: program
+                .code_pointers_in_data
+                .iter()
+                .map(|addr| program.data[addr])
+                .chain([program.entry])
+                .collect(),
+            known_values: [None; 32],
+        };
+        translator.forget_known_values();
+        translator
+    }
+
+    /// Translates all instructions and returns the powdr asm code.
+    fn translate_code(&mut self) -> Result<Vec<String>, String> {
+        let program = self.program;
+        // Register values are only propagated within straight-line code,
+        // so we need to know all places where control flow can join.
+        let instructions = program
+            .instructions()
+            .map(|(pc, word)| (pc, word, decode(word)))
+            .collect::<Vec<_>>();
+        let join_points = instructions
+            .iter()
+            .filter_map(|(pc, _, instr)| instr.as_ref().and_then(|i| i.static_target(*pc)))
+            .chain(self.referenced.iter().cloned())
+            .chain(program.functions.keys().cloned())
+            .collect::<BTreeSet<_>>();
+
+        let mut translated = vec![];
+        for (i, (pc, word, instr)) in instructions.iter().enumerate() {
+            let pc = *pc;
+            if join_points.contains(&pc) {
+                self.forget_known_values();
+            }
+            // The next instruction, if it is only reachable from this one.
+            let next = instructions
+                .get(i + 1)
+                .filter(|(next_pc, _, _)| !join_points.contains(next_pc))
+                .and_then(|(_, _, next)| next.as_ref());
+            let statements = match instr {
+                Some(instr) => self
+                    .translate_instruction(pc, instr, next)
+                    .map_err(|e| format!("Instruction at 0x{pc:x}: {e}"))?,
+                None => {
+                    log::debug!("Could not decode instruction 0x{word:08x} at 0x{pc:x}.");
+                    self.forget_known_values();
+                    vec![instruction("unimp", vec![])]
+                }
+            };
+            translated.push((pc, statements));
+        }
+
+        Ok(translated
+            .into_iter()
+            .flat_map(|(pc, statements)| {
+                let symbol = program.functions.get(&pc).map(|name| format!("// {name}"));
+                let label = self
+                    .referenced
+                    .contains(&pc)
+                    .then(|| Statement::Label(code_label(pc)));
+                symbol.into_iter().chain(
+                    label
+                        .into_iter()
+                        .chain(statements)
+                        .flat_map(process_statement),
+                )
+            })
+            .collect())
+    }
+
+    fn translate_instruction(
+        &mut self,
+        pc: u32,
+        instr: &Instr,
+        next: Option<&Instr>,
+    ) -> Result<Vec<Statement>, String> {
+        let statements = match *instr {
+            Instr::Lui { rd, imm: value } | Instr::Auipc { rd, imm: value } => {
+                let value = KnownValue {
+                    value: if matches!(instr, Instr::Auipc { .. }) {
+                        pc.wrapping_add(value)
+                    } else {
+                        value
+                    },
+                    is_address: self.program.address_instructions.contains(&pc),
+                };
+                // The upper part of an address is usually only used by the next
+                // instruction, in which case it does not need to be stored.
+                let statements = if next.is_some_and(|next| next.replaces_register(rd)) {
+                    vec![]
+                } else {
+                    vec![self.load_constant(rd, value)]
+                };
+                self.set_known_value(rd, Some(value));
+                statements
+            }
+            Instr::Imm {
+                name: "addi",
+                rd,
+                rs1,
+                imm,
+            } if self.known_values[rs1 as usize].is_some() => {
+                let base = self.known_values[rs1 as usize].unwrap();
+                let value = KnownValue {
+                    value: base.value.wrapping_add(imm as u32),
+                    is_address: base.is_address && self.program.address_instructions.contains(&pc),
+                };
+                let statement = self.load_constant(rd, value);
+                self.set_known_value(rd, Some(value));
+                vec![statement]
+            }
+            Instr::Jal { rd, offset } => {
+                let target = pc.wrapping_add(offset as u32);
+                let statements = self.jump_to(rd, target)?;
+                self.forget_known_values();
+                statements
+            }
+            Instr::Jalr { rd, rs1, offset } => {
+                let statements = if let Some(base) = self.known_values[rs1 as usize] {
+                    self.jump_to(rd, base.value.wrapping_add(offset as u32))?
+                } else if offset == 0 {
+                    vec![instruction("jalr", vec![reg(rd), reg(rs1)])]
+                } else {
+                    return Err(
+                        "jalr with a non-zero offset to an unknown address is not supported."
+                            .to_string(),
+                    );
+                };
+                self.forget_known_values();
+                statements
+            }
+            Instr::Branch {
+                name,
+                rs1,
+                rs2,
+                offset,
+            } => {
+                let target = pc.wrapping_add(offset as u32);
+                let label = self.label_reference(target)?;
+                self.forget_known_values();
+                vec![instruction(name, vec![reg(rs1), reg(rs2), label])]
+            }
+            Instr::Load {
+                name,
+                rd,
+                rs1,
+                offset,
+            } => {
+                self.set_known_value(rd, None);
+                vec![instruction(name, vec![reg(rd), reg_offset(rs1, offset)])]
+            }
+            Instr::Store {
+                name,
+                rs1,
+                rs2,
+                offset,
+            } => vec![instruction(name, vec![reg(rs2), reg_offset(rs1, offset)])],
+            Instr::Imm { name, rd, rs1, imm } => {
+                self.set_known_value(rd, None);
+                vec![instruction(
+                    name,
+                    vec![reg(rd), reg(rs1), number(imm as i64)],
+                )]
+            }
+            Instr::Reg { name, rd, rs1, rs2 } => {
+                self.set_known_value(rd, None);
+                vec![instruction(name, vec![reg(rd), reg(rs1), reg(rs2)])]
+            }
+            Instr::Csr { name, rd } => {
+                self.set_known_value(rd, None);
+                vec![instruction(name, vec![reg(rd)])]
+            }
+            Instr::Ecall => {
+                self.set_known_value(10, None);
+                vec![instruction("ecall", vec![])]
+            }
+            Instr::Other { name } => vec![instruction(name, vec![])],
+        };
+        Ok(statements)
+    }
+
+    /// Returns a statement that loads `value` into `rd`, as a label reference
+    /// if it is an address that points into code.
+    fn load_constant(&mut self, rd: u8, KnownValue { value, is_address }: KnownValue) -> Statement {
+        if is_address && self.program.is_code_address(value) {
+            self.referenced.insert(value);
+            instruction("la", vec![reg(rd), symbol(code_label(value))])
+        } else {
+            instruction("li", vec![reg(rd), number(value as i64)])
+        }
+    }
+
+    /// Returns statements that jump to the code address `target`, storing the
+    /// return address in `rd`.
+    fn jump_to(&mut self, rd: u8, target: u32) -> Result<Vec<Statement>, String> {
+        if let Some(name) = self.program.functions.get(&target) {
+            if try_coprocessor_substitution(name).is_some() {
+                match rd {
+                    0 => return Ok(vec![instruction("tail", vec![symbol(name.clone())])]),
+                    1 => return Ok(vec![instruction("call", vec![symbol(name.clone())])]),
+                    _ => {}
+                }
+            }
+        }
+        let label = self.label_reference(target)?;
+        Ok(vec![instruction("jal", vec![reg(rd), label])])
+    }
+
+    fn label_reference(&mut self, target: u32) -> Result<Argument, String> {
+        if !self.program.is_code_address(target) {
+            return Err(format!("Jump target 0x{target:x} is not a code address."));
+        }
+        self.referenced.insert(target);
+        Ok(symbol(code_label(target)))
+    }
+
+    fn set_known_value(&mut self, rd: u8, value: Option<KnownValue>) {
+        if rd != 0 {
+            self.known_values[rd as usize] = value;
+        }
+    }
+
+    fn forget_known_values(&mut self) {
+        self.known_values = [None; 32];
+        self.known_values[0] = Some(KnownValue {
+            value: 0,
+            is_address: false,
+        });
+    }
+
+    /// Returns the powdr asm code that initializes the memory.
+    fn data_initialization(&self) -> Vec<String> {
+        self.program
+            .data
+            .iter()
+            .flat_map(|(addr, value)| {
+                if self.program.code_pointers_in_data.contains(addr) {
+                    // TODO should be possible without temporary
+                    vec![
+                        format!("addr <=X= 0x{addr:x};"),
+                        format!("tmp1 <== load_label({});", code_label(*value)),
+                        "mstore tmp1;".to_string(),
+                    ]
+                } else {
+                    vec![
+                        format!("addr <=X= 0x{addr:x};"),
+                        format!("mstore 0x{value:x};"),
+                    ]
+                }
+            })
+            .collect()
+    }
+}
+
+fn code_label(addr: u32) -> String {
+    format!("__elf_{addr:x}")
+}
+
+fn instruction(name: &str, args: Vec<Argument>) -> Statement {
+    Statement::Instruction(name.to_string(), args)
+}
+
+fn reg(r: u8) -> Argument {
+    Argument::Register(Register::new(r))
+}
+
+fn reg_offset(r: u8, offset: i32) -> Argument {
+    Argument::RegOffset(Register::new(r), Expression::Number(offset as i64))
+}
+
+fn number(n: i64) -> Argument {
+    Argument::Expression(Expression::Number(n))
+}
+
+fn symbol(s: String) -> Argument {
+    Argument::Expression(Expression::Symbol(s))
+}
+
+/// A decoded RV32IM instruction. Register fields are register numbers.
+#[derive(Debug, PartialEq, Eq)]
+enum Instr {
+    Lui {
+        rd: u8,
+        imm: u32,
+    },
+    Auipc {
+        rd: u8,
+        imm: u32,
+    },
+    Jal {
+        rd: u8,
+        offset: i32,
+    },
+    Jalr {
+        rd: u8,
+        rs1: u8,
+        offset: i32,
+    },
+    Branch {
+        name: &'static str,
+        rs1: u8,
+        rs2: u8,
+        offset: i32,
+    },
+    Load {
+        name: &'static str,
+        rd: u8,
+        rs1: u8,
+        offset: i32,
+    },
+    Store {
+        name: &'static str,
+        rs1: u8,
+        rs2: u8,
+        offset: i32,
+    },
+    Imm {
+        name: &'static str,
+        rd: u8,
+        rs1: u8,
+        imm: i32,
+    },
+    Reg {
+        name: &'static str,
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    Csr {
+        name: &'static str,
+        rd: u8,
+    },
+    Ecall,
+    /// Instructions without operands that are relevant to us.
+    Other {
+        name: &'static str,
+    },
+}
+
+impl Instr {
+    /// Returns true if this instruction computes a new value for `r` from
+    /// the current value of `r` and a constant.
+    fn replaces_register(&self, r: u8) -> bool {
+        match self {
+            Instr::Imm {
+                name: "addi",
+                rd,
+                rs1,
+                ..
+            }
+            | Instr::Jalr { rd, rs1, .. } => *rd == r && *rs1 == r,
+            _ => false,
+        }
+    }
+
+    /// Returns the target of a branch or a direct jump.
+    fn static_target(&self, pc: u32) -> Option<u32> {
+        match self {
+            Instr::Jal { offset, .. } | Instr::Branch { offset, .. } => {
+                Some(pc.wrapping_add(*offset as u32))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Decodes a 32-bit RV32IM instruction.
+/// Returns None if the word is not a valid instruction.
+fn decode(word: u32) -> Option<Instr> {
+    let opcode = word & 0x7f;
+    let rd = ((word >> 7) & 0x1f) as u8;
+    let funct3 = (word >> 12) & 0x7;
+    let rs1 = ((word >> 15) & 0x1f) as u8;
+    let rs2 = ((word >> 20) & 0x1f) as u8;
+    let funct7 = word >> 25;
+
+    let imm_i = (word as i32) >> 20;
+    let imm_s = (((word as i32) >> 25) << 5) | ((word >> 7) & 0x1f) as i32;
+    let imm_b = (((word as i32) >> 31) << 12)
+        | (((word >> 7) & 0x1) << 11) as i32
+        | (((word >> 25) & 0x3f) << 5) as i32
+        | (((word >> 8) & 0xf) << 1) as i32;
+    let imm_u = word & 0xfffff000;
+    let imm_j = (((word as i32) >> 31) << 20)
+        | (word & 0xff000) as i32
+        | (((word >> 20) & 0x1) << 11) as i32
+        | (((word >> 21) & 0x3ff) << 1) as i32;
+
+    Some(match opcode {
+        0x37 => Instr::Lui { rd, imm: imm_u },
+        0x17 => Instr::Auipc { rd, imm: imm_u },
+        0x6f => Instr::Jal { rd, offset: imm_j },
+        0x67 if funct3 == 0 => Instr::Jalr {
+            rd,
+            rs1,
+            offset: imm_i,
+        },
+        0x63 => Instr::Branch {
+            name: match funct3 {
+                0 => "beq",
+                1 => "bne",
+                4 => "blt",
+                5 => "bge",
+                6 => "bltu",
+                7 => "bgeu",
+                _ => return None,
+            },
+            rs1,
+            rs2,
+            offset: imm_b,
+        },
+        0x03 => Instr::Load {
+            name: match funct3 {
+                0 => "lb",
+                1 => "lh",
+                2 => "lw",
+                4 => "lbu",
+                5 => "lhu",
+                _ => return None,
+            },
+            rd,
+            rs1,
+            offset: imm_i,
+        },
+        0x23 => Instr::Store {
+            name: match funct3 {
+                0 => "sb",
+                1 => "sh",
+                2 => "sw",
+                _ => return None,
+            },
+            rs1,
+            rs2,
+            offset: imm_s,
+        },
+        0x13 => {
+            let (name, imm) = match (funct3, funct7) {
+                (0, _) => ("addi", imm_i),
+                (2, _) => ("slti", imm_i),
+                (3, _) => ("sltiu", imm_i),
+                (4, _) => ("xori", imm_i),
+                (6, _) => ("ori", imm_i),
+                (7, _) => ("andi", imm_i),
+                (1, 0x00) => ("slli", rs2 as i32),
+                (5, 0x00) => ("srli", rs2 as i32),
+                (5, 0x20) => ("srai", rs2 as i32),
+                _ => return None,
+            };
+            Instr::Imm { name, rd, rs1, imm }
+        }
+        0x33 => Instr::Reg {
+            name: match (funct7, funct3) {
+                (0x00, 0) => "add",
+                (0x20, 0) => "sub",
+                (0x00, 1) => "sll",
+                (0x00, 2) => "slt",
+                (0x00, 3) => "sltu",
+                (0x00, 4) => "xor",
+                (0x00, 5) => "srl",
+                (0x20, 5) => "sra",
+                (0x00, 6) => "or",
+                (0x00, 7) => "and",
+                (0x01, 0) => "mul",
+                (0x01, 1) => "mulh",
+                (0x01, 2) => "mulhsu",
+                (0x01, 3) => "mulhu",
+                (0x01, 4) => "div",
+                (0x01, 5) => "divu",
+                (0x01, 6) => "rem",
+                (0x01, 7) => "remu",
+                _ => return None,
+            },
+            rd,
+            rs1,
+            rs2,
+        },
+        0x0f => Instr::Other {
+            name: match funct3 {
+                0 => "fence",
+                1 => "fence.i",
+                _ => return None,
+            },
+        },
+        0x73 => match (funct3, word) {
+            (0, 0x00000073) => Instr::Ecall,
+            (0, 0x00100073) => Instr::Other { name: "ebreak" },
+            // `unimp` is encoded as `csrrw x0, cycle, x0`, which would be a no-op otherwise.
+            (_, 0xc0001073) => Instr::Other { name: "unimp" },
+            (1, _) => Instr::Csr { name: "csrrw", rd },
+            (2, _) => Instr::Csr { name: "csrrs", rd },
+            (3, _) => Instr::Csr { name: "csrrc", rd },
+            (5, _) => Instr::Csr { name: "csrrwi", rd },
+            (6, _) => Instr::Csr { name: "csrrsi", rd },
+            (7, _) => Instr::Csr { name: "csrrci", rd },
+            _ => return None,
+        },
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decode_instructions() {
+        // addi sp, sp, -16
+        assert_eq!(
+            decode(0xff010113),
+            Some(Instr::Imm {
+                name: "addi",
+                rd: 2,
+                rs1: 2,
+                imm: -16
+            })
+        );
+        // sw ra, 12(sp)
+        assert_eq!(
+            decode(0x00112623),
+            Some(Instr::Store {
+                name: "sw",
+                rs1: 2,
+                rs2: 1,
+                offset: 12
+            })
+        );
+        // auipc t0, 0xfffff
+        assert_eq!(
+            decode(0xfffff297),
+            Some(Instr::Auipc {
+                rd: 5,
+                imm: 0xfffff000
+            })
+        );
+        // jalr ra, 116(ra)
+        assert_eq!(
+            decode(0x074080e7),
+            Some(Instr::Jalr {
+                rd: 1,
+                rs1: 1,
+                offset: 116
+            })
+        );
+        // bne a0, s1, +140
+        assert_eq!(
+            decode(0x08951663),
+            Some(Instr::Branch {
+                name: "bne",
+                rs1: 10,
+                rs2: 9,
+                offset: 140
+            })
+        );
+        // j -12
+        assert_eq!(decode(0xff5ff06f), Some(Instr::Jal { rd: 0, offset: -12 }));
+        // mul a1, a1, a2
+        assert_eq!(
+            decode(0x02c585b3),
+            Some(Instr::Reg {
+                name: "mul",
+                rd: 11,
+                rs1: 11,
+                rs2: 12
+            })
+        );
+        assert_eq!(decode(0x00000073), Some(Instr::Ecall));
+        assert_eq!(decode(0xc0001073), Some(Instr::Other { name: "unimp" }));
+        assert_eq!(decode(0x00000000), None);
+    }
+
+    /// Translates the given instructions, located at 0x1000, and returns the powdr asm code.
+    fn translate_instructions(instructions: &[u32], address_instructions: &[u32]) -> String {
+        let program = ElfProgram {
+            entry: 0x1000,
+            code: vec![(
+                0x1000,
+                instructions.iter().flat_map(|i| i.to_le_bytes()).collect(),
+            )],
+            data: Default::default(),
+            code_pointers_in_data: Default::default(),
+            address_instructions: address_instructions.iter().cloned().collect(),
+            functions: Default::default(),
+            lowest_address: 0x1000,
+        };
+        Translator::new(&program)
+            .translate_code()
+            .unwrap()
+            .join("\n")
+    }
+
+    #[test]
+    fn constant_in_code_range() {
+        // lui a0, 0x1; addi a0, a0, 8; ret
+        let instructions = [0x00001537, 0x00850513, 0x00008067];
+        // Without relocations, 0x1008 is a plain constant, although it is the address of `ret`.
+        let code = translate_instructions(&instructions, &[]);
+        assert!(code.contains("4104"), "{code}");
+        assert!(!code.contains(&code_label(0x1008)), "{code}");
+        // With relocations (as for `lui a0, %hi(sym); addi a0, a0, %lo(sym)`), it is an address.
+        let code = translate_instructions(&instructions, &[0x1000, 0x1004]);
+        assert!(code.contains(&code_label(0x1008)), "{code}");
+        // The relocations of both instructions are needed.
+        let code = translate_instructions(&instructions, &[0x1004]);
+        assert!(!code.contains(&code_label(0x1008)), "{code}");
+    }
+
+    #[test]
+    fn reject_non_riscv() {
+        let error =
+            translate(&std::fs::read(std::env::current_exe().unwrap()).unwrap()).unwrap_err();
+        assert_eq!(error, "Expected a 32-bit RISC-V ELF binary.");
+    }
+}
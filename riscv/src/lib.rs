@@ -2,37 +2,66 @@
 use std::{
     collections::BTreeMap,
     ffi::OsStr,
+    io::{BufWriter, Write},
     path::{Path, PathBuf},
     process::Command,
+    time::Instant,
 };
 
-use ::compiler::{compile_asm_string, BackendType};
+use ::compiler::{compile_asm_string_with_external_witness, BackendType, Inputs, LinkerMode};
 use asm_utils::compiler::Compiler;
 use json::JsonValue;
 use mktemp::Temp;
 use std::fs;
+use strum::{Display, EnumString, EnumVariantNames};
 
 use number::FieldElement;
+use parser_util::SourceError;
+use riscv_executor::{ExecutionOptions, Profile};
 
 use crate::compiler::{FunctionKind, Register};
 
 pub mod compiler;
 mod disambiguator;
+pub mod elf;
 pub mod parser;
 
 type Statement = asm_utils::ast::Statement<Register, FunctionKind>;
 type Argument = asm_utils::ast::Argument<Register, FunctionKind>;
 type Expression = asm_utils::ast::Expression<FunctionKind>;
 
+/// How the compiled program is run.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, EnumString, EnumVariantNames, Display)]
+pub enum ExecutionMode {
+    /// Witness generation computes all columns from the constraints.
+    #[default]
+    #[strum(serialize = "witgen")]
+    Witgen,
+    /// The program is only executed, which is much faster than witness generation
+    /// but does not create any columns.
+    #[strum(serialize = "execute")]
+    Execute,
+    /// The program is executed first, and the values of the registers in each row
+    /// are passed to witness generation, which only computes the remaining columns.
+    #[strum(serialize = "trace")]
+    Trace,
+    /// The program is only executed, and the rows are attributed to the functions and
+    /// source lines they were spent in. The call stacks are written to `<name>_profile.folded`.
+    #[strum(serialize = "profile")]
+    Profile,
+}
+
 /// Compiles a rust file all the way down to PIL and generates
-/// fixed and witness columns.
+/// fixed and witness columns, or executes it, depending on `mode`.
+/// Returns the outputs committed by the program, if witness generation or execution took place.
 pub fn compile_rust<T: FieldElement>(
     file_name: &str,
-    inputs: Vec<T>,
+    inputs: Inputs<T>,
     output_dir: &Path,
     force_overwrite: bool,
     prove_with: Option<BackendType>,
-) -> Result<(), Vec<String>> {
+    mode: ExecutionMode,
+) -> Result<Option<Vec<u8>>, ::compiler::Error> {
     let riscv_asm = if file_name.ends_with("Cargo.toml") {
         compile_rust_crate_to_riscv_asm(file_name, output_dir)
     } else if fs::metadata(file_name).unwrap().is_dir() {
@@ -53,7 +82,7 @@ pub fn compile_rust<T: FieldElement>(
                 "Target file {} already exists. Not overwriting.",
                 riscv_asm_file_name.to_str().unwrap()
             );
-            return Ok(());
+            return Ok(None);
         }
 
         fs::write(riscv_asm_file_name.clone(), contents).unwrap();
@@ -67,17 +96,73 @@ pub fn compile_rust<T: FieldElement>(
         output_dir,
         force_overwrite,
         prove_with,
+        mode,
     )
 }
 
 pub fn compile_riscv_asm_bundle<T: FieldElement>(
     original_file_name: &str,
     riscv_asm_files: BTreeMap<String, String>,
-    inputs: Vec<T>,
+    inputs: Inputs<T>,
+    output_dir: &Path,
+    force_overwrite: bool,
+    prove_with: Option<BackendType>,
+    mode: ExecutionMode,
+) -> Result<Option<Vec<u8>>, ::compiler::Error> {
+    translate_and_compile(
+        original_file_name,
+        || {
+            compiler::Risc::compile(riscv_asm_files).map_err(|e| {
+                ::compiler::Error::Parse(SourceError::new(e).in_file(original_file_name))
+            })
+        },
+        inputs,
+        output_dir,
+        force_overwrite,
+        prove_with,
+        mode,
+    )
+}
+
+/// Compiles a statically linked RV32IM ELF binary all the way down to PIL
+/// and generates fixed and witness columns, or executes it, depending on `mode`.
+/// Returns the outputs committed by the program, if witness generation or execution
+/// took place and the program ended its outputs.
+pub fn compile_elf<T: FieldElement>(
+    file_name: &str,
+    inputs: Inputs<T>,
+    output_dir: &Path,
+    force_overwrite: bool,
+    prove_with: Option<BackendType>,
+    mode: ExecutionMode,
+) -> Result<Option<Vec<u8>>, ::compiler::Error> {
+    translate_and_compile(
+        file_name,
+        || {
+            fs::read(file_name)
+                .map_err(|e| e.to_string())
+                .and_then(|contents| elf::translate(&contents))
+                .map_err(|e| ::compiler::Error::Parse(SourceError::new(e).in_file(file_name)))
+        },
+        inputs,
+        output_dir,
+        force_overwrite,
+        prove_with,
+        mode,
+    )
+}
+
+/// Writes the powdr asm program created by `translate` next to the other
+/// output files and compiles it.
+fn translate_and_compile<T: FieldElement>(
+    original_file_name: &str,
+    translate: impl FnOnce() -> Result<String, ::compiler::Error>,
+    inputs: Inputs<T>,
     output_dir: &Path,
     force_overwrite: bool,
     prove_with: Option<BackendType>,
-) -> Result<(), Vec<String>> {
+    mode: ExecutionMode,
+) -> Result<Option<Vec<u8>>, ::compiler::Error> {
     let powdr_asm_file_name = output_dir.join(format!(
         "{}.asm",
         Path::new(original_file_name)
@@ -91,35 +176,123 @@ pub fn compile_riscv_asm_bundle<T: FieldElement>(
             "Target file {} already exists. Not overwriting.",
             powdr_asm_file_name.to_str().unwrap()
         );
-        return Ok(());
+        return Ok(None);
     }
 
-    let powdr_asm = compiler::Risc::compile(riscv_asm_files);
+    let powdr_asm = translate()?;
 
     fs::write(powdr_asm_file_name.clone(), &powdr_asm).unwrap();
     log::info!("Wrote {}", powdr_asm_file_name.to_str().unwrap());
 
-    compile_asm_string(
-        powdr_asm_file_name.to_str().unwrap(),
+    let powdr_asm_file_name = powdr_asm_file_name.to_str().unwrap();
+    let trace = match mode {
+        ExecutionMode::Witgen => None,
+        ExecutionMode::Execute | ExecutionMode::Trace | ExecutionMode::Profile => {
+            let options = ExecutionOptions {
+                trace: mode == ExecutionMode::Trace,
+                profile: mode == ExecutionMode::Profile,
+            };
+            let execution = execute(powdr_asm_file_name, &powdr_asm, inputs.clone(), options)?;
+            if let Some(profile) = &execution.profile {
+                write_profile(profile, original_file_name, output_dir)?;
+            }
+            if mode != ExecutionMode::Trace {
+                return Ok(compiler::decode_outputs(
+                    (0..=compiler::OUTPUT_CAPACITY).map(|i| execution.outputs.get(i).copied()),
+                ));
+            }
+            execution.trace
+        }
+    };
+    let (names, values): (Vec<_>, Vec<_>) = trace
+        .map(|trace| trace.to_witness_columns(MAIN_NAMESPACE))
+        .unwrap_or_default()
+        .into_iter()
+        .unzip();
+    let external_witness_values = names
+        .iter()
+        .map(String::as_str)
+        .zip(values)
+        .collect::<Vec<_>>();
+
+    let (_, result) = compile_asm_string_with_external_witness(
+        powdr_asm_file_name,
         &powdr_asm,
         inputs,
         output_dir,
         force_overwrite,
         prove_with,
+        None,
+        None,
+        LinkerMode::Monolithic,
+        &external_witness_values,
     )?;
+    Ok(result
+        .and_then(|result| result.publics)
+        .and_then(|publics| compiler::committed_outputs(&publics)))
+}
+
+/// The namespace of the main machine in the generated PIL.
+const MAIN_NAMESPACE: &str = "main";
+
+/// Executes a powdr asm program created by the RISC-V compiler without
+/// generating a witness and logs a report of the execution.
+fn execute<T: FieldElement>(
+    file_name: &str,
+    powdr_asm: &str,
+    inputs: Inputs<T>,
+    options: ExecutionOptions,
+) -> Result<riscv_executor::Execution<T>, ::compiler::Error> {
+    log::info!("Executing {file_name}...");
+    let start = Instant::now();
+    let execution =
+        riscv_executor::execute(file_name, powdr_asm, inputs, options).map_err(|e| match e {
+            riscv_executor::Error::Source(errors) => ::compiler::Error::Analysis(errors),
+            riscv_executor::Error::Execution(e) => ::compiler::Error::Execution(e.to_string()),
+        })?;
+    log::info!("Execution took {}", start.elapsed().as_secs_f32());
+    log::info!("{execution}");
+    Ok(execution)
+}
+
+/// Logs the breakdown of the profile and writes its call stacks in the folded
+/// format of flamegraph tools.
+fn write_profile(
+    profile: &Profile,
+    original_file_name: &str,
+    output_dir: &Path,
+) -> Result<(), ::compiler::Error> {
+    log::info!("{profile}");
+    let file_name = output_dir.join(format!(
+        "{}_profile.folded",
+        Path::new(original_file_name)
+            .file_stem()
+            .unwrap()
+            .to_str()
+            .unwrap()
+    ));
+    let io_error = |e: std::io::Error| {
+        ::compiler::Error::Execution(format!("Could not write {}: {e}", file_name.display()))
+    };
+    let mut writer = BufWriter::new(fs::File::create(&file_name).map_err(io_error)?);
+    profile.write_folded(&mut writer).map_err(io_error)?;
+    writer.flush().map_err(io_error)?;
+    log::info!("Wrote {}", file_name.to_str().unwrap());
     Ok(())
 }
 
 /// Compiles a riscv asm file all the way down to PIL and generates
-/// fixed and witness columns.
+/// fixed and witness columns, or executes it, depending on `mode`.
+/// Returns the outputs committed by the program, if witness generation or execution took place.
 pub fn compile_riscv_asm<T: FieldElement>(
     original_file_name: &str,
     file_names: impl Iterator<Item = String>,
-    inputs: Vec<T>,
+    inputs: Inputs<T>,
     output_dir: &Path,
     force_overwrite: bool,
     prove_with: Option<BackendType>,
-) -> Result<(), Vec<String>> {
+    mode: ExecutionMode,
+) -> Result<Option<Vec<u8>>, ::compiler::Error> {
     compile_riscv_asm_bundle(
         original_file_name,
         file_names
@@ -132,6 +305,7 @@ pub fn compile_riscv_asm<T: FieldElement>(
         output_dir,
         force_overwrite,
         prove_with,
+        mode,
     )
 }
 
@@ -7,7 +7,7 @@ mod instruction_tests {
 
     fn run_instruction_test(assembly: &str, name: &str) {
         // TODO Should we create one powdr asm from all tests or keep them separate?
-        let powdr_asm = Risc::compile([(name.to_string(), assembly.to_string())].into());
+        let powdr_asm = Risc::compile([(name.to_string(), assembly.to_string())].into()).unwrap();
 
         verify_asm_string::<GoldilocksField>(&format!("{name}.asm"), &powdr_asm, vec![]);
     }
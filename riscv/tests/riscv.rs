@@ -1,7 +1,8 @@
 use asm_utils::compiler::Compiler;
-use compiler::verify_asm_string;
+use compiler::{verify_asm_string, BackendType, Inputs};
 use mktemp::Temp;
 use number::GoldilocksField;
+use riscv::ExecutionMode;
 use test_log::test;
 
 #[test]
@@ -95,13 +96,101 @@ fn test_print() {
     verify_file(case, vec![]);
 }
 
+#[test]
+#[ignore = "Too slow"]
+fn test_io() {
+    let case = "io.rs";
+    let values = [3u32, 1, 2, 3];
+    let data: Vec<u8> = values
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .chain(5u16.to_le_bytes())
+        .chain(*b"sum")
+        .collect();
+    let expected = Some([&b"sum"[..], &30u32.to_le_bytes()].concat());
+    for mode in [ExecutionMode::Witgen, ExecutionMode::Execute] {
+        let temp_dir = Temp::new_dir().unwrap();
+        let outputs = riscv::compile_rust::<GoldilocksField>(
+            &format!("tests/riscv_data/{case}"),
+            Inputs::from_bytes(data.clone()),
+            &temp_dir,
+            true,
+            Some(BackendType::PilCheck),
+            mode,
+        )
+        .unwrap();
+        assert_eq!(outputs, expected);
+    }
+}
+
+#[test]
+#[ignore = "Too slow"]
+fn test_elf() {
+    let case = "elf/program.elf";
+    let elf = std::fs::read(format!("tests/riscv_data/{case}")).unwrap();
+    let powdr_asm = riscv::elf::translate(&elf).unwrap();
+    let inputs: Vec<GoldilocksField> = [10, 55].iter().map(|&x| x.into()).collect();
+
+    verify_asm_string(&format!("{case}.asm"), &powdr_asm, inputs.clone());
+    execute(case, &powdr_asm, inputs);
+}
+
+#[test]
+fn test_elf_execute() {
+    let case = "elf/program.elf";
+    let temp_dir = Temp::new_dir().unwrap();
+    riscv::compile_elf::<GoldilocksField>(
+        &format!("tests/riscv_data/{case}"),
+        Inputs::from(vec![10.into(), 55.into()]),
+        &temp_dir,
+        true,
+        None,
+        ExecutionMode::Execute,
+    )
+    .unwrap();
+}
+
+#[test]
+#[ignore = "Too slow"]
+fn test_elf_with_trace() {
+    let case = "elf/program.elf";
+    let temp_dir = Temp::new_dir().unwrap();
+    riscv::compile_elf::<GoldilocksField>(
+        &format!("tests/riscv_data/{case}"),
+        Inputs::from(vec![10.into(), 55.into()]),
+        &temp_dir,
+        true,
+        Some(BackendType::PilCheck),
+        ExecutionMode::Trace,
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_jalr_with_offset() {
+    let riscv_asm = [(
+        "main".to_string(),
+        r#"
+.globl __runtime_start
+__runtime_start:
+    jalr ra, 4(t0)
+    ret
+"#
+        .to_string(),
+    )]
+    .into();
+    let err = riscv::compiler::Risc::compile(riscv_asm).unwrap_err();
+    assert!(err.contains("zero offset"), "{err}");
+}
+
 fn verify_file(case: &str, inputs: Vec<GoldilocksField>) {
     let temp_dir = Temp::new_dir().unwrap();
     let riscv_asm =
         riscv::compile_rust_to_riscv_asm(&format!("tests/riscv_data/{case}"), &temp_dir);
-    let powdr_asm = riscv::compiler::Risc::compile(riscv_asm);
+    let powdr_asm = riscv::compiler::Risc::compile(riscv_asm).unwrap();
 
-    verify_asm_string(&format!("{case}.asm"), &powdr_asm, inputs);
+    verify_asm_string(&format!("{case}.asm"), &powdr_asm, inputs.clone());
+    execute(case, &powdr_asm, inputs);
 }
 
 fn verify_crate(case: &str, inputs: Vec<GoldilocksField>) {
@@ -110,7 +199,46 @@ fn verify_crate(case: &str, inputs: Vec<GoldilocksField>) {
         &format!("tests/riscv_data/{case}/Cargo.toml"),
         &temp_dir,
     );
-    let powdr_asm = riscv::compiler::Risc::compile(riscv_asm);
+    let powdr_asm = riscv::compiler::Risc::compile(riscv_asm).unwrap();
+
+    verify_asm_string(&format!("{case}.asm"), &powdr_asm, inputs.clone());
+    execute(case, &powdr_asm, inputs);
+}
 
-    verify_asm_string(&format!("{case}.asm"), &powdr_asm, inputs);
+/// Checks that the program can also be executed directly.
+fn execute(case: &str, powdr_asm: &str, inputs: Vec<GoldilocksField>) {
+    riscv_executor::execute(
+        &format!("{case}.asm"),
+        powdr_asm,
+        Inputs::from(inputs),
+        Default::default(),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_unsupported_instruction_forms() {
+    for (code, expected) in [
+        ("auipc t0, 1", "only supported with %pcrel_hi"),
+        (
+            "auipc t0, %pcrel_hi(__runtime_start + 4)",
+            "%pcrel_hi of a symbol",
+        ),
+        ("csrr 0x300", "destination register"),
+    ] {
+        let riscv_asm = [(
+            "main".to_string(),
+            format!(
+                r#"
+.globl __runtime_start
+__runtime_start:
+    {code}
+    ret
+"#
+            ),
+        )]
+        .into();
+        let err = riscv::compiler::Risc::compile(riscv_asm).unwrap_err();
+        assert!(err.contains(expected), "{err}");
+    }
 }
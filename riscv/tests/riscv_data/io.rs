@@ -0,0 +1,18 @@
+#![no_std]
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+use runtime::io::{commit, read, read_bytes};
+
+#[no_mangle]
+pub fn main() {
+    let values: Vec<u32> = read();
+    let factor: u16 = read();
+    let mut tag = [0; 3];
+    read_bytes(&mut tag);
+
+    let sum: u32 = values.iter().sum();
+    commit(&tag);
+    commit(&(sum * factor as u32).to_le_bytes());
+}
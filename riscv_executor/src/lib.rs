@@ -0,0 +1,1041 @@
+//! A fast executor for the powdr asm programs created by the RISC-V frontend.
+//!
+//! Instead of running witness generation over all identities, the statements of the
+//! entry function of the main machine are interpreted directly. Every batch of statements
+//! is one row of the main machine, so the cycle count and the pc values match the ones
+//! of witness generation. The instructions of the RISC-V machine are implemented natively,
+//! calls to submachines are answered without running the submachines.
+
+use std::collections::{BTreeMap, HashMap};
+
+use asm_to_pil::rom_layout::{function_rows, DISPATCHER_ROWS};
+use ast::{
+    asm_analysis::{AnalysisASMFile, FunctionStatement, Machine},
+    parsed::{BinaryOperator, Expression, UnaryOperator},
+};
+use executor::witgen::prover_input::QueryItem;
+use executor::witgen::{ProverInput, ProverQuery};
+use number::{BigInt, FieldElement};
+use parser_util::SourceError;
+
+mod profile;
+mod report;
+mod trace;
+
+pub use asm_to_pil::rom_layout::SourceLocation;
+pub use profile::Profile;
+use profile::{Call, Profiler};
+pub use report::ExecutionError;
+pub use trace::ExecutionTrace;
+
+const MAIN_MACHINE: &str = "Main";
+const MAIN_FUNCTION: &str = "main";
+
+/// The result of executing a program.
+#[derive(Debug)]
+pub struct Execution<T> {
+    /// The number of rows of the main machine used by the execution,
+    /// including the dispatcher and the row of the final `return`.
+    pub cycles: u64,
+    /// How often each instruction was executed. Assignments from expressions
+    /// are counted as `assignment`.
+    pub instruction_counts: BTreeMap<String, u64>,
+    /// The final values of the registers, except the pc.
+    pub registers: BTreeMap<String, T>,
+    /// The final contents of memory, as words by their (aligned) byte address.
+    pub memory: BTreeMap<u32, u32>,
+    /// The values written to the outputs, in order.
+    pub outputs: Vec<T>,
+    /// The values of the pc and the write registers in each row, if requested.
+    pub trace: Option<ExecutionTrace<T>>,
+    /// The rows spent in each function and source line, if requested.
+    pub profile: Option<Profile>,
+}
+
+/// What to record during an execution, in addition to its result.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ExecutionOptions {
+    /// Record the values of the registers in each row, see [ExecutionTrace].
+    pub trace: bool,
+    /// Attribute the rows to functions and source lines, see [Profile].
+    pub profile: bool,
+}
+
+/// An error that prevented the program from being executed.
+#[derive(Debug)]
+pub enum Error {
+    /// The program could not be parsed or analyzed.
+    Source(Vec<SourceError>),
+    /// An instruction of the program failed.
+    Execution(ExecutionError),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Source(errors) => errors.iter().try_for_each(|e| writeln!(f, "{e}")),
+            Error::Execution(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+/// Parses and analyzes the powdr asm program in `contents` and executes it.
+/// Queries are answered by `inputs`.
+pub fn execute<T: FieldElement>(
+    file_name: &str,
+    contents: &str,
+    inputs: impl ProverInput<T>,
+    options: ExecutionOptions,
+) -> Result<Execution<T>, Error> {
+    let parsed = parser::parse_asm(Some(file_name), contents)
+        .map_err(|err| Error::Source(vec![err.into()]))?;
+    let analyzed = analysis::analyze_functions(parsed).map_err(|errors| {
+        Error::Source(errors.into_iter().map(|e| e.in_file(file_name)).collect())
+    })?;
+    execute_ast(&analyzed, inputs, options)
+}
+
+/// Executes the entry function of the main machine of an analyzed program.
+/// The functions have to be batched, see [analysis::analyze_functions].
+pub fn execute_ast<T: FieldElement>(
+    program: &AnalysisASMFile<T>,
+    inputs: impl ProverInput<T>,
+    options: ExecutionOptions,
+) -> Result<Execution<T>, Error> {
+    let machine = match program.machines.len() {
+        1 => program.machines.values().next(),
+        _ => program.machines.get(MAIN_MACHINE),
+    }
+    .ok_or_else(|| Error::Source(vec![format!("No {MAIN_MACHINE} machine found").into()]))?;
+    let rom = Rom::new(machine).map_err(|e| Error::Source(vec![e.into()]))?;
+    Executor::new(machine, &rom, inputs, options)
+        .run()
+        .map_err(Error::Execution)
+}
+
+/// The rows of a VM as created by the ROM generation: the dispatcher rows,
+/// followed by the batches of all functions.
+struct Rom<'a, T> {
+    rows: Vec<&'a [FunctionStatement<T>]>,
+    /// The source location of each row, given by the last `debug loc`
+    /// directive before it in the same function.
+    locations: Vec<Option<SourceLocation>>,
+    labels: HashMap<&'a str, u64>,
+    /// The first label of each row, which names the function starting there.
+    functions: HashMap<u64, &'a str>,
+    entry: &'a str,
+    entry_pc: u64,
+    degree: Option<u64>,
+}
+
+impl<'a, T: FieldElement> Rom<'a, T> {
+    fn new(machine: &'a Machine<T>) -> Result<Self, String> {
+        if !machine.has_pc() {
+            return Err("The main machine has no pc and cannot be executed".to_string());
+        }
+        let entry = machine
+            .functions
+            .iter()
+            .find(|f| f.name == MAIN_FUNCTION)
+            .or_else(|| (machine.functions.len() == 1).then(|| &machine.functions[0]))
+            .ok_or_else(|| format!("The main machine has no function called {MAIN_FUNCTION}"))?;
+
+        let mut rows = vec![];
+        let mut locations = vec![];
+        let mut labels = HashMap::new();
+        let mut functions = HashMap::new();
+        let function_rows = function_rows(machine);
+        let entry_pc = function_rows
+            .iter()
+            .find(|row| row.function == entry.name)
+            .map_or(DISPATCHER_ROWS, |row| row.pc);
+        for row in function_rows {
+            for statement in row.statements {
+                if let FunctionStatement::Label(label) = statement {
+                    labels.insert(label.name.as_str(), row.pc);
+                    functions.entry(row.pc).or_insert(label.name.as_str());
+                }
+            }
+            rows.push(row.statements);
+            locations.push(row.location);
+        }
+        let degree = machine
+            .degree
+            .as_ref()
+            .map(|d| {
+                u64::try_from(&d.degree).map_err(|_| {
+                    format!("The degree {} of the main machine is too large", d.degree)
+                })
+            })
+            .transpose()?;
+        Ok(Self {
+            rows,
+            locations,
+            labels,
+            functions,
+            entry: &entry.name,
+            entry_pc,
+            degree,
+        })
+    }
+
+    fn location(&self, pc: u64) -> Option<&SourceLocation> {
+        pc.checked_sub(DISPATCHER_ROWS)
+            .and_then(|row| self.locations.get(row as usize))
+            .and_then(Option::as_ref)
+    }
+}
+
+struct Executor<'a, 'b, T, I> {
+    rom: &'b Rom<'a, T>,
+    inputs: I,
+    register_names: Vec<&'a str>,
+    register_index: HashMap<&'a str, usize>,
+    /// The write registers, in the order of `register_names`.
+    write_registers: Vec<usize>,
+    registers: Vec<T>,
+    pc: u64,
+    next_pc: u64,
+    memory: BTreeMap<u32, u32>,
+    outputs: Vec<T>,
+    instruction_counts: BTreeMap<String, u64>,
+    trace: Option<ExecutionTrace<T>>,
+    profiler: Option<Profiler<'b>>,
+    /// The call executed in the current row, if any.
+    call: Option<Call>,
+}
+
+impl<'a, 'b, T: FieldElement, I: ProverInput<T>> Executor<'a, 'b, T, I> {
+    fn new(
+        machine: &'a Machine<T>,
+        rom: &'b Rom<'a, T>,
+        inputs: I,
+        options: ExecutionOptions,
+    ) -> Self {
+        let registers = machine
+            .registers
+            .iter()
+            .filter(|r| !r.ty.is_pc())
+            .collect::<Vec<_>>();
+        let register_names = registers
+            .iter()
+            .map(|r| r.name.as_str())
+            .collect::<Vec<_>>();
+        let write_registers = registers
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.ty.is_write())
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        let trace = options.trace.then(|| {
+            ExecutionTrace::new(
+                machine.pc().unwrap(),
+                write_registers
+                    .iter()
+                    .map(|i| register_names[*i].to_string()),
+            )
+        });
+        Self {
+            rom,
+            inputs,
+            register_index: register_names
+                .iter()
+                .enumerate()
+                .map(|(i, name)| (*name, i))
+                .collect(),
+            registers: vec![T::zero(); register_names.len()],
+            register_names,
+            write_registers,
+            pc: 0,
+            next_pc: 0,
+            memory: Default::default(),
+            outputs: vec![],
+            instruction_counts: Default::default(),
+            trace,
+            profiler: options
+                .profile
+                .then(|| Profiler::new(rom.entry, &rom.functions)),
+            call: None,
+        }
+    }
+
+    fn run(mut self) -> Result<Execution<T>, ExecutionError> {
+        // The dispatcher resets all registers and jumps to the entry function.
+        for pc in 0..DISPATCHER_ROWS {
+            self.pc = pc;
+            self.record_row();
+            self.profile_row(pc + 1);
+        }
+        let mut cycles = DISPATCHER_ROWS;
+        self.pc = self.rom.entry_pc;
+        loop {
+            if let Some(degree) = self.rom.degree.filter(|degree| cycles >= *degree) {
+                return Err(self.error(
+                    cycles,
+                    None,
+                    format!("The program did not finish within the degree of {degree} rows"),
+                ));
+            }
+            let Some(row) = self
+                .pc
+                .checked_sub(DISPATCHER_ROWS)
+                .and_then(|row| self.rom.rows.get(row as usize))
+            else {
+                return Err(self.error(cycles, None, format!("Invalid pc: {}", self.pc)));
+            };
+            self.record_row();
+            cycles += 1;
+            self.next_pc = self.pc + 1;
+            let mut finished = false;
+            for statement in row.iter() {
+                finished |= self
+                    .execute_statement(statement)
+                    .map_err(|message| self.error(cycles - 1, Some(statement), message))?;
+            }
+            self.profile_row(self.next_pc);
+            if finished {
+                break;
+            }
+            self.pc = self.next_pc;
+        }
+
+        Ok(Execution {
+            cycles,
+            instruction_counts: self.instruction_counts,
+            registers: self
+                .register_names
+                .into_iter()
+                .map(str::to_string)
+                .zip(self.registers)
+                .collect(),
+            memory: self.memory,
+            outputs: self.outputs,
+            trace: self.trace,
+            profile: self.profiler.map(Profiler::into_profile),
+        })
+    }
+
+    fn record_row(&mut self) {
+        if let Some(trace) = &mut self.trace {
+            trace.push_row(
+                T::from(self.pc),
+                self.write_registers.iter().map(|i| self.registers[*i]),
+            );
+        }
+    }
+
+    fn profile_row(&mut self, next_pc: u64) {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.row(
+                self.rom.location(self.pc),
+                self.pc,
+                next_pc,
+                self.call.take(),
+            );
+        }
+    }
+
+    fn error(
+        &self,
+        cycle: u64,
+        statement: Option<&FunctionStatement<T>>,
+        message: String,
+    ) -> ExecutionError {
+        ExecutionError {
+            cycle,
+            pc: self.pc,
+            statement: statement.map(|s| s.to_string()),
+            location: self.rom.location(self.pc).cloned(),
+            message,
+        }
+    }
+
+    /// Executes a single statement. Returns true if the function returns.
+    fn execute_statement(&mut self, statement: &FunctionStatement<T>) -> Result<bool, String> {
+        match statement {
+            FunctionStatement::Assignment(assignment) => {
+                let values = match assignment.rhs.as_ref() {
+                    Expression::FunctionCall(call) => {
+                        self.execute_instruction(&call.id, &call.arguments)?
+                    }
+                    rhs => {
+                        self.count("assignment");
+                        vec![self.evaluate(rhs)?]
+                    }
+                };
+                if values.len() != assignment.lhs.len() {
+                    return Err(format!(
+                        "Expected {} values, got {}",
+                        assignment.lhs.len(),
+                        values.len()
+                    ));
+                }
+                for (register, value) in assignment.lhs.iter().zip(values) {
+                    self.set_register(register, value)?;
+                }
+            }
+            FunctionStatement::Instruction(instruction) => {
+                let outputs =
+                    self.execute_instruction(&instruction.instruction, &instruction.inputs)?;
+                if !outputs.is_empty() {
+                    return Err(format!(
+                        "The outputs of {} are not assigned",
+                        instruction.instruction
+                    ));
+                }
+            }
+            FunctionStatement::Label(_) | FunctionStatement::DebugDirective(_) => {}
+            FunctionStatement::Return(_) => return Ok(true),
+        }
+        Ok(false)
+    }
+
+    fn count(&mut self, name: &str) {
+        *self.instruction_counts.entry(name.to_string()).or_default() += 1;
+    }
+
+    fn register(&self, name: &str) -> Result<T, String> {
+        self.register_index
+            .get(name)
+            .map(|i| self.registers[*i])
+            .ok_or_else(|| format!("Unknown register: {name}"))
+    }
+
+    fn set_register(&mut self, name: &str, value: T) -> Result<(), String> {
+        let index = *self
+            .register_index
+            .get(name)
+            .ok_or_else(|| format!("Unknown register: {name}"))?;
+        self.registers[index] = value;
+        Ok(())
+    }
+
+    fn evaluate(&mut self, expression: &Expression<T>) -> Result<T, String> {
+        Ok(match expression {
+            Expression::Number(n) => *n,
+            Expression::PolynomialReference(r) if r.namespace().is_none() && !r.shift() => {
+                let name = r.name();
+                match self.rom.labels.get(name) {
+                    Some(pc) => T::from(*pc),
+                    None => self.register(name)?,
+                }
+            }
+            Expression::BinaryOperation(left, op, right) => {
+                let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
+                match op {
+                    BinaryOperator::Add => left + right,
+                    BinaryOperator::Sub => left - right,
+                    BinaryOperator::Mul => left * right,
+                    BinaryOperator::Pow => left.pow(right.to_integer()),
+                    _ => return Err(format!("Unsupported operator: {op}")),
+                }
+            }
+            Expression::UnaryOperation(op, inner) => {
+                let value = self.evaluate(inner)?;
+                match op {
+                    UnaryOperator::Plus => value,
+                    UnaryOperator::Minus => -value,
+                }
+            }
+            Expression::FreeInput(query) => self.query(query)?,
+            _ => return Err(format!("Unsupported expression: {expression}")),
+        })
+    }
+
+    /// Answers a query like `("input", x10)` using the inputs.
+    fn query(&mut self, query: &Expression<T>) -> Result<T, String> {
+        let items = match query {
+            Expression::Tuple(items) => items,
+            _ => std::slice::from_ref(query),
+        }
+        .iter()
+        .map(|item| match item {
+            Expression::String(s) => Ok(QueryItem::String(s.clone())),
+            item => self.evaluate(item).map(QueryItem::Value),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+        let query = ProverQuery::from_items(items)?;
+        self.inputs
+            .answer(&query)
+            .or_else(|| {
+                let ProverQuery::PrintChar(c) = query else {
+                    return None;
+                };
+                print!("{}", c as char);
+                Some(0.into())
+            })
+            .ok_or_else(|| format!("No answer for query {query}"))
+    }
+
+    fn jump(&mut self, target: T) -> Result<(), String> {
+        self.next_pc = to_u64(target)?;
+        Ok(())
+    }
+
+    /// Jumps to `target`, which returns to the next row.
+    fn call(&mut self, target: T) -> Result<(), String> {
+        self.jump(target)?;
+        self.call = Some(Call::Call {
+            target: self.next_pc,
+            return_pc: self.pc + 1,
+        });
+        Ok(())
+    }
+
+    /// Executes an instruction of the RISC-V machine on the values of `arguments`
+    /// and returns its outputs.
+    fn execute_instruction(
+        &mut self,
+        name: &str,
+        arguments: &[Expression<T>],
+    ) -> Result<Vec<T>, String> {
+        self.count(name);
+        let args = arguments
+            .iter()
+            .map(|a| self.evaluate(a))
+            .collect::<Result<Vec<_>, _>>()?;
+        let outputs = match (name, &args[..]) {
+            // memory
+            ("mstore", [x]) => {
+                let addr = to_u32(self.register("addr")?)?;
+                self.store_word(addr, to_u32(*x)?)?;
+                vec![]
+            }
+            ("mload", []) => {
+                let addr = to_u32(self.register("addr")?)?;
+                vec![self.load_word(addr)?.into()]
+            }
+            ("load_word", [y, z, label]) => {
+                let addr = byte_address(*y, *z)?;
+                self.set_register("tmp4", T::from(self.pc + 1))?;
+                if addr & 3 == 0 {
+                    vec![self.load_word(addr)?.into()]
+                } else {
+                    self.jump(*label)?;
+                    vec![addr.into()]
+                }
+            }
+            ("load_byte_unsigned" | "load_byte_signed", [y, z]) => {
+                let addr = byte_address(*y, *z)?;
+                let byte = (self.load_word(addr & !3)? >> ((addr & 3) * 8)) & 0xff;
+                vec![match name {
+                    "load_byte_signed" => byte as u8 as i8 as i32 as u32,
+                    _ => byte,
+                }
+                .into()]
+            }
+            ("load_half_unsigned" | "load_half_signed", [y, z, label]) => {
+                let addr = byte_address(*y, *z)?;
+                self.set_register("tmp4", T::from(self.pc + 1))?;
+                if addr & 3 == 3 {
+                    self.jump(*label)?;
+                    return Ok(vec![addr.into()]);
+                }
+                let half = (self.load_word(addr & !3)? >> ((addr & 3) * 8)) & 0xffff;
+                vec![match name {
+                    "load_half_signed" => half as u16 as i16 as i32 as u32,
+                    _ => half,
+                }
+                .into()]
+            }
+            ("store_byte", [y, z, x]) => {
+                let addr = byte_address(*y, *z)?;
+                self.store_sub_word(addr, to_u32(*x)?, 0xff)?;
+                vec![]
+            }
+            ("store_word", [y, z, x, label]) => {
+                let addr = self.prepare_store(*y, *z, *x)?;
+                if addr & 3 == 0 {
+                    self.store_word(addr, to_u32(*x)?)?;
+                } else {
+                    self.jump(*label)?;
+                }
+                vec![]
+            }
+            ("store_half", [y, z, x, label]) => {
+                let addr = self.prepare_store(*y, *z, *x)?;
+                if addr & 3 == 3 {
+                    self.jump(*label)?;
+                } else {
+                    self.store_sub_word(addr, to_u32(*x)?, 0xffff)?;
+                }
+                vec![]
+            }
+
+            // control flow
+            ("jump" | "jump_dyn", [target]) => {
+                self.jump(*target)?;
+                vec![]
+            }
+            ("load_label", [label]) => vec![*label],
+            ("jump_and_link" | "jump_and_link_dyn", [target]) => {
+                self.call(*target)?;
+                vec![T::from(self.pc + 1)]
+            }
+            ("call", [target]) => {
+                self.call(*target)?;
+                self.set_register("x1", T::from(self.pc + 1))?;
+                vec![]
+            }
+            ("tail", [target]) => {
+                self.jump(*target)?;
+                self.call = Some(Call::Tail {
+                    target: self.next_pc,
+                });
+                self.set_register("x6", *target)?;
+                vec![]
+            }
+            ("ret", []) => {
+                self.jump(self.register("x1")?)?;
+                vec![]
+            }
+            ("branch_if_nonzero", [x, target]) => {
+                if !x.is_zero() {
+                    self.jump(*target)?;
+                }
+                vec![]
+            }
+            ("branch_if_zero", [x, target]) => {
+                if x.is_zero() {
+                    self.jump(*target)?;
+                }
+                vec![]
+            }
+            ("branch_if_positive", [x, target]) => {
+                if is_positive(*x)? {
+                    self.jump(*target)?;
+                }
+                vec![]
+            }
+            ("is_positive", [x]) => vec![is_positive(*x)?.into()],
+            ("is_equal_zero", [x]) => vec![x.is_zero().into()],
+            ("is_not_equal_zero", [x]) => vec![(!x.is_zero()).into()],
+
+            // submachines
+            ("and", [y, z]) => vec![(to_u32(*y)? & to_u32(*z)?).into()],
+            ("or", [y, z]) => vec![(to_u32(*y)? | to_u32(*z)?).into()],
+            ("xor", [y, z]) => vec![(to_u32(*y)? ^ to_u32(*z)?).into()],
+            ("shl", [y, z]) => vec![(to_u32(*y)? << shift_amount(*z)?).into()],
+            ("shr", [y, z]) => vec![(to_u32(*y)? >> shift_amount(*z)?).into()],
+            ("write_output", [y]) => {
+                self.outputs.push(*y);
+                vec![T::from(self.outputs.len() as u64 - 1)]
+            }
+            ("poseidon", [_, _]) => {
+                return Err("poseidon is not supported by the executor".to_string())
+            }
+
+            // arithmetic
+            ("wrap", [y]) => vec![wrap(*y, 0, 1 << 33)?.into()],
+            ("wrap_signed", [y]) => vec![wrap(*y, -(1 << 32), 1 << 32)?.into()],
+            ("wrap16", [y]) => vec![wrap(*y, 0, 1 << 48)?.into()],
+            ("to_signed", [y]) => vec![(to_u32(*y)? as i32).into()],
+            ("mul", [y, z]) => vec![(to_u32(*y)?.wrapping_mul(to_u32(*z)?)).into()],
+            ("mulhu", [y, z]) => {
+                vec![T::from((to_u32(*y)? as u64 * to_u32(*z)? as u64) >> 32)]
+            }
+            ("divu", [y, x]) => vec![to_u32(*y)?
+                .checked_div(to_u32(*x)?)
+                .unwrap_or(0xffffffff)
+                .into()],
+            ("remu", [y, x]) => {
+                let y = to_u32(*y)?;
+                vec![y.checked_rem(to_u32(*x)?).unwrap_or(y).into()]
+            }
+
+            ("fail", []) => return Err("The program reached a fail instruction".to_string()),
+            _ => {
+                return Err(format!(
+                    "Unknown instruction {name} with {} arguments",
+                    args.len()
+                ))
+            }
+        };
+        Ok(outputs)
+    }
+
+    /// Sets the registers used by the routines for misaligned stores and returns
+    /// the byte address `y + z`.
+    fn prepare_store(&mut self, y: T, z: T, x: T) -> Result<u32, String> {
+        let addr = byte_address(y, z)?;
+        self.set_register("tmp1", addr.into())?;
+        self.set_register("tmp2", x)?;
+        self.set_register("tmp4", T::from(self.pc + 1))?;
+        Ok(addr)
+    }
+
+    /// Checks that `addr` is a valid address of a word in memory, like the
+    /// memory machine does during witness generation.
+    fn check_address(&self, addr: u32) -> Result<(), String> {
+        if addr & 3 != 0 {
+            return Err(format!("Unaligned memory access to address 0x{addr:x}"));
+        }
+        match self.rom.degree {
+            Some(degree) if u64::from(addr) >= degree => Err(format!(
+                "Memory access to too large address: 0x{addr:x} (must be less than 0x{degree:x})"
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn load_word(&self, addr: u32) -> Result<u32, String> {
+        self.check_address(addr)?;
+        Ok(self.memory.get(&addr).copied().unwrap_or_default())
+    }
+
+    fn store_word(&mut self, addr: u32, value: u32) -> Result<(), String> {
+        self.check_address(addr)?;
+        self.memory.insert(addr, value);
+        Ok(())
+    }
+
+    /// Replaces the bits in `mask` of the value at byte address `addr` by `value`.
+    fn store_sub_word(&mut self, addr: u32, value: u32, mask: u32) -> Result<(), String> {
+        let shift = (addr & 3) * 8;
+        let word = self.load_word(addr & !3)?;
+        self.store_word(
+            addr & !3,
+            (word & !(mask << shift)) | ((value & mask) << shift),
+        )
+    }
+}
+
+/// The value as a signed integer, if it fits into an `i64`.
+fn to_i64<T: FieldElement>(value: T) -> Option<i64> {
+    let (abs, negative) = if value.is_in_lower_half() {
+        (value, false)
+    } else {
+        (-value, true)
+    };
+    (abs.to_integer().num_bits() < 64).then(|| {
+        let abs = abs.to_degree() as i64;
+        if negative {
+            -abs
+        } else {
+            abs
+        }
+    })
+}
+
+fn to_u64<T: FieldElement>(value: T) -> Result<u64, String> {
+    to_i64(value)
+        .and_then(|v| u64::try_from(v).ok())
+        .ok_or_else(|| format!("Expected a non-negative value, got {value}"))
+}
+
+fn to_u32<T: FieldElement>(value: T) -> Result<u32, String> {
+    to_u64(value)
+        .ok()
+        .and_then(|v| u32::try_from(v).ok())
+        .ok_or_else(|| format!("Expected a 32-bit value, got {value}"))
+}
+
+/// Wraps `value` to 32 bits, if it is in the range `[min, max)`.
+fn wrap<T: FieldElement>(value: T, min: i64, max: i64) -> Result<u32, String> {
+    to_i64(value)
+        .filter(|v| (min..max).contains(v))
+        .map(|v| v.rem_euclid(1 << 32) as u32)
+        .ok_or_else(|| format!("Value {value} cannot be wrapped, it has to be in [{min}, {max})"))
+}
+
+/// Returns true if the difference of two 32-bit values is positive.
+fn is_positive<T: FieldElement>(value: T) -> Result<bool, String> {
+    to_i64(value)
+        .filter(|v| -(1 << 32) < *v && *v < (1 << 32))
+        .map(|v| v > 0)
+        .ok_or_else(|| format!("Value {value} is not the difference of two 32-bit values"))
+}
+
+fn shift_amount<T: FieldElement>(value: T) -> Result<u32, String> {
+    to_u32(value)
+        .ok()
+        .filter(|v| *v < 32)
+        .ok_or_else(|| format!("Invalid shift amount: {value}"))
+}
+
+/// The byte address `y + z`, wrapped to 32 bits.
+fn byte_address<T: FieldElement>(y: T, z: T) -> Result<u32, String> {
+    wrap(y + z, 0, 1 << 33)
+}
+
+#[cfg(test)]
+mod test {
+    use number::GoldilocksField;
+    use test_log::test;
+
+    use super::*;
+
+    const PREAMBLE: &str = r#"
+    reg pc[@pc];
+    reg X[<=];
+    reg Y[<=];
+    reg Z[<=];
+    reg x1;
+    reg x10;
+    reg x11;
+    reg addr;
+    reg tmp1;
+    reg tmp2;
+    reg tmp4;
+
+    instr jump l: label { pc' = l }
+    instr jump_dyn X { pc' = X }
+    instr call l: label { pc' = l, x1' = pc + 1 }
+    instr ret { pc' = x1 }
+    instr branch_if_zero X, l: label { pc' = l }
+    instr branch_if_positive X, l: label { pc' = l }
+    instr wrap Y -> X { X = Y }
+    instr mstore X { }
+    instr mload -> X { }
+    instr store_byte Y, Z, X { }
+    instr load_byte_signed Y, Z -> X { }
+    instr load_word Y, Z, l: label -> X { }
+    instr store_word Y, Z, X, l: label { }
+    instr poseidon Y, Z -> X { }
+    instr fail { 1 = 0 }
+"#;
+
+    fn run(program: &str, inputs: Vec<u64>) -> Result<Execution<GoldilocksField>, Error> {
+        let contents = format!(
+            "machine Main {{\n    degree 1024;\n{PREAMBLE}\n    function main {{\n{program}\n    }}\n}}"
+        );
+        let inputs = executor::witgen::prover_input::InputList::from(
+            inputs
+                .into_iter()
+                .map(GoldilocksField::from)
+                .collect::<Vec<_>>(),
+        );
+        let options = ExecutionOptions {
+            trace: true,
+            profile: true,
+        };
+        execute("test.asm", &contents, inputs, options)
+    }
+
+    #[test]
+    fn sum_inputs() {
+        // Sums the first x10 inputs after the first one.
+        let execution = run(
+            r#"
+        x10 <=X= ${ ("input", 0) };
+        x11 <=X= 0;
+        loop::
+        branch_if_zero x10, end;
+        x11 <== wrap(x11 + ${ ("input", x10) });
+        x10 <=X= x10 - 1;
+        jump loop;
+        end::
+        return;
+"#,
+            vec![3, 4, 5, 6],
+        )
+        .unwrap();
+        assert_eq!(execution.registers["x11"], 15.into());
+        // 2 dispatcher rows, 2 assignments, 3 iterations of 4 rows, the final branch and the return
+        assert_eq!(execution.cycles, 2 + 2 + 3 * 4 + 2);
+        assert_eq!(execution.instruction_counts["branch_if_zero"], 4);
+        assert_eq!(execution.instruction_counts["assignment"], 5);
+
+        let trace = execution.trace.unwrap();
+        assert_eq!(trace.len(), execution.cycles as usize);
+        let columns = trace.to_witness_columns("main");
+        let (pc_name, pc) = &columns[0];
+        assert_eq!(pc_name, "main.pc");
+        // dispatcher, then the first statements of `main`
+        assert_eq!(pc[..5], [0, 1, 2, 3, 4].map(GoldilocksField::from));
+        let (_, x11) = columns.iter().find(|(name, _)| name == "main.x11").unwrap();
+        assert_eq!(x11.last(), Some(&15.into()));
+    }
+
+    #[test]
+    fn memory_and_calls() {
+        let execution = run(
+            r#"
+        addr <=X= 0x100;
+        mstore 0x11223344;
+        call store;
+        x10 <== load_byte_signed(x11, 0x10);
+        return;
+        store::
+        x11 <=X= 0xfe;
+        store_byte x11, 0x10, x11;
+        x11 <=X= 0xfe;
+        ret;
+"#,
+            vec![],
+        )
+        .unwrap();
+        assert_eq!(execution.memory[&0x100], 0x11223344);
+        assert_eq!(execution.memory[&0x10c], 0x00fe0000);
+        assert_eq!(execution.registers["x10"], 0xfffffffeu32.into());
+    }
+
+    #[test]
+    fn misaligned_word_access() {
+        let execution = run(
+            r#"
+        x11 <=X= 0x100;
+        store_word x11, 4, 0x11223344, unaligned_sw;
+        store_word x11, 2, 0x55667788, unaligned_sw;
+        x10 <== load_word(x11, 4, unaligned_lw);
+        x11 <== load_word(x11, 1, unaligned_lw);
+        return;
+        unaligned_sw::
+        addr <=X= tmp1 - 2;
+        mstore tmp2;
+        jump_dyn tmp4;
+        unaligned_lw::
+        addr <=X= x11 - 1;
+        x11 <== mload();
+        jump_dyn tmp4;
+"#,
+            vec![],
+        )
+        .unwrap();
+        assert_eq!(execution.memory[&0x104], 0x11223344);
+        assert_eq!(execution.memory[&0x100], 0x55667788);
+        assert_eq!(execution.registers["x10"], 0x11223344.into());
+        assert_eq!(execution.registers["x11"], 0x55667788.into());
+        assert_eq!(execution.instruction_counts["jump_dyn"], 2);
+    }
+
+    #[test]
+    fn invalid_memory_access() {
+        let Err(Error::Execution(error)) = run("addr <=X= 0x102;\nmstore 1;\nreturn;", vec![])
+        else {
+            panic!("Expected an execution error")
+        };
+        assert_eq!(error.statement.as_deref(), Some("mstore 1;"));
+        assert_eq!(error.message, "Unaligned memory access to address 0x102");
+
+        let Err(Error::Execution(error)) =
+            run("x10 <== load_byte_signed(0, 0x400);\nreturn;", vec![])
+        else {
+            panic!("Expected an execution error")
+        };
+        assert_eq!(
+            error.message,
+            "Memory access to too large address: 0x400 (must be less than 0x400)"
+        );
+    }
+
+    #[test]
+    fn profile() {
+        let execution = run(
+            r#"
+        debug file 1 "src" "main.rs";
+        debug loc 1 3 1;
+        call f;
+        debug loc 1 4 1;
+        return;
+        f::
+        debug loc 1 10 1;
+        x11 <=X= x1;
+        call g;
+        x1 <=X= x11;
+        ret;
+        g::
+        debug loc 1 20 1;
+        x10 <=X= 2;
+        ret;
+"#,
+            vec![],
+        )
+        .unwrap();
+        let profile = execution.profile.unwrap();
+        fn counts(counts: &BTreeMap<String, u64>) -> Vec<(&str, u64)> {
+            counts
+                .iter()
+                .map(|(name, count)| (name.as_str(), *count))
+                .collect()
+        }
+        assert_eq!(
+            counts(&profile.functions),
+            [("f", 4), ("g", 2), ("main", 4)]
+        );
+        assert_eq!(
+            counts(&profile.lines),
+            [
+                ("src/main.rs:10", 4),
+                ("src/main.rs:20", 2),
+                ("src/main.rs:3", 1),
+                ("src/main.rs:4", 1)
+            ]
+        );
+        let mut folded = vec![];
+        profile.write_folded(&mut folded).unwrap();
+        assert_eq!(
+            String::from_utf8(folded).unwrap(),
+            "main 4\nmain;f 4\nmain;f;g 2\n"
+        );
+        assert_eq!(execution.cycles, 10);
+    }
+
+    #[test]
+    fn failing_instruction() {
+        let Err(Error::Execution(error)) = run(
+            r#"
+        debug file 1 "src" "main.rs";
+        x10 <=X= 1;
+        debug loc 1 7 3;
+        fail;
+        return;
+"#,
+            vec![],
+        ) else {
+            panic!("Expected an execution error")
+        };
+        assert_eq!(error.cycle, 3);
+        assert_eq!(error.statement.as_deref(), Some("fail;"));
+        assert_eq!(
+            error.location,
+            Some(SourceLocation {
+                file: Some("src/main.rs".to_string()),
+                line: 7,
+                column: 3
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_wrap() {
+        let Err(Error::Execution(error)) = run("x10 <== wrap(0 - 1);\nreturn;", vec![]) else {
+            panic!("Expected an execution error")
+        };
+        assert!(error.message.contains("cannot be wrapped"));
+    }
+
+    #[test]
+    fn invalid_pc() {
+        let Err(Error::Execution(error)) = run("jump_dyn 0;", vec![]) else {
+            panic!("Expected an execution error")
+        };
+        assert_eq!(error.message, "Invalid pc: 0");
+    }
+
+    #[test]
+    fn unsupported_poseidon() {
+        let Err(Error::Execution(error)) = run("x10 <== poseidon(1, 2);\nreturn;", vec![]) else {
+            panic!("Expected an execution error")
+        };
+        assert_eq!(error.message, "poseidon is not supported by the executor");
+    }
+
+    #[test]
+    fn missing_input() {
+        let Err(Error::Execution(error)) = run("x10 <=X= ${ (\"input\", 2) };\nreturn;", vec![1])
+        else {
+            panic!("Expected an execution error")
+        };
+        assert_eq!(error.message, "No answer for query (\"input\", 2)");
+    }
+
+    #[test]
+    fn infinite_loop() {
+        let Err(Error::Execution(error)) = run("loop::\njump loop;", vec![]) else {
+            panic!("Expected an execution error")
+        };
+        assert_eq!(error.cycle, 1024);
+    }
+}
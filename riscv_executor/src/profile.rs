@@ -0,0 +1,151 @@
+//! Attribution of the rows of an execution to functions and source lines.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Write};
+
+use itertools::Itertools;
+
+use crate::SourceLocation;
+
+/// The number of entries of each breakdown shown in the report.
+const REPORT_ENTRIES: usize = 20;
+
+/// The number of rows spent in each function and source line during an execution.
+#[derive(Debug, Default)]
+pub struct Profile {
+    /// Rows by the function they were executed in.
+    pub functions: BTreeMap<String, u64>,
+    /// Rows by source line, as `file:line`. Rows before the first location are not included.
+    pub lines: BTreeMap<String, u64>,
+    /// Rows by call stack, with the functions from the outermost to the innermost
+    /// separated by `;`.
+    pub stacks: BTreeMap<String, u64>,
+}
+
+impl Profile {
+    /// Writes the call stacks in the folded format read by flamegraph tools,
+    /// i.e. one line per stack followed by its number of rows.
+    pub fn write_folded(&self, writer: &mut impl Write) -> io::Result<()> {
+        for (stack, rows) in &self.stacks {
+            writeln!(writer, "{stack} {rows}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Profile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let total = self.functions.values().sum::<u64>();
+        let mut write_breakdown = |title: &str, rows: &BTreeMap<String, u64>| {
+            if rows.is_empty() {
+                return Ok(());
+            }
+            writeln!(f, "{title}:")?;
+            for (name, count) in rows
+                .iter()
+                .sorted_by(|(n1, c1), (n2, c2)| c2.cmp(c1).then(n1.cmp(n2)))
+                .take(REPORT_ENTRIES)
+            {
+                let percentage = *count as f64 * 100.0 / total as f64;
+                writeln!(f, "  {count:>12} {percentage:>6.2}%  {name}")?;
+            }
+            Ok(())
+        };
+        write_breakdown("Rows by function", &self.functions)?;
+        write_breakdown("Rows by source line", &self.lines)
+    }
+}
+
+/// A call executed in a row.
+pub(crate) enum Call {
+    /// A call to `target` that returns to `return_pc`.
+    Call { target: u64, return_pc: u64 },
+    /// A call to `target` that returns to the caller of the current function.
+    Tail { target: u64 },
+}
+
+/// Follows the calls and returns of an execution to attribute each row to
+/// the function it belongs to.
+pub(crate) struct Profiler<'a> {
+    /// The names of functions by their first pc.
+    functions: &'a HashMap<u64, &'a str>,
+    /// The called functions with the pc they return to.
+    stack: Vec<(String, u64)>,
+    /// The current stack in the format of [Profile::stacks].
+    folded_stack: String,
+    /// The location of the previous row and its key in [Profile::lines].
+    line: Option<(SourceLocation, String)>,
+    profile: Profile,
+}
+
+impl<'a> Profiler<'a> {
+    pub(crate) fn new(entry: &str, functions: &'a HashMap<u64, &'a str>) -> Self {
+        Self {
+            functions,
+            // The entry function never returns to a valid pc.
+            stack: vec![(entry.to_string(), u64::MAX)],
+            folded_stack: entry.to_string(),
+            line: None,
+            profile: Default::default(),
+        }
+    }
+
+    /// Attributes a row to the current function and `location`, then follows
+    /// the call of the row, if any, or the return to `next_pc`.
+    pub(crate) fn row(
+        &mut self,
+        location: Option<&SourceLocation>,
+        pc: u64,
+        next_pc: u64,
+        call: Option<Call>,
+    ) {
+        let (function, return_pc) = self.stack.last().unwrap();
+        let returns = next_pc != pc + 1 && next_pc == *return_pc && self.stack.len() > 1;
+        increment(&mut self.profile.functions, function);
+        increment(&mut self.profile.stacks, &self.folded_stack);
+        if let Some(location) = location {
+            if self.line.as_ref().map(|(l, _)| l) != Some(location) {
+                let file = location.file.as_deref().unwrap_or("<unknown>");
+                let key = format!("{file}:{}", location.line);
+                self.line = Some((location.clone(), key));
+            }
+            increment(&mut self.profile.lines, &self.line.as_ref().unwrap().1);
+        }
+
+        match call {
+            Some(Call::Call { target, return_pc }) => {
+                let function = self.function_at(target);
+                self.stack.push((function, return_pc));
+            }
+            Some(Call::Tail { target }) => {
+                self.stack.last_mut().unwrap().0 = self.function_at(target);
+            }
+            None if returns => {
+                self.stack.pop();
+            }
+            None => return,
+        }
+        self.folded_stack = self.stack.iter().map(|(name, _)| name).join(";");
+    }
+
+    pub(crate) fn into_profile(self) -> Profile {
+        self.profile
+    }
+
+    fn function_at(&self, pc: u64) -> String {
+        self.functions
+            .get(&pc)
+            .map(|name| name.to_string())
+            .unwrap_or_else(|| format!("pc_{pc}"))
+    }
+}
+
+fn increment(counts: &mut BTreeMap<String, u64>, key: &str) {
+    match counts.get_mut(key) {
+        Some(count) => *count += 1,
+        None => {
+            counts.insert(key.to_string(), 1);
+        }
+    }
+}
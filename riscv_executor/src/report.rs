@@ -0,0 +1,58 @@
+//! Human-readable reports of executions and execution errors.
+
+use std::fmt::{self, Display, Formatter};
+
+use asm_to_pil::rom_layout::SourceLocation;
+use itertools::Itertools;
+use number::FieldElement;
+
+use crate::Execution;
+
+/// The first instruction that failed during an execution.
+#[derive(Debug)]
+pub struct ExecutionError {
+    /// The row of the main machine the failure occurred in.
+    pub cycle: u64,
+    pub pc: u64,
+    /// The statement that failed, if the failure was caused by a statement.
+    pub statement: Option<String>,
+    /// The source location of the failing row, given by the debug directives.
+    pub location: Option<SourceLocation>,
+    pub message: String,
+}
+
+impl Display for ExecutionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Execution failed in cycle {} (pc {})",
+            self.cycle, self.pc
+        )?;
+        if let Some(statement) = &self.statement {
+            write!(f, " at `{statement}`")?;
+        }
+        if let Some(location) = &self.location {
+            write!(f, " ({location})")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl<T: FieldElement> Display for Execution<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Cycles: {}", self.cycles)?;
+        writeln!(f, "Instructions:")?;
+        for (name, count) in self
+            .instruction_counts
+            .iter()
+            .sorted_by(|(n1, c1), (n2, c2)| c2.cmp(c1).then(n1.cmp(n2)))
+        {
+            writeln!(f, "  {name:<24} {count:>12}")?;
+        }
+        writeln!(f, "Registers:")?;
+        for (name, value) in self.registers.iter().filter(|(_, v)| !v.is_zero()) {
+            writeln!(f, "  {name:<24} {:>12}", value.to_string())?;
+        }
+        write!(f, "Memory: {} words written", self.memory.len())
+    }
+}
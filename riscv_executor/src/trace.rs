@@ -0,0 +1,50 @@
+//! The values of the registers of the main machine in each row of an execution.
+
+/// The values of the pc and the write registers in each row of an execution.
+/// These are exactly the values witness generation computes for the register
+/// columns of the main machine, so they can be passed to it as hints.
+#[derive(Debug)]
+pub struct ExecutionTrace<T> {
+    /// The names of the registers, starting with the pc.
+    pub registers: Vec<String>,
+    /// The values of the registers in each row, in the order of `registers`.
+    pub rows: Vec<Vec<T>>,
+}
+
+impl<T: Copy> ExecutionTrace<T> {
+    pub(crate) fn new(pc: String, write_registers: impl Iterator<Item = String>) -> Self {
+        Self {
+            registers: std::iter::once(pc).chain(write_registers).collect(),
+            rows: vec![],
+        }
+    }
+
+    pub(crate) fn push_row(&mut self, pc: T, write_registers: impl Iterator<Item = T>) {
+        self.rows
+            .push(std::iter::once(pc).chain(write_registers).collect());
+    }
+
+    /// The number of rows.
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Returns the trace as columns named after the registers in the namespace
+    /// of the main machine, e.g. `main.pc`.
+    pub fn to_witness_columns(&self, namespace: &str) -> Vec<(String, Vec<T>)> {
+        self.registers
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                (
+                    format!("{namespace}.{name}"),
+                    self.rows.iter().map(|row| row[i]).collect(),
+                )
+            })
+            .collect()
+    }
+}
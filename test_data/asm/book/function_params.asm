@@ -0,0 +1,38 @@
+/* ANCHOR: all */
+
+machine Machine {
+
+    degree 32;
+
+    reg pc[@pc];
+    reg X[<=];
+    reg Y[<=];
+    reg Z[<=];
+    reg A;
+
+    instr add X, Y -> Z {
+        Z = X + Y
+    }
+
+    // ANCHOR: function
+    // a function with two inputs and one output
+    function sum x: field, y: field -> field {
+        A <== add(x, y);
+        return A;
+    }
+    // ANCHOR_END: function
+
+    // a function without outputs
+    function check x: field {
+        A <=X= x;
+        return;
+    }
+
+    // the entry point of the machine
+    function main {
+        A <== add(2, 3);
+        return;
+    }
+}
+
+/* ANCHOR_END: all */
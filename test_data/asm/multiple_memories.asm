@@ -0,0 +1,82 @@
+machine MultipleMemories {
+    reg pc[@pc];
+    reg X[<=];
+    reg A;
+    reg ADDR;
+
+    constraints {
+        col witness XInv;
+        col witness XIsZero;
+        XIsZero  = 1 - X * XInv;
+        XIsZero * X = 0;
+        XIsZero * (1 - XIsZero) = 0;
+
+        col fixed POSITIVE(i) { i + 1 };
+        col fixed LAST  = [0]* + [1];
+        col fixed STEP(i) { i };
+
+        // Two independent read-write memories that do not use the
+        // default column names. The second memory has no operation flag
+        // and lists its columns in a different order.
+        col witness data_address;
+        col witness data_time;
+        col witness data_new_addr;
+        col witness data_content;
+        col witness data_active;
+        col witness data_store;
+        col witness data_load;
+
+        data_new_addr * (1 - data_new_addr) = 0;
+        (data_address' - data_address) * (1 - data_new_addr) = 0;
+        (1 - LAST) { data_new_addr * (data_address' - data_address) + (1 - data_new_addr) * (data_time' - data_time) } in POSITIVE;
+        data_active * (1 - data_active) = 0;
+        data_store * (1 - data_store) = 0;
+        data_load * (1 - data_load) = 0;
+        data_store * (1 - data_active) = 0;
+        data_load * (1 - data_active) = 0;
+        data_load * data_store = 0;
+        (1 - data_store') * (1 - data_new_addr) * (data_content' - data_content) = 0;
+        (1 - data_store') * data_new_addr * data_content' = 0;
+
+        col witness stack_addr;
+        col witness stack_step;
+        col witness stack_change;
+        col witness stack_value;
+        col witness stack_push;
+        col witness stack_pop;
+
+        stack_change * (1 - stack_change) = 0;
+        (stack_addr' - stack_addr) * (1 - stack_change) = 0;
+        (1 - LAST) { stack_change * (stack_addr' - stack_addr) + (1 - stack_change) * (stack_step' - stack_step) } in POSITIVE;
+        stack_push * (1 - stack_push) = 0;
+        stack_pop * (1 - stack_pop) = 0;
+        stack_pop * stack_push = 0;
+        (1 - stack_push') * (1 - stack_change) * (stack_value' - stack_value) = 0;
+        (1 - stack_push') * stack_change * stack_value' = 0;
+    }
+
+    instr assert_zero X { XIsZero = 1 }
+    instr mstore X { { ADDR, STEP, X } is data_store { data_address, data_time, data_content } }
+    instr mload -> X { { ADDR, STEP, X } is data_load { data_address, data_time, data_content } }
+    instr push X { { X, ADDR, STEP } is stack_push { stack_value, stack_addr, stack_step } }
+    instr pop -> X { { X, ADDR, STEP } is stack_pop { stack_value, stack_addr, stack_step } }
+
+    function main {
+        ADDR <=X= 4;
+        mstore 1;
+        push 7;
+        ADDR <=X= 8;
+        mstore 4;
+        push 9;
+        mload A;
+        assert_zero A - 4;
+        pop A;
+        assert_zero A - 9;
+        ADDR <=X= 4;
+        mload A;
+        assert_zero A - 1;
+        pop A;
+        assert_zero A - 7;
+        return;
+    }
+}
@@ -0,0 +1,43 @@
+machine Arith(latch, function_id) {
+
+    degree 64;
+
+    function add<0> x, y -> z {
+    }
+
+    function sub<1> x, y -> z {
+    }
+
+    constraints {
+        col witness function_id;
+        col fixed latch = [1]*;
+        col witness x;
+        col witness y;
+        col witness z;
+        z = (1 - function_id) * (x + y) + function_id * (x - y);
+    }
+}
+
+machine Main {
+
+    degree 16;
+
+    Arith arith;
+
+    reg pc[@pc];
+    reg X[<=];
+    reg Y[<=];
+    reg Z[<=];
+    reg A;
+
+    instr add X, Y -> Z = arith.add
+    instr sub X, Y -> Z = arith.sub
+    instr assert_eq X, Y { X = Y }
+
+    function main {
+        A <== add(2, 1);
+        A <== sub(A, 1);
+        assert_eq A, 2;
+        return;
+    }
+}
@@ -0,0 +1,57 @@
+machine Double(latch, function_id) {
+
+    function double<0> x -> y {
+    }
+
+    constraints {
+        col witness function_id;
+        col fixed latch = [1]*;
+        col witness x;
+        col witness y;
+        y = 2 * x;
+    }
+}
+
+machine Quadruple(latch, function_id) {
+
+    Double double;
+
+    instr double_x x -> z = double.double
+    instr double_z z -> y = double.double
+
+    function quadruple<0> x -> y {
+    }
+
+    constraints {
+        col witness function_id;
+        col fixed latch = [1]*;
+        col fixed instr_double_x = [1]*;
+        col fixed instr_double_z = [1]*;
+        col witness x;
+        col witness y;
+        col witness z;
+    }
+}
+
+machine Main {
+
+    degree 8;
+
+    Quadruple quad;
+
+    reg pc[@pc];
+    reg X[<=];
+    reg Y[<=];
+    reg A;
+
+    instr quadruple X -> Y = quad.quadruple
+    instr assert_eq X, Y { X = Y }
+
+    function main {
+        A <== quadruple(3);
+        assert_eq A, 12;
+        A <== quadruple(A);
+        assert_eq A, 48;
+        return;
+    }
+}
@@ -10,8 +10,9 @@ use ast::{
     parsed::asm::{ASMFile, FunctionStatement, MachineStatement, RegisterFlag},
 };
 use number::FieldElement;
+use parser_util::SourceError;
 
-pub fn check<T: FieldElement>(file: ASMFile<T>) -> Result<AnalysisASMFile<T>, Vec<String>> {
+pub fn check<T: FieldElement>(file: ASMFile<T>) -> Result<AnalysisASMFile<T>, Vec<SourceError>> {
     TypeChecker::default().check_file(file)
 }
 
@@ -26,7 +27,7 @@ impl<T: FieldElement> TypeChecker<T> {
     fn check_machine_type(
         &mut self,
         machine: ast::parsed::asm::Machine<T>,
-    ) -> Result<(), Vec<String>> {
+    ) -> Result<(), Vec<SourceError>> {
         if self
             .machines_types
             .get(&machine.name)
@@ -65,7 +66,10 @@ impl<T: FieldElement> TypeChecker<T> {
                 }
                 MachineStatement::InstructionDeclaration(start, name, instruction) => {
                     if name == "return" {
-                        errors.push("Instruction cannot use reserved name `return`".into());
+                        errors.push(SourceError::at(
+                            start,
+                            "Instruction cannot use reserved name `return`",
+                        ));
                     }
                     instructions.push(InstructionDefinitionStatement {
                         start,
@@ -79,11 +83,14 @@ impl<T: FieldElement> TypeChecker<T> {
                 MachineStatement::InlinePil(start, statements) => {
                     constraints.push(PilBlock { start, statements });
                 }
-                MachineStatement::Submachine(_, ty, name) => {
+                MachineStatement::Submachine(start, ty, name) => {
                     if self.machines_types.contains_key(&ty) {
                         submachines.push(SubmachineDeclaration { name, ty });
                     } else {
-                        errors.push(format!("Undeclared machine type {}", ty))
+                        errors.push(SourceError::at(
+                            start,
+                            format!("Undeclared machine type {}", ty),
+                        ))
                     }
                 }
                 MachineStatement::FunctionDeclaration(
@@ -148,49 +155,61 @@ impl<T: FieldElement> TypeChecker<T> {
 
         if !registers.iter().any(|r| r.ty.is_pc()) {
             if latch.is_none() {
-                errors.push(format!(
-                    "Machine {} should have a latch column because it does not have a pc",
-                    machine.name
+                errors.push(SourceError::at(
+                    machine.start,
+                    format!(
+                        "Machine {} should have a latch column because it does not have a pc",
+                        machine.name
+                    ),
                 ));
             }
             if function_id.is_none() {
-                errors.push(format!(
-                    "Machine {} should have a function id column because it does not have a pc",
-                    machine.name
+                errors.push(SourceError::at(
+                    machine.start,
+                    format!(
+                        "Machine {} should have a function id column because it does not have a pc",
+                        machine.name
+                    ),
                 ));
             }
             for o in &functions {
                 if o.id.is_none() {
-                    errors.push(format!("Function {} in machine {} should have an id because this machine does not have a pc", o.name, machine.name));
+                    errors.push(SourceError::at(o.start, format!("Function {} in machine {} should have an id because this machine does not have a pc", o.name, machine.name)));
                 }
                 if !o.body.statements.is_empty() {
-                    errors.push(format!("Function {} in machine {} should have an empty body because this machine does not have a pc", o.name, machine.name));
+                    errors.push(SourceError::at(o.start, format!("Function {} in machine {} should have an empty body because this machine does not have a pc", o.name, machine.name)));
                 }
             }
         } else {
             if latch.is_some() {
-                errors.push(format!(
-                    "Machine {} should not have a latch column because it has a pc",
-                    machine.name
+                errors.push(SourceError::at(
+                    machine.start,
+                    format!(
+                        "Machine {} should not have a latch column because it has a pc",
+                        machine.name
+                    ),
                 ));
             }
             if function_id.is_some() {
-                errors.push(format!(
-                    "Machine {} should not have a function id column because it has a pc",
-                    machine.name
+                errors.push(SourceError::at(
+                    machine.start,
+                    format!(
+                        "Machine {} should not have a function id column because it has a pc",
+                        machine.name
+                    ),
                 ));
             }
             for o in &functions {
                 if o.id.is_some() {
-                    errors.push(format!("Function {} in machine {} should not have an id because this machine has a pc", o.name, machine.name));
+                    errors.push(SourceError::at(o.start, format!("Function {} in machine {} should not have an id because this machine has a pc", o.name, machine.name)));
                 }
             }
         }
 
         if registers.iter().filter(|r| r.ty.is_pc()).count() > 1 {
-            errors.push(format!(
-                "Machine {} cannot have more than one pc",
-                machine.name
+            errors.push(SourceError::at(
+                machine.start,
+                format!("Machine {} cannot have more than one pc", machine.name),
             ));
         }
 
@@ -217,14 +236,17 @@ impl<T: FieldElement> TypeChecker<T> {
         }
     }
 
-    fn check_file(&mut self, file: ASMFile<T>) -> Result<AnalysisASMFile<T>, Vec<String>> {
+    fn check_file(&mut self, file: ASMFile<T>) -> Result<AnalysisASMFile<T>, Vec<SourceError>> {
         let mut errors = vec![];
 
         // first pass to get all the declared machine types
         for m in &file.machines {
             let already_declared = self.machines_types.contains_key(&m.name);
             if already_declared {
-                errors.push(format!("Machine with name {} is already declared", m.name));
+                errors.push(SourceError::at(
+                    m.start,
+                    format!("Machine with name {} is already declared", m.name),
+                ));
             } else {
                 self.machines_types.insert(m.name.clone(), None);
             }